@@ -0,0 +1,94 @@
+use mgraphics::{
+    winit::{
+        event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    AlphaMode, Rect, RenderContext, WindowOptions,
+};
+
+// Transparent texels are black, as image editors often leave them. With
+// straight alpha, filtering the scaled-up sprite blends that black into
+// its edge and leaves a dark fringe; premultiplied texels don't.
+fn disc(size: u32) -> image::RgbaImage {
+    let radius = size as f32 / 2.0;
+    image::RgbaImage::from_fn(size, size, |x, y| {
+        let dx = x as f32 + 0.5 - radius;
+        let dy = y as f32 + 0.5 - radius;
+        let inside = (dx * dx + dy * dy).sqrt() < radius - 1.0;
+        if inside {
+            image::Rgba([0xff, 0xd0, 0x40, 0xff])
+        } else {
+            image::Rgba([0, 0, 0, 0])
+        }
+    })
+}
+
+// A half-transparent white rounded rect over the desktop, which should
+// lighten what's behind it evenly, with no dark or bright rim. Space
+// switches between the alpha modes to compare them.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(
+        &event_loop,
+        &WindowOptions {
+            width: Some(640),
+            height: Some(360),
+            ..Default::default()
+        },
+    )?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+    let sprite = ctx.add_sprite("disc", disc(16))?;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Space),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let mode = match ctx.alpha_mode() {
+                    AlphaMode::Premultiplied => AlphaMode::Straight,
+                    AlphaMode::Straight => AlphaMode::Premultiplied,
+                };
+                ctx.set_alpha_mode(mode);
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                ctx.fill_rounded_rect(
+                    Rect::new(32.0, 32.0, 320.0, 200.0),
+                    24.0,
+                    [1.0, 1.0, 1.0, 0.5],
+                );
+                ctx.draw_sprite(sprite, Rect::new(384.0, 32.0, 224.0, 224.0), [1.0; 4]);
+                let label = format!("{:?} alpha, space to switch", ctx.alpha_mode());
+                ctx.queue_text(&label, [32.0, 280.0], 20.0, [1.0; 4]);
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
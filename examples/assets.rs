@@ -0,0 +1,32 @@
+use mgraphics::{Config, DrawCommand, Rect, Scene};
+
+// Shows the image given on the command line, gray until it has loaded.
+// Any error is shown in its place.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("usage: assets <image>"))?;
+    mgraphics::run_with(Config::default(), move |handle| {
+        let mut scene = Scene::new();
+        scene.push(DrawCommand::Texture {
+            name: "image".into(),
+            rect: Rect::new(16.0, 16.0, 256.0, 256.0),
+            tint: [1.0; 4],
+            placeholder: [0.3, 0.3, 0.3, 1.0],
+        });
+        handle.on_asset(|event, scene| {
+            if let Err(e) = &event.result {
+                scene.push(DrawCommand::Text {
+                    text: e.clone(),
+                    position: [16.0, 288.0],
+                    size: 14.0,
+                    color: [1.0, 0.4, 0.4, 1.0],
+                });
+            }
+        });
+        handle.set_scene(scene);
+        tokio::spawn(handle.load_texture("image", path));
+    })
+    .await
+}
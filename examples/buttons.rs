@@ -0,0 +1,86 @@
+use mgraphics::{
+    winit::event::MouseButton, ClickEvent, Config, DrawCommand, Rect, RegionId, Scene,
+};
+
+const BAR: [f32; 4] = [0.1, 0.1, 0.12, 0.9];
+const BUTTON: [f32; 4] = [0.25, 0.25, 0.3, 1.0];
+const PRESSED: [f32; 4] = [0.4, 0.5, 0.8, 1.0];
+const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// Each button is a rect with its label after it, and its region's id is
+// the rect's command index.
+const COUNTER: RegionId = RegionId(1);
+const QUIT: RegionId = RegionId(3);
+
+fn label(count: i32) -> String {
+    format!("count: {}", count)
+}
+
+fn add_button(scene: &mut Scene, id: RegionId, rect: Rect, text: String) {
+    scene.push(DrawCommand::FillRect {
+        rect,
+        color: BUTTON,
+    });
+    scene.push(DrawCommand::Text {
+        text,
+        position: [rect.x + 12.0, rect.y + 10.0],
+        size: 16.0,
+        color: TEXT,
+    });
+    scene.add_region(id, rect);
+}
+
+fn set_color(scene: &mut Scene, RegionId(index): RegionId, color: [f32; 4]) {
+    if let Some(DrawCommand::FillRect { color: fill, .. }) = scene.command_mut(index as usize) {
+        *fill = color;
+    }
+}
+
+// A bar with two buttons. Left-clicking the counter counts up and
+// right-clicking counts down; quit exits. Buttons light up while held and
+// releasing off them cancels the click.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    mgraphics::run_with(Config::default(), |handle| {
+        let mut scene = Scene::new();
+        scene.push(DrawCommand::FillRect {
+            rect: Rect::new(0.0, 0.0, 320.0, 56.0),
+            color: BAR,
+        });
+        add_button(
+            &mut scene,
+            COUNTER,
+            Rect::new(8.0, 8.0, 160.0, 40.0),
+            label(0),
+        );
+        add_button(
+            &mut scene,
+            QUIT,
+            Rect::new(232.0, 8.0, 80.0, 40.0),
+            "quit".to_string(),
+        );
+
+        let exit = handle.clone();
+        let mut count = 0;
+        handle.on_click(move |event, scene| match event {
+            ClickEvent::Press(id, _) => set_color(scene, id, PRESSED),
+            ClickEvent::Cancel(id, _) => set_color(scene, id, BUTTON),
+            ClickEvent::Click(id, button) => {
+                set_color(scene, id, BUTTON);
+                if id == QUIT {
+                    exit.exit();
+                    return;
+                }
+                count += match button {
+                    MouseButton::Right => -1,
+                    _ => 1,
+                };
+                if let Some(DrawCommand::Text { text, .. }) = scene.command_mut(2) {
+                    *text = label(count);
+                }
+            }
+        });
+        handle.set_scene(scene);
+    })
+    .await
+}
@@ -0,0 +1,87 @@
+use mgraphics::{
+    srgb8,
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    HexColor, Rect, RenderContext, WindowOptions,
+};
+
+// Reference colors as sRGB hex. An eyedropper on a screenshot should read
+// back exactly these values from the swatches, on sRGB and non-sRGB
+// surfaces alike.
+const SWATCHES: [&str; 8] = [
+    "#ff0000", "#00ff00", "#0000ff", "#ffffff", "#808080", "#bcbcbc", "#1e90ff", "#bada55",
+];
+const SWATCH: f32 = 96.0;
+const MARGIN: f32 = 16.0;
+
+fn queue_colors(ctx: &mut RenderContext) {
+    for (i, hex) in SWATCHES.iter().enumerate() {
+        let color: HexColor = hex.parse().expect("the swatches are valid hex colors");
+        let x = MARGIN + i as f32 * (SWATCH + MARGIN);
+        ctx.fill_rect(Rect::new(x, MARGIN, SWATCH, SWATCH), color.to_linear());
+        ctx.queue_text(hex, [x, MARGIN * 2.0 + SWATCH], 16.0, [1.0; 4]);
+    }
+
+    // One-pixel black and white stripes average to half the light, which
+    // is #bcbcbc in sRGB, not #808080. Seen from a distance the stripes
+    // should match the left square and not the right one.
+    let y = MARGIN * 4.0 + SWATCH + 16.0;
+    let stripes = Rect::new(MARGIN + SWATCH + MARGIN, y, SWATCH, SWATCH);
+    for column in 0..SWATCH as u32 {
+        let value = if column % 2 == 0 { 0 } else { 0xff };
+        ctx.fill_rect(
+            Rect::new(stripes.x + column as f32, y, 1.0, SWATCH),
+            srgb8([value, value, value, 0xff]),
+        );
+    }
+    ctx.fill_rect(
+        Rect::new(MARGIN, y, SWATCH, SWATCH),
+        srgb8([0xbc, 0xbc, 0xbc, 0xff]),
+    );
+    ctx.fill_rect(
+        Rect::new(stripes.x + SWATCH + MARGIN, y, SWATCH, SWATCH),
+        srgb8([0x80, 0x80, 0x80, 0xff]),
+    );
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(
+        &event_loop,
+        &WindowOptions {
+            width: Some(912),
+            height: Some(320),
+            ..Default::default()
+        },
+    )?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+    ctx.set_clear_color(HexColor([0x20, 0x20, 0x20, 0xff]).to_wgpu());
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::RedrawRequested(_) => {
+                queue_colors(&mut ctx);
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
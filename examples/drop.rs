@@ -0,0 +1,34 @@
+use mgraphics::{Config, DrawCommand, Scene};
+
+// Lists the names of the files dropped on the window. Directories are
+// refused, which shows the error on the window instead.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    mgraphics::run_with(Config::default(), |handle| {
+        let mut scene = Scene::new();
+        scene.push(DrawCommand::Text {
+            text: "drop files here".into(),
+            position: [16.0, 16.0],
+            size: 16.0,
+            color: [1.0; 4],
+        });
+        handle.on_file_drop(|drop, scene| {
+            if let Some(dir) = drop.paths.iter().find(|path| path.is_dir()) {
+                anyhow::bail!("{} is a directory", dir.display());
+            }
+            scene.clear();
+            for (i, path) in drop.paths.iter().enumerate() {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                scene.push(DrawCommand::Text {
+                    text: name.into_owned(),
+                    position: [16.0, 16.0 + i as f32 * 24.0],
+                    size: 16.0,
+                    color: [1.0; 4],
+                });
+            }
+            Ok(())
+        });
+        handle.set_scene(scene);
+    })
+    .await
+}
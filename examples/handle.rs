@@ -0,0 +1,33 @@
+use std::time::{Duration, Instant};
+
+use mgraphics::{Config, DrawCommand, Rect, Scene};
+
+// A background thread rebuilds the scene once a second; the event loop only
+// wakes up to draw it.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    mgraphics::run_with(Config::default(), |handle| {
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            loop {
+                let seconds = start.elapsed().as_secs();
+                let mut scene = Scene::new();
+                scene.push(DrawCommand::FillRect {
+                    rect: Rect::new(8.0, 8.0, 200.0, 32.0),
+                    color: [0.1, 0.1, 0.1, 0.8],
+                });
+                scene.push(DrawCommand::Text {
+                    text: format!("running for {}s", seconds),
+                    position: [16.0, 16.0],
+                    size: 16.0,
+                    color: [1.0, 1.0, 1.0, 1.0],
+                });
+                if !handle.set_scene(scene) {
+                    break;
+                }
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        });
+    })
+    .await
+}
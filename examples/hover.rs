@@ -0,0 +1,36 @@
+use mgraphics::{Config, DrawCommand, HoverEvent, Rect, RegionId, Scene};
+
+const IDLE: [f32; 4] = [0.2, 0.2, 0.25, 0.9];
+const HOVERED: [f32; 4] = [0.3, 0.55, 0.9, 0.9];
+
+// Three buttons that light up under the cursor; the middle one overlaps
+// the others, and being added last it wins where they overlap. Each
+// region's id is the index of the command drawing it.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    mgraphics::run_with(Config::default(), |handle| {
+        let mut scene = Scene::new();
+        let rects = [
+            Rect::new(16.0, 16.0, 160.0, 64.0),
+            Rect::new(304.0, 16.0, 160.0, 64.0),
+            Rect::new(144.0, 40.0, 192.0, 64.0),
+        ];
+        for (i, rect) in rects.into_iter().enumerate() {
+            scene.push(DrawCommand::FillRect { rect, color: IDLE });
+            scene.add_region(RegionId(i as u32), rect);
+        }
+        handle.on_hover(|event, scene| {
+            let (RegionId(index), color) = match event {
+                HoverEvent::Enter(id) => (id, HOVERED),
+                HoverEvent::Exit(id) => (id, IDLE),
+            };
+            if let Some(DrawCommand::FillRect { color: fill, .. }) =
+                scene.command_mut(index as usize)
+            {
+                *fill = color;
+            }
+        });
+        handle.set_scene(scene);
+    })
+    .await
+}
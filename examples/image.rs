@@ -0,0 +1,43 @@
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    RenderContext, Texture, WindowOptions,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("usage: image <path>"))?;
+
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    let texture = Texture::from_path(ctx.device(), ctx.queue(), &path)?;
+    ctx.set_texture(texture);
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::RedrawRequested(_) => {
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
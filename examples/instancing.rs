@@ -0,0 +1,66 @@
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    Instance, Mesh, Rect, RenderContext, WindowOptions,
+};
+
+const COUNT: usize = 10_000;
+
+// Spins COUNT small quads in one draw and prints frame timings every couple of
+// seconds.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+    let quad = ctx.upload_mesh(&Mesh::rect(
+        Rect::new(-2.0, -2.0, 4.0, 4.0),
+        [1.0, 1.0, 1.0, 1.0],
+    ));
+    let mut instances = vec![Instance::default(); COUNT];
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::MainEventsCleared => window.request_redraw(),
+            Event::RedrawRequested(_) => {
+                let globals = ctx.globals();
+                let [width, height] = globals.resolution;
+                let columns = (COUNT as f32).sqrt().ceil() as usize;
+                for (i, instance) in instances.iter_mut().enumerate() {
+                    let (column, row) = ((i % columns) as f32, (i / columns) as f32);
+                    let phase = globals.time + (column + row) * 0.05;
+                    instance.offset = [
+                        (column + 0.5) / columns as f32 * width,
+                        (row + 0.5) / columns as f32 * height,
+                    ];
+                    instance.scale = [1.0 + phase.sin().abs(), 1.0];
+                    instance.rotation = phase;
+                    instance.color = [column / columns as f32, row / columns as f32, 0.8, 1.0];
+                }
+                ctx.draw_instanced(&quad, &instances);
+
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                }
+                if globals.frame % 240 == 0 {
+                    println!("{} instances: {}", COUNT, ctx.stats());
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,172 @@
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    ComputeOptions, RenderContext, WindowOptions,
+};
+
+const COUNT: u32 = 2_000;
+
+// cs_main moves and bounces the particles in the storage buffer, vs_main
+// then draws one quad per particle straight from it; the CPU only writes
+// the initial state.
+const SHADER: &str = r#"
+struct Globals {
+    time: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    delta_time: f32;
+    scale_factor: f32;
+    encode_srgb: u32;
+    premultiply: u32;
+    opacity: f32;
+};
+
+struct Particle {
+    position: vec2<f32>;
+    velocity: vec2<f32>;
+    color: vec4<f32>;
+};
+
+struct Particles {
+    items: array<Particle>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> globals: Globals;
+[[group(1), binding(0)]]
+var<storage, read_write> state: Particles;
+[[group(1), binding(1)]]
+var<storage, read> particles: Particles;
+
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let index = id.x;
+    if (index >= arrayLength(&state.items)) {
+        return;
+    }
+    var particle = state.items[index];
+    let size = globals.resolution;
+    particle.position = particle.position + particle.velocity * globals.delta_time;
+    if (particle.position.x < 0.0 || particle.position.x > size.x) {
+        particle.velocity.x = -particle.velocity.x;
+    }
+    if (particle.position.y < 0.0 || particle.position.y > size.y) {
+        particle.velocity.y = -particle.velocity.y;
+    }
+    particle.position = clamp(particle.position, vec2<f32>(0.0), size);
+    state.items[index] = particle;
+}
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] local: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] corner_index: u32,
+    [[builtin(instance_index)]] particle_index: u32
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0)
+    );
+    let particle = particles.items[particle_index];
+    let corner = corners[corner_index];
+    let pixel = particle.position + corner * 3.0 * globals.scale_factor;
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        pixel / globals.resolution * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0),
+        0.0,
+        1.0
+    );
+    out.local = corner;
+    out.color = particle.color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let alpha = clamp((1.0 - length(input.local)) * 3.0, 0.0, 1.0);
+    return vec4<f32>(input.color.rgb, input.color.a * alpha * globals.opacity);
+}
+"#;
+
+// Matches `Particle` in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+    color: [f32; 4],
+}
+
+// Good enough randomness for a demo, in 0..1.
+fn random(seed: &mut u32) -> f32 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 17;
+    *seed ^= *seed << 5;
+    (*seed >> 8) as f32 / (1 << 24) as f32
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+
+    let size = window.inner_size();
+    let mut seed = 0x2545_f491;
+    let particles: Vec<_> = (0..COUNT)
+        .map(|_| {
+            let angle = random(&mut seed) * std::f32::consts::TAU;
+            let speed = 40.0 + random(&mut seed) * 160.0;
+            Particle {
+                position: [
+                    random(&mut seed) * size.width as f32,
+                    random(&mut seed) * size.height as f32,
+                ],
+                velocity: [angle.cos() * speed, angle.sin() * speed],
+                color: [0.3 + random(&mut seed) * 0.7, 0.6, 1.0, 0.9],
+            }
+        })
+        .collect();
+    ctx.set_compute(ComputeOptions {
+        source: SHADER.to_string(),
+        element_size: std::mem::size_of::<Particle>() as u64,
+        count: COUNT,
+        contents: bytemuck::cast_slice(&particles).to_vec(),
+        vertices_per_element: 6,
+    })?;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::MainEventsCleared => window.request_redraw(),
+            Event::RedrawRequested(_) => {
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,47 @@
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    RenderContext, Vertex, WindowOptions,
+};
+
+const VERTICES: [Vertex; 4] = [
+    Vertex::new([-0.5, -0.5], [1.0, 0.0, 0.0, 1.0]),
+    Vertex::new([0.5, -0.5], [0.0, 1.0, 0.0, 1.0]),
+    Vertex::new([0.5, 0.5], [0.0, 0.0, 1.0, 1.0]),
+    Vertex::new([-0.5, 0.5], [1.0, 1.0, 1.0, 1.0]),
+];
+
+const INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_indexed_geometry(&VERTICES, &INDICES);
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::RedrawRequested(_) => {
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,70 @@
+use mgraphics::{
+    winit::{
+        event::{ElementState, Event, MouseButton, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    DrawCommand, Rect, RenderContext, Scene, WindowOptions,
+};
+
+// Click to drop a circle. Nothing is redrawn until the scene changes, so the
+// loop sleeps between clicks.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+
+    let mut scene = Scene::new();
+    scene.push(DrawCommand::FillRect {
+        rect: Rect::new(8.0, 8.0, 240.0, 32.0),
+        color: [0.1, 0.1, 0.1, 0.8],
+    });
+    scene.push(DrawCommand::Text {
+        text: "click to add circles".to_string(),
+        position: [16.0, 16.0],
+        size: 16.0,
+        color: [1.0, 1.0, 1.0, 1.0],
+    });
+    let mut cursor = [0.0, 0.0];
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Resized(size) => {
+                    ctx.resize(size);
+                    scene.mark_dirty();
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    cursor = [position.x as f32, position.y as f32];
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } => scene.push(DrawCommand::FillCircle {
+                    center: cursor,
+                    radius: 12.0,
+                    color: [0.9, 0.5, 0.2, 0.8],
+                }),
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                _ => {}
+            },
+            Event::MainEventsCleared => {
+                if scene.take_dirty() {
+                    window.request_redraw();
+                }
+            }
+            Event::RedrawRequested(_) => {
+                if mgraphics::draw_scene(&mut ctx, &scene).is_err() {
+                    ctx.recreate_surface();
+                    scene.mark_dirty();
+                }
+            }
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,68 @@
+use mgraphics::{Config, DrawCommand, Rect, RegionId, Scene};
+
+const GAUGE: RegionId = RegionId(0);
+const GAUGE_RECT: Rect = Rect {
+    x: 8.0,
+    y: 8.0,
+    width: 200.0,
+    height: 24.0,
+};
+const TEXT_X: f32 = 224.0;
+// How much farther the text goes than there's room for.
+const OVERFLOW: f32 = 400.0;
+
+// Scrolling over the gauge fills and drains it, anywhere else moves the
+// long text sideways. Scrolling past either end changes nothing, so
+// nothing is redrawn.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    mgraphics::run_with(Config::default(), |handle| {
+        let mut scene = Scene::new();
+        scene.push(DrawCommand::FillRect {
+            rect: GAUGE_RECT,
+            color: [0.2, 0.2, 0.2, 1.0],
+        });
+        scene.push(DrawCommand::FillRect {
+            rect: Rect {
+                width: GAUGE_RECT.width / 2.0,
+                ..GAUGE_RECT
+            },
+            color: [0.3, 0.8, 0.4, 1.0],
+        });
+        scene.push(DrawCommand::Text {
+            text: "a line of text too long for the bar, scroll to read the rest of it".into(),
+            position: [TEXT_X, 12.0],
+            size: 16.0,
+            color: [1.0; 4],
+        });
+        scene.add_region(GAUGE, GAUGE_RECT);
+
+        let mut level = 0.5;
+        let mut offset = 0.0;
+        handle.on_scroll(move |event, scene| {
+            if event.region == Some(GAUGE) {
+                let next = (level + event.dy / GAUGE_RECT.width).clamp(0.0, 1.0);
+                if next == level {
+                    return;
+                }
+                level = next;
+                if let Some(DrawCommand::FillRect { rect, .. }) = scene.command_mut(1) {
+                    rect.width = GAUGE_RECT.width * level;
+                }
+            } else {
+                // Vertical wheels move the text too.
+                let delta = if event.dx != 0.0 { event.dx } else { event.dy };
+                let next = (offset + delta).clamp(-OVERFLOW, 0.0);
+                if next == offset {
+                    return;
+                }
+                offset = next;
+                if let Some(DrawCommand::Text { position, .. }) = scene.command_mut(2) {
+                    position[0] = TEXT_X + offset;
+                }
+            }
+        });
+        handle.set_scene(scene);
+    })
+    .await
+}
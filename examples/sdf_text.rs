@@ -0,0 +1,62 @@
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    RenderContext, TextStyle, WindowOptions,
+};
+
+const TEXT: &str = "mgraphics 0123";
+const SIZES: [f32; 3] = [12.0, 32.0, 72.0];
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// The same string from glyph bitmaps on the left and from distance fields on
+// the right, at three sizes, with an outlined and shadowed line below.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(
+        &event_loop,
+        &WindowOptions {
+            width: Some(1280),
+            height: Some(400),
+            ..Default::default()
+        },
+    )?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::RedrawRequested(_) => {
+                let mut y = 16.0;
+                for size in SIZES {
+                    ctx.text().set_sdf(false);
+                    ctx.queue_text(TEXT, [16.0, y], size, WHITE);
+                    ctx.text().set_sdf(true);
+                    ctx.queue_text(TEXT, [640.0, y], size, WHITE);
+                    y += size * 1.4;
+                }
+                let style = TextStyle::new(WHITE)
+                    .outline(2.0, [0.9, 0.3, 0.1, 1.0])
+                    .shadow([3.0, 3.0], 2.0, [0.0, 0.0, 0.0, 0.8]);
+                ctx.queue_styled_text(TEXT, [640.0, y], 48.0, &style);
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,91 @@
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    CornerRadii, Gradient, Rect, RenderContext, WindowOptions,
+};
+
+fn queue_shapes(ctx: &mut RenderContext) {
+    ctx.fill_rect(Rect::new(16.0, 16.0, 160.0, 96.0), [0.9, 0.3, 0.2, 1.0]);
+    ctx.stroke_rect(
+        Rect::new(200.0, 16.0, 160.0, 96.0),
+        4.0,
+        [0.2, 0.7, 0.9, 1.0],
+    );
+    ctx.fill_circle([448.0, 64.0], 48.0, [0.3, 0.8, 0.3, 1.0]);
+    ctx.fill_circle([544.0, 64.0], 8.0, [1.0, 1.0, 1.0, 1.0]);
+    ctx.line([592.0, 112.0], [752.0, 16.0], 2.0, [1.0, 0.8, 0.2, 1.0]);
+    ctx.line([592.0, 16.0], [752.0, 112.0], 8.0, [1.0, 0.8, 0.2, 0.5]);
+    ctx.fill_rect(Rect::new(784.0, 16.0, 224.0, 96.0), [0.5, 0.3, 0.9, 0.6]);
+
+    let stops = [(0.0, [1.0, 0.2, 0.1, 1.0]), (1.0, [0.1, 0.3, 1.0, 0.0])];
+    ctx.fill_rect_gradient(
+        Rect::new(16.0, 128.0, 480.0, 64.0),
+        &Gradient::linear([16.0, 0.0], [496.0, 0.0], &stops),
+    );
+    ctx.fill_rect_gradient(
+        Rect::new(512.0, 128.0, 128.0, 128.0),
+        &Gradient::radial(
+            [576.0, 192.0],
+            64.0,
+            &[
+                (0.0, [1.0, 1.0, 1.0, 1.0]),
+                (0.5, [1.0, 0.8, 0.2, 0.8]),
+                (1.0, [1.0, 0.4, 0.0, 0.0]),
+            ],
+        ),
+    );
+
+    ctx.with_shadow([0.0, 4.0], 12.0, [0.0, 0.0, 0.0, 0.6])
+        .fill_rounded_rect(
+            Rect::new(16.0, 272.0, 160.0, 48.0),
+            24.0,
+            [0.2, 0.6, 0.9, 1.0],
+        );
+    ctx.stroke_rounded_rect(
+        Rect::new(200.0, 272.0, 160.0, 48.0),
+        12.0,
+        2.0,
+        [1.0, 1.0, 1.0, 0.8],
+    );
+    ctx.fill_rounded_rect_with_border(
+        Rect::new(384.0, 272.0, 160.0, 48.0),
+        CornerRadii::new(16.0, 0.0, 16.0, 0.0),
+        [0.1, 0.1, 0.1, 0.6],
+        3.0,
+        [0.9, 0.6, 0.2, 1.0],
+    );
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::RedrawRequested(_) => {
+                queue_shapes(&mut ctx);
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
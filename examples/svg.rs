@@ -0,0 +1,67 @@
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    Rect, RenderContext, Svg, WindowOptions,
+};
+
+const ICON: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24">
+  <circle cx="12" cy="12" r="10" fill="none" stroke="#fff" stroke-width="2"/>
+  <path d="M12 6v6l4 2" fill="none" stroke="#fff" stroke-width="2" stroke-linecap="round"/>
+</svg>"##;
+// In logical pixels, scaled by the window's scale factor when drawn.
+const SIZES: [f32; 4] = [16.0, 24.0, 48.0, 96.0];
+
+// One icon at a few sizes and tints. Moving the window to a monitor with
+// another scale factor rasterizes it again at the new pixel sizes.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+    let icon = Svg::from_data(ICON.as_bytes())?;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::WindowEvent {
+                event:
+                    WindowEvent::ScaleFactorChanged {
+                        scale_factor,
+                        new_inner_size,
+                    },
+                ..
+            } => {
+                ctx.set_scale_factor(scale_factor);
+                ctx.resize(*new_inner_size);
+            }
+            Event::RedrawRequested(_) => {
+                let scale = ctx.scale_factor() as f32;
+                let mut x = 16.0 * scale;
+                for (i, size) in SIZES.into_iter().enumerate() {
+                    let size = size * scale;
+                    let tint = [1.0, 1.0 - i as f32 * 0.25, 0.4 + i as f32 * 0.2, 1.0];
+                    ctx.draw_svg(&icon, Rect::new(x, 16.0 * scale, size, size), tint);
+                    x += size + 16.0 * scale;
+                }
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,53 @@
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    RenderContext, WindowOptions,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    let font = match std::env::args().nth(1) {
+        Some(path) => Some(ctx.text().load_font(path)?),
+        None => None,
+    };
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::RedrawRequested(_) => {
+                // Emoji are drawn in color when a color emoji font is installed.
+                let text = "mgraphics\nmulti-line text on the bar\n🔋 87% ☀️ 21°C 👩‍💻 ok ✅";
+                match font {
+                    Some(font) => ctx.text().queue_text_with_font(
+                        font,
+                        text,
+                        [16.0, 16.0],
+                        32.0,
+                        [1.0, 1.0, 1.0, 1.0],
+                    ),
+                    None => ctx.queue_text(text, [16.0, 16.0], 32.0, [1.0, 1.0, 1.0, 1.0]),
+                }
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
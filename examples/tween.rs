@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+
+use mgraphics::{
+    winit::{
+        event::{ElementState, Event, MouseButton, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    Animator, DrawCommand, Easing, Property, Rect, RenderContext, Repeat, Scene, Tween,
+    WindowOptions,
+};
+
+// The square slides back and forth until clicked, and the label fades in
+// after a second. Once nothing is animating the loop sleeps again.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+
+    let mut scene = Scene::new();
+    scene.push(DrawCommand::FillRect {
+        rect: Rect::new(16.0, 64.0, 48.0, 48.0),
+        color: [0.9, 0.5, 0.2, 1.0],
+    });
+    scene.push(DrawCommand::Text {
+        text: "click to stop".to_string(),
+        position: [16.0, 16.0],
+        size: 16.0,
+        color: [1.0, 1.0, 1.0, 0.0],
+    });
+
+    let mut animator = Animator::new();
+    let slide = animator.add(
+        Tween::new(
+            Property::Position(0),
+            [16.0, 64.0],
+            [240.0, 64.0],
+            Duration::from_millis(1200),
+        )
+        .easing(Easing::CubicInOut)
+        .repeat(Repeat::PingPong),
+    );
+    animator.add(
+        Tween::new(Property::Opacity(1), 0.0, 1.0, Duration::from_millis(500))
+            .easing(Easing::QuadOut)
+            .delay(Duration::from_secs(1)),
+    );
+    let mut last_frame = Instant::now();
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = if animator.is_idle() {
+            ControlFlow::Wait
+        } else {
+            ControlFlow::Poll
+        };
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Resized(size) => {
+                    ctx.resize(size);
+                    scene.mark_dirty();
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    animator.cancel(slide);
+                }
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                _ => {}
+            },
+            Event::MainEventsCleared => {
+                if scene.take_dirty() || !animator.is_idle() {
+                    window.request_redraw();
+                }
+            }
+            Event::RedrawRequested(_) => {
+                let now = Instant::now();
+                animator.tick(now - last_frame);
+                last_frame = now;
+                animator.apply(&mut scene);
+                if mgraphics::draw_scene(&mut ctx, &scene).is_err() {
+                    ctx.recreate_surface();
+                    scene.mark_dirty();
+                }
+            }
+            _ => {}
+        }
+    });
+}
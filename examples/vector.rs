@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use mgraphics::{
+    winit::{
+        event::{Event, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+    },
+    FillRule, GpuMesh, LineCap, LineJoin, PathBuilder, Rect, RenderContext, Stroke, WindowOptions,
+};
+
+// Tessellated once up front; every frame only queues the uploaded meshes.
+fn build_meshes(ctx: &RenderContext) -> anyhow::Result<Vec<Arc<GpuMesh>>> {
+    let mut builder = PathBuilder::new();
+    builder.rounded_rect(Rect::new(16.0, 16.0, 200.0, 120.0), 24.0);
+    let card = builder.build();
+
+    let mut builder = PathBuilder::new();
+    for i in 0..10 {
+        let angle = i as f32 / 10.0 * std::f32::consts::TAU - std::f32::consts::FRAC_PI_2;
+        let radius = if i % 2 == 0 { 64.0 } else { 28.0 };
+        builder.line_to([320.0 + radius * angle.cos(), 80.0 + radius * angle.sin()]);
+    }
+    builder.close();
+    let star = builder.build();
+
+    let mut builder = PathBuilder::new();
+    builder
+        .move_to([420.0, 120.0])
+        .cubic_to([460.0, 0.0], [540.0, 160.0], [580.0, 40.0])
+        .quad_to([620.0, 0.0], [660.0, 80.0]);
+    let curve = builder.build();
+
+    let stroke = Stroke {
+        width: 6.0,
+        cap: LineCap::Round,
+        join: LineJoin::Round,
+    };
+
+    let meshes = [
+        card.fill(FillRule::NonZero, [0.2, 0.3, 0.5, 1.0])?,
+        card.stroke(&Stroke::default(), [0.8, 0.9, 1.0, 1.0])?,
+        star.fill(FillRule::EvenOdd, [0.9, 0.7, 0.2, 1.0])?,
+        curve.stroke(&stroke, [0.3, 0.9, 0.5, 1.0])?,
+    ];
+    Ok(meshes.iter().map(|mesh| ctx.upload_mesh(mesh)).collect())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let event_loop = EventLoop::new();
+
+    let window = mgraphics::create_window(&event_loop, &WindowOptions::default())?;
+
+    let mut ctx = RenderContext::new(&window).await?;
+    ctx.set_geometry(&[]);
+    let meshes = build_meshes(&ctx)?;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => ctx.resize(size),
+            Event::RedrawRequested(_) => {
+                for mesh in &meshes {
+                    ctx.draw_mesh(mesh);
+                }
+                if mgraphics::draw(&mut ctx).is_err() {
+                    ctx.recreate_surface();
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use winit::event_loop::ControlFlow;
+
+// Longest clock step taken per frame, so a stall does not make the
+// animation jump ahead.
+pub(crate) const MAX_FRAME_DELTA: Duration = Duration::from_millis(100);
+
+// Limits the `cycle-frame-rate` action steps through, uncapped last.
+const FRAME_RATE_STEPS: [Option<u32>; 4] = [Some(15), Some(30), Some(60), None];
+
+// Decides whether the event loop keeps redrawing on its own, and how often
+// animation and scene changes may redraw. When disabled the loop sleeps
+// until an event arrives, as it always did.
+pub(crate) struct Animation {
+    enabled: bool,
+    frame_rate: Option<u32>,
+    // Target frame interval, `None` redraws as fast as presenting allows.
+    interval: Option<Duration>,
+    // Refresh rate vsync already holds every window to, if it does.
+    vsync_rate: Option<u32>,
+    last_present: Option<Instant>,
+    next_frame: Instant,
+}
+
+impl Animation {
+    pub fn new(enabled: bool, frame_rate: Option<u32>) -> Self {
+        let mut animation = Self {
+            enabled,
+            frame_rate: None,
+            interval: None,
+            vsync_rate: None,
+            last_present: None,
+            next_frame: Instant::now(),
+        };
+        animation.set_frame_rate(frame_rate);
+        animation
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.next_frame = Instant::now();
+    }
+
+    // The cap, `None` or zero for uncapped. Applies from the next frame on.
+    pub fn frame_rate(&self) -> Option<u32> {
+        self.frame_rate
+    }
+
+    pub fn set_frame_rate(&mut self, frame_rate: Option<u32>) {
+        self.frame_rate = frame_rate.filter(|&fps| fps > 0);
+        self.update_interval();
+    }
+
+    // The next of the preset limits, for cycling through them with a key.
+    pub fn next_frame_rate(&self) -> Option<u32> {
+        let current = self.frame_rate.unwrap_or(u32::MAX);
+        FRAME_RATE_STEPS
+            .into_iter()
+            .find(|step| step.unwrap_or(u32::MAX) > current)
+            .unwrap_or(FRAME_RATE_STEPS[0])
+    }
+
+    // Fifo presentation already waits for the display, so a cap at or above
+    // its refresh rate would only delay frames past the next vblank.
+    pub fn set_vsync_rate(&mut self, rate: Option<u32>) {
+        if rate != self.vsync_rate {
+            self.vsync_rate = rate;
+            self.update_interval();
+        }
+    }
+
+    fn update_interval(&mut self) {
+        let limited = match (self.frame_rate, self.vsync_rate) {
+            (Some(fps), Some(vsync)) if fps >= vsync => None,
+            (fps, _) => fps,
+        };
+        self.interval = limited.map(|fps| Duration::from_secs(1) / fps);
+        self.next_frame = match (self.last_present, self.interval) {
+            (Some(last), Some(interval)) => last + interval,
+            _ => Instant::now(),
+        };
+    }
+
+    pub fn control_flow(&self) -> ControlFlow {
+        match (self.enabled, self.interval) {
+            (false, _) => ControlFlow::Wait,
+            (true, None) => ControlFlow::Poll,
+            (true, Some(_)) => ControlFlow::WaitUntil(self.next_frame),
+        }
+    }
+
+    // When the frame after the last present may start.
+    pub fn next_frame(&self) -> Instant {
+        self.next_frame
+    }
+
+    // Whether enough time passed since the last present for another frame.
+    pub fn ready(&self) -> bool {
+        self.interval.is_none() || Instant::now() >= self.next_frame
+    }
+
+    // True once per frame interval while enabled.
+    pub fn frame_due(&mut self) -> bool {
+        if !self.enabled || !self.ready() {
+            return false;
+        }
+        // Until the frame is presented, so the request isn't repeated.
+        if let Some(interval) = self.interval {
+            self.next_frame = Instant::now() + interval;
+        }
+        true
+    }
+
+    // Deadlines count from when frames were presented, so time spent
+    // drawing doesn't shorten the wait.
+    pub fn presented(&mut self, at: Instant) {
+        self.last_present = Some(at);
+        if let Some(interval) = self.interval {
+            self.next_frame = at + interval;
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use ab_glyph::FontArc;
+use anyhow::Context;
+use tokio::sync::mpsc;
+
+use crate::{render::RenderContext, sprite::SpriteId, text::FontId};
+
+// Where a load puts what it loaded. A newer load for the same slot
+// supersedes one still in flight, whose result is then thrown away.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AssetSlot {
+    // The sprite of that name, which `DrawCommand::Texture` draws.
+    Texture(String),
+    Font(String),
+    // The main window's shader.
+    Shader,
+}
+
+// What went into a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Loaded {
+    Texture(SpriteId),
+    Font(FontId),
+    Shader,
+}
+
+// A load that finished, or failed, see `Handle::on_asset`. Superseded
+// loads end without one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetEvent {
+    pub slot: AssetSlot,
+    pub path: PathBuf,
+    pub result: Result<Loaded, String>,
+}
+
+// Decoded on a blocking worker thread, to be uploaded on the event loop.
+#[derive(Debug, Clone)]
+enum AssetData {
+    Texture(image::RgbaImage),
+    Font(FontArc),
+    Shader(String),
+}
+
+fn decode_texture(path: &Path) -> anyhow::Result<AssetData> {
+    Ok(AssetData::Texture(
+        crate::viewer::load_image(path)?.into_rgba8(),
+    ))
+}
+
+fn decode_font(path: &Path) -> anyhow::Result<AssetData> {
+    let bytes = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+    let font = FontArc::try_from_vec(bytes).with_context(|| format!("invalid font {:?}", path))?;
+    Ok(AssetData::Font(font))
+}
+
+fn decode_shader(path: &Path) -> anyhow::Result<AssetData> {
+    crate::shader::load_shader(path).map(AssetData::Shader)
+}
+
+// A decoded asset on its way to the GPU, see `UserEvent::AssetLoaded`.
+#[derive(Debug, Clone)]
+pub struct LoadedAsset {
+    slot: AssetSlot,
+    generation: u64,
+    path: PathBuf,
+    data: Result<AssetData, String>,
+    reply: mpsc::UnboundedSender<Result<Loaded, String>>,
+}
+
+impl LoadedAsset {
+    // Puts the asset into its slot on `ctx`; scenes pick it up on their
+    // next frame.
+    pub(crate) fn upload(&mut self, ctx: &mut RenderContext) -> Result<Loaded, String> {
+        let data = std::mem::replace(&mut self.data, Err("already uploaded".to_string()))?;
+        let result = match (&self.slot, data) {
+            (AssetSlot::Texture(name), AssetData::Texture(image)) => match ctx.sprite(name) {
+                Some(id) => ctx.replace_sprite(id, image).map(|()| Loaded::Texture(id)),
+                None => ctx.add_sprite(name, image).map(Loaded::Texture),
+            },
+            (AssetSlot::Font(_), AssetData::Font(font)) => {
+                Ok(Loaded::Font(ctx.text().add_font(font)))
+            }
+            (AssetSlot::Shader, AssetData::Shader(source)) => {
+                ctx.reload_shader(&source).map(|()| Loaded::Shader)
+            }
+            (slot, _) => Err(anyhow::anyhow!("{:?} got the wrong kind of asset", slot)),
+        };
+        result.map_err(|e| format!("{:#}", e))
+    }
+
+    // Answers the load's future and makes the event for the callbacks.
+    pub(crate) fn finish(self, result: Result<Loaded, String>) -> AssetEvent {
+        // The future may have been dropped, which is fine.
+        let _ = self.reply.send(result.clone());
+        match &result {
+            Ok(_) => log::info!("loaded {:?} from {:?}", self.slot, self.path),
+            Err(e) => log::error!("failed to load {:?}: {}", self.slot, e),
+        }
+        AssetEvent {
+            slot: self.slot,
+            path: self.path,
+            result,
+        }
+    }
+}
+
+// The latest load of every slot, shared by the handles and the loads.
+#[derive(Clone, Default)]
+pub(crate) struct Generations(Arc<Mutex<HashMap<AssetSlot, u64>>>);
+
+impl Generations {
+    fn begin(&self, slot: &AssetSlot) -> u64 {
+        let mut generations = self.0.lock().unwrap();
+        let generation = generations.entry(slot.clone()).or_default();
+        *generation += 1;
+        *generation
+    }
+
+    pub(crate) fn is_current(&self, asset: &LoadedAsset) -> bool {
+        self.0.lock().unwrap().get(&asset.slot) == Some(&asset.generation)
+    }
+}
+
+// Claims the slot right away, so a later load supersedes this one even if
+// this future is only polled afterwards. Decoding runs on tokio's blocking
+// threads; the upload waits for the event loop, which `send` delivers to.
+pub(crate) fn load(
+    generations: &Generations,
+    slot: AssetSlot,
+    path: PathBuf,
+    send: impl FnOnce(LoadedAsset) -> bool + Send + 'static,
+) -> impl std::future::Future<Output = anyhow::Result<Loaded>> + Send + 'static {
+    let generation = generations.begin(&slot);
+    let generations = generations.clone();
+    async move {
+        let decode = match slot {
+            AssetSlot::Texture(_) => decode_texture,
+            AssetSlot::Font(_) => decode_font,
+            AssetSlot::Shader => decode_shader,
+        };
+        let decoding = path.clone();
+        let data = match tokio::task::spawn_blocking(move || decode(&decoding)).await {
+            Ok(data) => data.map_err(|e| format!("{:#}", e)),
+            Err(e) => Err(format!("decoding {:?} panicked: {}", path, e)),
+        };
+        let (reply, mut replied) = mpsc::unbounded_channel();
+        let asset = LoadedAsset {
+            slot,
+            generation,
+            path,
+            data,
+            reply,
+        };
+        if !generations.is_current(&asset) {
+            anyhow::bail!("superseded by a newer load of {:?}", asset.slot);
+        }
+        let slot = asset.slot.clone();
+        if !send(asset) {
+            anyhow::bail!("the event loop has exited");
+        }
+        match replied.recv().await {
+            Some(result) => result.map_err(anyhow::Error::msg),
+            // Dropped unanswered, superseded on the event loop.
+            None => anyhow::bail!("superseded by a newer load of {:?}", slot),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use winit::window::Window;
+
+// What the compositor draws behind a transparent window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backdrop {
+    // Whatever is behind the window, unchanged.
+    None,
+    Blur,
+    // Blur with the noise and tint of Windows' acrylic material, falling
+    // back to blur where it isn't available.
+    Acrylic,
+}
+
+impl Default for Backdrop {
+    fn default() -> Self {
+        Backdrop::None
+    }
+}
+
+impl FromStr for Backdrop {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Backdrop::None),
+            "blur" => Ok(Backdrop::Blur),
+            "acrylic" => Ok(Backdrop::Acrylic),
+            _ => anyhow::bail!("unknown backdrop {:?}, expected none, blur or acrylic", s),
+        }
+    }
+}
+
+// Only Windows lets applications ask for this, through DWM. On failure the
+// window keeps plain transparency.
+pub fn set_backdrop(window: &Window, backdrop: Backdrop) -> anyhow::Result<()> {
+    platform::set_backdrop(window, backdrop)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::{ffi::c_void, mem::size_of, ptr};
+
+    use winapi::{
+        shared::{minwindef::BOOL, windef::HWND},
+        um::{
+            dwmapi::{DwmEnableBlurBehindWindow, DWM_BB_ENABLE, DWM_BLURBEHIND},
+            libloaderapi::{GetModuleHandleA, GetProcAddress},
+        },
+    };
+    use winit::{platform::windows::WindowExtWindows, window::Window};
+
+    use super::Backdrop;
+
+    // SetWindowCompositionAttribute is undocumented, these are the values
+    // everyone uses.
+    const WCA_ACCENT_POLICY: u32 = 19;
+    const ACCENT_DISABLED: u32 = 0;
+    const ACCENT_ENABLE_BLURBEHIND: u32 = 3;
+    const ACCENT_ENABLE_ACRYLICBLURBEHIND: u32 = 4;
+    // Acrylic mixes in this ABGR tint; a fully transparent one turns it off
+    // on some builds, so keep a trace of alpha.
+    const ACRYLIC_TINT: u32 = 0x0100_0000;
+
+    #[repr(C)]
+    struct AccentPolicy {
+        accent_state: u32,
+        accent_flags: u32,
+        gradient_color: u32,
+        animation_id: u32,
+    }
+
+    #[repr(C)]
+    struct WindowCompositionAttribData {
+        attribute: u32,
+        data: *mut c_void,
+        size: usize,
+    }
+
+    type SetWindowCompositionAttribute =
+        unsafe extern "system" fn(HWND, *mut WindowCompositionAttribData) -> BOOL;
+
+    fn set_accent(hwnd: HWND, accent_state: u32) -> bool {
+        unsafe {
+            let user32 = GetModuleHandleA(b"user32.dll\0".as_ptr() as _);
+            if user32.is_null() {
+                return false;
+            }
+            let proc = GetProcAddress(user32, b"SetWindowCompositionAttribute\0".as_ptr() as _);
+            if proc.is_null() {
+                return false;
+            }
+            let set: SetWindowCompositionAttribute = std::mem::transmute(proc);
+            let mut policy = AccentPolicy {
+                accent_state,
+                accent_flags: 0,
+                gradient_color: ACRYLIC_TINT,
+                animation_id: 0,
+            };
+            let mut data = WindowCompositionAttribData {
+                attribute: WCA_ACCENT_POLICY,
+                data: &mut policy as *mut _ as *mut c_void,
+                size: size_of::<AccentPolicy>(),
+            };
+            set(hwnd, &mut data) != 0
+        }
+    }
+
+    // The Windows 7 and 8 way, blurring the whole client area.
+    fn blur_behind(hwnd: HWND, enable: bool) -> bool {
+        let blur = DWM_BLURBEHIND {
+            dwFlags: DWM_BB_ENABLE,
+            fEnable: enable as BOOL,
+            hRgnBlur: ptr::null_mut(),
+            fTransitionOnMaximized: 0,
+        };
+        unsafe { DwmEnableBlurBehindWindow(hwnd, &blur) >= 0 }
+    }
+
+    pub fn set_backdrop(window: &Window, backdrop: Backdrop) -> anyhow::Result<()> {
+        let hwnd = window.hwnd() as HWND;
+        if backdrop == Backdrop::None {
+            set_accent(hwnd, ACCENT_DISABLED);
+            blur_behind(hwnd, false);
+            return Ok(());
+        }
+        // Acrylic needs Windows 10 1803, the accent policy 1507.
+        if backdrop == Backdrop::Acrylic {
+            if set_accent(hwnd, ACCENT_ENABLE_ACRYLICBLURBEHIND) {
+                return Ok(());
+            }
+            log::info!("acrylic is not available, using blur");
+        }
+        if set_accent(hwnd, ACCENT_ENABLE_BLURBEHIND) || blur_behind(hwnd, true) {
+            Ok(())
+        } else {
+            anyhow::bail!("DWM refused to blur behind the window")
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    use winit::window::Window;
+
+    use super::Backdrop;
+
+    pub fn set_backdrop(_window: &Window, backdrop: Backdrop) -> anyhow::Result<()> {
+        if backdrop == Backdrop::None {
+            return Ok(());
+        }
+        anyhow::bail!("window backdrops are only supported on windows")
+    }
+}
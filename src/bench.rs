@@ -0,0 +1,224 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::{gpu::Gpu, render::RenderContext};
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    // Measured after the warm-up.
+    pub frames: u64,
+    // Drawn first and left out of the numbers, while pipelines are created
+    // and caches fill.
+    pub warmup: u64,
+    // Write the report here as JSON as well.
+    pub json: Option<PathBuf>,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            frames: 1000,
+            warmup: 60,
+            json: None,
+        }
+    }
+}
+
+// What the frames were drawn with.
+#[derive(Debug, Clone, Serialize)]
+struct BenchConfig {
+    adapter: String,
+    backend: String,
+    format: String,
+    // None offscreen, where nothing is presented.
+    present_mode: Option<String>,
+    msaa: u32,
+    width: u32,
+    height: u32,
+}
+
+impl BenchConfig {
+    fn of(ctx: &RenderContext) -> Self {
+        let info = ctx.gpu().adapter.get_info();
+        let size = ctx.size();
+        Self {
+            adapter: info.name,
+            backend: format!("{:?}", info.backend),
+            format: format!("{:?}", ctx.surface_config().format),
+            present_mode: (!ctx.is_headless()).then(|| format!("{:?}", ctx.present_mode())),
+            msaa: ctx.sample_count(),
+            width: size.width,
+            height: size.height,
+        }
+    }
+}
+
+// In milliseconds.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+struct Summary {
+    min: f64,
+    avg: f64,
+    p95: f64,
+    p99: f64,
+    max: f64,
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl Summary {
+    fn of(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * (samples.len() - 1) as f64).round() as usize;
+            ms(samples[rank])
+        };
+        let total: Duration = samples.iter().sum();
+        Self {
+            min: ms(samples[0]),
+            avg: ms(total) / samples.len() as f64,
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: ms(samples[samples.len() - 1]),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:8.3} {:8.3} {:8.3} {:8.3} {:8.3}",
+            self.min, self.avg, self.p95, self.p99, self.max
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BenchReport {
+    config: BenchConfig,
+    warmup: u64,
+    frames: u64,
+    failed: u64,
+    cpu_ms: Summary,
+    gpu_ms: Summary,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let config = &self.config;
+        writeln!(
+            f,
+            "{} ({}), {} {}x{}, present mode {}, MSAA {}x",
+            config.adapter,
+            config.backend,
+            config.format,
+            config.width,
+            config.height,
+            config.present_mode.as_deref().unwrap_or("offscreen"),
+            config.msaa
+        )?;
+        writeln!(
+            f,
+            "{} frames after {} warm-up, {} failed",
+            self.frames, self.warmup, self.failed
+        )?;
+        writeln!(f, "ms        min      avg      p95      p99      max")?;
+        writeln!(f, "cpu {}", self.cpu_ms)?;
+        write!(f, "gpu {}", self.gpu_ms)
+    }
+}
+
+// Times frames drawn back to back, on screen or offscreen. A frame's CPU
+// time is the draw call up to presenting; its GPU time runs from there
+// until the device is idle again, as there are no timestamp queries on
+// every backend. It includes the queue's latency, so it is comparable
+// between runs rather than exact.
+pub(crate) struct Bench {
+    options: BenchOptions,
+    config: BenchConfig,
+    drawn: u64,
+    failed: u64,
+    cpu: Vec<Duration>,
+    gpu: Vec<Duration>,
+}
+
+impl Bench {
+    pub fn new(options: BenchOptions, ctx: &RenderContext) -> Self {
+        let config = BenchConfig::of(ctx);
+        log::info!(
+            "benchmarking {} frames after {} warm-up on {}",
+            options.frames,
+            options.warmup,
+            config.adapter
+        );
+        Self {
+            cpu: Vec::with_capacity(options.frames as usize),
+            gpu: Vec::with_capacity(options.frames as usize),
+            options,
+            config,
+            drawn: 0,
+            failed: 0,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.drawn >= self.options.warmup + self.options.frames
+    }
+
+    // Counts a frame drawn since `started`, once the GPU finished it.
+    pub fn record<T>(
+        &mut self,
+        gpu: &Gpu,
+        started: Instant,
+        result: &Result<T, wgpu::SurfaceError>,
+    ) {
+        let drawn = Instant::now();
+        gpu.device.poll(wgpu::Maintain::Wait);
+        let idle = Instant::now();
+        self.drawn += 1;
+        match result {
+            Ok(_) if self.drawn > self.options.warmup => {
+                self.cpu.push(drawn - started);
+                self.gpu.push(idle - drawn);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("benchmark frame {} failed: {}", self.drawn, e);
+                self.failed += 1;
+            }
+        }
+    }
+
+    // Prints the report and writes the JSON one; fails if any frame did.
+    pub fn finish(self) -> anyhow::Result<()> {
+        let report = BenchReport {
+            config: self.config,
+            warmup: self.options.warmup,
+            frames: self.cpu.len() as u64,
+            failed: self.failed,
+            cpu_ms: Summary::of(self.cpu),
+            gpu_ms: Summary::of(self.gpu),
+        };
+        println!("{}", report);
+        if let Some(path) = &self.options.json {
+            let json = serde_json::to_string_pretty(&report)?;
+            std::fs::write(path, json).with_context(|| format!("failed to write {:?}", path))?;
+            log::info!("wrote {:?}", path);
+        }
+        if report.failed > 0 {
+            anyhow::bail!("{} of {} frames failed", report.failed, self.drawn);
+        }
+        Ok(())
+    }
+}
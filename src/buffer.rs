@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+
+// A GPU buffer that is rewritten through `queue.write_buffer` and reallocated
+// at the next power of two when the data no longer fits.
+pub(crate) struct DynamicBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: wgpu::Buffer,
+    capacity: wgpu::BufferAddress,
+    len: wgpu::BufferAddress,
+}
+
+impl DynamicBuffer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: wgpu::BufferAddress,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        let capacity = align(capacity.max(wgpu::COPY_BUFFER_ALIGNMENT));
+        Self {
+            label,
+            usage,
+            buffer: create_buffer(device, label, usage, capacity),
+            capacity,
+            len: 0,
+        }
+    }
+
+    // Returns true when the buffer had to be reallocated, so anything bound to
+    // the old one (bind groups) must be recreated.
+    pub(crate) fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[u8],
+    ) -> bool {
+        let data = pad(data);
+        let size = data.len() as wgpu::BufferAddress;
+        let grown = size > self.capacity;
+        if grown {
+            self.capacity = size.next_power_of_two();
+            self.buffer = create_buffer(device, self.label, self.usage, self.capacity);
+        }
+        if size > 0 {
+            queue.write_buffer(&self.buffer, 0, &data);
+        }
+        self.len = size;
+        grown
+    }
+
+    pub(crate) fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub(crate) fn slice(&self) -> wgpu::BufferSlice {
+        self.buffer
+            .slice(..self.len.max(wgpu::COPY_BUFFER_ALIGNMENT))
+    }
+}
+
+fn create_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    usage: wgpu::BufferUsages,
+    size: wgpu::BufferAddress,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage,
+        mapped_at_creation: false,
+    })
+}
+
+fn align(size: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    let mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+    (size + mask) & !mask
+}
+
+// `write_buffer` needs sizes that are a multiple of COPY_BUFFER_ALIGNMENT,
+// which an odd number of u16 indices isn't.
+fn pad(data: &[u8]) -> Cow<[u8]> {
+    let size = align(data.len() as wgpu::BufferAddress) as usize;
+    if size == data.len() {
+        Cow::Borrowed(data)
+    } else {
+        let mut padded = data.to_vec();
+        padded.resize(size, 0);
+        Cow::Owned(padded)
+    }
+}
@@ -0,0 +1,48 @@
+#[cfg(feature = "clipboard")]
+use anyhow::Context;
+
+// The system clipboard's text, with the `clipboard` feature. Opened on
+// first use and kept open, as on X11 and Wayland copied text lasts only as
+// long as the program that copied it keeps serving it.
+#[derive(Default)]
+pub(crate) struct Clipboard {
+    #[cfg(feature = "clipboard")]
+    inner: Option<arboard::Clipboard>,
+}
+
+#[cfg(feature = "clipboard")]
+impl Clipboard {
+    fn open(&mut self) -> anyhow::Result<&mut arboard::Clipboard> {
+        if self.inner.is_none() {
+            self.inner = Some(arboard::Clipboard::new().context("failed to open the clipboard")?);
+        }
+        Ok(self.inner.as_mut().expect("the clipboard was just opened"))
+    }
+
+    // `None` when the clipboard holds something other than text, e.g. an
+    // image, or nothing at all.
+    pub fn text(&mut self) -> anyhow::Result<Option<String>> {
+        match self.open()?.get_text() {
+            Ok(text) => Ok(Some(text)),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(e) => Err(e).context("failed to read the clipboard"),
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) -> anyhow::Result<()> {
+        self.open()?
+            .set_text(text.to_string())
+            .context("failed to write to the clipboard")
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+impl Clipboard {
+    pub fn text(&mut self) -> anyhow::Result<Option<String>> {
+        anyhow::bail!("built without clipboard support")
+    }
+
+    pub fn set_text(&mut self, _text: &str) -> anyhow::Result<()> {
+        anyhow::bail!("built without clipboard support")
+    }
+}
@@ -0,0 +1,232 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+// Colors are linear `[f32; 4]` wherever the crate takes them: vertex
+// colors, shapes, text, tints, gradient stops and the clear color. Colors
+// from users, hex strings and 8-bit values, are sRGB-encoded and converted
+// once where they come in, with `HexColor::to_linear` or `srgb8`. Whether
+// the surface encodes on write or a shader does it, see `Globals`, is then
+// the renderer's business.
+
+// An 8-bit RGBA color written as `#rrggbb` or `#rrggbbaa`, or short as
+// `#rgb` or `#rgba`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexColor(pub [u8; 4]);
+
+// One channel from 0 to 1, the curve from the sRGB spec rather than a
+// gamma of 2.2.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// An 8-bit sRGB color as the linear color the drawing calls take, e.g.
+// `srgb8([0x1e, 0x90, 0xff, 0xff])`.
+pub fn srgb8(rgba: [u8; 4]) -> [f32; 4] {
+    HexColor(rgba).to_linear()
+}
+
+// Multiplies RGBA8 texels by their alpha in place. Texels of sRGB formats
+// are decoded when sampled, so they're multiplied as linear values and
+// encoded again.
+pub(crate) fn premultiply_rgba8(data: &mut [u8], srgb: bool) {
+    for texel in data.chunks_exact_mut(4) {
+        let alpha = texel[3] as f32 / 255.0;
+        for c in &mut texel[..3] {
+            let value = *c as f32 / 255.0;
+            let value = if srgb {
+                linear_to_srgb(srgb_to_linear(value) * alpha)
+            } else {
+                value * alpha
+            };
+            *c = (value * 255.0).round() as u8;
+        }
+    }
+}
+
+impl HexColor {
+    pub const TRANSPARENT: HexColor = HexColor([0, 0, 0, 0]);
+
+    // Hex colors are sRGB-encoded; wgpu colors are linear like the rest of
+    // the color values in the crate. Alpha is linear either way.
+    pub fn to_linear(self) -> [f32; 4] {
+        let [r, g, b, a] = self.0.map(|c| c as f32 / 255.0);
+        [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+    }
+
+    // The nearest 8-bit color to a linear one.
+    pub fn from_linear(color: [f32; 4]) -> Self {
+        let [r, g, b, a] = color;
+        let encoded = [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a];
+        HexColor(encoded.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+    }
+
+    pub fn to_wgpu(self) -> wgpu::Color {
+        let [r, g, b, a] = self.to_linear().map(|c| c as f64);
+        wgpu::Color { r, g, b, a }
+    }
+
+    pub fn from_wgpu(color: wgpu::Color) -> Self {
+        Self::from_linear([color.r, color.g, color.b, color.a].map(|c| c as f32))
+    }
+
+    pub fn is_opaque(self) -> bool {
+        self.0[3] == 0xff
+    }
+}
+
+impl FromStr for HexColor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        // Digits per channel.
+        let digits = match hex.len() {
+            3 | 4 => 1,
+            6 | 8 => 2,
+            _ => 0,
+        };
+        if digits == 0 || !hex.is_ascii() {
+            anyhow::bail!(
+                "invalid color {:?}, expected #rgb, #rgba, #rrggbb or #rrggbbaa",
+                s
+            );
+        }
+        let mut rgba = [0xff; 4];
+        for (i, c) in rgba.iter_mut().enumerate().take(hex.len() / digits) {
+            let value = u8::from_str_radix(&hex[i * digits..(i + 1) * digits], 16)
+                .map_err(|_| anyhow::anyhow!("invalid color {:?}", s))?;
+            // `#f80` is `#ff8800`.
+            *c = if digits == 1 { value * 0x11 } else { value };
+        }
+        Ok(HexColor(rgba))
+    }
+}
+
+impl TryFrom<String> for HexColor {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<HexColor> for String {
+    fn from(color: HexColor) -> Self {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for HexColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.0;
+        write!(f, "#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn transfer_curve_ends() {
+        assert_close(srgb_to_linear(0.0), 0.0);
+        assert_close(srgb_to_linear(1.0), 1.0);
+        assert_close(linear_to_srgb(0.0), 0.0);
+        assert_close(linear_to_srgb(1.0), 1.0);
+    }
+
+    #[test]
+    fn transfer_curve_knee() {
+        assert_close(srgb_to_linear(0.040_45), 0.003_130_8);
+        assert_close(linear_to_srgb(0.003_130_8), 0.040_45);
+        // Both sides of the knee meet.
+        assert_close(srgb_to_linear(0.040_46), 0.003_131_6);
+    }
+
+    #[test]
+    fn transfer_curve_round_trips() {
+        for i in 0..=255 {
+            let c = i as f32 / 255.0;
+            assert_close(linear_to_srgb(srgb_to_linear(c)), c);
+            assert_close(srgb_to_linear(linear_to_srgb(c)), c);
+        }
+    }
+
+    #[test]
+    fn srgb8_decodes_color_but_not_alpha() {
+        let [r, g, b, a] = srgb8([0xff, 0x00, 0x80, 0x80]);
+        assert_close(r, 1.0);
+        assert_close(g, 0.0);
+        assert_close(b, 0.215_860_5);
+        assert_close(a, 128.0 / 255.0);
+    }
+
+    #[test]
+    fn premultiplies_linear_texels() {
+        let mut data = [255, 128, 0, 128, 10, 20, 30, 255, 10, 20, 30, 0];
+        premultiply_rgba8(&mut data, false);
+        assert_eq!(data, [128, 64, 0, 128, 10, 20, 30, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn premultiplies_srgb_texels_in_linear() {
+        let mut data = [255, 255, 255, 128];
+        premultiply_rgba8(&mut data, true);
+        // Half of linear white, encoded again, not half of 255.
+        assert_eq!(data, [188, 188, 188, 128]);
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        let parse = |s: &str| s.parse::<HexColor>().unwrap().0;
+        assert_eq!(parse("#f80"), [0xff, 0x88, 0x00, 0xff]);
+        assert_eq!(parse("#f808"), [0xff, 0x88, 0x00, 0x88]);
+        assert_eq!(parse("#1e90ff"), [0x1e, 0x90, 0xff, 0xff]);
+        assert_eq!(parse("1E90FF80"), [0x1e, 0x90, 0xff, 0x80]);
+    }
+
+    #[test]
+    fn rejects_bad_hex_colors() {
+        for s in [
+            "",
+            "#",
+            "#12",
+            "#12345",
+            "#1234567",
+            "#123456789",
+            "#gggggg",
+            "#1234é",
+        ] {
+            assert!(s.parse::<HexColor>().is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn displays_as_it_parses() {
+        let color: HexColor = "#1e90ff80".parse().unwrap();
+        assert_eq!(color.to_string(), "#1e90ff80");
+    }
+}
@@ -0,0 +1,230 @@
+use std::{borrow::Cow, sync::Arc};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::Gpu,
+    pipeline::{PipelineCache, ShaderId, TargetConfig},
+    shader::validate_compute_shader,
+};
+
+// A storage buffer that a compute entry point updates every frame and the
+// vertex stage then draws from, one instance per element.
+//
+// The WGSL declares the buffer twice, as the stages need different access:
+//
+// ```wgsl
+// [[group(1), binding(0)]]
+// var<storage, read_write> state: Particles; // cs_main
+// [[group(1), binding(1)]]
+// var<storage, read> particles: Particles;   // vs_main
+// ```
+//
+// Group 0 holds the globals, as in the main shader. See
+// examples/particles.rs.
+#[derive(Debug, Clone)]
+pub struct ComputeOptions {
+    // With `cs_main`, run once per element, and `vs_main`/`fs_main`.
+    pub source: String,
+    pub element_size: u64,
+    pub count: u32,
+    // Initial contents, the rest of the buffer is zeroed.
+    pub contents: Vec<u8>,
+    pub vertices_per_element: u32,
+}
+
+pub(crate) struct ComputeRenderer {
+    options: ComputeOptions,
+    compute_pipeline: wgpu::ComputePipeline,
+    compute_bind_group: wgpu::BindGroup,
+    shader: ShaderId,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    render_bind_group: wgpu::BindGroup,
+    _buffer: wgpu::Buffer,
+    workgroups: u32,
+}
+
+fn storage_layout(
+    device: &wgpu::Device,
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+    element_size: u64,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("compute storage"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(element_size),
+            },
+            count: None,
+        }],
+    })
+}
+
+fn storage_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    binding: u32,
+    buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("compute storage"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding(),
+        }],
+    })
+}
+
+impl ComputeRenderer {
+    pub fn new(
+        gpu: &Gpu,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+        globals_layout: &wgpu::BindGroupLayout,
+        options: ComputeOptions,
+    ) -> anyhow::Result<Self> {
+        let device = &gpu.device;
+        // GL and WebGL2-class devices can't read storage buffers in the
+        // vertex stage, if they have compute at all.
+        let downlevel = gpu.adapter.get_downlevel_properties();
+        if !downlevel
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            anyhow::bail!("the adapter has no compute shaders");
+        }
+        if !downlevel
+            .flags
+            .contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+            || device.limits().max_storage_buffers_per_shader_stage == 0
+        {
+            anyhow::bail!("the adapter can't read storage buffers in vertex shaders");
+        }
+
+        let workgroup_size = validate_compute_shader(&options.source, "cs_main")?;
+        if options.element_size == 0 || options.count == 0 {
+            anyhow::bail!("the storage buffer is empty");
+        }
+        let size = options.element_size * options.count as u64;
+        if options.element_size % 4 != 0 {
+            anyhow::bail!("elements must be a multiple of 4 bytes");
+        }
+        let max = device.limits().max_storage_buffer_binding_size as u64;
+        if size > max {
+            anyhow::bail!(
+                "{} bytes of storage is past the device's limit of {}",
+                size,
+                max
+            );
+        }
+        if options.contents.len() as u64 > size {
+            anyhow::bail!("the initial contents are larger than the buffer");
+        }
+
+        let mut contents = options.contents.clone();
+        contents.resize(size as usize, 0);
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("compute storage"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("compute"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&options.source)),
+        });
+
+        let compute_layout = storage_layout(
+            device,
+            0,
+            wgpu::ShaderStages::COMPUTE,
+            false,
+            options.element_size,
+        );
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("compute"),
+                bind_group_layouts: &[globals_layout, &compute_layout],
+                push_constant_ranges: &[],
+            });
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("compute"),
+            layout: Some(&compute_pipeline_layout),
+            module: &module,
+            entry_point: "cs_main",
+        });
+        let compute_bind_group = storage_bind_group(device, &compute_layout, 0, &buffer);
+
+        let render_layout = storage_layout(
+            device,
+            1,
+            wgpu::ShaderStages::VERTEX,
+            true,
+            options.element_size,
+        );
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("compute render"),
+                bind_group_layouts: &[globals_layout, &render_layout],
+                push_constant_ranges: &[],
+            });
+        let render_bind_group = storage_bind_group(device, &render_layout, 1, &buffer);
+        let shader = cache.add_shader("compute", module, render_pipeline_layout);
+        let pipeline = cache.get_or_create(device, shader, &[], target);
+
+        let workgroups = (options.count + workgroup_size - 1) / workgroup_size;
+        Ok(Self {
+            options,
+            compute_pipeline,
+            compute_bind_group,
+            shader,
+            pipeline,
+            render_bind_group,
+            _buffer: buffer,
+            workgroups,
+        })
+    }
+
+    pub fn options(&self) -> &ComputeOptions {
+        &self.options
+    }
+
+    pub fn shader(&self) -> ShaderId {
+        self.shader
+    }
+
+    pub(crate) fn retarget(
+        &mut self,
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+    ) {
+        self.pipeline = cache.get_or_create(device, self.shader, &[], target);
+    }
+
+    // Recorded in the frame's encoder ahead of the render pass, which then
+    // sees the results.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, globals: &wgpu::BindGroup) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("compute"),
+        });
+        cpass.set_pipeline(&self.compute_pipeline);
+        cpass.set_bind_group(0, globals, &[]);
+        cpass.set_bind_group(1, &self.compute_bind_group, &[]);
+        cpass.dispatch(self.workgroups, 1, 1);
+    }
+
+    pub fn render<'p>(&'p self, rpass: &mut wgpu::RenderPass<'p>, globals: &'p wgpu::BindGroup) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals, &[]);
+        rpass.set_bind_group(1, &self.render_bind_group, &[]);
+        rpass.draw(0..self.options.vertices_per_element, 0..self.options.count);
+    }
+}
@@ -0,0 +1,484 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    bench::BenchOptions,
+    color::HexColor,
+    control::default_socket_path,
+    gpu::AdapterOptions,
+    keymap::KeyBindings,
+    post::PostEffect,
+    record::RecordOptions,
+    render::{RenderOptions, COMPOSITE_BLEND, PREMULTIPLIED_BLEND},
+    shader::load_shader,
+    viewer::Fit,
+    window::WindowOptions,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub window: WindowOptions,
+    // Additional bars opened next to the main window.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<WindowOptions>,
+    pub render: RenderConfig,
+    // A `[keys]` table replaces the default bindings as a whole.
+    pub keys: KeyBindings,
+    // Draw the frame timing readout in the corner of every window.
+    pub show_stats: bool,
+    // Redraw continuously instead of only when something changes.
+    pub animate: bool,
+    // Draw each window on a thread of its own, so a slow frame doesn't hold
+    // up input; off draws everything on the event loop.
+    pub render_thread: bool,
+    // Milliseconds windows take to fade in when shown and out when hidden,
+    // by key, from the tray or over the control socket; 0 pops them.
+    pub fade_ms: u64,
+    // A system-wide shortcut that shows and hides the windows even while
+    // none has focus, e.g. "super+grave" or "ctrl+alt+space"; empty for
+    // none. Needs the `hotkey` feature and applies after a restart.
+    pub hotkey: String,
+    // Most frames per second, for continuous redraws and scene changes
+    // alike; unset or zero is uncapped.
+    #[serde(alias = "max_fps", skip_serializing_if = "Option::is_none")]
+    pub frame_rate: Option<u32>,
+    // Screenshot file name, `{timestamp}` is replaced by the capture time
+    // in milliseconds since the Unix epoch.
+    pub screenshot: String,
+    // Start recording the main window right away.
+    #[serde(skip)]
+    pub record: Option<RecordOptions>,
+    // Draw this many frames as fast as possible, print their timings and
+    // exit.
+    #[serde(skip)]
+    pub bench: Option<BenchOptions>,
+    // Load the main shader from this file and rebuild it on every change.
+    #[serde(skip)]
+    pub watch_shader: Option<PathBuf>,
+    // Show this image in the main window instead of the geometry.
+    #[serde(skip)]
+    pub image: Option<PathBuf>,
+    pub image_fit: Fit,
+    // Listen for commands from `mgraphics ctl` and other scripts.
+    pub control: bool,
+    // Defaults to `default_socket_path()`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control_socket: Option<PathBuf>,
+    // Where the config was loaded from and where `save` writes it.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window: WindowOptions::default(),
+            windows: Vec::new(),
+            render: RenderConfig::default(),
+            keys: KeyBindings::default(),
+            show_stats: false,
+            animate: false,
+            render_thread: true,
+            fade_ms: 200,
+            hotkey: "super+grave".to_string(),
+            frame_rate: None,
+            screenshot: "mgraphics-{timestamp}.png".to_string(),
+            record: None,
+            bench: None,
+            watch_shader: None,
+            image: None,
+            image_fit: Fit::default(),
+            control: true,
+            control_socket: None,
+            path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    Mailbox,
+    Fifo,
+    Immediate,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+// How the built-in renderers hand their colors to the blend stage. The
+// frame ends up premultiplied either way, which is what X11 and Wayland
+// compositors, DWM and Core Animation all take for transparent windows;
+// wgpu picks the surface's composite alpha itself, so the frame is what
+// has to match.
+//
+// Premultiplied shaders multiply by alpha and the atlases store
+// premultiplied texels, so filtering at sprite and emoji edges doesn't
+// bleed the color of transparent texels in. Straight keeps the colors as
+// they are and premultiplies in `COMPOSITE_BLEND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlphaMode {
+    Premultiplied,
+    Straight,
+}
+
+impl Default for AlphaMode {
+    fn default() -> Self {
+        AlphaMode::Premultiplied
+    }
+}
+
+impl FromStr for AlphaMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "premultiplied" => Ok(AlphaMode::Premultiplied),
+            "straight" => Ok(AlphaMode::Straight),
+            _ => anyhow::bail!(
+                "unknown alpha mode {:?}, expected premultiplied or straight",
+                s
+            ),
+        }
+    }
+}
+
+impl AlphaMode {
+    pub fn blend(self) -> wgpu::BlendState {
+        match self {
+            AlphaMode::Premultiplied => PREMULTIPLIED_BLEND,
+            AlphaMode::Straight => COMPOSITE_BLEND,
+        }
+    }
+
+    pub(crate) fn is_premultiplied(self) -> bool {
+        self == AlphaMode::Premultiplied
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+// Also takes the names wgpu's own `WGPU_BACKEND` accepts.
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Backend::Auto),
+            "vulkan" | "vk" => Ok(Backend::Vulkan),
+            "dx12" | "d3d12" => Ok(Backend::Dx12),
+            "metal" | "mtl" => Ok(Backend::Metal),
+            "gl" | "gles" | "opengl" => Ok(Backend::Gl),
+            _ => anyhow::bail!(
+                "unknown backend {:?}, expected vulkan, dx12, metal, gl or auto",
+                s
+            ),
+        }
+    }
+}
+
+impl Backend {
+    // The platform's native backend first, then the other one that works
+    // there, GL last since it is the least capable.
+    fn platform_order() -> &'static [Backend] {
+        if cfg!(windows) {
+            &[Backend::Dx12, Backend::Vulkan, Backend::Gl]
+        } else if cfg!(target_os = "macos") {
+            &[Backend::Metal, Backend::Gl]
+        } else {
+            &[Backend::Vulkan, Backend::Gl]
+        }
+    }
+
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            Backend::Auto => Backend::platform_order()
+                .iter()
+                .fold(wgpu::Backends::empty(), |all, backend| {
+                    all | backend.backends()
+                }),
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+
+    // The backends to try one after the other: this one, then the rest of
+    // the platform's in order.
+    pub fn fallbacks(self) -> Vec<wgpu::Backends> {
+        let rest = Backend::platform_order()
+            .iter()
+            .filter(|&&backend| backend != self);
+        std::iter::once(self)
+            .filter(|&backend| backend != Backend::Auto)
+            .chain(rest.copied())
+            .map(Backend::backends)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerPreference {
+    Low,
+    High,
+}
+
+impl FromStr for PowerPreference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(PowerPreference::Low),
+            "high" => Ok(PowerPreference::High),
+            _ => anyhow::bail!("unknown power preference {:?}, expected low or high", s),
+        }
+    }
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(preference: PowerPreference) -> Self {
+        match preference {
+            PowerPreference::Low => wgpu::PowerPreference::LowPower,
+            PowerPreference::High => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderConfig {
+    // A single mode or a preference list; unsupported modes are skipped.
+    #[serde(deserialize_with = "one_or_many")]
+    pub present_mode: Vec<PresentMode>,
+    pub backend: Backend,
+    // Pick the first adapter whose name contains this.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adapter: Option<String>,
+    pub power_preference: PowerPreference,
+    // `#rrggbb` or `#rrggbbaa`; an opaque color makes the windows opaque.
+    pub clear_color: HexColor,
+    // Draw over the previous frame instead of clearing it.
+    pub keep_contents: bool,
+    // WGSL file replacing the built-in shader, see `Config::resolve_path`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shader: Option<PathBuf>,
+    pub sample_count: u32,
+    pub depth: bool,
+    // "premultiplied" or "straight", see `AlphaMode`.
+    pub alpha_mode: AlphaMode,
+    // Full-screen effects in order, e.g. `[{ effect = "blur", radius = 4.0 }]`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub post: Vec<PostEffect>,
+    // Directory of PNGs for the sprite atlas, named by file name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sprites: Option<PathBuf>,
+    // Distance field text, which stays sharp when scaled.
+    pub sdf_text: bool,
+    // A color emoji font with PNG bitmaps, e.g. Noto Color Emoji. The usual
+    // system locations are searched when not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji_font: Option<PathBuf>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            present_mode: vec![
+                PresentMode::Mailbox,
+                PresentMode::Immediate,
+                PresentMode::Fifo,
+            ],
+            backend: Backend::Auto,
+            adapter: None,
+            power_preference: PowerPreference::Low,
+            clear_color: HexColor::TRANSPARENT,
+            keep_contents: false,
+            shader: None,
+            sample_count: 1,
+            depth: false,
+            alpha_mode: AlphaMode::default(),
+            post: Vec::new(),
+            sprites: None,
+            sdf_text: false,
+            emoji_font: None,
+        }
+    }
+}
+
+impl RenderConfig {
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            present_modes: self.present_mode.iter().map(|&mode| mode.into()).collect(),
+            backends: self.backend.fallbacks(),
+            adapter: AdapterOptions {
+                power_preference: self.power_preference.into(),
+                name: self.adapter.clone(),
+            },
+            clear_color: self.clear_color.to_wgpu(),
+            keep_contents: self.keep_contents,
+            sample_count: self.sample_count,
+            depth: self.depth,
+            alpha_mode: self.alpha_mode,
+            post_effects: self.post.clone(),
+            sdf_text: self.sdf_text,
+            ..Default::default()
+        }
+    }
+}
+
+impl Config {
+    pub fn fade_duration(&self) -> Duration {
+        Duration::from_millis(self.fade_ms)
+    }
+
+    // `~` is the home directory. Relative paths that don't exist in the
+    // working directory are taken relative to the config file.
+    pub fn resolve_path(&self, path: &Path) -> PathBuf {
+        if let (Ok(rest), Some(home)) = (path.strip_prefix("~"), dirs::home_dir()) {
+            return home.join(rest);
+        }
+        if path.is_relative() && !path.exists() {
+            if let Some(dir) = self.path.as_deref().and_then(Path::parent) {
+                let candidate = dir.join(path);
+                if candidate.exists() {
+                    return candidate;
+                }
+            }
+        }
+        path.to_path_buf()
+    }
+
+    // The render options with the configured shader loaded. A shader that
+    // can't be read or doesn't validate is reported and the built-in one
+    // used instead.
+    pub fn render_options(&self) -> RenderOptions {
+        let mut options = self.render.render_options();
+        options.sprites = self
+            .render
+            .sprites
+            .as_ref()
+            .map(|dir| self.resolve_path(dir));
+        options.emoji_font = self
+            .render
+            .emoji_font
+            .as_ref()
+            .map(|path| self.resolve_path(path));
+        if let Some(path) = &self.render.shader {
+            let path = self.resolve_path(path);
+            match load_shader(&path) {
+                Ok(source) => {
+                    log::info!("using shader {:?}", path);
+                    options.shader = Some(source);
+                }
+                Err(e) => log::error!("{:#}\nusing the default shader", e),
+            }
+        }
+        options
+    }
+
+    // `$XDG_CONFIG_HOME/mgraphics/config.toml` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("mgraphics").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("failed to read {:?}", path))?;
+        let mut config: Self =
+            toml::from_str(&text).with_context(|| format!("invalid config {:?}", path))?;
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    // An explicit path must exist; a missing file at the default location
+    // just means defaults, saved there once something changes.
+    pub fn load_or_default(path: Option<&Path>) -> anyhow::Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => Self::load(&path),
+                path => Ok(Self {
+                    path,
+                    ..Self::default()
+                }),
+            },
+        }
+    }
+
+    // Rewrites the whole file, so comments in it are lost.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("the config has no file to save to"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("failed to create {:?}", dir))?;
+        }
+        std::fs::write(path, self.to_toml()?).with_context(|| format!("failed to write {:?}", path))
+    }
+
+    pub fn control_socket(&self) -> PathBuf {
+        match &self.control_socket {
+            Some(path) => self.resolve_path(path),
+            None => default_socket_path(),
+        }
+    }
+
+    pub fn screenshot_path(&self) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        PathBuf::from(
+            self.screenshot
+                .replace("{timestamp}", &timestamp.to_string()),
+        )
+    }
+
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
@@ -0,0 +1,331 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::mpsc,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{color::HexColor, event::UserEvent};
+
+// One request per line on the control socket, e.g.
+// `{"command":"set-text","text":"hello"}`. Every line gets one
+// `ControlResponse` line back, in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum ControlCommand {
+    Show,
+    Hide,
+    Toggle,
+    // Replaces every window's scene with the text in the top-left corner.
+    // The size is in logical pixels.
+    SetText {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<HexColor>,
+    },
+    ClearText,
+    // Like `SetText` with the clipboard's text, wrapped to the windows.
+    SetTextFromClipboard,
+    ClearColor {
+        color: HexColor,
+    },
+    // For every window.
+    SetDecorations {
+        enabled: bool,
+    },
+    SetResizable {
+        enabled: bool,
+    },
+    // The main window, see `WindowSet::set_fullscreen`.
+    EnterFullscreen,
+    ExitFullscreen,
+    ToggleFullscreen,
+    State,
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlState {
+    pub windows: usize,
+    pub visible: bool,
+    pub animating: bool,
+    pub frame_rate: Option<u32>,
+    // This and the rest are of the main window.
+    pub text: Option<String>,
+    pub clear_color: HexColor,
+    #[serde(default)]
+    pub fullscreen: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Only for `state`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<ControlState>,
+}
+
+impl ControlResponse {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            state: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            state: None,
+        }
+    }
+
+    pub fn state(state: ControlState) -> Self {
+        Self {
+            state: Some(state),
+            ..Self::ok()
+        }
+    }
+}
+
+// Sent along with a command into the event loop, which answers through it.
+// One per connection, so the responses come back in order.
+#[derive(Debug, Clone)]
+pub struct ControlReply(mpsc::UnboundedSender<ControlResponse>);
+
+impl ControlReply {
+    // The client may have hung up already, which is fine.
+    pub fn send(self, response: ControlResponse) {
+        let _ = self.0.send(response);
+    }
+}
+
+// `$XDG_RUNTIME_DIR/mgraphics.sock`, or in the temporary directory where
+// there is no runtime directory. A named pipe on Windows.
+#[cfg(unix)]
+pub fn default_socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("mgraphics.sock")
+}
+
+#[cfg(windows)]
+pub fn default_socket_path() -> PathBuf {
+    PathBuf::from(r"\\.\pipe\mgraphics")
+}
+
+async fn handle<S>(stream: S, proxy: EventLoopProxy<UserEvent>) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<ControlCommand>(&line) {
+            Ok(command) => {
+                log::debug!("control command {:?}", command);
+                let event = UserEvent::Control(command, ControlReply(tx.clone()));
+                if proxy.send_event(event).is_err() {
+                    break;
+                }
+                match rx.recv().await {
+                    Some(response) => response,
+                    None => break,
+                }
+            }
+            Err(e) => ControlResponse::error(format!("invalid command: {}", e)),
+        };
+        let mut text = serde_json::to_string(&response)?;
+        text.push('\n');
+        writer.write_all(text.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+fn spawn_handler<S>(stream: S, proxy: EventLoopProxy<UserEvent>)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(e) = handle(stream, proxy).await {
+            log::debug!("control connection closed: {}", e);
+        }
+    });
+}
+
+// Interrupts end the event loop like `quit` does, so the socket is removed
+// on the way out instead of left behind.
+fn quit_on_signals(proxy: EventLoopProxy<UserEvent>) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut terminate = match signal(SignalKind::terminate()) {
+                Ok(terminate) => terminate,
+                Err(e) => {
+                    log::warn!("failed to listen for SIGTERM: {}", e);
+                    return;
+                }
+            };
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        #[cfg(windows)]
+        {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+        }
+        let _ = proxy.send_event(UserEvent::Quit);
+    });
+}
+
+// Listens on the control socket while alive; dropping it removes the
+// socket file.
+pub(crate) struct ControlServer {
+    #[cfg(unix)]
+    path: PathBuf,
+}
+
+impl ControlServer {
+    // A socket file nobody answers on is left from an instance that didn't
+    // exit cleanly and is replaced; one that answers belongs to another
+    // running instance.
+    #[cfg(unix)]
+    pub fn new(path: &Path, proxy: EventLoopProxy<UserEvent>) -> anyhow::Result<Self> {
+        use tokio::net::UnixListener;
+
+        tokio::runtime::Handle::try_current()
+            .context("the control socket needs a tokio runtime")?;
+        let listener = match UnixListener::bind(path) {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                    anyhow::bail!("another instance is listening on {:?}", path);
+                }
+                std::fs::remove_file(path)
+                    .with_context(|| format!("failed to remove the stale socket {:?}", path))?;
+                UnixListener::bind(path)
+            }
+            bound => bound,
+        }
+        .with_context(|| format!("failed to listen on {:?}", path))?;
+        log::info!("listening for commands on {:?}", path);
+
+        quit_on_signals(proxy.clone());
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => spawn_handler(stream, proxy.clone()),
+                    Err(e) => log::warn!("failed to accept a control connection: {}", e),
+                }
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    // A pipe instance is connected at a time, the next one created right
+    // away for the next client. Pipes go away with their last handle, so
+    // there is nothing to clean up.
+    #[cfg(windows)]
+    pub fn new(path: &Path, proxy: EventLoopProxy<UserEvent>) -> anyhow::Result<Self> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        tokio::runtime::Handle::try_current()
+            .context("the control socket needs a tokio runtime")?;
+        let name = path.to_path_buf();
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&name)
+            .with_context(|| format!("failed to create the pipe {:?}", name))?;
+        log::info!("listening for commands on {:?}", name);
+
+        quit_on_signals(proxy.clone());
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = server.connect().await {
+                    log::warn!("failed to accept a control connection: {}", e);
+                    continue;
+                }
+                let next = match ServerOptions::new().create(&name) {
+                    Ok(next) => next,
+                    Err(e) => {
+                        log::error!("failed to create the pipe {:?}: {}", name, e);
+                        return;
+                    }
+                };
+                spawn_handler(std::mem::replace(&mut server, next), proxy.clone());
+            }
+        });
+        Ok(Self {})
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => log::debug!("removed {:?}", self.path),
+            Err(e) => log::warn!("failed to remove {:?}: {}", self.path, e),
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn connect(path: &Path) -> std::io::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(path).await
+}
+
+// Every instance is busy while a client is connected, so wait for the
+// next one.
+#[cfg(windows)]
+async fn connect(path: &Path) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    const ERROR_PIPE_BUSY: i32 = 231;
+    loop {
+        match ClientOptions::new().open(path) {
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            opened => return opened,
+        }
+    }
+}
+
+// Sends one command to the instance listening on `path` and waits for its
+// answer.
+pub async fn send_command(
+    path: &Path,
+    command: &ControlCommand,
+) -> anyhow::Result<ControlResponse> {
+    let stream = connect(path)
+        .await
+        .with_context(|| format!("failed to connect to {:?}, is mgraphics running?", path))?;
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut line = serde_json::to_string(command)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    let response = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("the connection closed without a response"))?;
+    serde_json::from_str(&response).context("invalid response")
+}
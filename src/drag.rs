@@ -0,0 +1,158 @@
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, MouseButton},
+    window::{CursorIcon, Window},
+};
+
+use crate::window::SizeLimits;
+
+// The square in the bottom-right corner that resizes undecorated windows,
+// in logical pixels.
+const RESIZE_CORNER: f64 = 16.0;
+
+// A window-relative area, in physical pixels, that keeps its clicks instead
+// of starting a drag.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub min: PhysicalPosition<f64>,
+    pub max: PhysicalPosition<f64>,
+}
+
+impl Region {
+    pub fn new(min: PhysicalPosition<f64>, max: PhysicalPosition<f64>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn contains(&self, pos: PhysicalPosition<f64>) -> bool {
+        pos.x >= self.min.x && pos.x < self.max.x && pos.y >= self.min.y && pos.y < self.max.y
+    }
+}
+
+// Click-and-drag moving for undecorated windows. The platform's own move
+// loop is used where winit has one; otherwise the window follows the cursor
+// by hand. Windows without decorations to resize them by can be resized by
+// their bottom-right corner, which wins over moving.
+#[derive(Debug, Default)]
+pub(crate) struct WindowDrag {
+    cursor: Option<PhysicalPosition<f64>>,
+    // Where the cursor grabbed the window during a manual drag.
+    anchor: Option<PhysicalPosition<f64>>,
+    interactive: Vec<Region>,
+    // The bounds of resizing by the corner, `None` without the corner.
+    resize_corner: Option<SizeLimits>,
+    // Where the cursor grabbed the corner, and the size back then.
+    resizing: Option<(PhysicalPosition<f64>, PhysicalSize<u32>)>,
+    over_corner: bool,
+}
+
+impl WindowDrag {
+    pub fn set_resize_corner(&mut self, window: &Window, limits: Option<SizeLimits>) {
+        self.resize_corner = limits;
+        if limits.is_none() {
+            self.resizing = None;
+            self.set_over_corner(window, false);
+        }
+    }
+
+    fn set_over_corner(&mut self, window: &Window, over_corner: bool) {
+        if over_corner != self.over_corner {
+            self.over_corner = over_corner;
+            window.set_cursor_icon(if over_corner {
+                CursorIcon::SeResize
+            } else {
+                CursorIcon::Default
+            });
+        }
+    }
+
+    fn in_corner(&self, window: &Window, pos: PhysicalPosition<f64>) -> bool {
+        if self.resize_corner.is_none() {
+            return false;
+        }
+        let size = window.inner_size();
+        let corner = RESIZE_CORNER * window.scale_factor();
+        pos.x >= size.width as f64 - corner && pos.y >= size.height as f64 - corner
+    }
+
+    pub fn add_interactive_region(&mut self, region: Region) {
+        self.interactive.push(region);
+    }
+
+    pub fn clear_interactive_regions(&mut self) {
+        self.interactive.clear();
+    }
+
+    pub fn cursor_moved(&mut self, window: &Window, pos: PhysicalPosition<f64>) {
+        self.cursor = Some(pos);
+        if let (Some((grab, start)), Some(limits)) = (self.resizing, self.resize_corner) {
+            // The top-left corner stays put, so the cursor's window-relative
+            // movement is the change in size. The resulting Resized event
+            // reconfigures the surface.
+            let size = PhysicalSize::new(
+                start.width as f64 + pos.x - grab.x,
+                start.height as f64 + pos.y - grab.y,
+            );
+            window.set_inner_size(limits.clamp(size, window.scale_factor()));
+            return;
+        }
+        self.set_over_corner(window, self.in_corner(window, pos));
+        let anchor = match self.anchor {
+            Some(anchor) => anchor,
+            None => return,
+        };
+        let outer = match window.outer_position() {
+            Ok(outer) => outer,
+            Err(_) => return,
+        };
+        // Moving the window by the delta puts the cursor back on the anchor.
+        window.set_outer_position(PhysicalPosition::new(
+            outer.x + (pos.x - anchor.x).round() as i32,
+            outer.y + (pos.y - anchor.y).round() as i32,
+        ));
+    }
+
+    pub fn cursor_left(&mut self, window: &Window) {
+        // A fast manual drag can briefly outrun the window, keep going.
+        if self.anchor.is_none() && self.resizing.is_none() {
+            self.cursor = None;
+            self.set_over_corner(window, false);
+        }
+    }
+
+    pub fn mouse_input(&mut self, window: &Window, state: ElementState, button: MouseButton) {
+        if button != MouseButton::Left {
+            return;
+        }
+        match state {
+            ElementState::Pressed => {
+                let cursor = match self.cursor {
+                    Some(cursor) => cursor,
+                    None => return,
+                };
+                if self
+                    .interactive
+                    .iter()
+                    .any(|region| region.contains(cursor))
+                {
+                    return;
+                }
+                if self.in_corner(window, cursor) {
+                    self.resizing = Some((cursor, window.inner_size()));
+                    return;
+                }
+                if let Err(e) = window.drag_window() {
+                    log::debug!("drag_window is unavailable ({}), dragging manually", e);
+                    self.anchor = Some(cursor);
+                }
+            }
+            ElementState::Released => self.end(),
+        }
+    }
+
+    // Also called when focus is lost, since a release outside the window is
+    // not always delivered.
+    pub fn end(&mut self) {
+        self.anchor = None;
+        self.resizing = None;
+    }
+}
@@ -0,0 +1,205 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawParams {
+    pub color: [f32; 4],
+    pub offset: [f32; 2],
+}
+
+impl Default for DrawParams {
+    fn default() -> Self {
+        Self {
+            color: [1.0; 4],
+            offset: [0.0; 2],
+        }
+    }
+}
+
+// Matches the WGSL struct layout, which rounds up to a 16 byte multiple.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct RawDrawParams {
+    color: [f32; 4],
+    offset: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl From<&DrawParams> for RawDrawParams {
+    fn from(params: &DrawParams) -> Self {
+        Self {
+            color: params.color,
+            offset: params.offset,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+const SIZE: u32 = std::mem::size_of::<RawDrawParams>() as u32;
+
+const PUSH_CONSTANT_DECL: &str = "var<push_constant> draw_params: DrawParams;";
+const UNIFORM_DECL: &str = "[[group(1), binding(0)]]\nvar<uniform> draw_params: DrawParams;";
+
+// Per-draw parameters go through push constants when the device has them,
+// otherwise through one uniform buffer indexed with dynamic offsets.
+pub(crate) enum DrawParamsBinding {
+    PushConstants {
+        params: Vec<RawDrawParams>,
+    },
+    Uniform {
+        layout: wgpu::BindGroupLayout,
+        buffer: crate::buffer::DynamicBuffer,
+        bind_group: wgpu::BindGroup,
+        stride: u32,
+    },
+}
+
+impl DrawParamsBinding {
+    pub(crate) fn supported(adapter: &wgpu::Adapter) -> bool {
+        adapter.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && adapter.limits().max_push_constant_size >= SIZE
+    }
+
+    pub(crate) fn required_limits(limits: wgpu::Limits) -> wgpu::Limits {
+        wgpu::Limits {
+            max_push_constant_size: limits.max_push_constant_size.max(SIZE),
+            ..limits
+        }
+    }
+
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        if device.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && device.limits().max_push_constant_size >= SIZE
+        {
+            return Self::PushConstants { params: Vec::new() };
+        }
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment;
+        let stride = (SIZE + alignment - 1) / alignment * alignment;
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("draw params"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(SIZE as wgpu::BufferAddress),
+                },
+                count: None,
+            }],
+        });
+        let buffer = crate::buffer::DynamicBuffer::new(
+            device,
+            "draw params",
+            wgpu::BufferUsages::UNIFORM,
+            stride as wgpu::BufferAddress,
+        );
+        let bind_group = create_bind_group(device, &layout, &buffer);
+
+        Self::Uniform {
+            layout,
+            buffer,
+            bind_group,
+            stride,
+        }
+    }
+
+    pub(crate) fn is_push_constants(&self) -> bool {
+        matches!(self, Self::PushConstants { .. })
+    }
+
+    pub(crate) fn bind_group_layout(&self) -> Option<&wgpu::BindGroupLayout> {
+        match self {
+            Self::PushConstants { .. } => None,
+            Self::Uniform { layout, .. } => Some(layout),
+        }
+    }
+
+    pub(crate) fn push_constant_ranges(&self) -> Vec<wgpu::PushConstantRange> {
+        match self {
+            Self::PushConstants { .. } => vec![wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..SIZE,
+            }],
+            Self::Uniform { .. } => Vec::new(),
+        }
+    }
+
+    // Rewrites the WGSL `draw_params` declaration for the active path.
+    pub(crate) fn patch_shader(&self, source: &str) -> String {
+        match self {
+            Self::PushConstants { .. } => source.to_string(),
+            Self::Uniform { .. } => source.replace(PUSH_CONSTANT_DECL, UNIFORM_DECL),
+        }
+    }
+
+    // Uploads the parameters for every draw of the coming frame; draw `i`
+    // then binds them with `apply(rpass, i)`.
+    pub(crate) fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        params: &[DrawParams],
+    ) {
+        match self {
+            Self::PushConstants { params: stored } => {
+                stored.clear();
+                stored.extend(params.iter().map(RawDrawParams::from));
+            }
+            Self::Uniform {
+                layout,
+                buffer,
+                bind_group,
+                stride,
+            } => {
+                let stride = *stride as usize;
+                let mut data = vec![0u8; params.len().max(1) * stride];
+                for (i, params) in params.iter().enumerate() {
+                    let raw = RawDrawParams::from(params);
+                    data[i * stride..i * stride + SIZE as usize]
+                        .copy_from_slice(bytemuck::bytes_of(&raw));
+                }
+                if buffer.write(device, queue, &data) {
+                    *bind_group = create_bind_group(device, layout, buffer);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn apply<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, index: usize) {
+        match self {
+            Self::PushConstants { params } => {
+                if let Some(raw) = params.get(index) {
+                    rpass.set_push_constants(
+                        wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        0,
+                        bytemuck::bytes_of(raw),
+                    );
+                }
+            }
+            Self::Uniform {
+                bind_group, stride, ..
+            } => {
+                rpass.set_bind_group(1, bind_group, &[index as u32 * stride]);
+            }
+        }
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &crate::buffer::DynamicBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("draw params"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: buffer.buffer(),
+                offset: 0,
+                size: wgpu::BufferSize::new(SIZE as wgpu::BufferAddress),
+            }),
+        }],
+    })
+}
@@ -0,0 +1,447 @@
+use std::{borrow::Cow, collections::HashMap, num::NonZeroU32, ops::Range, path::Path, sync::Arc};
+
+use ab_glyph::{Font, FontArc, GlyphId, GlyphImageFormat, PxScale, ScaleFont};
+use anyhow::Context;
+
+use crate::{
+    buffer::DynamicBuffer,
+    color::premultiply_rgba8,
+    packer::ShelfPacker,
+    pipeline::{PipelineCache, ShaderId, TargetConfig},
+    sprite::atlas_format,
+    texture::Texture,
+};
+
+const ATLAS_SIZE: u32 = 1024;
+const GLYPH_PADDING: u32 = 1;
+
+// Where distributions put their color emoji font, tried in order when none
+// is configured. Only fonts with PNG bitmaps (CBDT or sbix) are drawn in
+// color, so COLR fonts such as Segoe UI Emoji aren't listed.
+const DEFAULT_FONTS: &[&str] = &[
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/truetype/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/google-noto-emoji/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto-emoji/NotoColorEmoji.ttf",
+    "/System/Library/Fonts/Apple Color Emoji.ttc",
+];
+
+pub(crate) fn load_default_font() -> Option<FontArc> {
+    DEFAULT_FONTS.iter().find_map(|path| {
+        let font = load_font(Path::new(path)).ok()?;
+        log::debug!("using the emoji font {:?}", path);
+        Some(font)
+    })
+}
+
+pub(crate) fn load_font(path: &Path) -> anyhow::Result<FontArc> {
+    let bytes = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+    FontArc::try_from_vec(bytes).with_context(|| format!("invalid font {:?}", path))
+}
+
+// Pictographs that have a color presentation. Digits, `#` and `*` only do
+// as part of keycap sequences and are left to the text font.
+pub(crate) fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2300..=0x23FF | 0x2B00..=0x2BFF
+            | 0x3030 | 0x303D | 0x3297 | 0x3299
+    )
+}
+
+// Joins the emoji around it into one, e.g. a family.
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+// Parts of emoji sequences drawn as nothing: variation selectors, skin
+// tones, tags of subdivision flags and the keycap mark. Dropping a skin
+// tone shows the default one.
+fn is_sequence_modifier(c: char) -> bool {
+    matches!(
+        c as u32,
+        0xFE00..=0xFE0F | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F | 0x20E3
+    )
+}
+
+// Flags are pairs of these.
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c as u32, 0x1F1E6..=0x1F1FF)
+}
+
+// The characters of `text` that get a glyph of their own. Sequences are cut
+// down to their first emoji, which the font is sure to have: a family is
+// drawn as its first member and a flag as its first letter.
+pub(crate) fn drawn_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = text.chars().peekable();
+    std::iter::from_fn(move || loop {
+        let c = chars.next()?;
+        if c == ZERO_WIDTH_JOINER || is_sequence_modifier(c) {
+            continue;
+        }
+        if is_regional_indicator(c) && chars.peek().map_or(false, |&c| is_regional_indicator(c)) {
+            chars.next();
+        }
+        if is_emoji(c) {
+            while let Some(&next) = chars.peek() {
+                if is_sequence_modifier(next) {
+                    chars.next();
+                } else if next == ZERO_WIDTH_JOINER {
+                    chars.next();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+        }
+        return Some(c);
+    })
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct EmojiVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    tint: [f32; 4],
+}
+
+impl EmojiVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedEmoji {
+    // Offset of the image from the pen position, in pixels.
+    offset: [f32; 2],
+    size: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+}
+
+// Color glyphs from the emoji font's bitmaps, scaled to the size they're
+// shown at in an RGBA atlas of their own. Like the glyph atlas it is
+// emptied when full and refilled with what the frame needs.
+pub(crate) struct EmojiRenderer {
+    font: Option<FontArc>,
+    glyphs: HashMap<(GlyphId, u32), Option<CachedEmoji>>,
+    packer: ShelfPacker,
+    format: wgpu::TextureFormat,
+    // Like `TextureAtlas`, for premultiplied pipelines.
+    premultiplied: bool,
+    texture: wgpu::Texture,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    vertices: Vec<EmojiVertex>,
+    shader: ShaderId,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+}
+
+impl EmojiRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+        globals_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let layout = Texture::bind_group_layout(device);
+        let format = atlas_format(target);
+        let (texture, bind_group) = create_texture(device, &layout, format);
+
+        // Textured and tinted quads, the same as sprites.
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("emoji"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("sprite.wgsl"))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("emoji"),
+            bind_group_layouts: &[globals_layout, &layout],
+            push_constant_ranges: &[],
+        });
+        let shader = cache.add_shader("emoji", shader, pipeline_layout);
+        let pipeline = cache.get_or_create(device, shader, &Self::buffers(), target);
+        let vertex_buffer =
+            DynamicBuffer::new(device, "emoji vertices", wgpu::BufferUsages::VERTEX, 0);
+
+        Self {
+            font: None,
+            glyphs: HashMap::new(),
+            packer: ShelfPacker::new(ATLAS_SIZE, ATLAS_SIZE),
+            format,
+            premultiplied: target.alpha_mode.is_premultiplied(),
+            texture,
+            layout,
+            bind_group,
+            vertices: Vec::new(),
+            shader,
+            pipeline,
+            vertex_buffer,
+        }
+    }
+
+    fn buffers() -> [wgpu::VertexBufferLayout<'static>; 1] {
+        [EmojiVertex::layout()]
+    }
+
+    // The cached emoji are dropped when the atlas format or the alpha mode
+    // changes and drawn again on demand.
+    pub(crate) fn retarget(
+        &mut self,
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+    ) {
+        self.pipeline = cache.get_or_create(device, self.shader, &Self::buffers(), target);
+        let format = atlas_format(target);
+        if format != self.format {
+            self.format = format;
+            let (texture, bind_group) = create_texture(device, &self.layout, format);
+            self.texture = texture;
+            self.bind_group = bind_group;
+            self.clear();
+        }
+        let premultiplied = target.alpha_mode.is_premultiplied();
+        if premultiplied != self.premultiplied {
+            self.premultiplied = premultiplied;
+            self.clear();
+        }
+    }
+
+    pub(crate) fn font(&self) -> Option<&FontArc> {
+        self.font.as_ref()
+    }
+
+    pub(crate) fn set_font(&mut self, font: Option<FontArc>) {
+        self.font = font;
+        self.clear();
+    }
+
+    // Also drops what was queued, which is laid out again.
+    pub(crate) fn clear(&mut self) {
+        self.packer.clear();
+        self.glyphs.clear();
+        self.vertices.clear();
+    }
+
+    // The emoji font's glyph for `c`, if it is an emoji the font has.
+    pub(crate) fn glyph_id(&self, c: char) -> Option<GlyphId> {
+        let font = self.font.as_ref()?;
+        if !is_emoji(c) {
+            return None;
+        }
+        match font.glyph_id(c) {
+            GlyphId(0) => None,
+            id => Some(id),
+        }
+    }
+
+    pub(crate) fn h_advance(&self, id: GlyphId, size: f32) -> f32 {
+        self.font.as_ref().map_or(0.0, |font| {
+            font.as_scaled(PxScale::from(size)).h_advance(id)
+        })
+    }
+
+    // The number of vertices queued so far.
+    pub(crate) fn mark(&self) -> u32 {
+        self.vertices.len() as u32
+    }
+
+    // Queues the emoji with its origin at `pen`, on the baseline. Returns
+    // None when the atlas is full; emoji without a PNG bitmap are skipped.
+    pub(crate) fn queue(
+        &mut self,
+        queue: &wgpu::Queue,
+        id: GlyphId,
+        pen: [f32; 2],
+        size: f32,
+        alpha: f32,
+    ) -> Option<()> {
+        let emoji = match self.get_or_insert(queue, id, size)? {
+            Some(emoji) => emoji,
+            None => return Some(()),
+        };
+        let min = [pen[0] + emoji.offset[0], pen[1] + emoji.offset[1]];
+        let max = [min[0] + emoji.size[0], min[1] + emoji.size[1]];
+        let vertex = |x: usize, y: usize| EmojiVertex {
+            position: [[min[0], max[0]][x], [min[1], max[1]][y]],
+            tex_coords: [
+                [emoji.uv_min[0], emoji.uv_max[0]][x],
+                [emoji.uv_min[1], emoji.uv_max[1]][y],
+            ],
+            tint: [1.0, 1.0, 1.0, alpha],
+        };
+        self.vertices.extend_from_slice(&[
+            vertex(0, 0),
+            vertex(1, 0),
+            vertex(1, 1),
+            vertex(0, 0),
+            vertex(1, 1),
+            vertex(0, 1),
+        ]);
+        Some(())
+    }
+
+    fn get_or_insert(
+        &mut self,
+        queue: &wgpu::Queue,
+        id: GlyphId,
+        size: f32,
+    ) -> Option<Option<CachedEmoji>> {
+        // Quarter pixel steps, like the glyph atlas.
+        let key = (id, (size * 4.0).round() as u32);
+        if let Some(cached) = self.glyphs.get(&key) {
+            return Some(*cached);
+        }
+        let (mut rgba, offset) = match self
+            .font
+            .as_ref()
+            .and_then(|font| rasterize(font, id, size))
+        {
+            Some(image) => image,
+            None => {
+                self.glyphs.insert(key, None);
+                return Some(None);
+            }
+        };
+        let (width, height) = rgba.dimensions();
+        if self.premultiplied {
+            premultiply_rgba8(&mut rgba, self.format.describe().srgb);
+        }
+
+        let (x, y) = self
+            .packer
+            .allocate(width + GLYPH_PADDING * 2, height + GLYPH_PADDING * 2)?;
+        let (x, y) = (x + GLYPH_PADDING, y + GLYPH_PADDING);
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            rgba.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(width * 4),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let cached = CachedEmoji {
+            offset,
+            size: [width as f32, height as f32],
+            uv_min: [x as f32 / ATLAS_SIZE as f32, y as f32 / ATLAS_SIZE as f32],
+            uv_max: [
+                (x + width) as f32 / ATLAS_SIZE as f32,
+                (y + height) as f32 / ATLAS_SIZE as f32,
+            ],
+        };
+        self.glyphs.insert(key, Some(cached));
+        Some(Some(cached))
+    }
+
+    pub(crate) fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.vertex_buffer
+            .write(device, queue, bytemuck::cast_slice(&self.vertices));
+        self.vertices.clear();
+    }
+
+    pub(crate) fn render_range<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        globals: &'a wgpu::BindGroup,
+        vertices: Range<u32>,
+    ) {
+        if vertices.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals, &[]);
+        rpass.set_bind_group(1, &self.bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice());
+        rpass.draw(vertices, 0..1);
+    }
+}
+
+// The font's largest bitmap scaled to `size` pixels per em, and its offset
+// from the pen position. Bitmap offsets are to their bottom-left corner,
+// upwards from the baseline.
+fn rasterize(font: &FontArc, id: GlyphId, size: f32) -> Option<(image::RgbaImage, [f32; 2])> {
+    let glyph = font.glyph_raster_image2(id, u16::MAX)?;
+    if glyph.format != GlyphImageFormat::Png || glyph.pixels_per_em == 0 {
+        return None;
+    }
+    let decoded = image::load_from_memory_with_format(glyph.data, image::ImageFormat::Png)
+        .map_err(|e| log::debug!("invalid emoji bitmap for {:?}: {}", id, e))
+        .ok()?
+        .to_rgba8();
+    let scale = size / glyph.pixels_per_em as f32;
+    let width = ((decoded.width() as f32 * scale).round() as u32).max(1);
+    let height = ((decoded.height() as f32 * scale).round() as u32).max(1);
+    let scaled = image::imageops::resize(
+        &decoded,
+        width,
+        height,
+        image::imageops::FilterType::Triangle,
+    );
+    let offset = [
+        glyph.origin.x * scale,
+        -(glyph.origin.y + decoded.height() as f32) * scale,
+    ];
+    Some((scaled, offset))
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> (wgpu::Texture, wgpu::BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("emoji atlas"),
+        size: wgpu::Extent3d {
+            width: ATLAS_SIZE,
+            height: ATLAS_SIZE,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("emoji atlas"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("emoji atlas"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    });
+    (texture, bind_group)
+}
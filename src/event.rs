@@ -0,0 +1,52 @@
+use std::{path::PathBuf, time::Instant};
+
+use winit::{dpi::PhysicalSize, window::WindowId};
+
+use crate::{
+    assets::LoadedAsset,
+    control::{ControlCommand, ControlReply},
+    tween::{Tween, TweenId},
+};
+
+#[derive(Debug, Clone)]
+pub enum UserEvent {
+    // The watched shader file changed on disk.
+    ShaderChanged(PathBuf),
+    // Turn input passthrough on or off for every window.
+    TogglePassthrough,
+    // Start or stop redrawing continuously.
+    SetAnimating(bool),
+    // Cap the frame rate, `None` or zero for uncapped.
+    SetFrameRate(Option<u32>),
+    // Finish any recording in progress.
+    StopRecording,
+    // Stacking hints for every window, e.g. to demote them while screen
+    // sharing. Saved to the config file like the hotkey toggles.
+    SetAlwaysOnTop(bool),
+    SetSkipTaskbar(bool),
+    // Hide every window if any is shown, otherwise show them all.
+    ToggleVisibility,
+    // Read the config file again and apply what can change at runtime.
+    ReloadConfig,
+    Quit,
+    // Rebuild every window's renderer as if the GPU had been reset.
+    SimulateDeviceLoss,
+    // A `Handle` left a new scene for the main window.
+    SceneChanged,
+    RequestRedraw,
+    // Start or stop a tween on the main window's scene.
+    Animate(Tween),
+    CancelTween(TweenId),
+    // Set the main window's inner size.
+    Resize(PhysicalSize<u32>),
+    // A command from the control socket, answered through the reply.
+    Control(ControlCommand, ControlReply),
+    // A render thread finished a window's frame, presented at the instant
+    // or not.
+    FrameDone(WindowId, Result<Instant, wgpu::SurfaceError>),
+    // A render thread panicked drawing a window's frame, and ended.
+    RenderThreadPanicked(WindowId),
+    // A load from the handle decoded its file, for the event loop to
+    // upload.
+    AssetLoaded(LoadedAsset),
+}
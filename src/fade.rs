@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use crate::tween::Easing;
+
+// Symmetric, so turning around mid-fade retraces the same curve instead of
+// jumping.
+const FADE_EASING: Easing = Easing::SineInOut;
+
+// A window's opacity while it is shown or hidden, see
+// `WindowSet::set_visible`. Progress runs from 0, hidden, to 1, shown, and
+// reverses from wherever it is when the window is toggled again.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Fade {
+    duration: Duration,
+    progress: f32,
+    showing: bool,
+}
+
+impl Fade {
+    pub(crate) fn new(duration: Duration, shown: bool) -> Self {
+        Self {
+            duration,
+            progress: if shown { 1.0 } else { 0.0 },
+            showing: shown,
+        }
+    }
+
+    // Zero shows and hides at once. A fade in progress keeps its place and
+    // finishes at the new pace.
+    pub(crate) fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+        if duration.is_zero() {
+            self.progress = self.target();
+        }
+    }
+
+    pub(crate) fn show(&mut self) {
+        self.turn(true);
+    }
+
+    pub(crate) fn hide(&mut self) {
+        self.turn(false);
+    }
+
+    fn turn(&mut self, showing: bool) {
+        self.showing = showing;
+        if self.duration.is_zero() {
+            self.progress = self.target();
+        }
+    }
+
+    fn target(&self) -> f32 {
+        if self.showing {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    // Shown or on the way there.
+    pub(crate) fn is_showing(&self) -> bool {
+        self.showing
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.progress != self.target()
+    }
+
+    // Done fading out, the window can go.
+    pub(crate) fn is_hidden(&self) -> bool {
+        !self.showing && self.progress <= 0.0
+    }
+
+    pub(crate) fn tick(&mut self, delta: Duration) {
+        if !self.is_running() {
+            return;
+        }
+        let step = if self.duration.is_zero() {
+            1.0
+        } else {
+            delta.as_secs_f32() / self.duration.as_secs_f32()
+        };
+        self.progress = if self.showing {
+            (self.progress + step).min(1.0)
+        } else {
+            (self.progress - step).max(0.0)
+        };
+    }
+
+    pub(crate) fn opacity(&self) -> f32 {
+        FADE_EASING.apply(self.progress)
+    }
+}
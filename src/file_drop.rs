@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use crate::{render::RenderContext, shapes::Rect};
+
+const HIGHLIGHT: [f32; 4] = [0.25, 0.55, 1.0, 0.25];
+const HIGHLIGHT_BORDER: [f32; 4] = [0.25, 0.55, 1.0, 1.0];
+const ERROR: [f32; 4] = [0.8, 0.15, 0.15, 0.85];
+// In logical pixels.
+const BORDER_WIDTH: f32 = 3.0;
+const ERROR_TEXT_SIZE: f32 = 14.0;
+
+// Files dropped on a window at once, see `Handle::on_file_drop`.
+// `position` is where the cursor was last seen over the window, in
+// physical pixels; not every platform reports it during a drag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDropEvent {
+    pub paths: Vec<PathBuf>,
+    pub position: Option<[f32; 2]>,
+}
+
+// Files dragged over and dropped on a window. winit reports every file of a
+// drop as its own event, one after the other in the same batch, so they are
+// collected here and handed over together once the batch is done.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FileDrop {
+    hovering: bool,
+    dropped: Vec<PathBuf>,
+    position: Option<[f32; 2]>,
+    // Why the last drop wasn't taken, shown until the next drag comes.
+    error: Option<String>,
+}
+
+impl FileDrop {
+    // These return whether the window looks different now.
+    pub fn hovered(&mut self) -> bool {
+        let changed = !self.hovering || self.error.is_some();
+        self.hovering = true;
+        self.error = None;
+        changed
+    }
+
+    pub fn cancelled(&mut self) -> bool {
+        std::mem::replace(&mut self.hovering, false)
+    }
+
+    pub fn dropped(&mut self, path: PathBuf, position: Option<[f32; 2]>) -> bool {
+        self.dropped.push(path);
+        self.position = position;
+        self.cancelled()
+    }
+
+    pub fn set_error(&mut self, message: String) {
+        self.error = Some(message);
+    }
+
+    pub fn take(&mut self) -> Option<FileDropEvent> {
+        if self.dropped.is_empty() {
+            return None;
+        }
+        Some(FileDropEvent {
+            paths: std::mem::take(&mut self.dropped),
+            position: self.position.take(),
+        })
+    }
+
+    // A highlight over the whole window while files hover over it, or the
+    // error across the top.
+    pub fn queue(&self, ctx: &mut RenderContext) {
+        let scale = ctx.scale_factor() as f32;
+        let size = ctx.size();
+        let (width, height) = (size.width as f32, size.height as f32);
+        if self.hovering {
+            let window = Rect::new(0.0, 0.0, width, height);
+            ctx.fill_rect(window, HIGHLIGHT);
+            ctx.stroke_rect(window, BORDER_WIDTH * scale, HIGHLIGHT_BORDER);
+        } else if let Some(error) = &self.error {
+            let text_size = ERROR_TEXT_SIZE * scale;
+            let padding = text_size / 2.0;
+            ctx.fill_rect(Rect::new(0.0, 0.0, width, text_size + padding * 2.0), ERROR);
+            ctx.queue_text(error, [padding, padding], text_size, [1.0; 4]);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+/// Per-frame values bound at `@group(0) @binding(0)`.
+///
+/// The WGSL side is:
+///
+/// ```wgsl
+/// struct Globals {
+///     time: f32;
+///     frame: u32;
+///     resolution: vec2<f32>;
+///     delta_time: f32;
+///     scale_factor: f32;
+///     encode_srgb: u32;
+///     premultiply: u32;
+///     opacity: f32;
+/// };
+/// ```
+///
+/// Shaders written before `encode_srgb`, `premultiply` and `opacity`
+/// existed can leave them out, and then don't fade with the window.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Globals {
+    /// Animation time in seconds, the sum of all frame deltas so far.
+    pub time: f32,
+    /// Number of frames presented so far.
+    pub frame: u32,
+    /// Surface width and height in physical pixels.
+    pub resolution: [f32; 2],
+    /// Seconds the clock advanced since the previous frame.
+    pub delta_time: f32,
+    /// Physical pixels per logical pixel, e.g. to snap to device pixels.
+    pub scale_factor: f32,
+    /// 1 when the target stores colors as they're written instead of
+    /// encoding them to sRGB, so fragment shaders have to encode their
+    /// linear output themselves; 0 otherwise.
+    pub encode_srgb: u32,
+    /// 1 when the built-in pipelines blend premultiplied colors, see
+    /// `AlphaMode`; 0 for straight ones. The main shader's blend state is
+    /// `RenderOptions::blend` regardless.
+    pub premultiply: u32,
+    /// The window's opacity, from 0 to 1, to multiply into the alpha of
+    /// every fragment; it changes while the window fades in or out.
+    pub opacity: f32,
+    // The WGSL struct's size is rounded up to its 8-byte alignment.
+    pub(crate) padding: u32,
+}
+
+pub(crate) struct GlobalsUniform {
+    pub(crate) layout: wgpu::BindGroupLayout,
+    pub(crate) buffer: wgpu::Buffer,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl GlobalsUniform {
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("globals"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<Globals>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+        });
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("globals"),
+            size: std::mem::size_of::<Globals>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("globals"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            layout,
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub(crate) fn write(&self, queue: &wgpu::Queue, globals: &Globals) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(globals));
+    }
+}
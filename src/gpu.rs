@@ -0,0 +1,359 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::Context;
+
+use crate::draw_params::DrawParamsBinding;
+
+// How to pick the adapter when several are available.
+#[derive(Debug, Clone, Default)]
+pub struct AdapterOptions {
+    pub power_preference: wgpu::PowerPreference,
+    // Case-insensitive substring of the adapter name; wins over the power
+    // preference when it matches.
+    pub name: Option<String>,
+}
+
+// All adapters for `backends`, whether or not they can present anywhere.
+pub fn list_adapters(backends: wgpu::Backends) -> Vec<wgpu::AdapterInfo> {
+    wgpu::Instance::new(backends)
+        .enumerate_adapters(backends)
+        .map(|adapter| adapter.get_info())
+        .collect()
+}
+
+fn find_adapter(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+    compatible_surface: Option<&wgpu::Surface>,
+    name: &str,
+) -> Option<wgpu::Adapter> {
+    let name = name.to_lowercase();
+    instance.enumerate_adapters(backends).find(|adapter| {
+        adapter.get_info().name.to_lowercase().contains(&name)
+            && compatible_surface.map_or(true, |surface| adapter.is_surface_supported(surface))
+    })
+}
+
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface>,
+    options: &AdapterOptions,
+    force_fallback_adapter: bool,
+) -> Option<wgpu::Adapter> {
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: options.power_preference,
+            compatible_surface,
+            force_fallback_adapter,
+        })
+        .await
+}
+
+// What the instance can see, for when none of it is usable.
+fn describe_adapters(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+    compatible_surface: Option<&wgpu::Surface>,
+) -> String {
+    let adapters: Vec<_> = instance
+        .enumerate_adapters(backends)
+        .map(|adapter| {
+            let info = adapter.get_info();
+            let presents = match compatible_surface {
+                Some(surface) if !adapter.is_surface_supported(surface) => {
+                    ", cannot present to the window"
+                }
+                _ => "",
+            };
+            format!(
+                "{:?} ({:?}, {:?}{})",
+                info.name, info.backend, info.device_type, presents
+            )
+        })
+        .collect();
+    if adapters.is_empty() {
+        "no adapters are visible".to_string()
+    } else {
+        format!("visible adapters: {}", adapters.join(", "))
+    }
+}
+
+// The sets of limits asked for, from the most to the least capable. Each
+// is raised to the adapter's texture size, so only hardware that can't meet
+// a tier at all has to go down to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitsTier {
+    Default,
+    Downlevel,
+    DownlevelWebgl2,
+}
+
+impl LimitsTier {
+    const ALL: [LimitsTier; 3] = [
+        LimitsTier::Default,
+        LimitsTier::Downlevel,
+        LimitsTier::DownlevelWebgl2,
+    ];
+
+    fn limits(self) -> wgpu::Limits {
+        match self {
+            LimitsTier::Default => wgpu::Limits::default(),
+            LimitsTier::Downlevel => wgpu::Limits::downlevel_defaults(),
+            LimitsTier::DownlevelWebgl2 => wgpu::Limits::downlevel_webgl2_defaults(),
+        }
+    }
+}
+
+impl fmt::Display for LimitsTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LimitsTier::Default => "default",
+            LimitsTier::Downlevel => "downlevel",
+            LimitsTier::DownlevelWebgl2 => "downlevel webgl2",
+        })
+    }
+}
+
+// Tries each tier in turn. Push constants are requested only where the
+// adapter has room for the draw params; without them they go through a
+// uniform buffer instead.
+async fn request_device(
+    adapter: &wgpu::Adapter,
+) -> anyhow::Result<(wgpu::Device, wgpu::Queue, LimitsTier)> {
+    let push_constants = DrawParamsBinding::supported(adapter);
+    let mut features =
+        adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+    if push_constants {
+        features |= wgpu::Features::PUSH_CONSTANTS;
+    }
+    let mut last_error = None;
+    for tier in LimitsTier::ALL {
+        let mut limits = tier.limits().using_resolution(adapter.limits());
+        if push_constants {
+            limits = DrawParamsBinding::required_limits(limits);
+        }
+        let descriptor = wgpu::DeviceDescriptor {
+            label: None,
+            features,
+            limits,
+        };
+        match adapter.request_device(&descriptor, None).await {
+            Ok((device, queue)) => return Ok((device, queue, tier)),
+            Err(e) => {
+                log::warn!("the adapter refused the {} limits: {}", tier, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.expect("there is more than one tier").into())
+}
+
+// wgpu reports a lost device as the failure of whatever call noticed it.
+fn is_device_lost(error: &wgpu::Error) -> bool {
+    matches!(error, wgpu::Error::Validation { description, .. } if description.contains("device is lost"))
+}
+
+// A wgpu error with what the app was doing when it happened.
+#[derive(Debug, Clone)]
+pub struct GpuError {
+    pub operation: String,
+    pub message: String,
+    pub out_of_memory: bool,
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.operation, self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ErrorLog {
+    count: u64,
+    last: Option<GpuError>,
+    out_of_memory: bool,
+}
+
+// Everything wgpu reports ends up here, from error scopes and from the
+// uncaptured error handler alike. A lost device is not an error to report
+// but a state to recover from.
+fn report(lost: &AtomicBool, errors: &Mutex<ErrorLog>, operation: &str, error: wgpu::Error) {
+    if is_device_lost(&error) {
+        if !lost.swap(true, Ordering::Relaxed) {
+            log::error!("the GPU device was lost: {}", error);
+        }
+        return;
+    }
+    let error = GpuError {
+        operation: operation.to_string(),
+        message: error.to_string(),
+        out_of_memory: matches!(error, wgpu::Error::OutOfMemory { .. }),
+    };
+    log::error!("wgpu error {}", error);
+    let mut errors = errors.lock().unwrap();
+    errors.count += 1;
+    errors.out_of_memory |= error.out_of_memory;
+    errors.last = Some(error);
+}
+
+// The adapter and device, which can be shared between the surfaces of
+// several windows.
+pub struct Gpu {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub limits_tier: LimitsTier,
+    // Set once the device is gone; everything created on it has to be
+    // created again on a new one.
+    lost: Arc<AtomicBool>,
+    errors: Arc<Mutex<ErrorLog>>,
+}
+
+impl Gpu {
+    pub async fn new(
+        instance: wgpu::Instance,
+        backends: wgpu::Backends,
+        compatible_surface: Option<&wgpu::Surface>,
+        options: &AdapterOptions,
+    ) -> anyhow::Result<Self> {
+        let named = options.name.as_deref().and_then(|name| {
+            let adapter = find_adapter(&instance, backends, compatible_surface, name);
+            if adapter.is_none() {
+                log::warn!("no adapter matches {:?}, using the default choice", name);
+            }
+            adapter
+        });
+        let adapter = match named {
+            Some(adapter) => adapter,
+            None => match request_adapter(&instance, compatible_surface, options, false).await {
+                Some(adapter) => adapter,
+                None => {
+                    log::warn!("no hardware adapter found, trying the fallback adapter");
+                    let adapter = request_adapter(&instance, compatible_surface, options, true)
+                        .await
+                        .ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "tried backends {:?}, {}",
+                                backends,
+                                describe_adapters(&instance, backends, compatible_surface)
+                            )
+                        })
+                        .context(
+                            "failed to find an appropriate adapter; make sure Vulkan, DX12 \
+                             or Metal drivers are installed",
+                        )?;
+                    log::warn!("using the fallback adapter {:?}", adapter.get_info().name);
+                    adapter
+                }
+            },
+        };
+
+        let info = adapter.get_info();
+        log::info!(
+            "using the {:?} backend on {:?} ({:?}, vendor {:#06x}, device {:#06x})",
+            info.backend,
+            info.name,
+            info.device_type,
+            info.vendor,
+            info.device
+        );
+        if info.device_type == wgpu::DeviceType::Cpu {
+            log::warn!(
+                "{:?} is a software adapter, expect poor performance",
+                info.name
+            );
+        }
+
+        let (device, queue, tier) = request_device(&adapter).await.with_context(|| {
+            format!(
+                "failed to create a device on {:?} ({:?})",
+                info.name, info.backend
+            )
+        })?;
+        log::info!("{} limits: {:?}", tier, device.limits());
+
+        // Instead of wgpu's default handler, which panics.
+        let lost = Arc::new(AtomicBool::new(false));
+        let errors = Arc::new(Mutex::new(ErrorLog::default()));
+        let (flag, log) = (lost.clone(), errors.clone());
+        device
+            .on_uncaptured_error(move |error| report(&flag, &log, "outside an error scope", error));
+
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            limits_tier: tier,
+            lost,
+            errors,
+        })
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    // Treats the device as lost without it being so, to exercise recovery.
+    pub fn simulate_loss(&self) {
+        self.lost.store(true, Ordering::Relaxed);
+    }
+
+    // Errors until the matching `pop_error_scopes` are reported with the
+    // operation given there.
+    pub(crate) fn push_error_scopes(&self) {
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+    }
+
+    // Returns how many errors were reported.
+    pub(crate) fn pop_error_scopes(&self, operation: &str) -> u64 {
+        let mut count = 0;
+        for _ in 0..2 {
+            if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+                report(&self.lost, &self.errors, operation, error);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    pub fn error_count(&self) -> u64 {
+        self.errors.lock().unwrap().count
+    }
+
+    pub fn last_error(&self) -> Option<GpuError> {
+        self.errors.lock().unwrap().last.clone()
+    }
+
+    // Set after any out-of-memory error; nothing created afterwards can be
+    // trusted, so the app should shut down.
+    pub fn is_out_of_memory(&self) -> bool {
+        self.errors.lock().unwrap().out_of_memory
+    }
+
+    // Creates a buffer with conflicting usages, to check that errors are
+    // reported.
+    pub fn inject_error(&self) {
+        log::warn!("creating an invalid buffer on purpose");
+        self.push_error_scopes();
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("invalid on purpose"),
+            size: 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::MAP_WRITE,
+            mapped_at_creation: false,
+        });
+        self.pop_error_scopes("injecting a test error");
+    }
+
+    pub fn supports(&self, surface: &wgpu::Surface) -> bool {
+        surface.get_preferred_format(&self.adapter).is_some()
+    }
+}
@@ -0,0 +1,342 @@
+use std::{borrow::Cow, num::NonZeroU32, ops::Range, sync::Arc};
+
+use crate::{
+    buffer::DynamicBuffer,
+    color::linear_to_srgb,
+    pipeline::{PipelineCache, ShaderId, TargetConfig},
+    shapes::Rect,
+    texture::Texture,
+};
+
+// Texels per ramp; each queued gradient gets one row of the ramp texture.
+const RAMP_WIDTH: u32 = 256;
+const RAMP_BYTES: usize = RAMP_WIDTH as usize * 4;
+// The smallest max_texture_dimension_2d wgpu's downlevel limits allow.
+const MAX_RAMPS: u32 = 2048;
+const VERTICES_PER_RECT: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    Linear { start: [f32; 2], end: [f32; 2] },
+    Radial { center: [f32; 2], radius: f32 },
+}
+
+// Colors are linear like every other color the renderer takes, and stops are
+// `(offset, color)` pairs with offsets from 0 to 1. The gradient is clamped
+// to the first and last stop outside of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    shape: Shape,
+    stops: Vec<(f32, [f32; 4])>,
+}
+
+impl Gradient {
+    // In pixels, like the shapes it fills.
+    pub fn linear(start: [f32; 2], end: [f32; 2], stops: &[(f32, [f32; 4])]) -> Self {
+        Self::new(Shape::Linear { start, end }, stops)
+    }
+
+    pub fn radial(center: [f32; 2], radius: f32, stops: &[(f32, [f32; 4])]) -> Self {
+        Self::new(Shape::Radial { center, radius }, stops)
+    }
+
+    fn new(shape: Shape, stops: &[(f32, [f32; 4])]) -> Self {
+        let mut stops = stops.to_vec();
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Self { shape, stops }
+    }
+
+    fn color_at(&self, t: f32) -> [f32; 4] {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return [0.0; 4],
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+        let i = self.stops.partition_point(|stop| stop.0 <= t);
+        let ((t0, c0), (t1, c1)) = (self.stops[i - 1], self.stops[i]);
+        let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+        // Premultiplied, so fading into a transparent stop doesn't drag in
+        // that stop's color.
+        let alpha = c0[3] + (c1[3] - c0[3]) * f;
+        if alpha <= 0.0 {
+            return [0.0; 4];
+        }
+        let mut color = [0.0, 0.0, 0.0, alpha];
+        for (c, (a, b)) in color.iter_mut().zip(c0.iter().zip(c1.iter())).take(3) {
+            *c = (a * c0[3] + (b * c1[3] - a * c0[3]) * f) / alpha;
+        }
+        color
+    }
+
+    // sRGB encoded; the texture view decides whether sampling decodes it.
+    fn ramp(&self) -> [u8; RAMP_BYTES] {
+        let mut ramp = [0; RAMP_BYTES];
+        for (i, texel) in ramp.chunks_exact_mut(4).enumerate() {
+            let [r, g, b, a] = self.color_at(i as f32 / (RAMP_WIDTH - 1) as f32);
+            let encoded = [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a];
+            for (byte, c) in texel.iter_mut().zip(encoded) {
+                *byte = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+        ramp
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientVertex {
+    position: [f32; 2],
+    start: [f32; 2],
+    end: [f32; 2],
+    row: u32,
+    kind: u32,
+}
+
+impl GradientVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Uint32,
+        4 => Uint32,
+    ];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Gradient fills for the next frame, drawn after the solid shapes. Each one
+// bakes its stops into a row of a small texture that the fragment shader
+// samples at the pixel's position along the gradient.
+pub(crate) struct GradientRenderer {
+    vertices: Vec<GradientVertex>,
+    ramps: Vec<u8>,
+    shader: ShaderId,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+    texture_layout: wgpu::BindGroupLayout,
+    // sRGB targets get an sRGB view so blending happens on linear values;
+    // other targets take the encoded values as they are.
+    ramp_format: wgpu::TextureFormat,
+    ramp_texture: wgpu::Texture,
+    ramp_rows: u32,
+    bind_group: wgpu::BindGroup,
+}
+
+impl GradientRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+        globals_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("gradient"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("gradient.wgsl"))),
+        });
+
+        let texture_layout = Texture::bind_group_layout(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("gradient"),
+            bind_group_layouts: &[globals_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = cache.add_shader("gradient", shader, pipeline_layout);
+        let pipeline = cache.get_or_create(device, shader, &Self::buffers(), target);
+
+        let vertex_buffer =
+            DynamicBuffer::new(device, "gradient vertices", wgpu::BufferUsages::VERTEX, 0);
+
+        let ramp_format = ramp_format(target);
+        let ramp_rows = 16;
+        let ramp_texture = create_ramp_texture(device, ramp_format, ramp_rows);
+        let bind_group = create_bind_group(device, &texture_layout, &ramp_texture);
+
+        Self {
+            vertices: Vec::new(),
+            ramps: Vec::new(),
+            shader,
+            pipeline,
+            vertex_buffer,
+            texture_layout,
+            ramp_format,
+            ramp_texture,
+            ramp_rows,
+            bind_group,
+        }
+    }
+
+    fn buffers() -> [wgpu::VertexBufferLayout<'static>; 1] {
+        [GradientVertex::layout()]
+    }
+
+    // Switches to the pipeline for a new target format or sample count.
+    pub(crate) fn retarget(
+        &mut self,
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+    ) {
+        self.pipeline = cache.get_or_create(device, self.shader, &Self::buffers(), target);
+        let ramp_format = ramp_format(target);
+        if ramp_format != self.ramp_format {
+            self.ramp_format = ramp_format;
+            self.ramp_texture = create_ramp_texture(device, ramp_format, self.ramp_rows);
+            self.bind_group = create_bind_group(device, &self.texture_layout, &self.ramp_texture);
+        }
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, gradient: &Gradient) {
+        let row = (self.ramps.len() / RAMP_BYTES) as u32;
+        if row >= MAX_RAMPS {
+            log::warn!("more than {} gradients in one frame, dropping", MAX_RAMPS);
+            return;
+        }
+        self.ramps.extend_from_slice(&gradient.ramp());
+
+        let (start, end, kind) = match gradient.shape {
+            Shape::Linear { start, end } => (start, end, 0),
+            Shape::Radial { center, radius } => (center, [radius, 0.0], 1),
+        };
+        let vertex = |position| GradientVertex {
+            position,
+            start,
+            end,
+            row,
+            kind,
+        };
+        let (x0, y0) = (rect.x, rect.y);
+        let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+        self.vertices
+            .extend([[x0, y0], [x1, y0], [x1, y1], [x0, y0], [x1, y1], [x0, y1]].map(vertex));
+    }
+
+    // The number of gradient rects queued so far.
+    pub(crate) fn mark(&self) -> u32 {
+        self.vertices.len() as u32 / VERTICES_PER_RECT
+    }
+
+    pub(crate) fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let rows = (self.ramps.len() / RAMP_BYTES) as u32;
+        if rows > self.ramp_rows {
+            self.ramp_rows = rows.next_power_of_two().min(MAX_RAMPS);
+            self.ramp_texture = create_ramp_texture(device, self.ramp_format, self.ramp_rows);
+            self.bind_group = create_bind_group(device, &self.texture_layout, &self.ramp_texture);
+        }
+        if rows > 0 {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.ramp_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &self.ramps,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(RAMP_BYTES as u32),
+                    rows_per_image: NonZeroU32::new(rows),
+                },
+                wgpu::Extent3d {
+                    width: RAMP_WIDTH,
+                    height: rows,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        self.vertex_buffer
+            .write(device, queue, bytemuck::cast_slice(&self.vertices));
+        self.vertices.clear();
+        self.ramps.clear();
+    }
+
+    pub(crate) fn render_range<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        globals: &'a wgpu::BindGroup,
+        rects: Range<u32>,
+    ) {
+        if rects.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals, &[]);
+        rpass.set_bind_group(1, &self.bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice());
+        rpass.draw(
+            rects.start * VERTICES_PER_RECT..rects.end * VERTICES_PER_RECT,
+            0..1,
+        );
+    }
+}
+
+fn ramp_format(target: &TargetConfig) -> wgpu::TextureFormat {
+    if target.format.describe().srgb {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    }
+}
+
+fn create_ramp_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    rows: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("gradient ramps"),
+        size: wgpu::Extent3d {
+            width: RAMP_WIDTH,
+            height: rows,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &wgpu::Texture,
+) -> wgpu::BindGroup {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("gradient ramps"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("gradient ramps"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    })
+}
@@ -0,0 +1,71 @@
+struct Globals {
+    time: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    delta_time: f32;
+    scale_factor: f32;
+    encode_srgb: u32;
+    premultiply: u32;
+    opacity: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> globals: Globals;
+
+[[group(1), binding(0)]]
+var ramps: texture_2d<f32>;
+[[group(1), binding(1)]]
+var ramp_sampler: sampler;
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] start: vec2<f32>;
+    [[location(2)]] end: vec2<f32>;
+    [[location(3)]] row: u32;
+    [[location(4)]] kind: u32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] pixel: vec2<f32>;
+    [[location(1), interpolate(flat)]] start: vec2<f32>;
+    [[location(2), interpolate(flat)]] end: vec2<f32>;
+    [[location(3), interpolate(flat)]] row: u32;
+    [[location(4), interpolate(flat)]] kind: u32;
+};
+
+// Pixel coordinates with the origin at the top-left corner.
+[[stage(vertex)]]
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = input.position / globals.resolution * 2.0 - 1.0;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.pixel = input.position;
+    out.start = input.start;
+    out.end = input.end;
+    out.row = input.row;
+    out.kind = input.kind;
+    return out;
+}
+
+// `kind` 0 is linear from `start` to `end`; 1 is radial around `start`
+// with the radius in `end.x`.
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let axis = input.end - input.start;
+    let offset = input.pixel - input.start;
+    let linear = dot(offset, axis) / max(dot(axis, axis), 0.0001);
+    let radial = length(offset) / max(input.end.x, 0.0001);
+    let t = clamp(select(linear, radial, input.kind == 1u), 0.0, 1.0);
+
+    // Keep to texel centers so neighbouring ramps don't bleed in.
+    let size = vec2<f32>(textureDimensions(ramps));
+    let u = (t * (size.x - 1.0) + 0.5) / size.x;
+    let v = (f32(input.row) + 0.5) / size.y;
+    let sampled = textureSampleLevel(ramps, ramp_sampler, vec2<f32>(u, v), 0.0);
+    let color = vec4<f32>(sampled.rgb, sampled.a * globals.opacity);
+    if (globals.premultiply == 0u) {
+        return color;
+    }
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
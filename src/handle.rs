@@ -0,0 +1,299 @@
+use std::{
+    future::Future,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy};
+
+use crate::{
+    assets::{self, AssetEvent, AssetSlot, Generations, Loaded, LoadedAsset},
+    event::UserEvent,
+    file_drop::FileDropEvent,
+    scene::{ClickEvent, HoverEvent, Scene, ScrollEvent, TouchEvent},
+    sprite::SpriteId,
+    text::FontId,
+    tween::{Tween, TweenId},
+};
+
+type HoverCallback = Box<dyn FnMut(HoverEvent, &mut Scene) + Send>;
+type ClickCallback = Box<dyn FnMut(ClickEvent, &mut Scene) + Send>;
+type ScrollCallback = Box<dyn FnMut(ScrollEvent, &mut Scene) + Send>;
+type TouchCallback = Box<dyn FnMut(TouchEvent, &mut Scene) + Send>;
+type FileDropCallback = Box<dyn FnMut(&FileDropEvent, &mut Scene) -> anyhow::Result<()> + Send>;
+type AssetCallback = Box<dyn FnMut(&AssetEvent, &mut Scene) + Send>;
+
+// Runs the callbacks without holding their lock, so a callback can
+// register more through a handle of its own without deadlocking. Those run
+// from the next event on, after the ones already there.
+fn run_callbacks<C, R>(callbacks: &Mutex<Vec<C>>, run: impl FnOnce(&mut Vec<C>) -> R) -> R {
+    let mut running = std::mem::take(&mut *callbacks.lock().unwrap());
+    let result = run(&mut running);
+    let mut callbacks = callbacks.lock().unwrap();
+    running.append(&mut callbacks);
+    *callbacks = running;
+    result
+}
+
+// Drives a running `run_with` from other threads. Cheap to clone; every
+// method returns false once the event loop has exited, so producers know
+// to stop.
+//
+// Scenes are coalesced: only the latest one waits for the event loop, and
+// at most one event is queued for it no matter how often `set_scene` is
+// called between frames.
+#[derive(Clone)]
+pub struct Handle {
+    proxy: EventLoopProxy<UserEvent>,
+    scene: Arc<Mutex<Option<Scene>>>,
+    hover_callbacks: Arc<Mutex<Vec<HoverCallback>>>,
+    click_callbacks: Arc<Mutex<Vec<ClickCallback>>>,
+    scroll_callbacks: Arc<Mutex<Vec<ScrollCallback>>>,
+    touch_callbacks: Arc<Mutex<Vec<TouchCallback>>>,
+    file_drop_callbacks: Arc<Mutex<Vec<FileDropCallback>>>,
+    asset_callbacks: Arc<Mutex<Vec<AssetCallback>>>,
+    assets: Generations,
+}
+
+impl Handle {
+    pub(crate) fn new(proxy: EventLoopProxy<UserEvent>) -> Self {
+        Self {
+            proxy,
+            scene: Arc::new(Mutex::new(None)),
+            hover_callbacks: Arc::new(Mutex::new(Vec::new())),
+            click_callbacks: Arc::new(Mutex::new(Vec::new())),
+            scroll_callbacks: Arc::new(Mutex::new(Vec::new())),
+            touch_callbacks: Arc::new(Mutex::new(Vec::new())),
+            file_drop_callbacks: Arc::new(Mutex::new(Vec::new())),
+            asset_callbacks: Arc::new(Mutex::new(Vec::new())),
+            assets: Generations::default(),
+        }
+    }
+
+    fn send(&self, event: UserEvent) -> bool {
+        self.proxy.send_event(event).is_ok()
+    }
+
+    // Replaces the main window's scene.
+    pub fn set_scene(&self, scene: Scene) -> bool {
+        let queued = self.scene.lock().unwrap().replace(scene).is_some();
+        queued || self.send(UserEvent::SceneChanged)
+    }
+
+    pub(crate) fn take_scene(&self) -> Option<Scene> {
+        self.scene.lock().unwrap().take()
+    }
+
+    // Tweens the main window's scene, cancel it with the tween's id.
+    pub fn animate(&self, tween: Tween) -> bool {
+        self.send(UserEvent::Animate(tween))
+    }
+
+    pub fn cancel_tween(&self, id: TweenId) -> bool {
+        self.send(UserEvent::CancelTween(id))
+    }
+
+    // Runs `callback` on the event loop's thread whenever the cursor enters
+    // or leaves a region of the main window's scene, with that scene to
+    // change, e.g. to highlight the region. Changes mark the scene dirty as
+    // usual, so it's redrawn only if the callback changed something.
+    pub fn on_hover(&self, callback: impl FnMut(HoverEvent, &mut Scene) + Send + 'static) {
+        self.hover_callbacks
+            .lock()
+            .unwrap()
+            .push(Box::new(callback));
+    }
+
+    pub(crate) fn emit_hover(&self, event: HoverEvent, scene: &mut Scene) {
+        run_callbacks(&self.hover_callbacks, |callbacks| {
+            for callback in callbacks {
+                callback(event, scene);
+            }
+        });
+    }
+
+    // Like `on_hover`, for presses and clicks of any mouse button on the
+    // regions. A clone of the handle moved into `callback` can do the rest,
+    // e.g. `exit` for a quit button.
+    pub fn on_click(&self, callback: impl FnMut(ClickEvent, &mut Scene) + Send + 'static) {
+        self.click_callbacks
+            .lock()
+            .unwrap()
+            .push(Box::new(callback));
+    }
+
+    pub(crate) fn emit_click(&self, event: ClickEvent, scene: &mut Scene) {
+        run_callbacks(&self.click_callbacks, |callbacks| {
+            for callback in callbacks {
+                callback(event, scene);
+            }
+        });
+    }
+
+    // Like `on_hover`, for scrolling anywhere over the main window. Every
+    // wheel event arrives, however many come between two frames, so deltas
+    // add up instead of getting lost; a callback that leaves the scene
+    // alone causes no redraw.
+    pub fn on_scroll(&self, callback: impl FnMut(ScrollEvent, &mut Scene) + Send + 'static) {
+        self.scroll_callbacks
+            .lock()
+            .unwrap()
+            .push(Box::new(callback));
+    }
+
+    pub(crate) fn emit_scroll(&self, event: ScrollEvent, scene: &mut Scene) {
+        run_callbacks(&self.scroll_callbacks, |callbacks| {
+            for callback in callbacks {
+                callback(event, scene);
+            }
+        });
+    }
+
+    // Like `on_hover`, for every finger's every touch over the main window,
+    // for gestures of one's own. The first finger also taps and drags like
+    // the mouse regardless.
+    pub fn on_touch(&self, callback: impl FnMut(TouchEvent, &mut Scene) + Send + 'static) {
+        self.touch_callbacks
+            .lock()
+            .unwrap()
+            .push(Box::new(callback));
+    }
+
+    pub(crate) fn emit_touch(&self, event: TouchEvent, scene: &mut Scene) {
+        run_callbacks(&self.touch_callbacks, |callbacks| {
+            for callback in callbacks {
+                callback(event, scene);
+            }
+        });
+    }
+
+    // Like `on_hover`, for files dropped on the main window, all of one drop
+    // in one call. Registering any takes drops away from the built-in image
+    // viewer. An error, e.g. for a file the callback can't use, is shown
+    // across the top of the window.
+    pub fn on_file_drop(
+        &self,
+        callback: impl FnMut(&FileDropEvent, &mut Scene) -> anyhow::Result<()> + Send + 'static,
+    ) {
+        self.file_drop_callbacks
+            .lock()
+            .unwrap()
+            .push(Box::new(callback));
+    }
+
+    pub(crate) fn has_file_drop_callbacks(&self) -> bool {
+        !self.file_drop_callbacks.lock().unwrap().is_empty()
+    }
+
+    // Every callback runs; the first error is returned.
+    pub(crate) fn emit_file_drop(
+        &self,
+        event: &FileDropEvent,
+        scene: &mut Scene,
+    ) -> anyhow::Result<()> {
+        run_callbacks(&self.file_drop_callbacks, |callbacks| {
+            let mut result = Ok(());
+            for callback in callbacks {
+                let outcome = callback(event, scene);
+                if result.is_ok() {
+                    result = outcome;
+                }
+            }
+            result
+        })
+    }
+
+    // Loads an image into the main window's sprite of that name, which
+    // `DrawCommand::Texture` draws; the sprite it had before, or the
+    // placeholder, stays on screen until then. The file is decoded on
+    // tokio's blocking threads and uploaded on the event loop, so neither
+    // holds up a frame. Polling the future isn't needed for the load to
+    // count as the slot's latest, but is for it to run, e.g. with
+    // `tokio::spawn`.
+    pub fn load_texture(
+        &self,
+        name: &str,
+        path: impl Into<PathBuf>,
+    ) -> impl Future<Output = anyhow::Result<SpriteId>> + Send + 'static {
+        let load = self.load(AssetSlot::Texture(name.to_string()), path.into());
+        async move {
+            match load.await? {
+                Loaded::Texture(id) => Ok(id),
+                other => anyhow::bail!("expected a texture, got {:?}", other),
+            }
+        }
+    }
+
+    // Like `load_texture`, for a font added to the main window's text
+    // renderer. Fonts stay for the lifetime of the context, so loading the
+    // same slot again adds another.
+    pub fn load_font(
+        &self,
+        name: &str,
+        path: impl Into<PathBuf>,
+    ) -> impl Future<Output = anyhow::Result<FontId>> + Send + 'static {
+        let load = self.load(AssetSlot::Font(name.to_string()), path.into());
+        async move {
+            match load.await? {
+                Loaded::Font(id) => Ok(id),
+                other => anyhow::bail!("expected a font, got {:?}", other),
+            }
+        }
+    }
+
+    // Like `load_texture`, for the main window's shader, which is
+    // validated before it replaces the one drawing now.
+    pub fn load_shader(
+        &self,
+        path: impl Into<PathBuf>,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send + 'static {
+        let load = self.load(AssetSlot::Shader, path.into());
+        async move { load.await.map(|_| ()) }
+    }
+
+    fn load(
+        &self,
+        slot: AssetSlot,
+        path: PathBuf,
+    ) -> impl Future<Output = anyhow::Result<Loaded>> + Send + 'static {
+        let proxy = self.proxy.clone();
+        assets::load(&self.assets, slot, path, move |asset| {
+            proxy.send_event(UserEvent::AssetLoaded(asset)).is_ok()
+        })
+    }
+
+    // Whether no newer load for the asset's slot started since.
+    pub(crate) fn is_current(&self, asset: &LoadedAsset) -> bool {
+        self.assets.is_current(asset)
+    }
+
+    // Like `on_hover`, for loads that finished or failed, e.g. to put a
+    // font into the scene once it's there or to show the error.
+    pub fn on_asset(&self, callback: impl FnMut(&AssetEvent, &mut Scene) + Send + 'static) {
+        self.asset_callbacks
+            .lock()
+            .unwrap()
+            .push(Box::new(callback));
+    }
+
+    pub(crate) fn emit_asset(&self, event: &AssetEvent, scene: &mut Scene) {
+        run_callbacks(&self.asset_callbacks, |callbacks| {
+            for callback in callbacks {
+                callback(event, scene);
+            }
+        });
+    }
+
+    pub fn request_redraw(&self) -> bool {
+        self.send(UserEvent::RequestRedraw)
+    }
+
+    // The main window's inner size in physical pixels.
+    pub fn resize(&self, size: PhysicalSize<u32>) -> bool {
+        self.send(UserEvent::Resize(size))
+    }
+
+    pub fn exit(&self) -> bool {
+        self.send(UserEvent::Quit)
+    }
+}
@@ -0,0 +1,107 @@
+use winit::window::Window;
+
+// Keeps the window out of the taskbar and, where there is one, the pager
+// and alt-tab list. Can be switched at any time.
+//
+// Wayland and macOS have no client-side control over this through winit.
+pub fn set_skip_taskbar(window: &Window, enabled: bool) -> anyhow::Result<()> {
+    platform::set_skip_taskbar(window, enabled)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use winapi::{
+        shared::windef::HWND,
+        um::winuser::{
+            GetWindowLongPtrW, IsWindowVisible, SetWindowLongPtrW, ShowWindow, GWL_EXSTYLE,
+            SW_HIDE, SW_SHOWNA, WS_EX_APPWINDOW, WS_EX_TOOLWINDOW,
+        },
+    };
+    use winit::{platform::windows::WindowExtWindows, window::Window};
+
+    pub fn set_skip_taskbar(window: &Window, enabled: bool) -> anyhow::Result<()> {
+        let hwnd = window.hwnd() as HWND;
+        unsafe {
+            // The taskbar only notices the style change when the window is
+            // shown again.
+            let visible = IsWindowVisible(hwnd) != 0;
+            if visible {
+                ShowWindow(hwnd, SW_HIDE);
+            }
+            let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+            let style = if enabled {
+                (style | WS_EX_TOOLWINDOW as isize) & !(WS_EX_APPWINDOW as isize)
+            } else {
+                style & !(WS_EX_TOOLWINDOW as isize)
+            };
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style);
+            if visible {
+                ShowWindow(hwnd, SW_SHOWNA);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::os::raw::{c_char, c_long};
+
+    use winit::{platform::unix::WindowExtUnix, window::Window};
+    use x11_dl::xlib::{self, Display, Xlib};
+
+    // From the EWMH spec.
+    const NET_WM_STATE_REMOVE: c_long = 0;
+    const NET_WM_STATE_ADD: c_long = 1;
+    const SOURCE_APPLICATION: c_long = 1;
+
+    pub fn set_skip_taskbar(window: &Window, enabled: bool) -> anyhow::Result<()> {
+        let (display, xwindow) = match (window.xlib_display(), window.xlib_window()) {
+            (Some(display), Some(xwindow)) => (display as *mut Display, xwindow),
+            _ => anyhow::bail!("skipping the taskbar is not supported on wayland"),
+        };
+        let xlib = Xlib::open()?;
+        unsafe {
+            let atom = |name: &[u8]| (xlib.XInternAtom)(display, name.as_ptr() as *const c_char, 0);
+            // A mapped window asks the window manager to change its state.
+            let mut message: xlib::XClientMessageEvent = std::mem::zeroed();
+            message.type_ = xlib::ClientMessage;
+            message.window = xwindow;
+            message.message_type = atom(b"_NET_WM_STATE\0");
+            message.format = 32;
+            let action = if enabled {
+                NET_WM_STATE_ADD
+            } else {
+                NET_WM_STATE_REMOVE
+            };
+            message.data.set_long(0, action);
+            message
+                .data
+                .set_long(1, atom(b"_NET_WM_STATE_SKIP_TASKBAR\0") as c_long);
+            message
+                .data
+                .set_long(2, atom(b"_NET_WM_STATE_SKIP_PAGER\0") as c_long);
+            message.data.set_long(3, SOURCE_APPLICATION);
+            let mut event = xlib::XEvent::from(message);
+            let root = (xlib.XDefaultRootWindow)(display);
+            (xlib.XSendEvent)(
+                display,
+                root,
+                xlib::False,
+                xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask,
+                &mut event,
+            );
+            (xlib.XFlush)(display);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+mod platform {
+    use winit::window::Window;
+
+    pub fn set_skip_taskbar(_window: &Window, _enabled: bool) -> anyhow::Result<()> {
+        anyhow::bail!("skipping the taskbar is not supported on this platform")
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::Context;
+use global_hotkey::{hotkey::HotKey, GlobalHotKeyEvent, GlobalHotKeyManager};
+use winit::event_loop::EventLoopProxy;
+
+use crate::event::UserEvent;
+
+// global-hotkey's parser takes W3C key names and `super` for the logo key;
+// these are the names the key bindings use for the same keys.
+fn normalize(binding: &str) -> String {
+    binding
+        .split('+')
+        .map(|part| match part.trim().to_lowercase().as_str() {
+            "grave" | "backtick" => "backquote".to_string(),
+            "win" | "meta" | "logo" => "super".to_string(),
+            "minus" => "-".to_string(),
+            "equals" => "=".to_string(),
+            other => other.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+fn parse(binding: &str) -> anyhow::Result<HotKey> {
+    normalize(binding)
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid hotkey {:?}: {}", binding, e))
+}
+
+// A system-wide shortcut that shows and hides the windows, delivered
+// whichever window has focus, or none. X11 grabs the key with
+// `XGrabKey`, Windows registers it with `RegisterHotKey`; Wayland has no
+// such thing. Unregistered when dropped.
+pub(crate) struct GlobalHotkey {
+    manager: GlobalHotKeyManager,
+    hotkey: HotKey,
+}
+
+impl GlobalHotkey {
+    // Fails when another program already holds the combination.
+    pub fn new(binding: &str, proxy: EventLoopProxy<UserEvent>) -> anyhow::Result<Self> {
+        let hotkey = parse(binding)?;
+        let manager =
+            GlobalHotKeyManager::new().context("failed to start listening for global hotkeys")?;
+        manager.register(hotkey).with_context(|| {
+            format!(
+                "failed to register the hotkey {:?}, another program may be using it",
+                binding
+            )
+        })?;
+        forward(hotkey.id(), proxy);
+        log::info!("{} shows and hides the windows", binding);
+        Ok(Self { manager, hotkey })
+    }
+}
+
+impl Drop for GlobalHotkey {
+    fn drop(&mut self) {
+        if let Err(e) = self.manager.unregister(self.hotkey) {
+            log::warn!("failed to unregister the hotkey: {}", e);
+        }
+    }
+}
+
+// Presses arrive on global-hotkey's own channel, so a thread forwards them
+// to the event loop.
+fn forward(id: u32, proxy: EventLoopProxy<UserEvent>) {
+    std::thread::spawn(move || {
+        for event in GlobalHotKeyEvent::receiver() {
+            if event.id != id {
+                continue;
+            }
+            if proxy.send_event(UserEvent::ToggleVisibility).is_err() {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,154 @@
+use std::{borrow::Cow, ops::Range, sync::Arc};
+
+use crate::{
+    buffer::DynamicBuffer,
+    mesh::GpuMesh,
+    pipeline::{PipelineCache, ShaderId, TargetConfig},
+    vertex::Vertex,
+};
+
+// Per-instance transform and tint for `RenderContext::draw_instanced`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    // In pixels, where the mesh's origin ends up.
+    pub offset: [f32; 2],
+    pub scale: [f32; 2],
+    // Radians, clockwise on screen.
+    pub rotation: f32,
+    // Multiplied with the mesh's vertex colors.
+    pub color: [f32; 4],
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            offset: [0.0, 0.0],
+            scale: [1.0, 1.0],
+            rotation: 0.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl Instance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+        wgpu::vertex_attr_array![2 => Float32x2, 3 => Float32x2, 4 => Float32, 5 => Float32x4];
+
+    pub const fn new(offset: [f32; 2], color: [f32; 4]) -> Self {
+        Self {
+            offset,
+            scale: [1.0, 1.0],
+            rotation: 0.0,
+            color,
+        }
+    }
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Instances queued for the next frame share one buffer, reused across frames
+// and grown when it runs out. Each `draw_instanced` call is one draw.
+pub(crate) struct InstanceRenderer {
+    instances: Vec<Instance>,
+    queued: Vec<(Arc<GpuMesh>, Range<u32>)>,
+    batches: Vec<(Arc<GpuMesh>, Range<u32>)>,
+    shader: ShaderId,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    instance_buffer: DynamicBuffer,
+}
+
+impl InstanceRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+        globals_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("instanced"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("instanced.wgsl"))),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("instanced"),
+            bind_group_layouts: &[globals_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = cache.add_shader("instanced", shader, pipeline_layout);
+        let pipeline = cache.get_or_create(device, shader, &Self::buffers(), target);
+
+        let instance_buffer =
+            DynamicBuffer::new(device, "instances", wgpu::BufferUsages::VERTEX, 0);
+
+        Self {
+            instances: Vec::new(),
+            queued: Vec::new(),
+            batches: Vec::new(),
+            shader,
+            pipeline,
+            instance_buffer,
+        }
+    }
+
+    fn buffers() -> [wgpu::VertexBufferLayout<'static>; 2] {
+        [Vertex::layout(), Instance::layout()]
+    }
+
+    // Switches to the pipeline for a new target format or sample count.
+    pub(crate) fn retarget(
+        &mut self,
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+    ) {
+        self.pipeline = cache.get_or_create(device, self.shader, &Self::buffers(), target);
+    }
+
+    pub fn draw_instanced(&mut self, mesh: Arc<GpuMesh>, instances: &[Instance]) {
+        if instances.is_empty() {
+            return;
+        }
+        let start = self.instances.len() as u32;
+        self.instances.extend_from_slice(instances);
+        self.queued.push((mesh, start..self.instances.len() as u32));
+    }
+
+    // The number of `draw_instanced` calls queued so far.
+    pub(crate) fn mark(&self) -> u32 {
+        self.queued.len() as u32
+    }
+
+    pub(crate) fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.instance_buffer
+            .write(device, queue, bytemuck::cast_slice(&self.instances));
+        self.instances.clear();
+        self.batches.clear();
+        std::mem::swap(&mut self.batches, &mut self.queued);
+    }
+
+    pub(crate) fn render_range<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        globals: &'a wgpu::BindGroup,
+        batches: Range<u32>,
+    ) {
+        let batches = &self.batches[batches.start as usize..batches.end as usize];
+        if batches.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals, &[]);
+        rpass.set_vertex_buffer(1, self.instance_buffer.slice());
+        for (mesh, instances) in batches {
+            mesh.render_instanced(rpass, instances.clone());
+        }
+    }
+}
@@ -0,0 +1,71 @@
+struct Globals {
+    time: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    delta_time: f32;
+    scale_factor: f32;
+    encode_srgb: u32;
+    premultiply: u32;
+    opacity: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> globals: Globals;
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+struct InstanceInput {
+    [[location(2)]] offset: vec2<f32>;
+    [[location(3)]] scale: vec2<f32>;
+    [[location(4)]] rotation: f32;
+    [[location(5)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+// Mesh positions are scaled, then rotated clockwise on screen, then moved
+// to `offset`, all in pixels from the top-left corner.
+[[stage(vertex)]]
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+    let scaled = vertex.position * instance.scale;
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let pixel = vec2<f32>(scaled.x * c - scaled.y * s, scaled.x * s + scaled.y * c) + instance.offset;
+    let ndc = pixel / globals.resolution * 2.0 - 1.0;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.color = vertex.color * instance.color;
+    return out;
+}
+
+// Targets that aren't sRGB store colors as written, so the linear output
+// is encoded here to look the same as on an sRGB target.
+fn encode_output(color: vec4<f32>) -> vec4<f32> {
+    if (globals.encode_srgb == 0u) {
+        return color;
+    }
+    let c = max(color.rgb, vec3<f32>(0.0));
+    let curve = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return vec4<f32>(select(curve, c * 12.92, c <= vec3<f32>(0.0031308)), color.a);
+}
+
+// The window's fade scales alpha, and the blend state of
+// `AlphaMode::Premultiplied` takes the color already multiplied by it.
+fn output_color(color: vec4<f32>) -> vec4<f32> {
+    let c = encode_output(vec4<f32>(color.rgb, color.a * globals.opacity));
+    if (globals.premultiply == 0u) {
+        return c;
+    }
+    return vec4<f32>(c.rgb * c.a, c.a);
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return output_color(input.color);
+}
@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use winit::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    Redraw,
+    ToggleVisibility,
+    Grow,
+    Shrink,
+    TogglePassthrough,
+    ToggleAnimation,
+    Screenshot,
+    ToggleRecording,
+    ToggleOverlay,
+    ToggleAlwaysOnTop,
+    ToggleSkipTaskbar,
+    ToggleFullscreen,
+    ToggleDecorations,
+    ToggleResizable,
+    // Steps the frame rate limit through 15, 30, 60 and uncapped.
+    CycleFrameRate,
+    // Debugging aid, unbound unless `--simulate-device-loss` is given.
+    SimulateDeviceLoss,
+    // Debugging aid, unbound unless `--inject-gpu-error` is given.
+    InjectGpuError,
+    // Shows the clipboard's text like `set-text` does, and puts the shown
+    // text back.
+    Paste,
+    Copy,
+}
+
+impl std::str::FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "quit" => Action::Quit,
+            "redraw" => Action::Redraw,
+            "toggle-visibility" => Action::ToggleVisibility,
+            "grow" => Action::Grow,
+            "shrink" => Action::Shrink,
+            "toggle-passthrough" => Action::TogglePassthrough,
+            "toggle-animation" => Action::ToggleAnimation,
+            "screenshot" => Action::Screenshot,
+            "toggle-recording" => Action::ToggleRecording,
+            "toggle-overlay" => Action::ToggleOverlay,
+            "toggle-always-on-top" => Action::ToggleAlwaysOnTop,
+            "toggle-skip-taskbar" => Action::ToggleSkipTaskbar,
+            "toggle-fullscreen" => Action::ToggleFullscreen,
+            "toggle-decorations" => Action::ToggleDecorations,
+            "toggle-resizable" => Action::ToggleResizable,
+            "cycle-frame-rate" => Action::CycleFrameRate,
+            "simulate-device-loss" => Action::SimulateDeviceLoss,
+            "inject-gpu-error" => Action::InjectGpuError,
+            "paste" => Action::Paste,
+            "copy" => Action::Copy,
+            _ => anyhow::bail!("unknown action {:?}", s),
+        })
+    }
+}
+
+// Key names as written in the config, e.g. `escape = "quit"`, with any
+// modifiers first, e.g. `"ctrl+v" = "paste"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KeyBindings(pub BTreeMap<String, Action>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self(
+            [
+                ("escape", Action::Quit),
+                ("f5", Action::Redraw),
+                ("v", Action::ToggleVisibility),
+                ("plus", Action::Grow),
+                ("equals", Action::Grow),
+                ("numpad-add", Action::Grow),
+                ("minus", Action::Shrink),
+                ("numpad-subtract", Action::Shrink),
+                ("p", Action::TogglePassthrough),
+                ("a", Action::ToggleAnimation),
+                ("s", Action::Screenshot),
+                ("print-screen", Action::Screenshot),
+                ("r", Action::ToggleRecording),
+                ("f1", Action::ToggleOverlay),
+                ("t", Action::ToggleAlwaysOnTop),
+                ("k", Action::ToggleSkipTaskbar),
+                ("f11", Action::ToggleFullscreen),
+                ("d", Action::ToggleDecorations),
+                ("ctrl+r", Action::ToggleResizable),
+                ("l", Action::CycleFrameRate),
+                ("ctrl+v", Action::Paste),
+                ("ctrl+c", Action::Copy),
+            ]
+            .into_iter()
+            .map(|(key, action)| (key.to_string(), action))
+            .collect(),
+        )
+    }
+}
+
+impl KeyBindings {
+    pub fn keymap(&self) -> anyhow::Result<KeyMap> {
+        let bindings = self
+            .0
+            .iter()
+            .map(|(name, action)| {
+                parse_binding(name)
+                    .map(|binding| (binding, *action))
+                    .ok_or_else(|| anyhow::anyhow!("unknown key {:?}", name))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(KeyMap {
+            bindings,
+            pressed: HashSet::new(),
+            modifiers: ModifiersState::empty(),
+        })
+    }
+}
+
+pub struct KeyMap {
+    bindings: HashMap<(ModifiersState, VirtualKeyCode), Action>,
+    pressed: HashSet<VirtualKeyCode>,
+    modifiers: ModifiersState,
+}
+
+impl KeyMap {
+    // Only the initial press of a key triggers its action, auto-repeat
+    // while it is held is ignored.
+    pub fn handle(&mut self, input: &KeyboardInput) -> Option<Action> {
+        let key = input.virtual_keycode?;
+        match input.state {
+            ElementState::Pressed => {
+                if !self.pressed.insert(key) {
+                    return None;
+                }
+                if let Some(action) = self.bindings.get(&(self.modifiers, key)) {
+                    return Some(*action);
+                }
+                // Plain keys ignore shift, which some layouts need to type
+                // them, e.g. plus.
+                if (self.modifiers - ModifiersState::SHIFT).is_empty() {
+                    self.bindings.get(&(ModifiersState::empty(), key)).copied()
+                } else {
+                    None
+                }
+            }
+            ElementState::Released => {
+                self.pressed.remove(&key);
+                None
+            }
+        }
+    }
+
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    // Keys released while unfocused never report it.
+    pub fn reset(&mut self) {
+        self.pressed.clear();
+        self.modifiers = ModifiersState::empty();
+    }
+}
+
+fn parse_binding(name: &str) -> Option<(ModifiersState, VirtualKeyCode)> {
+    let mut parts: Vec<&str> = name.split('+').collect();
+    let key = parse_key(parts.pop()?)?;
+    let mut modifiers = ModifiersState::empty();
+    for part in parts {
+        modifiers |= match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => ModifiersState::CTRL,
+            "shift" => ModifiersState::SHIFT,
+            "alt" => ModifiersState::ALT,
+            "super" | "logo" | "win" | "meta" => ModifiersState::LOGO,
+            _ => return None,
+        };
+    }
+    Some((modifiers, key))
+}
+
+fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+
+    const LETTERS: [VirtualKeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [VirtualKeyCode; 10] =
+        [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    const FUNCTION: [VirtualKeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+
+    let name = name.to_ascii_lowercase();
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return match c {
+            'a'..='z' => Some(LETTERS[(c as u8 - b'a') as usize]),
+            '0'..='9' => Some(DIGITS[(c as u8 - b'0') as usize]),
+            _ => None,
+        };
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTION.get(n.checked_sub(1)?).copied();
+    }
+    Some(match name.as_str() {
+        "escape" | "esc" => Escape,
+        "space" => Space,
+        "return" | "enter" => Return,
+        "tab" => Tab,
+        "backspace" => Back,
+        "plus" => Plus,
+        "minus" => Minus,
+        "equals" => Equals,
+        "numpad-add" => NumpadAdd,
+        "numpad-subtract" => NumpadSubtract,
+        "up" => Up,
+        "down" => Down,
+        "left" => Left,
+        "right" => Right,
+        "print-screen" => Snapshot,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(deprecated)]
+    fn input(key: VirtualKeyCode, state: ElementState) -> KeyboardInput {
+        KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    fn press(keymap: &mut KeyMap, key: VirtualKeyCode) -> Option<Action> {
+        let action = keymap.handle(&input(key, ElementState::Pressed));
+        keymap.handle(&input(key, ElementState::Released));
+        action
+    }
+
+    #[test]
+    fn parses_modifiers() {
+        assert_eq!(
+            parse_binding("ctrl+shift+v"),
+            Some((
+                ModifiersState::CTRL | ModifiersState::SHIFT,
+                VirtualKeyCode::V
+            ))
+        );
+        assert_eq!(
+            parse_binding("Super+Escape"),
+            Some((ModifiersState::LOGO, VirtualKeyCode::Escape))
+        );
+        assert_eq!(
+            parse_binding("q"),
+            Some((ModifiersState::empty(), VirtualKeyCode::Q))
+        );
+        assert_eq!(parse_binding("hyper+q"), None);
+        assert_eq!(parse_binding("ctrl+"), None);
+    }
+
+    #[test]
+    fn parses_function_keys() {
+        assert_eq!(parse_key("f1"), Some(VirtualKeyCode::F1));
+        assert_eq!(parse_key("F12"), Some(VirtualKeyCode::F12));
+        assert_eq!(parse_key("f0"), None);
+        assert_eq!(parse_key("f13"), None);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert_eq!(parse_key("nope"), None);
+        assert_eq!(parse_key(""), None);
+        assert_eq!(parse_key("!"), None);
+        assert!(KeyBindings([("nope".to_string(), Action::Quit)].into())
+            .keymap()
+            .is_err());
+    }
+
+    #[test]
+    fn default_bindings_parse() {
+        assert!(KeyBindings::default().keymap().is_ok());
+    }
+
+    #[test]
+    fn ignores_auto_repeat() {
+        let mut keymap = KeyBindings::default().keymap().unwrap();
+        let pressed = input(VirtualKeyCode::Escape, ElementState::Pressed);
+        assert_eq!(keymap.handle(&pressed), Some(Action::Quit));
+        assert_eq!(keymap.handle(&pressed), None);
+        keymap.handle(&input(VirtualKeyCode::Escape, ElementState::Released));
+        assert_eq!(keymap.handle(&pressed), Some(Action::Quit));
+    }
+
+    #[test]
+    fn plain_keys_ignore_only_shift() {
+        let mut keymap = KeyBindings::default().keymap().unwrap();
+        keymap.set_modifiers(ModifiersState::SHIFT);
+        assert_eq!(press(&mut keymap, VirtualKeyCode::Plus), Some(Action::Grow));
+        keymap.set_modifiers(ModifiersState::CTRL);
+        // Its own binding, not `v`'s.
+        assert_eq!(press(&mut keymap, VirtualKeyCode::V), Some(Action::Paste));
+        assert_eq!(press(&mut keymap, VirtualKeyCode::P), None);
+    }
+}
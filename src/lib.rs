@@ -0,0 +1,1362 @@
+mod animation;
+mod assets;
+mod backdrop;
+mod bench;
+mod buffer;
+mod clipboard;
+mod color;
+mod compute;
+mod config;
+mod control;
+mod drag;
+mod draw_params;
+mod emoji;
+mod event;
+mod fade;
+mod file_drop;
+mod globals;
+mod gpu;
+mod gradient;
+mod handle;
+mod hints;
+#[cfg(feature = "hotkey")]
+mod hotkey;
+mod instance;
+mod keymap;
+mod logging;
+mod mesh;
+mod nine_patch;
+#[cfg(feature = "egui")]
+mod overlay;
+mod packer;
+mod passthrough;
+mod pipeline;
+mod post;
+mod readback;
+mod record;
+mod render;
+mod render_thread;
+mod rounded_rect;
+mod scene;
+mod sdf;
+mod shader;
+mod shadow;
+mod shapes;
+mod sprite;
+mod stats;
+#[cfg(feature = "svg")]
+mod svg;
+mod text;
+mod texture;
+#[cfg(feature = "tray")]
+mod tray;
+mod tween;
+#[cfg(feature = "vector")]
+mod vector;
+mod vertex;
+mod viewer;
+mod watch;
+mod window;
+mod window_set;
+
+pub use assets::{AssetEvent, AssetSlot, Loaded, LoadedAsset};
+pub use backdrop::{set_backdrop, Backdrop};
+pub use bench::BenchOptions;
+pub use color::{linear_to_srgb, srgb8, srgb_to_linear, HexColor};
+pub use compute::ComputeOptions;
+pub use config::{AlphaMode, Backend, Config, PowerPreference, PresentMode, RenderConfig};
+pub use control::{
+    default_socket_path, send_command, ControlCommand, ControlReply, ControlResponse, ControlState,
+};
+pub use drag::Region;
+pub use draw_params::DrawParams;
+pub use event::UserEvent;
+pub use file_drop::FileDropEvent;
+pub use globals::Globals;
+pub use gpu::{list_adapters, AdapterOptions, Gpu, GpuError, LimitsTier};
+pub use gradient::Gradient;
+pub use handle::Handle;
+pub use hints::set_skip_taskbar;
+pub use instance::Instance;
+pub use keymap::{Action, KeyBindings, KeyMap};
+pub use logging::{init_logging, LogOptions};
+pub use mesh::{GpuMesh, Mesh};
+pub use nine_patch::NinePatch;
+pub use passthrough::set_input_passthrough;
+pub use pipeline::PipelineCacheStats;
+pub use post::PostEffect;
+pub use record::RecordOptions;
+pub use render::{
+    draw, draw_scene, draw_with_delta, draw_with_overlay, render_with, Frame, RenderContext,
+    RenderOptions, COMPOSITE_BLEND, DEPTH_FORMAT, PREMULTIPLIED_BLEND,
+};
+pub use rounded_rect::CornerRadii;
+pub use scene::{
+    ClickEvent, Damage, DrawCommand, HoverEvent, MeshId, RegionId, Scene, ScrollEvent,
+    ScrollSource, TouchEvent,
+};
+pub use shader::{load_shader, validate_shader, DEFAULT_SHADER};
+pub use shadow::{Shadow, Shadowed};
+pub use shapes::Rect;
+pub use sprite::{SpriteId, TextureAtlas};
+pub use stats::{FrameStats, FrameTimings};
+#[cfg(feature = "svg")]
+pub use svg::Svg;
+pub use text::{FontId, TextRenderer, TextStyle};
+pub use texture::{Texture, TexturedVertex};
+pub use tween::{Animator, Easing, Property, Repeat, Tween, TweenId, Value};
+#[cfg(feature = "vector")]
+pub use vector::{FillRule, LineCap, LineJoin, Path, PathBuilder, Stroke};
+pub use vertex::Vertex;
+pub use viewer::{load_image, Fit};
+pub use window::{create_window, Anchor, Margins, MonitorSelector, WindowOptions};
+pub use window_set::WindowSet;
+
+pub use ab_glyph;
+pub use wgpu;
+pub use winit;
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use animation::{Animation, MAX_FRAME_DELTA};
+use anyhow::Context;
+use bench::Bench;
+use clipboard::Clipboard;
+use control::ControlServer;
+use render_thread::settle_frame;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, Event, MouseButton, Touch, TouchPhase, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
+    window::WindowId,
+};
+
+#[cfg(target_os = "windows")]
+use winit::platform::windows::EventLoopExtWindows;
+
+#[cfg(target_os = "linux")]
+use winit::platform::unix::EventLoopExtUnix;
+
+// macOS only allows the event loop on the main thread, so there is no
+// any-thread constructor there and `run` has to be called from main.
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn new_event_loop() -> EventLoop<UserEvent> {
+    EventLoop::new_any_thread()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn new_event_loop() -> EventLoop<UserEvent> {
+    EventLoop::with_user_event()
+}
+
+fn reload_shader(ctx: &mut RenderContext, path: &Path) {
+    let result = std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|source| ctx.reload_shader(&source));
+    match result {
+        Ok(()) => log::info!("reloaded shader {:?}", path),
+        Err(e) => log::error!("failed to reload shader {:?}: {}", path, e),
+    }
+}
+
+// How often the frame statistics are logged.
+const STATS_LOG_INTERVAL: Duration = Duration::from_secs(5);
+// winit has no events for monitor layout changes, so check now and then.
+const MONITOR_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+fn set_animating(windows: &mut WindowSet, animation: &mut Animation, enabled: bool) {
+    animation.set_enabled(enabled);
+    // Don't let the idle time before this count as one long frame.
+    for (_, ctx) in windows.iter_mut() {
+        ctx.advance(Duration::ZERO);
+    }
+}
+
+// Draws a window's frame on the calling thread, as for windows without a
+// render thread, and settles it; None once the window is gone.
+fn draw_window(
+    windows: &mut WindowSet,
+    id: WindowId,
+    show_stats: bool,
+    clamp_delta: bool,
+) -> Option<Result<Instant, wgpu::SurfaceError>> {
+    windows.queue_scene(id);
+    let delta = {
+        let (_, mut ctx) = windows.get_mut(id)?;
+        if show_stats {
+            let readout = ctx.stats().to_string();
+            ctx.queue_text(&readout, [8.0, 8.0], 14.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+        if clamp_delta {
+            ctx.since_last_frame().min(MAX_FRAME_DELTA)
+        } else {
+            ctx.since_last_frame()
+        }
+    };
+    let result = windows.draw(id, delta)?;
+    let (_, mut ctx) = windows.get_mut(id)?;
+    Some(settle_frame(&mut ctx, result))
+}
+
+// Where a frame ends up, drawn on the event loop or on the window's render
+// thread; `settle_frame` already recreated a lost surface.
+fn frame_presented(
+    windows: &mut WindowSet,
+    animation: &mut Animation,
+    id: WindowId,
+    result: Result<Instant, wgpu::SurfaceError>,
+    control_flow: &mut ControlFlow,
+) {
+    windows.presented(id, result.is_ok());
+    match result {
+        Ok(presented) => animation.presented(presented),
+        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => windows.request_redraw(id),
+        Err(wgpu::SurfaceError::Timeout) => {
+            log::warn!("timed out acquiring surface texture, skipping frame");
+            windows.request_redraw(id);
+        }
+        Err(wgpu::SurfaceError::OutOfMemory) => {
+            log::error!("the GPU ran out of memory, exiting");
+            *control_flow = ControlFlow::Exit;
+        }
+    }
+}
+
+fn set_frame_rate(windows: &mut WindowSet, animation: &mut Animation, frame_rate: Option<u32>) {
+    animation.set_frame_rate(frame_rate);
+    for (_, mut ctx) in windows.iter_mut() {
+        ctx.set_frame_rate_limit(animation.frame_rate());
+    }
+    match animation.frame_rate() {
+        Some(fps) => log::info!("frame rate limited to {}", fps),
+        None => log::info!("frame rate uncapped"),
+    }
+}
+
+// Nothing is drawn while every window is hidden, occluded or minimized, so
+// the loop just sleeps, unless lost devices are waiting to be recreated.
+fn next_control_flow(windows: &WindowSet, animation: &Animation) -> ControlFlow {
+    if let Some(due) = windows.next_recovery() {
+        ControlFlow::WaitUntil(due)
+    } else if !windows.any_on_screen() {
+        ControlFlow::Wait
+    } else if !animation.is_enabled() && windows.any_dirty() {
+        // Scene changes and tweens are held back to the frame rate limit
+        // too.
+        ControlFlow::WaitUntil(animation.next_frame())
+    } else {
+        animation.control_flow()
+    }
+}
+
+// Re-reads the config file and applies the settings that can change
+// without reopening the windows.
+fn reload_config(
+    config: &mut Config,
+    keymap: &mut KeyMap,
+    windows: &mut WindowSet,
+    animation: &mut Animation,
+) {
+    let path = match &config.path {
+        Some(path) if path.exists() => path.clone(),
+        _ => {
+            log::warn!("there is no config file to reload");
+            return;
+        }
+    };
+    let result = Config::load(&path).and_then(|loaded| Ok((loaded.keys.keymap()?, loaded)));
+    let (new_keymap, loaded) = match result {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            log::error!("failed to reload the config: {:#}", e);
+            return;
+        }
+    };
+    *keymap = new_keymap;
+    set_frame_rate(windows, animation, loaded.frame_rate);
+    set_animating(windows, animation, loaded.animate);
+    windows.set_fade_duration(loaded.fade_duration());
+    *config = Config {
+        record: config.record.take(),
+        watch_shader: config.watch_shader.take(),
+        image: config.image.take(),
+        ..loaded
+    };
+    windows.request_redraws();
+    log::info!("reloaded {:?}, window options apply after a restart", path);
+}
+
+// Factor applied to the window size by the grow and shrink actions.
+const RESIZE_STEP: f64 = 1.1;
+
+// Writes a window's stacking hints to its entry in the config file.
+// `slots` are the config's windows in order, the main window first. The
+// file is read again so command line overrides don't end up in it.
+fn save_hints(config: &Config, slots: &[WindowId], windows: &WindowSet, id: WindowId) {
+    let path = match &config.path {
+        Some(path) => path,
+        None => return,
+    };
+    let (current, slot) = match (windows.options(id), slots.iter().position(|&s| s == id)) {
+        (Some(current), Some(slot)) => (current, slot),
+        _ => return,
+    };
+    let saved = if path.exists() {
+        Config::load(path)
+    } else {
+        Ok(Config::default())
+    };
+    let result = saved.and_then(|mut saved| {
+        saved.path = Some(path.clone());
+        let options = match slot {
+            0 => &mut saved.window,
+            slot => match saved.windows.get_mut(slot - 1) {
+                Some(options) => options,
+                None => return Ok(()),
+            },
+        };
+        options.always_on_top = current.always_on_top;
+        options.skip_taskbar = current.skip_taskbar;
+        saved.save()
+    });
+    if let Err(e) = result {
+        log::warn!("failed to save the config: {:#}", e);
+    }
+}
+
+fn set_always_on_top(windows: &mut WindowSet, id: WindowId, enabled: bool) {
+    windows.set_always_on_top(id, enabled);
+    log::info!(
+        "{:?}: always on top {}",
+        id,
+        if enabled { "on" } else { "off" }
+    );
+}
+
+// Only the main window's scene comes from the handle, so only its hover
+// and click events go to the handle's callbacks.
+fn dispatch<E>(
+    windows: &mut WindowSet,
+    id: WindowId,
+    main_window: WindowId,
+    events: Vec<E>,
+    mut emit: impl FnMut(E, &mut Scene),
+) {
+    if id != main_window {
+        return;
+    }
+    if let Some(scene) = windows.scene_mut(id) {
+        for event in events {
+            emit(event, scene);
+        }
+    }
+}
+
+fn cursor_moved(
+    windows: &mut WindowSet,
+    handle: &Handle,
+    main_window: WindowId,
+    id: WindowId,
+    position: PhysicalPosition<f64>,
+) {
+    if let Some((window, drag)) = windows.drag_mut(id) {
+        drag.cursor_moved(window, position);
+    }
+    let events = windows.hover(id, Some(position));
+    dispatch(windows, id, main_window, events, |event, scene| {
+        handle.emit_hover(event, scene)
+    });
+}
+
+fn cursor_left(windows: &mut WindowSet, handle: &Handle, main_window: WindowId, id: WindowId) {
+    if let Some((window, drag)) = windows.drag_mut(id) {
+        drag.cursor_left(window);
+    }
+    let events = windows.hover(id, None);
+    dispatch(windows, id, main_window, events, |event, scene| {
+        handle.emit_hover(event, scene)
+    });
+}
+
+fn mouse_input(
+    windows: &mut WindowSet,
+    handle: &Handle,
+    main_window: WindowId,
+    id: WindowId,
+    state: ElementState,
+    button: MouseButton,
+) {
+    // A press on a region is its own, not the start of a drag.
+    let on_region = windows.is_over_region(id);
+    if state == ElementState::Released || !on_region {
+        if let Some((window, drag)) = windows.drag_mut(id) {
+            drag.mouse_input(window, state, button);
+        }
+    }
+    let events = windows.mouse_input(id, state, button);
+    dispatch(windows, id, main_window, events, |event, scene| {
+        handle.emit_click(event, scene)
+    });
+}
+
+// Ends a drag and cancels a press when the pointer goes away without a
+// release, e.g. when the window loses focus.
+fn cancel_pointer(windows: &mut WindowSet, handle: &Handle, main_window: WindowId, id: WindowId) {
+    if let Some((_, drag)) = windows.drag_mut(id) {
+        drag.end();
+    }
+    let events = windows.cancel_press(id);
+    dispatch(windows, id, main_window, events, |event, scene| {
+        handle.emit_click(event, scene)
+    });
+}
+
+// The first finger plays the mouse's left button: a tap clicks like one and
+// a drag off the regions moves the window.
+fn primary_touch(
+    windows: &mut WindowSet,
+    handle: &Handle,
+    main_window: WindowId,
+    id: WindowId,
+    touch: &Touch,
+) {
+    match touch.phase {
+        TouchPhase::Started => {
+            cursor_moved(windows, handle, main_window, id, touch.location);
+            let state = ElementState::Pressed;
+            mouse_input(windows, handle, main_window, id, state, MouseButton::Left);
+        }
+        TouchPhase::Moved => cursor_moved(windows, handle, main_window, id, touch.location),
+        TouchPhase::Ended => {
+            cursor_moved(windows, handle, main_window, id, touch.location);
+            let state = ElementState::Released;
+            mouse_input(windows, handle, main_window, id, state, MouseButton::Left);
+            cursor_left(windows, handle, main_window, id);
+        }
+        TouchPhase::Cancelled => {
+            cancel_pointer(windows, handle, main_window, id);
+            cursor_left(windows, handle, main_window, id);
+        }
+    }
+}
+
+// The handle's callbacks take the main window's drops when there are any,
+// otherwise dropped images are shown like `--image`. Either way a failure
+// shows on the window.
+fn files_dropped(
+    windows: &mut WindowSet,
+    handle: &Handle,
+    image_fit: Fit,
+    main_window: WindowId,
+    id: WindowId,
+    drop: FileDropEvent,
+) {
+    let result = if id == main_window && handle.has_file_drop_callbacks() {
+        match windows.scene_mut(id) {
+            Some(scene) => handle.emit_file_drop(&drop, scene),
+            None => Ok(()),
+        }
+    } else {
+        show_dropped_image(windows, id, image_fit, &drop.paths)
+    };
+    if let Err(e) = result {
+        log::error!("{:#}", e);
+        windows.set_file_drop_error(id, format!("{:#}", e));
+    }
+}
+
+// One image shows at a time, the first of the drop that decodes.
+fn show_dropped_image(
+    windows: &mut WindowSet,
+    id: WindowId,
+    image_fit: Fit,
+    paths: &[PathBuf],
+) -> anyhow::Result<()> {
+    let mut error = None;
+    for path in paths {
+        match viewer::load_image(path) {
+            Ok(image) => {
+                if let Some((window, mut ctx)) = windows.get_mut(id) {
+                    ctx.set_geometry(&[]);
+                    let fit = ctx.image_fit().unwrap_or(image_fit);
+                    ctx.set_image(&image, fit);
+                    window.request_redraw();
+                    log::info!("showing {:?}", path);
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    Err(error.unwrap_or_else(|| anyhow::anyhow!("nothing was dropped")))
+}
+
+// Uploads a load from the handle to the main window, unless a newer one
+// for the same slot started meanwhile; dropping it tells its future.
+fn asset_loaded(
+    windows: &mut WindowSet,
+    handle: &Handle,
+    main_window: WindowId,
+    mut asset: LoadedAsset,
+) {
+    if !handle.is_current(&asset) {
+        return;
+    }
+    let result = match windows.get_mut(main_window) {
+        Some((window, mut ctx)) => {
+            let result = asset.upload(&mut ctx);
+            window.request_redraw();
+            result
+        }
+        None => Err("the main window is gone".to_string()),
+    };
+    let event = asset.finish(result);
+    dispatch(
+        windows,
+        main_window,
+        main_window,
+        vec![event],
+        |event, scene| handle.emit_asset(&event, scene),
+    );
+}
+
+fn set_skip_taskbar(windows: &mut WindowSet, id: WindowId, enabled: bool) {
+    match windows.set_skip_taskbar(id, enabled) {
+        Ok(()) => log::info!(
+            "{:?}: skip taskbar {}",
+            id,
+            if enabled { "on" } else { "off" }
+        ),
+        Err(e) => log::warn!("failed to toggle skipping the taskbar: {}", e),
+    }
+}
+
+fn apply_action(
+    windows: &mut WindowSet,
+    id: WindowId,
+    action: Action,
+    animation: &mut Animation,
+    config: &Config,
+    slots: &[WindowId],
+    control_flow: &mut ControlFlow,
+) {
+    match action {
+        Action::Quit => *control_flow = ControlFlow::Exit,
+        Action::ToggleAnimation => set_animating(windows, animation, !animation.is_enabled()),
+        Action::Redraw => {
+            if windows.is_visible(id) == Some(true) {
+                if let Some((window, _)) = windows.get_mut(id) {
+                    window.request_redraw();
+                }
+            }
+        }
+        Action::ToggleVisibility => windows.toggle_visible(id),
+        Action::Grow | Action::Shrink => {
+            if let Some((window, _)) = windows.get_mut(id) {
+                let scale = if action == Action::Grow {
+                    RESIZE_STEP
+                } else {
+                    1.0 / RESIZE_STEP
+                };
+                let size = window.inner_size();
+                // The resulting Resized event reconfigures the surface.
+                window.set_inner_size(PhysicalSize::new(
+                    ((size.width as f64 * scale).round() as u32).max(1),
+                    ((size.height as f64 * scale).round() as u32).max(1),
+                ));
+            }
+        }
+        Action::ToggleOverlay => windows.toggle_overlay(id),
+        Action::Screenshot => {
+            if let Some((window, ctx)) = windows.get_mut(id) {
+                ctx.request_capture(config.screenshot_path());
+                window.request_redraw();
+            }
+        }
+        Action::ToggleRecording => {
+            if let Some((_, ctx)) = windows.get_mut(id) {
+                if ctx.is_recording() {
+                    ctx.stop_recording();
+                } else {
+                    let options = config.record.clone().unwrap_or_default();
+                    if let Err(e) = ctx.start_recording(&options) {
+                        log::error!("failed to start recording: {:#}", e);
+                    }
+                }
+            }
+        }
+        Action::TogglePassthrough => {
+            let enabled = !windows.is_passthrough(id);
+            if let Err(e) = windows.set_passthrough(id, enabled) {
+                log::warn!("failed to toggle input passthrough: {}", e);
+            }
+        }
+        Action::CycleFrameRate => {
+            let frame_rate = animation.next_frame_rate();
+            set_frame_rate(windows, animation, frame_rate);
+        }
+        // The event loop has the clipboard.
+        Action::Paste | Action::Copy => {}
+        Action::SimulateDeviceLoss => windows.simulate_device_loss(),
+        Action::InjectGpuError => {
+            if let Some((_, ctx)) = windows.get_mut(id) {
+                ctx.gpu().inject_error();
+            }
+        }
+        Action::ToggleAlwaysOnTop => {
+            if let Some(enabled) = windows.options(id).map(|o| !o.always_on_top) {
+                set_always_on_top(windows, id, enabled);
+                save_hints(config, slots, windows, id);
+            }
+        }
+        Action::ToggleSkipTaskbar => {
+            if let Some(enabled) = windows.options(id).map(|o| !o.skip_taskbar) {
+                set_skip_taskbar(windows, id, enabled);
+                save_hints(config, slots, windows, id);
+            }
+        }
+        // Placing the window again takes the event loop.
+        Action::ToggleFullscreen => {}
+        Action::ToggleDecorations => {
+            if let Some(enabled) = windows.options(id).map(|o| !o.decorations) {
+                windows.set_decorations(id, enabled);
+                log::info!(
+                    "{:?}: decorations {}",
+                    id,
+                    if enabled { "on" } else { "off" }
+                );
+            }
+        }
+        Action::ToggleResizable => {
+            if let Some(enabled) = windows.options(id).map(|o| !o.resizable) {
+                windows.set_resizable(id, enabled);
+                log::info!("{:?}: resizable {}", id, if enabled { "on" } else { "off" });
+            }
+        }
+    }
+}
+
+// Text set through the control socket, in logical pixels.
+const CONTROL_TEXT_MARGIN: f32 = 16.0;
+const CONTROL_TEXT_SIZE: f32 = 24.0;
+const MAX_PASTE_CHARS: usize = 4096;
+
+// Replaces every window's scene with `text` in the top-left corner, `size`
+// in logical pixels. Wrapped text breaks into lines that fit the window and
+// is cut off below.
+fn show_text(windows: &mut WindowSet, text: &str, size: f32, color: [f32; 4], wrap: bool) {
+    for id in windows.ids() {
+        let (scale, text) = match windows.get_mut(id) {
+            Some((_, ctx)) => {
+                let scale = ctx.scale_factor() as f32;
+                let margin = CONTROL_TEXT_MARGIN * scale * 2.0;
+                let bounds = [
+                    ctx.size().width as f32 - margin,
+                    ctx.size().height as f32 - margin,
+                ];
+                let text = if wrap {
+                    ctx.wrap_text(text, size * scale, bounds)
+                } else {
+                    text.to_string()
+                };
+                (scale, text)
+            }
+            None => continue,
+        };
+        if let Some(scene) = windows.scene_mut(id) {
+            scene.set_commands(vec![DrawCommand::Text {
+                text,
+                position: [CONTROL_TEXT_MARGIN * scale; 2],
+                size: size * scale,
+                color,
+            }]);
+        }
+    }
+}
+
+// The text of the main window, from `set-text` or a paste.
+fn shown_text(windows: &mut WindowSet, main_window: WindowId) -> Option<String> {
+    windows.scene_mut(main_window).and_then(|scene| {
+        scene.commands().iter().find_map(|command| match command {
+            DrawCommand::Text { text, .. } => Some(text.clone()),
+            _ => None,
+        })
+    })
+}
+
+// Only text is pasted, anything else on the clipboard is left alone. Past
+// `MAX_PASTE_CHARS` the text is cut off, so a huge clipboard doesn't turn
+// into as many glyphs.
+fn paste(windows: &mut WindowSet, clipboard: &mut Clipboard) -> anyhow::Result<()> {
+    let text = match clipboard.text()? {
+        Some(text) => text,
+        None => {
+            log::info!("nothing to paste, the clipboard holds no text");
+            return Ok(());
+        }
+    };
+    let text = match text.char_indices().nth(MAX_PASTE_CHARS) {
+        Some((end, _)) => format!("{}\u{2026}", &text[..end]),
+        None => text,
+    };
+    show_text(windows, &text, CONTROL_TEXT_SIZE, [1.0; 4], true);
+    Ok(())
+}
+
+fn copy(
+    windows: &mut WindowSet,
+    main_window: WindowId,
+    clipboard: &mut Clipboard,
+) -> anyhow::Result<()> {
+    let text =
+        shown_text(windows, main_window).ok_or_else(|| anyhow::anyhow!("no text to copy"))?;
+    clipboard.set_text(&text)
+}
+
+fn control_state(
+    windows: &mut WindowSet,
+    animation: &Animation,
+    main_window: WindowId,
+) -> ControlState {
+    let text = shown_text(windows, main_window);
+    let scene_color = windows
+        .scene_mut(main_window)
+        .and_then(|scene| scene.clear_color());
+    let clear_color = match (scene_color, windows.get_mut(main_window)) {
+        (Some(color), _) => HexColor::from_wgpu(color),
+        (None, Some((_, ctx))) => HexColor::from_wgpu(ctx.clear_color()),
+        (None, None) => HexColor::TRANSPARENT,
+    };
+    ControlState {
+        windows: windows.len(),
+        visible: windows.any_visible(),
+        animating: animation.is_enabled(),
+        frame_rate: animation.frame_rate(),
+        text,
+        clear_color,
+        fullscreen: windows.is_fullscreen(main_window),
+    }
+}
+
+// What `quit` does is up to the caller, after the reply is sent.
+fn apply_command(
+    event_loop: &EventLoopWindowTarget<UserEvent>,
+    windows: &mut WindowSet,
+    main_window: WindowId,
+    animation: &Animation,
+    clipboard: &mut Clipboard,
+    command: ControlCommand,
+) -> ControlResponse {
+    match command {
+        ControlCommand::Show | ControlCommand::Hide | ControlCommand::Toggle => {
+            let visible = match command {
+                ControlCommand::Show => true,
+                ControlCommand::Hide => false,
+                _ => !windows.any_visible(),
+            };
+            for id in windows.ids() {
+                windows.set_visible(id, visible);
+            }
+        }
+        ControlCommand::SetText { text, size, color } => {
+            let size = size.unwrap_or(CONTROL_TEXT_SIZE);
+            if !size.is_finite() || size <= 0.0 {
+                return ControlResponse::error("the text size must be positive");
+            }
+            let color = color.unwrap_or(HexColor([0xff; 4])).to_linear();
+            show_text(windows, &text, size, color, false);
+        }
+        ControlCommand::SetTextFromClipboard => {
+            if let Err(e) = paste(windows, clipboard) {
+                return ControlResponse::error(format!("{:#}", e));
+            }
+        }
+        ControlCommand::ClearText => {
+            for id in windows.ids() {
+                if let Some(scene) = windows.scene_mut(id) {
+                    scene.clear();
+                }
+            }
+        }
+        ControlCommand::ClearColor { color } => {
+            for id in windows.ids() {
+                if let Some(scene) = windows.scene_mut(id) {
+                    scene.set_clear_color(Some(color.to_wgpu()));
+                }
+            }
+        }
+        ControlCommand::SetDecorations { enabled } => {
+            for id in windows.ids() {
+                windows.set_decorations(id, enabled);
+            }
+        }
+        ControlCommand::SetResizable { enabled } => {
+            for id in windows.ids() {
+                windows.set_resizable(id, enabled);
+            }
+        }
+        ControlCommand::EnterFullscreen
+        | ControlCommand::ExitFullscreen
+        | ControlCommand::ToggleFullscreen => {
+            let enabled = match command {
+                ControlCommand::EnterFullscreen => true,
+                ControlCommand::ExitFullscreen => false,
+                _ => !windows.is_fullscreen(main_window),
+            };
+            windows.set_fullscreen(event_loop, main_window, enabled);
+        }
+        ControlCommand::State => {
+            return ControlResponse::state(control_state(windows, animation, main_window))
+        }
+        ControlCommand::Quit => {}
+    }
+    ControlResponse::ok()
+}
+
+async fn headless_context(
+    config: &Config,
+    size: PhysicalSize<u32>,
+) -> anyhow::Result<RenderContext> {
+    let mut ctx = RenderContext::headless(size, &config.render_options()).await?;
+    if let Some(path) = &config.watch_shader {
+        let path = &config.resolve_path(path);
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("failed to read {:?}", path))?;
+        ctx.reload_shader(&source)?;
+    }
+    if let Some(path) = &config.image {
+        let image = viewer::load_image(&config.resolve_path(path))?;
+        ctx.set_geometry(&[]);
+        ctx.set_image(&image, config.image_fit);
+    }
+    Ok(ctx)
+}
+
+// Renders a single frame without a window and writes it to `output` as PNG.
+pub async fn run_headless(
+    config: Config,
+    size: PhysicalSize<u32>,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let mut ctx = headless_context(&config, size).await?;
+    draw(&mut ctx).map_err(|e| anyhow::anyhow!("failed to render: {}", e))?;
+    ctx.read_pixels()?
+        .save(output)
+        .with_context(|| format!("failed to write {:?}", output))?;
+    log::info!("wrote {:?}", output);
+    Ok(())
+}
+
+// Draws `config.bench`'s frames back to back into one offscreen target and
+// prints their timings; fails if any frame did.
+pub async fn run_bench_headless(config: Config, size: PhysicalSize<u32>) -> anyhow::Result<()> {
+    let mut ctx = headless_context(&config, size).await?;
+    let mut bench = Bench::new(config.bench.clone().unwrap_or_default(), &ctx);
+    while !bench.is_done() {
+        let started = Instant::now();
+        let result = draw(&mut ctx);
+        bench.record(ctx.gpu(), started, &result);
+    }
+    bench.finish()
+}
+
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    run_with(config, |_| {}).await
+}
+
+// Like `run`, handing `setup` a `Handle` first, e.g. to pass to threads
+// that update the main window.
+pub async fn run_with(mut config: Config, setup: impl FnOnce(Handle)) -> anyhow::Result<()> {
+    if config.bench.is_some() {
+        // Frames are timed on the event loop, one right after the other.
+        config.animate = true;
+        config.frame_rate = None;
+        config.render_thread = false;
+    }
+    let event_loop = new_event_loop();
+    let handle = Handle::new(event_loop.create_proxy());
+
+    let mut keymap = config.keys.keymap()?;
+    let mut clipboard = Clipboard::default();
+    let mut stats_logged = Instant::now();
+    let mut monitors_checked = Instant::now();
+    let mut monitors_changed = false;
+    let mut animation = Animation::new(config.animate, config.frame_rate);
+
+    let mut windows = WindowSet::new(config.render_options());
+    windows.set_fade_duration(config.fade_duration());
+    windows.use_render_threads(config.render_thread.then(|| event_loop.create_proxy()));
+    let main_window = windows
+        .spawn_window(&event_loop, &config.window)
+        .context("failed to open the main window")?;
+    let mut slots = vec![main_window];
+    for (index, options) in config.windows.iter().enumerate() {
+        let id = windows
+            .spawn_window(&event_loop, options)
+            .with_context(|| format!("failed to open window {}", index + 1))?;
+        slots.push(id);
+    }
+
+    set_frame_rate(&mut windows, &mut animation, config.frame_rate);
+    animation.set_vsync_rate(windows.vsync_rate());
+    let mut bench = match (&config.bench, windows.get_mut(main_window)) {
+        (Some(options), Some((_, ctx))) => Some(Bench::new(options.clone(), &ctx)),
+        _ => None,
+    };
+
+    if let Some(options) = &config.record {
+        if let Some((_, ctx)) = windows.get_mut(main_window) {
+            ctx.start_recording(options)?;
+        }
+    }
+
+    if let Some(path) = &config.watch_shader {
+        let path = config.resolve_path(path);
+        for (_, mut ctx) in windows.iter_mut() {
+            reload_shader(&mut ctx, &path);
+        }
+        watch::watch_shader(path, event_loop.create_proxy())?;
+    }
+
+    if let Some(path) = &config.image {
+        let image = viewer::load_image(&config.resolve_path(path))?;
+        if let Some((_, mut ctx)) = windows.get_mut(main_window) {
+            ctx.set_geometry(&[]);
+            ctx.set_image(&image, config.image_fit);
+        }
+    }
+
+    #[cfg(feature = "tray")]
+    let _tray = tray::Tray::new(event_loop.create_proxy())?;
+
+    // Taken by another program perhaps, which the windows work without.
+    #[cfg(feature = "hotkey")]
+    let mut hotkey = if config.hotkey.is_empty() {
+        None
+    } else {
+        hotkey::GlobalHotkey::new(&config.hotkey, event_loop.create_proxy())
+            .map_err(|e| log::warn!("global hotkey disabled: {:#}", e))
+            .ok()
+    };
+
+    setup(handle.clone());
+
+    // Another instance may already own the socket, which isn't worth
+    // failing over.
+    let mut control = if config.control {
+        ControlServer::new(&config.control_socket(), event_loop.create_proxy())
+            .map_err(|e| log::warn!("control socket disabled: {:#}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    event_loop.run(move |event, event_loop, control_flow| {
+        *control_flow = next_control_flow(&windows, &animation);
+        match event {
+            Event::WindowEvent {
+                window_id,
+                ref event,
+            } if windows.overlay_event(window_id, event) => {}
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(size),
+            } => windows.resize(window_id, size),
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Occluded(occluded),
+            } => windows.set_occluded(window_id, occluded),
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::HoveredFile(_),
+            } => windows.file_hovered(window_id),
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::HoveredFileCancelled,
+            } => windows.file_hover_cancelled(window_id),
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::DroppedFile(path),
+            } => windows.file_dropped(window_id, path),
+            Event::WindowEvent {
+                window_id,
+                event:
+                    WindowEvent::ScaleFactorChanged {
+                        scale_factor,
+                        new_inner_size,
+                    },
+            } => {
+                if let Some((window, mut ctx)) = windows.get_mut(window_id) {
+                    // Keep the logical size the window had on the old monitor.
+                    let logical = window.inner_size().to_logical::<f64>(ctx.scale_factor());
+                    *new_inner_size = logical.to_physical(scale_factor);
+                    log::info!(
+                        "{:?}: scale factor changed to {}, resizing to {}x{}",
+                        window_id,
+                        scale_factor,
+                        new_inner_size.width,
+                        new_inner_size.height
+                    );
+                    ctx.set_scale_factor(scale_factor);
+                }
+                windows.resize(window_id, *new_inner_size);
+                // Re-anchor with the new scale once the event is handled.
+                monitors_changed = true;
+            }
+            Event::RedrawRequested(window_id) if !windows.is_on_screen(window_id) => {
+                windows.redraw_skipped(window_id)
+            }
+            Event::RedrawRequested(window_id) => {
+                if windows.defer_frame(window_id) {
+                    return;
+                }
+                // Tweens step by at most a frame's worth, even after the
+                // loop slept.
+                let since_last_frame = match windows.get_mut(window_id) {
+                    Some((_, ctx)) => ctx.since_last_frame(),
+                    None => return,
+                };
+                windows.animate(window_id, since_last_frame.min(MAX_FRAME_DELTA));
+                if windows.send_frame(window_id, config.show_stats, animation.is_enabled()) {
+                    return;
+                }
+                let started = Instant::now();
+                let result = match draw_window(
+                    &mut windows,
+                    window_id,
+                    config.show_stats,
+                    animation.is_enabled(),
+                ) {
+                    Some(result) => result,
+                    None => return,
+                };
+                if let Some(bench) = bench.as_mut().filter(|_| window_id == main_window) {
+                    if let Some((_, ctx)) = windows.get_mut(window_id) {
+                        bench.record(ctx.gpu(), started, &result);
+                    }
+                    if bench.is_done() {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+                frame_presented(
+                    &mut windows,
+                    &mut animation,
+                    window_id,
+                    result,
+                    control_flow,
+                );
+            }
+            Event::UserEvent(UserEvent::AssetLoaded(asset)) => {
+                asset_loaded(&mut windows, &handle, main_window, asset);
+            }
+            Event::UserEvent(UserEvent::FrameDone(window_id, result)) => {
+                windows.frame_done(window_id);
+                frame_presented(
+                    &mut windows,
+                    &mut animation,
+                    window_id,
+                    result,
+                    control_flow,
+                );
+            }
+            // What the context was left with is anyone's guess, so the
+            // window goes as if closed.
+            Event::UserEvent(UserEvent::RenderThreadPanicked(window_id)) => {
+                log::error!(
+                    "{:?}: the render thread panicked, closing the window",
+                    window_id
+                );
+                windows.remove(window_id);
+                if windows.is_empty() {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            Event::MainEventsCleared => {
+                if monitors_changed
+                    || windows.needs_reanchor()
+                    || monitors_checked.elapsed() >= MONITOR_CHECK_INTERVAL
+                {
+                    monitors_changed = false;
+                    monitors_checked = Instant::now();
+                    windows.reanchor(event_loop);
+                    animation.set_vsync_rate(windows.vsync_rate());
+                }
+                windows.recover_lost_devices();
+                for (id, drop) in windows.take_file_drops() {
+                    files_dropped(
+                        &mut windows,
+                        &handle,
+                        config.image_fit,
+                        main_window,
+                        id,
+                        drop,
+                    );
+                }
+                if animation.ready() {
+                    windows.redraw_dirty();
+                }
+                if animation.frame_due() {
+                    windows.request_redraws();
+                }
+                if stats_logged.elapsed() >= STATS_LOG_INTERVAL {
+                    stats_logged = Instant::now();
+                    for (window, ctx) in windows.iter_mut() {
+                        if ctx.stats().frames() > 0 {
+                            log::debug!(
+                                "{:?}: {}, {}",
+                                window.id(),
+                                ctx.stats(),
+                                ctx.pipeline_stats()
+                            );
+                        }
+                    }
+                }
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::UserEvent(UserEvent::StopRecording) => {
+                for (_, ctx) in windows.iter_mut() {
+                    ctx.stop_recording();
+                }
+            }
+            Event::UserEvent(UserEvent::SetAnimating(enabled)) => {
+                set_animating(&mut windows, &mut animation, enabled);
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::UserEvent(UserEvent::ShaderChanged(path)) => {
+                for (_, mut ctx) in windows.iter_mut() {
+                    reload_shader(&mut ctx, &path);
+                }
+                windows.request_redraws();
+            }
+            // From the tray or the global hotkey, so the main window takes
+            // the focus it's shown with to be typed into right away.
+            Event::UserEvent(UserEvent::ToggleVisibility) => {
+                let visible = !windows.any_visible();
+                for id in windows.ids() {
+                    windows.set_visible(id, visible);
+                }
+                if visible {
+                    windows.focus(main_window);
+                }
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::UserEvent(UserEvent::ReloadConfig) => {
+                reload_config(&mut config, &mut keymap, &mut windows, &mut animation);
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::UserEvent(UserEvent::SetFrameRate(frame_rate)) => {
+                set_frame_rate(&mut windows, &mut animation, frame_rate);
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::UserEvent(UserEvent::SimulateDeviceLoss) => windows.simulate_device_loss(),
+            Event::UserEvent(UserEvent::Quit) => *control_flow = ControlFlow::Exit,
+            // Redrawn like any other scene change, at the next frame the
+            // frame rate allows.
+            Event::UserEvent(UserEvent::SceneChanged) => {
+                if let (Some(scene), Some(current)) =
+                    (handle.take_scene(), windows.scene_mut(main_window))
+                {
+                    if !current.replace(scene) {
+                        windows.redundant_redraw(main_window);
+                    }
+                }
+                let events = windows.rehover(main_window);
+                dispatch(
+                    &mut windows,
+                    main_window,
+                    main_window,
+                    events,
+                    |event, scene| handle.emit_hover(event, scene),
+                );
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::UserEvent(UserEvent::RequestRedraw) => windows.request_redraws(),
+            Event::UserEvent(UserEvent::Animate(tween)) => {
+                if let Some(animator) = windows.animator_mut(main_window) {
+                    animator.add(tween);
+                }
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::UserEvent(UserEvent::CancelTween(id)) => {
+                if let Some(animator) = windows.animator_mut(main_window) {
+                    animator.cancel(id);
+                }
+            }
+            Event::UserEvent(UserEvent::Resize(size)) => {
+                if let Some((window, _)) = windows.get_mut(main_window) {
+                    // The resulting Resized event reconfigures the surface.
+                    window.set_inner_size(size);
+                }
+            }
+            Event::UserEvent(UserEvent::Control(command, reply)) => {
+                let quit = command == ControlCommand::Quit;
+                reply.send(apply_command(
+                    event_loop,
+                    &mut windows,
+                    main_window,
+                    &animation,
+                    &mut clipboard,
+                    command,
+                ));
+                *control_flow = if quit {
+                    ControlFlow::Exit
+                } else {
+                    next_control_flow(&windows, &animation)
+                };
+            }
+            Event::UserEvent(UserEvent::SetAlwaysOnTop(enabled)) => {
+                for id in windows.ids() {
+                    set_always_on_top(&mut windows, id, enabled);
+                    save_hints(&config, &slots, &windows, id);
+                }
+            }
+            Event::UserEvent(UserEvent::SetSkipTaskbar(enabled)) => {
+                for id in windows.ids() {
+                    set_skip_taskbar(&mut windows, id, enabled);
+                    save_hints(&config, &slots, &windows, id);
+                }
+            }
+            Event::UserEvent(UserEvent::TogglePassthrough) => {
+                for id in windows.ids() {
+                    let enabled = !windows.is_passthrough(id);
+                    match windows.set_passthrough(id, enabled) {
+                        Ok(()) => {
+                            log::info!("input passthrough {}", if enabled { "on" } else { "off" })
+                        }
+                        Err(e) => log::warn!("failed to toggle input passthrough: {}", e),
+                    }
+                }
+            }
+            // Pointer input belongs to the windows below while passing through.
+            Event::WindowEvent {
+                window_id,
+                event:
+                    WindowEvent::CursorMoved { .. }
+                    | WindowEvent::MouseInput { .. }
+                    | WindowEvent::MouseWheel { .. }
+                    | WindowEvent::Touch(..),
+            } if windows.is_passthrough(window_id) => {}
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::CursorMoved { position, .. },
+            } => {
+                cursor_moved(&mut windows, &handle, main_window, window_id, position);
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::CursorLeft { .. },
+            } => {
+                cursor_left(&mut windows, &handle, main_window, window_id);
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::MouseInput { state, button, .. },
+            } => {
+                mouse_input(&mut windows, &handle, main_window, window_id, state, button);
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Touch(touch),
+            } => {
+                let events = windows.touch(window_id, &touch);
+                dispatch(
+                    &mut windows,
+                    window_id,
+                    main_window,
+                    events,
+                    |event, scene| handle.emit_touch(event, scene),
+                );
+                if windows.is_primary_touch(window_id, &touch) {
+                    primary_touch(&mut windows, &handle, main_window, window_id, &touch);
+                }
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::MouseWheel { delta, .. },
+            } => {
+                let events = windows.scroll(window_id, delta);
+                dispatch(
+                    &mut windows,
+                    window_id,
+                    main_window,
+                    events,
+                    |event, scene| handle.emit_scroll(event, scene),
+                );
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::KeyboardInput { input, .. },
+            } => {
+                let result = match keymap.handle(&input) {
+                    Some(Action::Paste) => paste(&mut windows, &mut clipboard),
+                    Some(Action::Copy) => copy(&mut windows, main_window, &mut clipboard),
+                    Some(Action::ToggleFullscreen) => {
+                        let enabled = !windows.is_fullscreen(window_id);
+                        windows.set_fullscreen(event_loop, window_id, enabled);
+                        Ok(())
+                    }
+                    Some(action) => {
+                        apply_action(
+                            &mut windows,
+                            window_id,
+                            action,
+                            &mut animation,
+                            &config,
+                            &slots,
+                            control_flow,
+                        );
+                        Ok(())
+                    }
+                    None => Ok(()),
+                };
+                if let Err(e) = result {
+                    log::warn!("{:#}", e);
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(modifiers),
+                ..
+            } => keymap.set_modifiers(modifiers),
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Focused(false),
+            } => {
+                keymap.reset();
+                cancel_pointer(&mut windows, &handle, main_window, window_id);
+                *control_flow = next_control_flow(&windows, &animation);
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::CloseRequested,
+            } => {
+                windows.remove(window_id);
+                if windows.is_empty() {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            // winit exits the process right after, without dropping
+            // anything.
+            Event::LoopDestroyed => {
+                windows.close_all();
+                drop(control.take());
+                #[cfg(feature = "hotkey")]
+                drop(hotkey.take());
+                if let Some(Err(e)) = bench.take().map(Bench::finish) {
+                    log::error!("{:#}", e);
+                    std::process::exit(1);
+                }
+            }
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,102 @@
+use std::{
+    cell::Cell,
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    // 0 logs warnings, and info from this crate; every step adds detail.
+    pub verbosity: u8,
+    // Write JSON lines there instead of text to stderr.
+    pub file: Option<PathBuf>,
+}
+
+// wgpu's own logging goes through `log` as well, so it is filtered the same
+// way; its crates are quieter than ours at each step.
+fn default_filter(verbosity: u8) -> &'static str {
+    match verbosity {
+        0 => "warn,mgraphics=info",
+        1 => "warn,mgraphics=debug",
+        2 => "info,mgraphics=trace",
+        3 => "debug,mgraphics=trace",
+        _ => "trace",
+    }
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c < ' ' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Sets up the global logger. `RUST_LOG` wins over the verbosity, e.g.
+// `RUST_LOG=mgraphics::render=trace,wgpu_core=warn` to look at one
+// subsystem.
+pub fn init_logging(options: &LogOptions) -> anyhow::Result<()> {
+    let env = env_logger::Env::default().default_filter_or(default_filter(options.verbosity));
+    let mut builder = env_logger::Builder::from_env(env);
+    if let Some(path) = &options.file {
+        let file = File::create(path).with_context(|| format!("failed to create {:?}", path))?;
+        builder
+            .target(env_logger::Target::Pipe(Box::new(file)))
+            .format(|buf, record| {
+                writeln!(
+                    buf,
+                    r#"{{"time":"{}","level":"{}","target":"{}","message":"{}"}}"#,
+                    buf.timestamp_millis(),
+                    record.level(),
+                    escape_json(record.target()),
+                    escape_json(&record.args().to_string())
+                )
+            });
+    }
+    builder.try_init().context("a logger is already set")
+}
+
+// Lets messages logged every frame through at most once per interval, and
+// counts the ones it held back.
+pub(crate) struct RateLimit {
+    interval: Duration,
+    last: Cell<Option<Instant>>,
+    suppressed: Cell<u64>,
+}
+
+impl RateLimit {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Cell::new(None),
+            suppressed: Cell::new(0),
+        }
+    }
+
+    // The number of messages skipped since the last one, if this one is due.
+    pub fn check(&self) -> Option<u64> {
+        let now = Instant::now();
+        if self
+            .last
+            .get()
+            .map_or(false, |last| now - last < self.interval)
+        {
+            self.suppressed.set(self.suppressed.get() + 1);
+            return None;
+        }
+        self.last.set(Some(now));
+        Some(self.suppressed.replace(0))
+    }
+}
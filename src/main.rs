@@ -1,204 +1,507 @@
-use std::borrow::Cow;
-
-use wgpu::RenderPipeline;
-use winit::{
-    dpi::{PhysicalPosition, PhysicalSize},
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use mgraphics::{
+    Action, AlphaMode, Anchor, Backdrop, Backend, BenchOptions, Config, ControlCommand, Fit,
+    HexColor, LogOptions, Margins, MonitorSelector, PostEffect, PowerPreference, RecordOptions,
 };
 
-#[cfg(target_os = "windows")]
-use winit::platform::windows::{EventLoopExtWindows, WindowBuilderExtWindows};
+#[derive(Debug, Parser)]
+#[clap(version, about)]
+struct Args {
+    /// Config file, defaults to ~/.config/mgraphics/config.toml
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// Log more, repeat for even more; RUST_LOG overrides it
+    #[clap(short, long, parse(from_occurrences))]
+    verbose: u8,
+    /// Write the log to this file as JSON lines instead of to stderr
+    #[clap(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// Print the effective configuration and exit
+    #[clap(long)]
+    dump_config: bool,
+    /// Render one frame offscreen and save it instead of opening a window
+    #[clap(long)]
+    headless: bool,
+    /// Image written by --headless
+    #[clap(long, default_value = "frame.png")]
+    output: PathBuf,
+    /// Image size for --headless, defaults to the window size
+    #[clap(long, value_name = "WxH", parse(try_from_str = parse_size))]
+    size: Option<(u32, u32)>,
+    /// Record every frame as numbered PNGs into this directory
+    #[clap(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+    /// With --record, pipe the frames into ffmpeg and write this video file
+    #[clap(long, requires = "record")]
+    ffmpeg: bool,
+    /// With --record, stop after this many frames; with --bench, measure
+    /// this many, 1000 by default
+    #[clap(long)]
+    frames: Option<u64>,
+    /// Draw frames as fast as possible, print their CPU and GPU times and
+    /// exit; with --headless, offscreen
+    #[clap(long)]
+    bench: bool,
+    /// With --bench, frames drawn first and left out of the numbers
+    #[clap(long, value_name = "FRAMES", requires = "bench")]
+    bench_warmup: Option<u64>,
+    /// With --bench, also write the results to this file as JSON
+    #[clap(long, value_name = "PATH", requires = "bench")]
+    bench_json: Option<PathBuf>,
+    /// List the available adapters and exit
+    #[clap(long)]
+    list_adapters: bool,
+    /// Graphics API to try first, the others follow if it has no adapter
+    /// (vulkan, dx12, metal, gl or auto)
+    #[clap(long, env = "WGPU_BACKEND")]
+    backend: Option<Backend>,
+    /// Use the first adapter whose name contains this
+    #[clap(long, env = "MGRAPHICS_ADAPTER")]
+    adapter: Option<String>,
+    /// Prefer the low-power or the high-performance adapter
+    #[clap(long, possible_values = &["low", "high"])]
+    power_preference: Option<PowerPreference>,
+    /// WGSL file to use instead of the built-in shader
+    #[clap(long, value_name = "PATH")]
+    shader: Option<PathBuf>,
+    /// Show this PNG, JPEG or WebP file scaled to the window; dropping
+    /// another file onto the window replaces it
+    #[clap(long, value_name = "PATH")]
+    image: Option<PathBuf>,
+    /// How --image is scaled to the window
+    #[clap(long, possible_values = &["contain", "cover", "stretch", "center"])]
+    fit: Option<Fit>,
+    /// Load the shader from disk and reload it whenever it changes,
+    /// defaults to --shader or the config's shader
+    #[clap(long, value_name = "PATH")]
+    watch: Option<Option<PathBuf>>,
+    /// Window width in logical pixels
+    #[clap(long)]
+    width: Option<u32>,
+    /// Window height in logical pixels
+    #[clap(long)]
+    height: Option<u32>,
+    /// Horizontal position relative to the monitor, overrides the anchor
+    #[clap(long, allow_hyphen_values = true)]
+    x: Option<i32>,
+    /// Vertical position relative to the monitor, overrides the anchor
+    #[clap(long, allow_hyphen_values = true)]
+    y: Option<i32>,
+    /// Edge or corner to anchor the window to, e.g. bottom or top-right
+    #[clap(long)]
+    anchor: Option<Anchor>,
+    /// Margins from the monitor edges: ALL, VERTICAL,HORIZONTAL or
+    /// TOP,RIGHT,BOTTOM,LEFT
+    #[clap(long)]
+    margin: Option<Margins>,
+    /// Center the window on both axes
+    #[clap(long)]
+    center: bool,
+    /// Monitor to place the window on, by index or part of its name
+    #[clap(long)]
+    monitor: Option<MonitorSelector>,
+    /// Disable window transparency
+    #[clap(long)]
+    opaque: bool,
+    /// Background as #rrggbb or #rrggbbaa, an opaque one disables
+    /// transparency
+    #[clap(long, value_name = "COLOR")]
+    clear_color: Option<HexColor>,
+    /// Draw over the previous frame instead of clearing it
+    #[clap(long)]
+    keep_contents: bool,
+    /// Whether the built-in renderers blend premultiplied or straight
+    /// colors
+    #[clap(long, possible_values = &["premultiplied", "straight"])]
+    alpha_mode: Option<AlphaMode>,
+    /// Blur the whole frame by this many pixels, after the configured
+    /// post effects
+    #[clap(long, value_name = "RADIUS")]
+    blur: Option<f32>,
+    /// Blur behind the transparent window, on Windows
+    #[clap(long, possible_values = &["none", "blur", "acrylic"])]
+    backdrop: Option<Backdrop>,
+    /// Hide the window decorations; the bottom-right corner resizes the
+    /// window instead
+    #[clap(long)]
+    no_decorations: bool,
+    /// Keep the window at its size
+    #[clap(long)]
+    no_resize: bool,
+    /// Let mouse clicks pass through to the windows below
+    #[clap(long)]
+    passthrough: bool,
+    /// Keep the window above normal windows
+    #[clap(long)]
+    always_on_top: bool,
+    /// Keep the window out of the taskbar and alt-tab
+    #[clap(long)]
+    skip_taskbar: bool,
+    /// Start borderless fullscreen on the window's monitor
+    #[clap(long)]
+    fullscreen: bool,
+    /// Show frame timing statistics on screen
+    #[clap(long)]
+    stats: bool,
+    /// Redraw continuously, e.g. for animated shaders
+    #[clap(long)]
+    animate: bool,
+    /// Draw on the event loop instead of a render thread per window
+    #[clap(long)]
+    single_threaded: bool,
+    /// Most frames per second, 0 for uncapped
+    #[clap(long = "max-fps", alias = "fps", value_name = "FPS")]
+    fps: Option<u32>,
+    /// Bind F12 to dropping and rebuilding the renderers, as after a GPU reset
+    #[clap(long)]
+    simulate_device_loss: bool,
+    /// Bind F11 to creating an invalid GPU resource, to test error reporting
+    #[clap(long)]
+    inject_gpu_error: bool,
+    /// Bind a key to an action, e.g. --bind q=quit
+    #[clap(long, value_name = "KEY=ACTION", parse(try_from_str = parse_binding))]
+    bind: Vec<(String, Action)>,
+    /// Control socket to listen on, or to send to with ctl, defaults to
+    /// $XDG_RUNTIME_DIR/mgraphics.sock
+    #[clap(long, global = true, value_name = "PATH")]
+    socket: Option<PathBuf>,
+    /// Don't listen for commands on the control socket
+    #[clap(long)]
+    no_control: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
 
-#[cfg(target_os = "linux")]
-use winit::platform::unix::{EventLoopExtUnix, WindowBuilderExtUnix, XWindowType};
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Send a command to the running instance
+    Ctl {
+        #[clap(subcommand)]
+        command: CtlCommand,
+    },
+}
 
-fn create_window(event_loop: &EventLoop<()>) -> anyhow::Result<Window> {
-    #[allow(unused_mut)]
-    let mut window_builder = WindowBuilder::new();
+#[derive(Debug, Subcommand)]
+enum CtlCommand {
+    /// Show every window
+    Show,
+    /// Hide every window
+    Hide,
+    /// Hide every window if any is shown, otherwise show them all
+    Toggle,
+    /// Draw this text in every window, replacing what was there
+    SetText {
+        text: String,
+        /// In logical pixels
+        #[clap(long)]
+        size: Option<f32>,
+        /// As #rrggbb or #rrggbbaa
+        #[clap(long)]
+        color: Option<HexColor>,
+    },
+    /// Remove the text again
+    ClearText,
+    /// Draw the clipboard's text like set-text does, wrapped to fit
+    SetTextFromClipboard,
+    /// Change the background, as #rrggbb or #rrggbbaa
+    ClearColor { color: HexColor },
+    /// Show or hide every window's decorations
+    SetDecorations {
+        #[clap(parse(try_from_str))]
+        enabled: bool,
+    },
+    /// Let every window be resized or not
+    SetResizable {
+        #[clap(parse(try_from_str))]
+        enabled: bool,
+    },
+    /// Make the main window borderless fullscreen
+    EnterFullscreen,
+    /// Put the main window back at its anchor
+    ExitFullscreen,
+    /// Enter fullscreen, or exit it if the main window is fullscreen
+    ToggleFullscreen,
+    /// Print the current state, one `name value` line each
+    State,
+    /// Close every window and exit
+    Quit,
+}
 
-    #[cfg(target_os = "linux")]
-    {
-        window_builder = window_builder.with_x11_window_type(vec![XWindowType::Toolbar]);
+impl From<&CtlCommand> for ControlCommand {
+    fn from(command: &CtlCommand) -> Self {
+        match command {
+            CtlCommand::Show => ControlCommand::Show,
+            CtlCommand::Hide => ControlCommand::Hide,
+            CtlCommand::Toggle => ControlCommand::Toggle,
+            CtlCommand::SetText { text, size, color } => ControlCommand::SetText {
+                text: text.clone(),
+                size: *size,
+                color: *color,
+            },
+            CtlCommand::ClearText => ControlCommand::ClearText,
+            CtlCommand::SetTextFromClipboard => ControlCommand::SetTextFromClipboard,
+            CtlCommand::ClearColor { color } => ControlCommand::ClearColor { color: *color },
+            CtlCommand::SetDecorations { enabled } => {
+                ControlCommand::SetDecorations { enabled: *enabled }
+            }
+            CtlCommand::SetResizable { enabled } => {
+                ControlCommand::SetResizable { enabled: *enabled }
+            }
+            CtlCommand::EnterFullscreen => ControlCommand::EnterFullscreen,
+            CtlCommand::ExitFullscreen => ControlCommand::ExitFullscreen,
+            CtlCommand::ToggleFullscreen => ControlCommand::ToggleFullscreen,
+            CtlCommand::State => ControlCommand::State,
+            CtlCommand::Quit => ControlCommand::Quit,
+        }
     }
+}
 
-    let primary = event_loop
-        .primary_monitor()
-        .ok_or(anyhow::anyhow!("primary monitor is not found"))?;
-
-    let primary_size = primary.size();
-
-    let size = PhysicalSize::<u32>::new(1024, 128);
+async fn ctl(config: &Config, command: &CtlCommand) -> anyhow::Result<()> {
+    let response = mgraphics::send_command(&config.control_socket(), &command.into()).await?;
+    if let Some(error) = response.error {
+        anyhow::bail!("{}", error);
+    }
+    if let Some(state) = response.state {
+        let or_none = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+        println!("windows {}", state.windows);
+        println!("visible {}", state.visible);
+        println!("animating {}", state.animating);
+        println!(
+            "frame_rate {}",
+            or_none(state.frame_rate.map(|fps| fps.to_string()))
+        );
+        println!("clear_color {}", state.clear_color);
+        println!("fullscreen {}", state.fullscreen);
+        // Last, as it may span lines.
+        println!("text {}", or_none(state.text));
+    }
+    Ok(())
+}
 
-    let pos = PhysicalPosition::<i32>::new(((primary_size.width - size.width) / 2).try_into()?, 8);
+fn parse_size(s: &str) -> anyhow::Result<(u32, u32)> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| anyhow::anyhow!("expected WxH, got {:?}", s))?;
+    Ok((
+        width.parse().context("invalid width")?,
+        height.parse().context("invalid height")?,
+    ))
+}
 
-    let window = window_builder
-        .with_position(pos)
-        .with_inner_size(size)
-        // .with_decorations(false)
-        .with_transparent(true)
-        .build(&event_loop)?;
-    Ok(window)
+fn parse_binding(s: &str) -> anyhow::Result<(String, Action)> {
+    let (key, action) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("expected KEY=ACTION, got {:?}", s))?;
+    Ok((key.to_string(), action.parse()?))
 }
 
-async fn run() -> anyhow::Result<()> {
-    let event_loop = EventLoop::<()>::new_any_thread();
+impl Args {
+    // Flags given on the command line win over the config file.
+    fn apply(&self, config: &mut Config) -> anyhow::Result<()> {
+        if let Some(backend) = self.backend {
+            config.render.backend = backend;
+        }
+        if self.adapter.is_some() {
+            config.render.adapter = self.adapter.clone();
+        }
+        if let Some(preference) = self.power_preference {
+            config.render.power_preference = preference;
+        }
+        if let Some(color) = self.clear_color {
+            config.render.clear_color = color;
+        }
+        if self.keep_contents {
+            config.render.keep_contents = true;
+        }
+        if let Some(mode) = self.alpha_mode {
+            config.render.alpha_mode = mode;
+        }
+        if let Some(radius) = self.blur {
+            config.render.post.push(PostEffect::Blur { radius });
+        }
+        let window = &mut config.window;
+        if self.width.is_some() {
+            window.width = self.width;
+        }
+        if self.height.is_some() {
+            window.height = self.height;
+        }
+        if let Some(anchor) = self.anchor {
+            window.anchor = anchor;
+        }
+        if let Some(margin) = self.margin {
+            window.margin = margin;
+        }
+        if self.x.is_some() {
+            window.x = self.x;
+        }
+        if self.y.is_some() {
+            window.y = self.y;
+        }
+        if self.center {
+            window.center = true;
+        }
+        if self.monitor.is_some() {
+            window.monitor = self.monitor.clone();
+        }
+        if self.opaque {
+            window.transparent = false;
+        }
+        if self.no_decorations {
+            window.decorations = false;
+        }
+        if self.no_resize {
+            window.resizable = false;
+        }
+        if self.passthrough {
+            window.input_passthrough = true;
+        }
+        if self.always_on_top {
+            window.always_on_top = true;
+        }
+        if self.skip_taskbar {
+            window.skip_taskbar = true;
+        }
+        if self.fullscreen {
+            window.fullscreen = true;
+        }
+        if let Some(backdrop) = self.backdrop {
+            window.backdrop = backdrop;
+        }
+        if self.stats {
+            config.show_stats = true;
+        }
+        if self.animate {
+            config.animate = true;
+        }
+        if self.single_threaded {
+            config.render_thread = false;
+        }
+        if self.fps.is_some() {
+            config.frame_rate = self.fps;
+        }
+        if self.simulate_device_loss {
+            config
+                .keys
+                .0
+                .insert("f12".to_string(), Action::SimulateDeviceLoss);
+        }
+        if self.inject_gpu_error {
+            config
+                .keys
+                .0
+                .insert("f11".to_string(), Action::InjectGpuError);
+        }
+        for (key, action) in &self.bind {
+            config.keys.0.insert(key.clone(), *action);
+        }
+        if let Some(output) = &self.record {
+            config.record = Some(RecordOptions {
+                output: output.clone(),
+                ffmpeg: self.ffmpeg,
+                frames: self.frames,
+                frame_rate: config.frame_rate.filter(|&fps| fps > 0).unwrap_or(60),
+            });
+        }
+        if self.bench {
+            let defaults = BenchOptions::default();
+            config.bench = Some(BenchOptions {
+                frames: self.frames.unwrap_or(defaults.frames),
+                warmup: self.bench_warmup.unwrap_or(defaults.warmup),
+                json: self.bench_json.clone(),
+            });
+        }
+        if self.shader.is_some() {
+            config.render.shader = self.shader.clone();
+        }
+        if self.image.is_some() {
+            config.image = self.image.clone();
+        }
+        if let Some(fit) = self.fit {
+            config.image_fit = fit;
+        }
+        if self.socket.is_some() {
+            config.control_socket = self.socket.clone();
+        }
+        if self.no_control {
+            config.control = false;
+        }
+        if let Some(path) = &self.watch {
+            let path = path
+                .clone()
+                .or_else(|| config.render.shader.clone())
+                .ok_or_else(|| anyhow::anyhow!("--watch needs a path when there is no shader"))?;
+            config.watch_shader = Some(path);
+        }
+        Ok(())
+    }
+}
 
-    let window = create_window(&event_loop)?;
+async fn run(args: Args) -> anyhow::Result<()> {
+    let mut config = Config::load_or_default(args.config.as_deref())?;
+    args.apply(&mut config)?;
 
-    let mut ctx = RenderContext::new(&window).await;
+    if let Some(Command::Ctl { command }) = &args.command {
+        return ctl(&config, command).await;
+    }
 
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
-        match event {
-            Event::WindowEvent {
-                event: WindowEvent::Resized(size),
-                ..
-            } => {
-                // Reconfigure the surface with the new size
-                ctx.surface_config.width = size.width;
-                ctx.surface_config.height = size.height;
-                ctx.surface.configure(&ctx.device, &ctx.surface_config);
-            }
-            Event::RedrawRequested(_) => {
-                draw(&ctx);
+    if args.list_adapters {
+        // In the order they'd be tried; the first with adapters is used.
+        let mut chosen = false;
+        for backends in config.render.backend.fallbacks() {
+            let adapters = mgraphics::list_adapters(backends);
+            let note = if adapters.is_empty() {
+                ", no adapters"
+            } else if !chosen {
+                chosen = true;
+                ", chosen"
+            } else {
+                ""
+            };
+            println!("{:?}{}", backends, note);
+            for (index, info) in adapters.iter().enumerate() {
+                println!(
+                    "  {}: {} ({:?}, {:?}, vendor {:#06x}, device {:#06x})",
+                    index, info.name, info.backend, info.device_type, info.vendor, info.device
+                );
             }
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            } => *control_flow = ControlFlow::Exit,
-            _ => {}
         }
-    });
-}
-
-struct RenderContext {
-    surface: wgpu::Surface,
-    surface_config: wgpu::SurfaceConfiguration,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    render_pipeline: RenderPipeline,
-}
+        return Ok(());
+    }
 
-impl RenderContext {
-    async fn new(window: &Window) -> Self {
-        let window_size = window.inner_size();
-
-        let instance = wgpu::Instance::new(if cfg!(windows) {
-            wgpu::Backends::DX12
-        } else {
-            wgpu::Backends::PRIMARY
-        });
-
-        let surface = unsafe { instance.create_surface(&window) };
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .expect("Failed to find an appropriate adapter");
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                },
-                None,
-            )
-            .await
-            .expect("Failed to create device");
-
-        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
-        });
-
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[],
-            push_constant_ranges: &[],
-        });
-
-        let swapchain_format = surface.get_preferred_format(&adapter).unwrap();
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[swapchain_format.into()],
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-        });
-
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: swapchain_format,
-            width: window_size.width,
-            height: window_size.height,
-            present_mode: wgpu::PresentMode::Mailbox,
-        };
-
-        surface.configure(&device, &surface_config);
-
-        RenderContext {
-            surface,
-            surface_config,
-            device,
-            queue,
-            render_pipeline,
-        }
+    if args.dump_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
     }
-}
 
-fn draw(ctx: &RenderContext) {
-    let frame = ctx
-        .surface
-        .get_current_texture()
-        .expect("Failed to acquire next swap chain texture");
-    let view = frame
-        .texture
-        .create_view(&wgpu::TextureViewDescriptor::default());
-    let mut encoder = ctx
-        .device
-        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-    {
-        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: None,
-            color_attachments: &[wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
-                    store: true,
-                },
-            }],
-            depth_stencil_attachment: None,
-        });
-        rpass.set_pipeline(&ctx.render_pipeline);
-        rpass.draw(0..3, 0..1);
+    if args.headless {
+        let (width, height) = args.size.unwrap_or_else(|| config.window.size());
+        let size = mgraphics::winit::dpi::PhysicalSize::new(width, height);
+        if config.bench.is_some() {
+            return mgraphics::run_bench_headless(config, size).await;
+        }
+        return mgraphics::run_headless(config, size, &args.output).await;
     }
 
-    ctx.queue.submit(Some(encoder.finish()));
-    frame.present();
+    mgraphics::run(config).await
 }
 
 #[tokio::main]
 async fn main() {
-    run().await.unwrap();
+    let args = Args::parse();
+    let log = LogOptions {
+        verbosity: args.verbose,
+        file: args.log_file.clone(),
+    };
+    if let Err(e) = mgraphics::init_logging(&log) {
+        eprintln!("error: {:?}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = run(args).await {
+        eprintln!("error: {:?}", e);
+        std::process::exit(1);
+    }
 }
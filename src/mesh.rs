@@ -0,0 +1,75 @@
+use wgpu::util::DeviceExt;
+
+use std::ops::Range;
+
+use crate::{shapes::Rect, vertex::Vertex};
+
+// Indexed triangles in pixel coordinates, origin at the top-left corner.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+}
+
+impl Mesh {
+    // Handy as the unit for instanced bars and particles.
+    pub fn rect(rect: Rect, color: [f32; 4]) -> Self {
+        let (x0, y0) = (rect.x, rect.y);
+        let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+        Self {
+            vertices: vec![
+                Vertex::new([x0, y0], color),
+                Vertex::new([x1, y0], color),
+                Vertex::new([x1, y1], color),
+                Vertex::new([x0, y1], color),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+}
+
+// A mesh uploaded once and drawn as often as needed with
+// `RenderContext::draw_mesh`.
+pub struct GpuMesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+}
+
+impl GpuMesh {
+    pub fn new(device: &wgpu::Device, mesh: &Mesh) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("mesh vertices"),
+            contents: bytemuck::cast_slice(&mesh.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("mesh indices"),
+            contents: bytemuck::cast_slice(&mesh.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: mesh.indices.len() as u32,
+        }
+    }
+
+    pub(crate) fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        self.render_instanced(rpass, 0..1);
+    }
+
+    // The instance buffer, if any, is bound to slot 1 by the caller.
+    pub(crate) fn render_instanced<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        instances: Range<u32>,
+    ) {
+        if self.index_count == 0 {
+            return;
+        }
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.draw_indexed(0..self.index_count, 0, instances);
+    }
+}
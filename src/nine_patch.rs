@@ -0,0 +1,180 @@
+use crate::{shapes::Rect, sprite::SpriteId};
+
+// Tiles along one side of a slice at most; smaller tiles than that stretch
+// instead.
+const MAX_TILES: f32 = 256.0;
+
+// A sprite cut into a 3x3 grid by four insets: the corners keep their size,
+// the edges stretch along the panel's sides and the center fills the rest.
+// Insets are in the sprite's pixels, like CSS: top, right, bottom, left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NinePatch {
+    pub sprite: SpriteId,
+    // The part of the sprite to slice, in its pixels. All of it if `None`.
+    pub region: Option<Rect>,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+    pub tint: [f32; 4],
+    // Repeats the edges and the center at their own size instead of
+    // stretching them, cutting off the last tile.
+    pub tile: bool,
+}
+
+impl NinePatch {
+    pub fn new(sprite: SpriteId, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        Self {
+            sprite,
+            region: None,
+            top,
+            right,
+            bottom,
+            left,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            tile: false,
+        }
+    }
+
+    // The same inset on every side.
+    pub fn uniform(sprite: SpriteId, inset: f32) -> Self {
+        Self::new(sprite, inset, inset, inset, inset)
+    }
+
+    pub fn region(self, region: Rect) -> Self {
+        Self {
+            region: Some(region),
+            ..self
+        }
+    }
+
+    pub fn tint(self, tint: [f32; 4]) -> Self {
+        Self { tint, ..self }
+    }
+
+    pub fn tile(self, tile: bool) -> Self {
+        Self { tile, ..self }
+    }
+
+    // Pairs of a source rect, in the sprite's pixels, and where it goes.
+    // Empty slices are left out, so a panel no wider than its corners gets
+    // no center.
+    pub(crate) fn slices(&self, sprite_size: (u32, u32), dest: Rect) -> Vec<(Rect, Rect)> {
+        let region = self.region.unwrap_or(Rect::new(
+            0.0,
+            0.0,
+            sprite_size.0 as f32,
+            sprite_size.1 as f32,
+        ));
+        // Insets larger than the region are shrunk to fit it first.
+        let (left, right) = fit(self.left, self.right, region.width);
+        let (top, bottom) = fit(self.top, self.bottom, region.height);
+        // Then the corners shrink together when the panel is smaller than
+        // them, instead of overlapping.
+        let (dest_left, dest_right) = fit(left, right, dest.width);
+        let (dest_top, dest_bottom) = fit(top, bottom, dest.height);
+        let scale_x = if left + right > 0.0 {
+            (dest_left + dest_right) / (left + right)
+        } else {
+            1.0
+        };
+        let scale_y = if top + bottom > 0.0 {
+            (dest_top + dest_bottom) / (top + bottom)
+        } else {
+            1.0
+        };
+
+        let columns = [
+            (region.x, left, dest.x, dest_left),
+            (
+                region.x + left,
+                region.width - left - right,
+                dest.x + dest_left,
+                dest.width - dest_left - dest_right,
+            ),
+            (
+                region.x + region.width - right,
+                right,
+                dest.x + dest.width - dest_right,
+                dest_right,
+            ),
+        ];
+        let rows = [
+            (region.y, top, dest.y, dest_top),
+            (
+                region.y + top,
+                region.height - top - bottom,
+                dest.y + dest_top,
+                dest.height - dest_top - dest_bottom,
+            ),
+            (
+                region.y + region.height - bottom,
+                bottom,
+                dest.y + dest.height - dest_bottom,
+                dest_bottom,
+            ),
+        ];
+
+        let mut slices = Vec::with_capacity(9);
+        for (row, &(sy, sh, dy, dh)) in rows.iter().enumerate() {
+            for (column, &(sx, sw, dx, dw)) in columns.iter().enumerate() {
+                if sw <= 0.0 || sh <= 0.0 || dw <= 0.0 || dh <= 0.0 {
+                    continue;
+                }
+                // Along a stretched side a tile is as large as its source,
+                // scaled like the corners across it.
+                let xs = if self.tile && column == 1 {
+                    tiles(sx, sw, dx, dw, sw * scale_y)
+                } else {
+                    vec![(sx, sw, dx, dw)]
+                };
+                let ys = if self.tile && row == 1 {
+                    tiles(sy, sh, dy, dh, sh * scale_x)
+                } else {
+                    vec![(sy, sh, dy, dh)]
+                };
+                for &(sy, sh, dy, dh) in &ys {
+                    for &(sx, sw, dx, dw) in &xs {
+                        slices.push((Rect::new(sx, sy, sw, sh), Rect::new(dx, dy, dw, dh)));
+                    }
+                }
+            }
+        }
+        slices
+    }
+}
+
+// Scales two sizes down together until they fit in `available`.
+fn fit(a: f32, b: f32, available: f32) -> (f32, f32) {
+    let (a, b) = (a.max(0.0), b.max(0.0));
+    let available = available.max(0.0);
+    if a + b <= available {
+        (a, b)
+    } else {
+        let scale = available / (a + b);
+        (a * scale, b * scale)
+    }
+}
+
+// Covers `dest_size` with tiles `tile` long, keeping only as much of the
+// source as the last one shows.
+fn tiles(
+    source: f32,
+    source_size: f32,
+    dest: f32,
+    dest_size: f32,
+    tile: f32,
+) -> Vec<(f32, f32, f32, f32)> {
+    if tile < 1.0 || dest_size / tile > MAX_TILES {
+        return vec![(source, source_size, dest, dest_size)];
+    }
+    let mut tiles = Vec::new();
+    let mut offset = 0.0;
+    // Not a sliver left over from rounding.
+    while dest_size - offset > 1e-3 {
+        let size = tile.min(dest_size - offset);
+        tiles.push((source, source_size * size / tile, dest + offset, size));
+        offset += tile;
+    }
+    tiles
+}
@@ -0,0 +1,143 @@
+use egui_wgpu::renderer::{RenderPass, ScreenDescriptor};
+use winit::{event::WindowEvent, window::Window};
+
+use crate::render::RenderContext;
+
+// Debug UI drawn over the scene with egui. Hidden by default; while hidden
+// it neither runs nor takes any input.
+pub(crate) struct DebugOverlay {
+    context: egui::Context,
+    state: egui_winit::State,
+    renderer: RenderPass,
+    format: wgpu::TextureFormat,
+    visible: bool,
+    frame: Option<(Vec<egui::ClippedPrimitive>, ScreenDescriptor)>,
+    textures_free: Vec<egui::TextureId>,
+}
+
+impl DebugOverlay {
+    pub fn new(window: &Window, ctx: &RenderContext) -> Self {
+        let max_texture_side = ctx.device().limits().max_texture_dimension_2d as usize;
+        let format = ctx.surface_config().format;
+        Self {
+            context: egui::Context::default(),
+            state: egui_winit::State::new(max_texture_side, window),
+            // Painted after MSAA resolve, straight onto the surface.
+            renderer: RenderPass::new(ctx.device(), format, 1),
+            format,
+            visible: false,
+            frame: None,
+            textures_free: Vec::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    // True when egui wants the event for itself.
+    pub fn on_event(&mut self, event: &WindowEvent) -> bool {
+        self.visible && self.state.on_event(&self.context, event)
+    }
+
+    // Runs the UI and uploads what it wants to paint, before the frame.
+    pub fn prepare(&mut self, window: &Window, ctx: &mut RenderContext) {
+        self.frame = None;
+        if !self.visible {
+            return;
+        }
+
+        // The renderer's pipeline is built for one format. A fresh egui
+        // context uploads its textures again to the new renderer.
+        let format = ctx.surface_config().format;
+        if format != self.format {
+            self.renderer = RenderPass::new(ctx.device(), format, 1);
+            self.context = egui::Context::default();
+            self.format = format;
+        }
+
+        let input = self.state.take_egui_input(window);
+        let output = self.context.run(input, |egui_ctx| ui(egui_ctx, ctx));
+        self.state
+            .handle_platform_output(window, &self.context, output.platform_output);
+
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer
+                .update_texture(ctx.device(), ctx.queue(), *id, delta);
+        }
+        let jobs = self.context.tessellate(output.shapes);
+        let config = ctx.surface_config();
+        let screen = ScreenDescriptor {
+            size_in_pixels: [config.width, config.height],
+            pixels_per_point: window.scale_factor() as f32,
+        };
+        self.renderer
+            .update_buffers(ctx.device(), ctx.queue(), &jobs, &screen);
+        self.textures_free = output.textures_delta.free;
+        self.frame = Some((jobs, screen));
+
+        if output.needs_repaint {
+            window.request_redraw();
+        }
+    }
+
+    // Loads what the scene left in `view`, so egui blends over the
+    // transparent clear instead of replacing it.
+    pub fn paint(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        if let Some((jobs, screen)) = &self.frame {
+            self.renderer.execute(encoder, view, jobs, screen, None);
+        }
+        for id in self.textures_free.drain(..) {
+            self.renderer.free_texture(&id);
+        }
+    }
+}
+
+fn ui(egui_ctx: &egui::Context, ctx: &mut RenderContext) {
+    egui::Window::new("debug").show(egui_ctx, |ui| {
+        ui.label(ctx.stats().to_string());
+        ui.label(ctx.pipeline_stats().to_string());
+        if let Some(error) = ctx.gpu().last_error() {
+            ui.colored_label(egui::Color32::RED, format!("last GPU error {}", error));
+        }
+
+        let info = ctx.gpu().adapter.get_info();
+        ui.label(format!(
+            "{} ({:?}, {:?}, {} limits)",
+            info.name,
+            info.backend,
+            info.device_type,
+            ctx.limits_tier()
+        ));
+
+        let color = ctx.clear_color();
+        let mut rgba = [color.r, color.g, color.b, color.a];
+        let mut changed = false;
+        for (value, name) in rgba.iter_mut().zip(["r", "g", "b", "a"]) {
+            changed |= ui
+                .add(egui::Slider::new(value, 0.0..=1.0).text(name))
+                .changed();
+        }
+        if changed {
+            ctx.set_clear_color(wgpu::Color {
+                r: rgba[0],
+                g: rgba[1],
+                b: rgba[2],
+                a: rgba[3],
+            });
+        }
+
+        let mut vsync = ctx.present_mode() == wgpu::PresentMode::Fifo;
+        if ui.checkbox(&mut vsync, "vsync").changed() {
+            ctx.set_present_mode(if vsync {
+                wgpu::PresentMode::Fifo
+            } else {
+                wgpu::PresentMode::Mailbox
+            });
+        }
+    });
+}
@@ -0,0 +1,63 @@
+// Shelf packing: rectangles go left to right on the first shelf tall enough
+// to hold them, and a new shelf is opened below when none fits.
+pub(crate) struct ShelfPacker {
+    width: u32,
+    height: u32,
+    shelves: Vec<Shelf>,
+    next_y: u32,
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+impl ShelfPacker {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            shelves: Vec::new(),
+            next_y: 0,
+        }
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub(crate) fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.width || height > self.height {
+            return None;
+        }
+
+        let fit = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| height <= shelf.height && shelf.x + width <= self.width)
+            .min_by_key(|shelf| shelf.height - height);
+        if let Some(shelf) = fit {
+            let x = shelf.x;
+            shelf.x += width;
+            return Some((x, shelf.y));
+        }
+
+        if self.next_y + height > self.height {
+            return None;
+        }
+        let y = self.next_y;
+        self.next_y += height;
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+        Some((0, y))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.shelves.clear();
+        self.next_y = 0;
+    }
+}
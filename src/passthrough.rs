@@ -0,0 +1,87 @@
+use winit::window::Window;
+
+// Lets mouse input fall through to whatever is below the window. Keyboard
+// focus is unaffected, so hotkeys keep working while it is enabled.
+//
+// Wayland has no client-side way to do this through winit: the input region
+// belongs to the wl_surface winit owns, so enabling passthrough fails there.
+// macOS is not supported yet either.
+pub fn set_input_passthrough(window: &Window, enabled: bool) -> anyhow::Result<()> {
+    platform::set_input_passthrough(window, enabled)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use winapi::{
+        shared::windef::HWND,
+        um::winuser::{
+            GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE,
+            LWA_ALPHA, WS_EX_LAYERED, WS_EX_TRANSPARENT,
+        },
+    };
+    use winit::{platform::windows::WindowExtWindows, window::Window};
+
+    pub fn set_input_passthrough(window: &Window, enabled: bool) -> anyhow::Result<()> {
+        let hwnd = window.hwnd() as HWND;
+        let flags = (WS_EX_TRANSPARENT | WS_EX_LAYERED) as isize;
+        unsafe {
+            let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+            let style = if enabled {
+                style | flags
+            } else {
+                style & !flags
+            };
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style);
+            // A layered window stays invisible until its attributes are set.
+            if enabled && SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA) == 0 {
+                anyhow::bail!("SetLayeredWindowAttributes failed");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::ptr;
+
+    use winit::{platform::unix::WindowExtUnix, window::Window};
+    use x11_dl::{
+        xfixes::XFixes,
+        xlib::{Display, Xlib},
+    };
+
+    // ShapeInput from X11/extensions/shape.h
+    const SHAPE_INPUT: i32 = 2;
+
+    pub fn set_input_passthrough(window: &Window, enabled: bool) -> anyhow::Result<()> {
+        let (display, xwindow) = match (window.xlib_display(), window.xlib_window()) {
+            (Some(display), Some(xwindow)) => (display as *mut Display, xwindow),
+            _ => anyhow::bail!("input passthrough is not supported on wayland"),
+        };
+        let xlib = Xlib::open()?;
+        let xfixes = XFixes::open()?;
+        unsafe {
+            if enabled {
+                // An empty input shape means the window never receives pointer events.
+                let region = (xfixes.XFixesCreateRegion)(display, ptr::null_mut(), 0);
+                (xfixes.XFixesSetWindowShapeRegion)(display, xwindow, SHAPE_INPUT, 0, 0, region);
+                (xfixes.XFixesDestroyRegion)(display, region);
+            } else {
+                // Region 0 (None) restores the default, full-window input shape.
+                (xfixes.XFixesSetWindowShapeRegion)(display, xwindow, SHAPE_INPUT, 0, 0, 0);
+            }
+            (xlib.XFlush)(display);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+mod platform {
+    use winit::window::Window;
+
+    pub fn set_input_passthrough(_window: &Window, _enabled: bool) -> anyhow::Result<()> {
+        anyhow::bail!("input passthrough is not supported on this platform")
+    }
+}
@@ -0,0 +1,214 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use crate::config::AlphaMode;
+
+// Everything about the render target that a pipeline has to be built for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TargetConfig {
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) blend: wgpu::BlendState,
+    // What the built-in renderers' shaders and atlases are set up for;
+    // `blend` is the matching state, except for the user's pipelines.
+    pub(crate) alpha_mode: AlphaMode,
+    pub(crate) sample_count: u32,
+    pub(crate) depth_format: Option<wgpu::TextureFormat>,
+}
+
+// Builds a pipeline from a module with the usual `vs_main`/`fs_main` entry
+// points.
+fn create_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    buffers: &[wgpu::VertexBufferLayout],
+    target: &TargetConfig,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: target.format,
+                blend: Some(target.blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        // LessEqual so later draws at the same depth still land, matching
+        // painter's order for the flat 2D content.
+        depth_stencil: target.depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: target.sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+// A shader module added to a `PipelineCache`, with the layout its
+// pipelines use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ShaderId(usize);
+
+struct CachedShader {
+    label: String,
+    module: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct VertexLayoutKey {
+    array_stride: wgpu::BufferAddress,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    shader: ShaderId,
+    buffers: Vec<VertexLayoutKey>,
+    blend: wgpu::BlendState,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_format: Option<wgpu::TextureFormat>,
+}
+
+impl PipelineKey {
+    fn is_for(&self, target: &TargetConfig) -> bool {
+        self.format == target.format
+            && self.sample_count == target.sample_count
+            && self.depth_format == target.depth_format
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl fmt::Display for PipelineCacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pipelines, {} hits, {} misses",
+            self.entries, self.hits, self.misses
+        )
+    }
+}
+
+// Pipelines built lazily from the shaders added to the cache and reused for
+// every later request with the same vertex layout and target.
+#[derive(Default)]
+pub(crate) struct PipelineCache {
+    shaders: Vec<Option<CachedShader>>,
+    pipelines: HashMap<PipelineKey, Arc<wgpu::RenderPipeline>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl PipelineCache {
+    pub fn add_shader(
+        &mut self,
+        label: &str,
+        module: wgpu::ShaderModule,
+        layout: wgpu::PipelineLayout,
+    ) -> ShaderId {
+        self.shaders.push(Some(CachedShader {
+            label: label.to_string(),
+            module,
+            layout,
+        }));
+        ShaderId(self.shaders.len() - 1)
+    }
+
+    // Drops the shader and its pipelines, e.g. after it was replaced.
+    pub fn remove_shader(&mut self, id: ShaderId) {
+        if let Some(shader) = self.shaders.get_mut(id.0) {
+            *shader = None;
+        }
+        self.pipelines.retain(|key, _| key.shader != id);
+    }
+
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        shader: ShaderId,
+        buffers: &[wgpu::VertexBufferLayout],
+        target: &TargetConfig,
+    ) -> Arc<wgpu::RenderPipeline> {
+        let key = PipelineKey {
+            shader,
+            buffers: buffers
+                .iter()
+                .map(|layout| VertexLayoutKey {
+                    array_stride: layout.array_stride,
+                    step_mode: layout.step_mode,
+                    attributes: layout.attributes.to_vec(),
+                })
+                .collect(),
+            blend: target.blend,
+            format: target.format,
+            sample_count: target.sample_count,
+            depth_format: target.depth_format,
+        };
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            self.hits += 1;
+            return pipeline.clone();
+        }
+        self.misses += 1;
+        let cached = self.shaders[shader.0]
+            .as_ref()
+            .expect("shader was removed from the cache");
+        log::debug!(
+            "building the {} pipeline for {:?}, {}x",
+            cached.label,
+            target.format,
+            target.sample_count
+        );
+        let pipeline = Arc::new(create_pipeline(
+            device,
+            &cached.label,
+            &cached.layout,
+            &cached.module,
+            buffers,
+            target,
+        ));
+        self.pipelines.insert(key, pipeline.clone());
+        pipeline
+    }
+
+    // Drops the pipelines built for other formats or sample counts; they
+    // can't be used with the new target.
+    pub fn retain_target(&mut self, target: &TargetConfig) {
+        let before = self.pipelines.len();
+        self.pipelines.retain(|key, _| key.is_for(target));
+        let dropped = before - self.pipelines.len();
+        if dropped > 0 {
+            log::debug!("dropped {} pipelines built for the old target", dropped);
+        }
+    }
+
+    pub fn stats(&self) -> PipelineCacheStats {
+        PipelineCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.pipelines.len(),
+        }
+    }
+}
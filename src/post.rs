@@ -0,0 +1,343 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+
+fn one() -> f32 {
+    1.0
+}
+
+// Full-screen effects applied to the rendered scene, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "effect", rename_all = "lowercase", deny_unknown_fields)]
+pub enum PostEffect {
+    // Gaussian blur, the radius in physical pixels.
+    Blur {
+        radius: f32,
+    },
+    // Exposure in stops; contrast and saturation as factors, 1 leaves the
+    // image as is.
+    Grade {
+        #[serde(default)]
+        exposure: f32,
+        #[serde(default = "one")]
+        contrast: f32,
+        #[serde(default = "one")]
+        saturation: f32,
+    },
+}
+
+// Matches `PostParams` in post.wgsl.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostParams {
+    texel_size: [f32; 2],
+    direction: [f32; 2],
+    radius: f32,
+    exposure: f32,
+    contrast: f32,
+    saturation: f32,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Blur,
+    Grade,
+}
+
+// The passes an effect takes; blur is separable, horizontal then vertical.
+fn passes(effect: &PostEffect, texel_size: [f32; 2]) -> Vec<(Kind, PostParams)> {
+    let params = PostParams {
+        texel_size,
+        direction: [0.0; 2],
+        radius: 0.0,
+        exposure: 0.0,
+        contrast: 1.0,
+        saturation: 1.0,
+    };
+    match *effect {
+        PostEffect::Blur { radius } => [[1.0, 0.0], [0.0, 1.0]]
+            .into_iter()
+            .map(|direction| {
+                let params = PostParams {
+                    direction,
+                    radius: radius.max(0.0),
+                    ..params
+                };
+                (Kind::Blur, params)
+            })
+            .collect(),
+        PostEffect::Grade {
+            exposure,
+            contrast,
+            saturation,
+        } => vec![(
+            Kind::Grade,
+            PostParams {
+                exposure,
+                contrast,
+                saturation,
+                ..params
+            },
+        )],
+    }
+}
+
+struct Target {
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl Target {
+    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            _texture: texture,
+            view,
+        }
+    }
+}
+
+struct Pass {
+    kind: Kind,
+    // Which of the two targets the pass samples.
+    input: usize,
+    _params: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+// The scene goes into `input_view`, then every pass samples the previous
+// result and writes the next, alternating between two textures sized to
+// the surface. The last pass writes to the output.
+pub(crate) struct PostChain {
+    effects: Vec<PostEffect>,
+    layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    module: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    blur: wgpu::RenderPipeline,
+    grade: wgpu::RenderPipeline,
+    targets: Vec<Target>,
+    passes: Vec<Pass>,
+}
+
+impl PostChain {
+    // `None` for no effects, which renders straight to the surface.
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        effects: &[PostEffect],
+    ) -> Option<Self> {
+        if effects.is_empty() {
+            return None;
+        }
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<PostParams>() as wgpu::BufferAddress
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("post"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("post.wgsl"))),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let blur = create_pipeline(device, &pipeline_layout, &module, "fs_blur", config.format);
+        let grade = create_pipeline(device, &pipeline_layout, &module, "fs_grade", config.format);
+        let mut chain = Self {
+            effects: effects.to_vec(),
+            layout,
+            pipeline_layout,
+            module,
+            sampler,
+            blur,
+            grade,
+            targets: Vec::new(),
+            passes: Vec::new(),
+        };
+        chain.resize(device, config);
+        Some(chain)
+    }
+
+    pub fn effects(&self) -> &[PostEffect] {
+        &self.effects
+    }
+
+    // The textures and the texel sizes depend on the surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let texel_size = [1.0 / config.width as f32, 1.0 / config.height as f32];
+        let passes: Vec<_> = self
+            .effects
+            .iter()
+            .flat_map(|effect| passes(effect, texel_size))
+            .collect();
+        // A single pass reads the scene and writes the output directly.
+        let count = if passes.len() > 1 { 2 } else { 1 };
+        self.targets = (0..count)
+            .map(|_| Target::new(device, config, "post"))
+            .collect();
+        self.passes = passes
+            .into_iter()
+            .enumerate()
+            .map(|(index, (kind, params))| {
+                let input = index % 2;
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("post params"),
+                    contents: bytemuck::bytes_of(&params),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("post"),
+                    layout: &self.layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&self.targets[input].view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: buffer.as_entire_binding(),
+                        },
+                    ],
+                });
+                Pass {
+                    kind,
+                    input,
+                    _params: buffer,
+                    bind_group,
+                }
+            })
+            .collect();
+    }
+
+    // The pipelines are built for the surface format.
+    pub fn retarget(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let (layout, module) = (&self.pipeline_layout, &self.module);
+        self.blur = create_pipeline(device, layout, module, "fs_blur", config.format);
+        self.grade = create_pipeline(device, layout, module, "fs_grade", config.format);
+        self.resize(device, config);
+    }
+
+    // Where the scene has to be rendered for the chain to pick it up.
+    pub fn input_view(&self) -> &wgpu::TextureView {
+        &self.targets[0].view
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        for (index, pass) in self.passes.iter().enumerate() {
+            let view = if index + 1 == self.passes.len() {
+                output
+            } else {
+                &self.targets[1 - pass.input].view
+            };
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("post"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(match pass.kind {
+                Kind::Blur => &self.blur,
+                Kind::Grade => &self.grade,
+            });
+            rpass.set_bind_group(0, &pass.bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+}
+
+// Writes every pixel, so no blending with what was there.
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+    fragment_entry: &str,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(fragment_entry),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module,
+            entry_point: "vs_post",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module,
+            entry_point: fragment_entry,
+            targets: &[wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,63 @@
+struct PostParams {
+    texel_size: vec2<f32>;
+    direction: vec2<f32>;
+    radius: f32;
+    exposure: f32;
+    contrast: f32;
+    saturation: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+[[group(0), binding(0)]]
+var t_input: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_input: sampler;
+[[group(0), binding(2)]]
+var<uniform> params: PostParams;
+
+// One triangle covering the whole target, no vertex buffer needed.
+[[stage(vertex)]]
+fn vs_post([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+// One direction of a separable gaussian, sigma half the radius. The input
+// is premultiplied, so transparent pixels don't bleed their color.
+[[stage(fragment)]]
+fn fs_blur(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let taps = i32(ceil(params.radius));
+    let sigma = max(params.radius * 0.5, 0.001);
+    let step = params.direction * params.texel_size;
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = -taps; i <= taps; i = i + 1) {
+        let x = f32(i);
+        let weight = exp(-x * x / (2.0 * sigma * sigma));
+        sum = sum + weight * textureSampleLevel(t_input, s_input, input.tex_coords + step * x, 0.0);
+        total = total + weight;
+    }
+    return sum / total;
+}
+
+// Exposure in stops, then contrast around mid grey and saturation around
+// the luminance, on the straight color.
+[[stage(fragment)]]
+fn fs_grade(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let texel = textureSampleLevel(t_input, s_input, input.tex_coords, 0.0);
+    if (texel.a <= 0.0) {
+        return texel;
+    }
+    var color = texel.rgb / texel.a * exp2(params.exposure);
+    color = (color - vec3<f32>(0.5)) * params.contrast + vec3<f32>(0.5);
+    let luma = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    color = mix(vec3<f32>(luma), color, params.saturation);
+    return vec4<f32>(max(color, vec3<f32>(0.0)) * texel.a, texel.a);
+}
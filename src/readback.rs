@@ -0,0 +1,107 @@
+use anyhow::Context;
+
+// A mappable buffer that textures of one size are copied into. Rows keep the
+// padded stride wgpu requires for copies and are trimmed again when read.
+// The buffer can be reused once a read has finished.
+pub(crate) struct Readback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded: u32,
+}
+
+impl Readback {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded = (width * 4 + align - 1) / align * align;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: (padded * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            width,
+            height,
+            padded,
+        }
+    }
+
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(self.padded),
+                    rows_per_image: std::num::NonZeroU32::new(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    // Blocks until the copy has finished. `format` is the copied texture's
+    // format; BGRA data is swizzled so the result is always RGBA8, alpha
+    // included.
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> anyhow::Result<image::RgbaImage> {
+        let bgra = match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => anyhow::bail!("cannot read back {:?} textures", format),
+        };
+
+        let slice = self.buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).context("failed to map the readback buffer")?;
+
+        let unpadded = (self.width * 4) as usize;
+        let mut pixels = Vec::with_capacity(unpadded * self.height as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(self.padded as usize) {
+                pixels.extend_from_slice(&row[..unpadded]);
+            }
+        }
+        self.buffer.unmap();
+
+        if bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("readback size mismatch"))
+    }
+}
+
+pub(crate) fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> anyhow::Result<image::RgbaImage> {
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("readback"),
+    });
+    let readback = Readback::new(device, width, height);
+    readback.copy(&mut encoder, texture);
+    queue.submit(Some(encoder.finish()));
+    readback.read(device, format)
+}
@@ -0,0 +1,275 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+use crate::{gpu::Gpu, readback::Readback};
+
+// Readback buffers in flight; with all of them busy the render thread
+// waits for the writer instead of queueing more frames.
+const RING_SIZE: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct RecordOptions {
+    // A directory for numbered PNGs, or the video file with `ffmpeg`.
+    pub output: PathBuf,
+    // Pipe raw RGBA frames into ffmpeg instead of writing PNGs.
+    pub ffmpeg: bool,
+    // Stop after this many frames, otherwise when recording is stopped.
+    pub frames: Option<u64>,
+    // Frame rate ffmpeg assumes for the input; the real timing is in the
+    // timestamps file.
+    pub frame_rate: u32,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            output: PathBuf::from("mgraphics-recording"),
+            ffmpeg: false,
+            frames: None,
+            frame_rate: 60,
+        }
+    }
+}
+
+enum Sink {
+    Frames(PathBuf),
+    Ffmpeg(Child, ChildStdin),
+}
+
+impl Sink {
+    fn write(&mut self, index: u64, image: &image::RgbaImage) -> anyhow::Result<()> {
+        match self {
+            Sink::Frames(dir) => {
+                let path = dir.join(format!("frame-{:06}.png", index));
+                image
+                    .save(&path)
+                    .with_context(|| format!("failed to write {:?}", path))
+            }
+            Sink::Ffmpeg(_, stdin) => stdin
+                .write_all(image.as_raw())
+                .context("failed to write to ffmpeg"),
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        if let Sink::Ffmpeg(mut child, stdin) = self {
+            // Closing stdin tells ffmpeg the stream has ended.
+            drop(stdin);
+            let status = child.wait()?;
+            if !status.success() {
+                anyhow::bail!("ffmpeg exited with {}", status);
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Job {
+    slot: usize,
+    index: u64,
+    time: Duration,
+}
+
+// Records every presented frame of one `RenderContext`. Frames are rendered
+// into one reused texture, copied into a ring of readback buffers and
+// written out on a separate thread.
+pub(crate) struct Recorder {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+    slots: Vec<Arc<Readback>>,
+    free: Receiver<usize>,
+    jobs: Option<SyncSender<Job>>,
+    writer: Option<JoinHandle<()>>,
+    started: Instant,
+    frames: u64,
+    limit: Option<u64>,
+    pending: Option<usize>,
+}
+
+impl Recorder {
+    pub fn new(
+        gpu: Arc<Gpu>,
+        config: &wgpu::SurfaceConfiguration,
+        options: &RecordOptions,
+    ) -> anyhow::Result<Self> {
+        let (width, height) = (config.width, config.height);
+        let (sink, timestamps) = if options.ffmpeg {
+            let mut child = Command::new("ffmpeg")
+                .args(["-y", "-f", "rawvideo", "-pix_fmt", "rgba", "-s"])
+                .arg(format!("{}x{}", width, height))
+                .arg("-r")
+                .arg(options.frame_rate.to_string())
+                .args(["-i", "-", "-pix_fmt", "yuv420p"])
+                .arg(&options.output)
+                .stdin(Stdio::piped())
+                .spawn()
+                .context("failed to start ffmpeg")?;
+            let stdin = child.stdin.take().context("ffmpeg has no stdin")?;
+            let mut timestamps = options.output.clone().into_os_string();
+            timestamps.push(".timestamps");
+            (Sink::Ffmpeg(child, stdin), PathBuf::from(timestamps))
+        } else {
+            std::fs::create_dir_all(&options.output)
+                .with_context(|| format!("failed to create {:?}", options.output))?;
+            (
+                Sink::Frames(options.output.clone()),
+                options.output.join("timestamps.txt"),
+            )
+        };
+        let timestamps = BufWriter::new(
+            File::create(&timestamps)
+                .with_context(|| format!("failed to create {:?}", timestamps))?,
+        );
+
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("recording"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let slots: Vec<_> = (0..RING_SIZE)
+            .map(|_| Arc::new(Readback::new(&gpu.device, width, height)))
+            .collect();
+
+        let (free_tx, free) = mpsc::sync_channel(RING_SIZE);
+        for slot in 0..RING_SIZE {
+            free_tx.send(slot).unwrap();
+        }
+        let (jobs, job_rx) = mpsc::sync_channel(RING_SIZE);
+
+        let writer = {
+            let slots = slots.clone();
+            let format = config.format;
+            std::thread::spawn(move || {
+                write_frames(gpu, format, slots, job_rx, free_tx, sink, timestamps)
+            })
+        };
+
+        log::info!(
+            "recording {}x{} frames to {:?}",
+            width,
+            height,
+            options.output
+        );
+        Ok(Self {
+            texture,
+            view,
+            width,
+            height,
+            slots,
+            free,
+            jobs: Some(jobs),
+            writer: Some(writer),
+            started: Instant::now(),
+            frames: 0,
+            limit: options.frames,
+            pending: None,
+        })
+    }
+
+    // The texture the frame should also be rendered into, or None when the
+    // frame can't be recorded. Blocks while every readback buffer is busy.
+    pub fn begin(&mut self, config: &wgpu::SurfaceConfiguration) -> Option<&wgpu::TextureView> {
+        // Encoded video has a fixed size, so resized frames are dropped.
+        if (config.width, config.height) != (self.width, self.height) || self.is_done() {
+            return None;
+        }
+        self.pending = Some(self.free.recv().ok()?);
+        Some(&self.view)
+    }
+
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(slot) = self.pending {
+            self.slots[slot].copy(encoder, &self.texture);
+        }
+    }
+
+    // Hands the copied frame to the writer; call after submitting.
+    pub fn submit(&mut self) {
+        let slot = match self.pending.take() {
+            Some(slot) => slot,
+            None => return,
+        };
+        let job = Job {
+            slot,
+            index: self.frames,
+            time: self.started.elapsed(),
+        };
+        if let Some(jobs) = &self.jobs {
+            if jobs.send(job).is_err() {
+                // The writer gave up, see its log.
+                self.jobs = None;
+            }
+        }
+        self.frames += 1;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.jobs.is_none() || self.limit.map_or(false, |limit| self.frames >= limit)
+    }
+}
+
+impl Drop for Recorder {
+    // Waits until every queued frame is written.
+    fn drop(&mut self) {
+        self.jobs = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+        log::info!("recorded {} frames", self.frames);
+    }
+}
+
+fn write_frames(
+    gpu: Arc<Gpu>,
+    format: wgpu::TextureFormat,
+    slots: Vec<Arc<Readback>>,
+    jobs: Receiver<Job>,
+    free: SyncSender<usize>,
+    mut sink: Sink,
+    mut timestamps: BufWriter<File>,
+) {
+    let mut write = |job: Job| -> anyhow::Result<()> {
+        let image = slots[job.slot].read(&gpu.device, format)?;
+        let _ = free.send(job.slot);
+        sink.write(job.index, &image)?;
+        writeln!(timestamps, "{} {:.6}", job.index, job.time.as_secs_f64())?;
+        Ok(())
+    };
+    for job in jobs.iter() {
+        if let Err(e) = write(job) {
+            log::error!("recording failed: {:#}", e);
+            break;
+        }
+    }
+    drop(jobs);
+    if let Err(e) = timestamps.flush() {
+        log::error!("failed to write recording timestamps: {}", e);
+    }
+    if let Err(e) = sink.finish() {
+        log::error!("failed to finish recording: {:#}", e);
+    }
+}
@@ -0,0 +1,2166 @@
+use std::{
+    borrow::Cow,
+    cell::{Cell, Ref, RefCell},
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use wgpu::util::DeviceExt;
+use winit::{dpi::PhysicalSize, window::Window};
+
+#[cfg(feature = "svg")]
+use crate::svg::{Svg, SvgCache};
+use crate::{
+    buffer::DynamicBuffer,
+    color::linear_to_srgb,
+    compute::{ComputeOptions, ComputeRenderer},
+    config::{AlphaMode, Backend},
+    draw_params::{DrawParams, DrawParamsBinding},
+    emoji,
+    globals::{Globals, GlobalsUniform},
+    gpu::{AdapterOptions, Gpu, LimitsTier},
+    gradient::{Gradient, GradientRenderer},
+    instance::{Instance, InstanceRenderer},
+    logging::RateLimit,
+    mesh::{GpuMesh, Mesh},
+    nine_patch::NinePatch,
+    pipeline::{PipelineCache, PipelineCacheStats, ShaderId, TargetConfig},
+    post::{PostChain, PostEffect},
+    readback::{read_texture, Readback},
+    record::{RecordOptions, Recorder},
+    rounded_rect::{CornerRadii, RoundedRectRenderer},
+    scene::{DrawCommand, MeshId, Scene},
+    shader::{validate_shader, DEFAULT_SHADER},
+    shadow::{Shadow, Shadowed},
+    shapes::{Rect, ShapeRenderer},
+    sprite::{atlas_format, SpriteId, SpriteRenderer, TextureAtlas},
+    stats::{FrameStats, FrameTimings},
+    text::{FontId, TextRenderer, TextStyle},
+    texture::{Texture, TexturedVertex, QUAD_INDICES},
+    vertex::{Vertex, DEFAULT_TRIANGLE},
+    viewer::Fit,
+};
+
+// Where frames end up: a window's surface or, when headless, a texture
+// that can be read back.
+enum Output {
+    Surface(wgpu::Surface),
+    Offscreen(wgpu::Texture),
+}
+
+fn create_offscreen(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    })
+}
+
+// Which renderer a run of queued draws went to. Runs are drawn back in queue
+// order, so content from different renderers keeps painter's order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Shapes,
+    Gradients,
+    RoundedRects,
+    Instances,
+    Text,
+    Sprites,
+}
+
+pub struct RenderContext {
+    output: Output,
+    surface_config: wgpu::SurfaceConfiguration,
+    gpu: Arc<Gpu>,
+    pipelines: PipelineCache,
+    target: TargetConfig,
+    // `target` with the blend state of the user's pipelines.
+    user_blend: wgpu::BlendState,
+    // Kept over `alpha_mode` changes, see `RenderOptions::builtin_blend`.
+    builtin_blend: Option<wgpu::BlendState>,
+    main_shader: ShaderId,
+    render_pipeline: Arc<wgpu::RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+    vertex_count: u32,
+    index_buffer: DynamicBuffer,
+    index_count: u32,
+    globals: GlobalsUniform,
+    draw_params: DrawParams,
+    draw_params_binding: DrawParamsBinding,
+    texture_layout: wgpu::BindGroupLayout,
+    texture_shader: ShaderId,
+    texture_pipeline: Arc<wgpu::RenderPipeline>,
+    quad_index_buffer: wgpu::Buffer,
+    textured_quad: Option<TexturedQuad>,
+    text: TextRenderer,
+    shapes: ShapeRenderer,
+    gradients: GradientRenderer,
+    rounded_rects: RoundedRectRenderer,
+    instances: InstanceRenderer,
+    sprites: SpriteRenderer,
+    #[cfg(feature = "svg")]
+    svgs: SvgCache,
+    compute: Option<ComputeRenderer>,
+    // Runs queued for the next frame as (layer, start mark), and the ones
+    // being drawn as ranges of each renderer's items.
+    queued_layers: Vec<(Layer, u32)>,
+    layers: Vec<(Layer, Range<u32>)>,
+    meshes: Vec<Arc<GpuMesh>>,
+    // CPU copies of what the context uploaded, to upload it again on a new
+    // device.
+    mesh_sources: Vec<Mesh>,
+    geometry: (Vec<Vertex>, Vec<u16>),
+    shader_source: Option<String>,
+    recreate_hooks: Vec<RecreateHook>,
+    // The latest size from `resize`, applied before the next frame.
+    pending_size: Option<PhysicalSize<u32>>,
+    // Minimized windows report a zero size; nothing is drawn until they
+    // come back.
+    zero_sized: bool,
+    // Occluded or minimized, see `set_suspended`.
+    suspended: bool,
+    scale_factor: f64,
+    clear_color: wgpu::Color,
+    // Clears with an opaque clear color, see `set_opaque`.
+    opaque: bool,
+    keep_contents: bool,
+    opacity: f32,
+    sample_count: u32,
+    msaa: Option<Attachment>,
+    depth: Option<Attachment>,
+    // Only there while effects are enabled, the scene is drawn to the
+    // surface directly otherwise.
+    post: Option<PostChain>,
+    // Animation clock, advanced by the frame deltas.
+    elapsed: Cell<Duration>,
+    delta: Cell<Duration>,
+    last_frame: Cell<Option<Instant>>,
+    frame_index: Cell<u32>,
+    stats: RefCell<FrameStats>,
+    // Where to save the next frame, see `request_capture`.
+    capture: Cell<Option<PathBuf>>,
+    recorder: RefCell<Option<Recorder>>,
+    frame_log: RateLimit,
+}
+
+type RecreateHook = Box<dyn FnMut(&mut RenderContext) + Send>;
+
+struct TexturedQuad {
+    texture: Texture,
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    // Set for images from `set_image`, which follow the window size. The
+    // pixels are kept to upload again on a new device.
+    image: Option<(Fit, image::RgbaImage)>,
+}
+
+// Straight-alpha "over" for color, but alpha accumulates as
+// srcA + dstA * (1 - srcA) so stacked translucent draws don't thin out the
+// coverage the compositor sees.
+pub const COMPOSITE_BLEND: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::SrcAlpha,
+        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+        operation: wgpu::BlendOperation::Add,
+    },
+    alpha: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+        operation: wgpu::BlendOperation::Add,
+    },
+};
+
+// "Over" for colors the shader already multiplied by alpha, see
+// `AlphaMode::Premultiplied`.
+pub const PREMULTIPLIED_BLEND: wgpu::BlendState = wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING;
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    // Tried in order until one yields an adapter, see `Backend::fallbacks`.
+    pub backends: Vec<wgpu::Backends>,
+    pub adapter: AdapterOptions,
+    // Tried in order; Fifo is always available as the last resort.
+    pub present_modes: Vec<wgpu::PresentMode>,
+    // Linear; encoded for surfaces that aren't sRGB. An opaque color
+    // makes windows opaque too.
+    pub clear_color: wgpu::Color,
+    // Draw over the previous frame instead of clearing it.
+    pub keep_contents: bool,
+    // WGSL replacing the built-in shader, see `load_shader`. Falls back to
+    // the built-in one when it doesn't validate.
+    pub shader: Option<String>,
+    // For the main shader and `set_texture` quads, which output straight
+    // colors; the built-in renderers follow `alpha_mode`.
+    pub blend: wgpu::BlendState,
+    pub alpha_mode: AlphaMode,
+    // Replaces the state `alpha_mode` picks for the built-in 2D renderers,
+    // shapes, text and sprites among them. Their colors still come out as
+    // `alpha_mode` says.
+    pub builtin_blend: Option<wgpu::BlendState>,
+    // 1, 2, 4 or 8; unsupported counts are lowered with a warning.
+    pub sample_count: u32,
+    // Adds a Depth32Float buffer cleared to 1.0 every frame.
+    pub depth: bool,
+    // Applied in order to the whole frame, see `set_post_effects`.
+    pub post_effects: Vec<PostEffect>,
+    // PNGs loaded into the sprite atlas on creation, see `load_sprites`.
+    pub sprites: Option<PathBuf>,
+    // Draw `queue_text` from distance fields, see `TextRenderer::set_sdf`.
+    pub sdf_text: bool,
+    // Color emoji font, see `TextRenderer::set_emoji_font`. Looked for in
+    // the usual system locations when not set.
+    pub emoji_font: Option<PathBuf>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            backends: Backend::Auto.fallbacks(),
+            adapter: AdapterOptions::default(),
+            present_modes: vec![
+                wgpu::PresentMode::Mailbox,
+                wgpu::PresentMode::Immediate,
+                wgpu::PresentMode::Fifo,
+            ],
+            clear_color: wgpu::Color::TRANSPARENT,
+            keep_contents: false,
+            shader: None,
+            blend: COMPOSITE_BLEND,
+            alpha_mode: AlphaMode::default(),
+            builtin_blend: None,
+            sample_count: 1,
+            depth: false,
+            post_effects: Vec::new(),
+            sprites: None,
+            sdf_text: false,
+            emoji_font: None,
+        }
+    }
+}
+
+// Per-frame trace messages are held to one per this interval.
+const FRAME_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// A render attachment sized to the surface: the MSAA color target or the
+// depth buffer.
+struct Attachment {
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+}
+
+impl Attachment {
+    fn new(
+        device: &wgpu::Device,
+        label: &str,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        });
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            _texture: texture,
+            width: config.width,
+            height: config.height,
+        }
+    }
+
+    fn msaa(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Option<Self> {
+        (sample_count > 1).then(|| Self::new(device, "msaa", config, config.format, sample_count))
+    }
+
+    fn depth(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        enabled: bool,
+    ) -> Option<Self> {
+        enabled.then(|| Self::new(device, "depth", config, DEPTH_FORMAT, sample_count))
+    }
+
+    fn matches(&self, config: &wgpu::SurfaceConfiguration) -> bool {
+        (self.width, self.height) == (config.width, config.height)
+    }
+}
+
+// The globals, then the draw params if they aren't push constants.
+fn main_pipeline_layout(
+    device: &wgpu::Device,
+    globals: &GlobalsUniform,
+    draw_params_binding: &DrawParamsBinding,
+) -> wgpu::PipelineLayout {
+    let mut bind_group_layouts = vec![&globals.layout];
+    bind_group_layouts.extend(draw_params_binding.bind_group_layout());
+    device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &bind_group_layouts,
+        push_constant_ranges: &draw_params_binding.push_constant_ranges(),
+    })
+}
+
+// WebGPU guarantees 1 and 4; 2 and 8 depend on the adapter and format.
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    let count = match requested {
+        0 | 1 => 1,
+        2 | 4 | 8 => requested,
+        _ => {
+            log::warn!("invalid MSAA sample count {}, using 4", requested);
+            4
+        }
+    };
+    if count == 2 || count == 8 {
+        let flags = adapter.get_texture_format_features(format).flags;
+        if !adapter
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            || !flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE)
+        {
+            log::warn!(
+                "{}x MSAA is not supported for {:?}, using 4x",
+                count,
+                format
+            );
+            return 4;
+        }
+    }
+    count
+}
+
+// wgpu doesn't report per-surface present modes yet and silently falls back
+// to Fifo when a mode is unsupported, so only filter out what the backend is
+// known not to offer.
+fn supported_present_modes(adapter: &wgpu::Adapter) -> &'static [wgpu::PresentMode] {
+    match adapter.get_info().backend {
+        wgpu::Backend::Gl => &[wgpu::PresentMode::Fifo],
+        _ => &[
+            wgpu::PresentMode::Mailbox,
+            wgpu::PresentMode::Immediate,
+            wgpu::PresentMode::Fifo,
+        ],
+    }
+}
+
+fn choose_present_mode(
+    adapter: &wgpu::Adapter,
+    preferences: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    let supported = supported_present_modes(adapter);
+    for &mode in preferences {
+        if supported.contains(&mode) {
+            return mode;
+        }
+        log::info!(
+            "present mode {:?} is not supported, trying the next one",
+            mode
+        );
+    }
+    wgpu::PresentMode::Fifo
+}
+
+// Goes through `options.backends` until one of them has an adapter, with a
+// surface for `window` made by that backend's instance.
+async fn request_gpu(
+    window: Option<&Window>,
+    options: &RenderOptions,
+) -> anyhow::Result<(Gpu, Option<wgpu::Surface>)> {
+    let mut failures = Vec::new();
+    for &backends in &options.backends {
+        log::info!("trying backend {:?}", backends);
+        let instance = wgpu::Instance::new(backends);
+        let surface = window.map(|window| unsafe { instance.create_surface(window) });
+        match Gpu::new(instance, backends, surface.as_ref(), &options.adapter).await {
+            Ok(gpu) => return Ok((gpu, surface)),
+            Err(e) => {
+                log::warn!("backend {:?} is not usable: {:#}", backends, e);
+                failures.push(format!("{:?}: {:#}", backends, e));
+            }
+        }
+    }
+    if failures.is_empty() {
+        anyhow::bail!("no backends to try");
+    }
+    anyhow::bail!("no backend is usable\n{}", failures.join("\n"))
+}
+
+impl RenderContext {
+    pub async fn new(window: &Window) -> anyhow::Result<Self> {
+        Self::with_options(window, &RenderOptions::default()).await
+    }
+
+    pub async fn with_options(window: &Window, options: &RenderOptions) -> anyhow::Result<Self> {
+        let (gpu, surface) = request_gpu(Some(window), options).await?;
+        let surface = surface.expect("a surface is created for the window");
+        Self::from_surface(Arc::new(gpu), surface, window, options)
+    }
+
+    // Renders into an `Rgba8UnormSrgb` texture instead of a window, for use
+    // without a display. Read frames back with `read_pixels`.
+    pub async fn headless(
+        size: PhysicalSize<u32>,
+        options: &RenderOptions,
+    ) -> anyhow::Result<Self> {
+        let (gpu, _) = request_gpu(None, options).await?;
+        let device = &gpu.device;
+        let max = device.limits().max_texture_dimension_2d;
+        if size.width > max || size.height > max {
+            log::warn!(
+                "{}x{} is past the device's limit of {}",
+                size.width,
+                size.height,
+                max
+            );
+        }
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: Texture::FORMAT,
+            width: size.width.clamp(1, max),
+            height: size.height.clamp(1, max),
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let output = Output::Offscreen(create_offscreen(device, &config));
+        Self::from_output(Arc::new(gpu), output, config, options)
+    }
+
+    // Renders into `window` with an existing device, failing when that
+    // device's adapter can't present to the window's surface.
+    pub fn with_gpu(
+        gpu: Arc<Gpu>,
+        window: &Window,
+        options: &RenderOptions,
+    ) -> anyhow::Result<Self> {
+        let surface = unsafe { gpu.instance.create_surface(&window) };
+        if !gpu.supports(&surface) {
+            anyhow::bail!("the shared adapter cannot present to this window");
+        }
+        Self::from_surface(gpu, surface, window, options)
+    }
+
+    fn from_surface(
+        gpu: Arc<Gpu>,
+        surface: wgpu::Surface,
+        window: &Window,
+        options: &RenderOptions,
+    ) -> anyhow::Result<Self> {
+        let adapter = &gpu.adapter;
+        let window_size = window.inner_size();
+
+        let format = surface.get_preferred_format(adapter).ok_or_else(|| {
+            let info = adapter.get_info();
+            anyhow::anyhow!(
+                "the surface reported no formats for adapter {:?} ({:?}), it cannot present \
+                 to this window",
+                info.name,
+                info.backend
+            )
+        })?;
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: window_size.width,
+            height: window_size.height,
+            present_mode: choose_present_mode(adapter, &options.present_modes),
+        };
+        log::info!(
+            "using surface format {:?}{}, present mode {:?}",
+            format,
+            if format.describe().srgb {
+                " (sRGB)"
+            } else {
+                ""
+            },
+            surface_config.present_mode
+        );
+
+        surface.configure(&gpu.device, &surface_config);
+
+        let mut ctx = Self::from_output(gpu, Output::Surface(surface), surface_config, options)?;
+        ctx.set_scale_factor(window.scale_factor());
+        Ok(ctx)
+    }
+
+    // `surface_config` describes the output either way; for offscreen
+    // output only its format and size are used.
+    fn from_output(
+        gpu: Arc<Gpu>,
+        output: Output,
+        surface_config: wgpu::SurfaceConfiguration,
+        options: &RenderOptions,
+    ) -> anyhow::Result<Self> {
+        let started = Instant::now();
+        gpu.push_error_scopes();
+        let ctx = Self::build(gpu.clone(), output, surface_config, options);
+        let errors = gpu.pop_error_scopes("creating the renderers");
+        let mut ctx = ctx?;
+        ctx.stats.get_mut().add_gpu_errors(errors);
+        log::debug!(
+            "created the renderers for {}x{} in {:?}",
+            ctx.surface_config.width,
+            ctx.surface_config.height,
+            started.elapsed()
+        );
+        Ok(ctx)
+    }
+
+    fn build(
+        gpu: Arc<Gpu>,
+        output: Output,
+        surface_config: wgpu::SurfaceConfiguration,
+        options: &RenderOptions,
+    ) -> anyhow::Result<Self> {
+        let device = &gpu.device;
+        let adapter = &gpu.adapter;
+
+        let draw_params_binding = DrawParamsBinding::new(device);
+        log::debug!(
+            "draw params via {}",
+            if draw_params_binding.is_push_constants() {
+                "push constants"
+            } else {
+                "dynamic uniform buffer"
+            }
+        );
+
+        let custom_shader = options.shader.as_ref().filter(|source| {
+            validate_shader(source)
+                .map_err(|e| log::error!("{:#}\nusing the default shader", e))
+                .is_ok()
+        });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(
+                draw_params_binding
+                    .patch_shader(custom_shader.map_or(DEFAULT_SHADER, String::as_str)),
+            )),
+        });
+
+        let globals = GlobalsUniform::new(device);
+        let pipeline_layout = main_pipeline_layout(device, &globals, &draw_params_binding);
+
+        let sample_count =
+            supported_sample_count(adapter, surface_config.format, options.sample_count);
+
+        let target = TargetConfig {
+            format: surface_config.format,
+            blend: options
+                .builtin_blend
+                .unwrap_or_else(|| options.alpha_mode.blend()),
+            alpha_mode: options.alpha_mode,
+            sample_count,
+            depth_format: options.depth.then(|| DEPTH_FORMAT),
+        };
+        let user_target = TargetConfig {
+            blend: options.blend,
+            ..target
+        };
+
+        let mut pipelines = PipelineCache::default();
+        let main_shader = pipelines.add_shader("main", shader, pipeline_layout);
+        let render_pipeline =
+            pipelines.get_or_create(device, main_shader, &[Vertex::layout()], &user_target);
+
+        let texture_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("texture"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("texture.wgsl"))),
+        });
+
+        let texture_layout = Texture::bind_group_layout(device);
+
+        let texture_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("texture"),
+                bind_group_layouts: &[&texture_layout, &globals.layout],
+                push_constant_ranges: &[],
+            });
+
+        let texture_shader =
+            pipelines.add_shader("texture", texture_shader, texture_pipeline_layout);
+        let texture_pipeline = pipelines.get_or_create(
+            device,
+            texture_shader,
+            &[TexturedVertex::layout()],
+            &user_target,
+        );
+
+        let quad_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("quad indices"),
+            contents: bytemuck::cast_slice(&QUAD_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let text = TextRenderer::new(device, &mut pipelines, &target, &globals.layout);
+        let shapes = ShapeRenderer::new(device, &mut pipelines, &target, &globals.layout);
+        let gradients = GradientRenderer::new(device, &mut pipelines, &target, &globals.layout);
+        let rounded_rects =
+            RoundedRectRenderer::new(device, &mut pipelines, &target, &globals.layout);
+        let instances = InstanceRenderer::new(device, &mut pipelines, &target, &globals.layout);
+        let sprites = SpriteRenderer::new(device, &mut pipelines, &target, &globals.layout);
+
+        let msaa = Attachment::msaa(device, &surface_config, sample_count);
+        let depth = Attachment::depth(device, &surface_config, sample_count, options.depth);
+        let post = PostChain::new(device, &surface_config, &options.post_effects);
+
+        let vertex_buffer = DynamicBuffer::new(
+            device,
+            "vertex buffer",
+            wgpu::BufferUsages::VERTEX,
+            std::mem::size_of_val(&DEFAULT_TRIANGLE) as wgpu::BufferAddress,
+        );
+        let index_buffer = DynamicBuffer::new(device, "index buffer", wgpu::BufferUsages::INDEX, 0);
+
+        let mut ctx = RenderContext {
+            output,
+            surface_config,
+            gpu,
+            pipelines,
+            target,
+            user_blend: options.blend,
+            builtin_blend: options.builtin_blend,
+            main_shader,
+            render_pipeline,
+            vertex_buffer,
+            vertex_count: 0,
+            index_buffer,
+            index_count: 0,
+            globals,
+            draw_params: DrawParams::default(),
+            draw_params_binding,
+            texture_layout,
+            texture_shader,
+            texture_pipeline,
+            quad_index_buffer,
+            textured_quad: None,
+            text,
+            shapes,
+            gradients,
+            rounded_rects,
+            instances,
+            sprites,
+            #[cfg(feature = "svg")]
+            svgs: SvgCache::default(),
+            compute: None,
+            queued_layers: Vec::new(),
+            layers: Vec::new(),
+            meshes: Vec::new(),
+            mesh_sources: Vec::new(),
+            geometry: (Vec::new(), Vec::new()),
+            shader_source: custom_shader.cloned(),
+            recreate_hooks: Vec::new(),
+            pending_size: None,
+            zero_sized: false,
+            suspended: false,
+            scale_factor: 1.0,
+            clear_color: options.clear_color,
+            opaque: false,
+            keep_contents: options.keep_contents,
+            opacity: 1.0,
+            sample_count,
+            msaa,
+            depth,
+            post,
+            elapsed: Cell::new(Duration::ZERO),
+            delta: Cell::new(Duration::ZERO),
+            last_frame: Cell::new(None),
+            frame_index: Cell::new(0),
+            stats: RefCell::new(FrameStats::default()),
+            capture: Cell::new(None),
+            recorder: RefCell::new(None),
+            frame_log: RateLimit::new(FRAME_LOG_INTERVAL),
+        };
+        ctx.set_geometry(&DEFAULT_TRIANGLE);
+        ctx.text.set_sdf(options.sdf_text);
+        match &options.emoji_font {
+            Some(path) => {
+                if let Err(e) = ctx.text.load_emoji_font(path) {
+                    log::error!("{:#}", e);
+                }
+            }
+            None => ctx.text.set_emoji_font(emoji::load_default_font()),
+        }
+        if let Some(dir) = &options.sprites {
+            if let Err(e) = ctx.load_sprites(dir) {
+                log::error!("{:#}", e);
+            }
+        }
+        Ok(ctx)
+    }
+}
+
+impl RenderContext {
+    pub fn gpu(&self) -> &Arc<Gpu> {
+        &self.gpu
+    }
+
+    // The limits the device was created with; everything sized by the
+    // context stays within `device().limits()`.
+    pub fn limits_tier(&self) -> LimitsTier {
+        self.gpu.limits_tier
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.gpu.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.gpu.queue
+    }
+
+    pub fn surface_config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.surface_config
+    }
+
+    pub fn set_geometry(&mut self, vertices: &[Vertex]) {
+        self.vertex_buffer.write(
+            &self.gpu.device,
+            &self.gpu.queue,
+            bytemuck::cast_slice(vertices),
+        );
+        self.vertex_count = vertices.len() as u32;
+        self.index_count = 0;
+        self.geometry = (vertices.to_vec(), Vec::new());
+    }
+
+    pub fn set_indexed_geometry(&mut self, vertices: &[Vertex], indices: &[u16]) {
+        self.set_geometry(vertices);
+        self.index_buffer.write(
+            &self.gpu.device,
+            &self.gpu.queue,
+            bytemuck::cast_slice(indices),
+        );
+        self.index_count = indices.len() as u32;
+        self.geometry.1 = indices.to_vec();
+    }
+
+    pub fn set_texture(&mut self, texture: Texture) {
+        self.set_texture_quad(texture, [-1.0, -1.0], [1.0, 1.0]);
+    }
+
+    // `min`/`max` are in clip space.
+    pub fn set_texture_quad(&mut self, texture: Texture, min: [f32; 2], max: [f32; 2]) {
+        let bind_group = texture.bind_group(&self.gpu.device, &self.texture_layout);
+        let vertex_buffer = self
+            .gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("textured quad"),
+                contents: bytemuck::cast_slice(&TexturedVertex::quad(min, max)),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            });
+        self.textured_quad = Some(TexturedQuad {
+            texture,
+            bind_group,
+            vertex_buffer,
+            image: None,
+        });
+    }
+
+    // Shows `image` behind the layers, scaled to the window by `fit` and
+    // scaled again on every resize. Replaces any texture set before.
+    pub fn set_image(&mut self, image: &image::DynamicImage, fit: Fit) {
+        self.set_rgba_image(image.to_rgba8(), fit);
+    }
+
+    fn set_rgba_image(&mut self, image: image::RgbaImage, fit: Fit) {
+        let (width, height) = image.dimensions();
+        // Matching the target's encoding, an sRGB target gets the texels
+        // decoded to linear on sampling and encoded again on the way out.
+        let texture = Texture::from_rgba_with_format(
+            &self.gpu.device,
+            &self.gpu.queue,
+            width,
+            height,
+            &image,
+            atlas_format(&self.target),
+        );
+        let (min, max) = fit.quad(
+            (width, height),
+            (self.surface_config.width, self.surface_config.height),
+        );
+        self.set_texture_quad(texture, min, max);
+        if let Some(quad) = &mut self.textured_quad {
+            quad.image = Some((fit, image));
+        }
+    }
+
+    pub fn image_fit(&self) -> Option<Fit> {
+        self.textured_quad
+            .as_ref()
+            .and_then(|quad| quad.image.as_ref())
+            .map(|(fit, _)| *fit)
+    }
+
+    // Does nothing unless an image from `set_image` is shown.
+    pub fn set_image_fit(&mut self, fit: Fit) {
+        if let Some((current, _)) = self
+            .textured_quad
+            .as_mut()
+            .and_then(|quad| quad.image.as_mut())
+        {
+            *current = fit;
+        }
+        self.refit_image();
+    }
+
+    fn refit_image(&mut self) {
+        let quad = match &self.textured_quad {
+            Some(quad) => quad,
+            None => return,
+        };
+        if let Some((fit, image)) = &quad.image {
+            let (min, max) = fit.quad(
+                image.dimensions(),
+                (self.surface_config.width, self.surface_config.height),
+            );
+            self.gpu.queue.write_buffer(
+                &quad.vertex_buffer,
+                0,
+                bytemuck::cast_slice(&TexturedVertex::quad(min, max)),
+            );
+        }
+    }
+
+    pub fn texture(&self) -> Option<&Texture> {
+        self.textured_quad.as_ref().map(|quad| &quad.texture)
+    }
+
+    pub fn clear_texture(&mut self) -> Option<Texture> {
+        self.textured_quad.take().map(|quad| quad.texture)
+    }
+
+    // Rebuilds the main pipeline from new WGSL. On failure the previous
+    // pipeline stays active and the validation error is returned.
+    pub fn reload_shader(&mut self, source: &str) -> anyhow::Result<()> {
+        validate_shader(source)?;
+        self.gpu
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = self
+            .gpu
+            .device
+            .create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("main"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(
+                    self.draw_params_binding.patch_shader(source),
+                )),
+            });
+        let layout =
+            main_pipeline_layout(&self.gpu.device, &self.globals, &self.draw_params_binding);
+        let id = self.pipelines.add_shader("main", shader, layout);
+        let pipeline = self.pipelines.get_or_create(
+            &self.gpu.device,
+            id,
+            &[Vertex::layout()],
+            &self.user_target(),
+        );
+        if let Some(error) = pollster::block_on(self.gpu.device.pop_error_scope()) {
+            self.pipelines.remove_shader(id);
+            anyhow::bail!("{}", error);
+        }
+        self.pipelines.remove_shader(self.main_shader);
+        self.main_shader = id;
+        self.render_pipeline = pipeline;
+        self.shader_source = Some(source.to_string());
+        Ok(())
+    }
+
+    // Runs `cs_main` over a storage buffer before every frame and draws the
+    // buffer after the geometry, replacing the previous compute pass. On
+    // failure the previous one stays.
+    pub fn set_compute(&mut self, options: ComputeOptions) -> anyhow::Result<()> {
+        self.gpu
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let compute = ComputeRenderer::new(
+            &self.gpu,
+            &mut self.pipelines,
+            &self.user_target(),
+            &self.globals.layout,
+            options,
+        );
+        let error = pollster::block_on(self.gpu.device.pop_error_scope());
+        let compute = compute?;
+        if let Some(error) = error {
+            self.pipelines.remove_shader(compute.shader());
+            anyhow::bail!("{}", error);
+        }
+        self.clear_compute();
+        self.compute = Some(compute);
+        Ok(())
+    }
+
+    pub fn clear_compute(&mut self) {
+        if let Some(compute) = self.compute.take() {
+            self.pipelines.remove_shader(compute.shader());
+        }
+    }
+
+    // Text queued through the returned renderer is ordered as if it was
+    // queued now.
+    pub fn text(&mut self) -> &mut TextRenderer {
+        self.enter(Layer::Text);
+        &mut self.text
+    }
+
+    pub fn queue_text(&mut self, text: &str, position: [f32; 2], size: f32, color: [f32; 4]) {
+        self.enter(Layer::Text);
+        self.text.queue_text(text, position, size, color);
+    }
+
+    // See `TextRenderer::wrap_text`, with the default font.
+    pub fn wrap_text(&self, text: &str, size: f32, bounds: [f32; 2]) -> String {
+        self.text.wrap_text(FontId::DEFAULT, text, size, bounds)
+    }
+
+    // Distance field text with an outline or shadow, see `TextStyle`.
+    pub fn queue_styled_text(
+        &mut self,
+        text: &str,
+        position: [f32; 2],
+        size: f32,
+        style: &TextStyle,
+    ) {
+        self.enter(Layer::Text);
+        self.text.queue_styled_text(text, position, size, style);
+    }
+
+    // Shapes, gradients, meshes and queued text are in pixels from the
+    // top-left corner, drawn by the next frame only, above the geometry and
+    // in the order they were queued.
+    pub fn fill_rect(&mut self, rect: Rect, color: [f32; 4]) {
+        self.enter(Layer::Shapes);
+        self.shapes.fill_rect(rect, color);
+    }
+
+    pub fn fill_rect_gradient(&mut self, rect: Rect, gradient: &Gradient) {
+        self.enter(Layer::Gradients);
+        self.gradients.fill_rect(rect, gradient);
+    }
+
+    pub fn stroke_rect(&mut self, rect: Rect, width: f32, color: [f32; 4]) {
+        self.enter(Layer::Shapes);
+        self.shapes.stroke_rect(rect, width, color);
+    }
+
+    // Antialiased by the fragment shader rather than tessellated. Radii
+    // past half the shorter side are clamped to it.
+    pub fn fill_rounded_rect(
+        &mut self,
+        rect: Rect,
+        radius: impl Into<CornerRadii>,
+        color: [f32; 4],
+    ) {
+        self.fill_rounded_rect_with_border(rect, radius, color, 0.0, [0.0; 4]);
+    }
+
+    // The stroke lies inside `rect`, like `stroke_rect`'s.
+    pub fn stroke_rounded_rect(
+        &mut self,
+        rect: Rect,
+        radius: impl Into<CornerRadii>,
+        width: f32,
+        color: [f32; 4],
+    ) {
+        self.fill_rounded_rect_with_border(rect, radius, [0.0; 4], width, color);
+    }
+
+    pub fn fill_rounded_rect_with_border(
+        &mut self,
+        rect: Rect,
+        radius: impl Into<CornerRadii>,
+        color: [f32; 4],
+        border_width: f32,
+        border_color: [f32; 4],
+    ) {
+        self.enter(Layer::RoundedRects);
+        self.rounded_rects
+            .draw(rect, radius.into(), color, border_width, border_color);
+    }
+
+    // The shadow a rounded rect would cast, on its own. Blurred shadows are
+    // evaluated in the fragment shader, so they cost no extra pass.
+    pub fn draw_shadow(&mut self, rect: Rect, radius: impl Into<CornerRadii>, shadow: &Shadow) {
+        self.enter(Layer::RoundedRects);
+        self.rounded_rects.draw_shadow(
+            shadow.offset_rect(rect),
+            radius.into(),
+            shadow.blur_radius,
+            shadow.color,
+        );
+    }
+
+    // For the next draw call only, e.g.
+    // `ctx.with_shadow([0.0, 2.0], 8.0, color).fill_rounded_rect(...)`; the
+    // shadow is queued right before the shape, so it's beneath it and above
+    // everything queued earlier.
+    pub fn with_shadow(
+        &mut self,
+        offset: [f32; 2],
+        blur_radius: f32,
+        color: [f32; 4],
+    ) -> Shadowed<'_> {
+        Shadowed {
+            ctx: self,
+            shadow: Shadow::new(offset, blur_radius, color),
+        }
+    }
+
+    pub fn fill_circle(&mut self, center: [f32; 2], radius: f32, color: [f32; 4]) {
+        self.enter(Layer::Shapes);
+        self.shapes.fill_circle(center, radius, color);
+    }
+
+    pub fn line(&mut self, p0: [f32; 2], p1: [f32; 2], width: f32, color: [f32; 4]) {
+        self.enter(Layer::Shapes);
+        self.shapes.line(p0, p1, width, color);
+    }
+
+    // Adds an image to the sprite atlas, which grows as needed. Sprites stay
+    // for the lifetime of the context.
+    pub fn add_sprite(&mut self, name: &str, image: image::RgbaImage) -> anyhow::Result<SpriteId> {
+        self.sprites
+            .add_sprite(&self.gpu.device, &self.gpu.queue, name, image)
+    }
+
+    // Gives a sprite a new image under the same id, so scenes drawing it
+    // show the new one from the next frame.
+    pub fn replace_sprite(&mut self, id: SpriteId, image: image::RgbaImage) -> anyhow::Result<()> {
+        self.sprites
+            .replace_sprite(&self.gpu.device, &self.gpu.queue, id, image)
+    }
+
+    // Adds every PNG in `dir`, named by its file name without the
+    // extension.
+    pub fn load_sprites(
+        &mut self,
+        dir: impl AsRef<Path>,
+    ) -> anyhow::Result<HashMap<String, SpriteId>> {
+        self.sprites
+            .load_dir(&self.gpu.device, &self.gpu.queue, dir.as_ref())
+    }
+
+    pub fn sprite(&self, name: &str) -> Option<SpriteId> {
+        self.sprites.atlas().get(name)
+    }
+
+    pub fn atlas(&self) -> &TextureAtlas {
+        self.sprites.atlas()
+    }
+
+    // Stretched over `dest`, with its colors multiplied by `tint`. Every
+    // sprite queued in a row goes into the same draw call.
+    pub fn draw_sprite(&mut self, id: SpriteId, dest: Rect, tint: [f32; 4]) {
+        self.enter(Layer::Sprites);
+        self.sprites.draw(id, dest, tint);
+    }
+
+    // Rasterized at the pixel size it covers, centered in `dest` keeping
+    // its aspect ratio. Drawing it at another size, e.g. after the scale
+    // factor changed, rasterizes it again.
+    #[cfg(feature = "svg")]
+    pub fn draw_svg(&mut self, svg: &Svg, dest: Rect, tint: [f32; 4]) {
+        let (width, height) = svg.fit(dest.width, dest.height);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let sprite = match self.svgs.sprite(
+            &mut self.sprites,
+            &self.gpu.device,
+            &self.gpu.queue,
+            svg,
+            (width, height),
+            self.frame_index.get(),
+        ) {
+            Ok(sprite) => sprite,
+            Err(e) => {
+                log::error!("failed to draw {:?}: {:#}", svg, e);
+                return;
+            }
+        };
+        let rect = Rect::new(
+            (dest.x + (dest.width - width as f32) / 2.0).round(),
+            (dest.y + (dest.height - height as f32) / 2.0).round(),
+            width as f32,
+            height as f32,
+        );
+        self.draw_sprite(sprite, rect, tint);
+    }
+
+    // Scales the patch's sprite to `dest` keeping its corners, see
+    // `NinePatch`.
+    pub fn draw_nine_patch(&mut self, patch: &NinePatch, dest: Rect) {
+        self.enter(Layer::Sprites);
+        self.sprites.draw_nine_patch(patch, dest);
+    }
+
+    // Uploads a mesh once so it can be queued every frame without copying
+    // its vertices again.
+    pub fn upload_mesh(&self, mesh: &Mesh) -> Arc<GpuMesh> {
+        Arc::new(GpuMesh::new(&self.gpu.device, mesh))
+    }
+
+    pub fn draw_mesh(&mut self, mesh: &Arc<GpuMesh>) {
+        self.enter(Layer::Shapes);
+        self.shapes.draw_mesh(mesh.clone());
+    }
+
+    // One draw call for all of `instances`.
+    pub fn draw_instanced(&mut self, mesh: &Arc<GpuMesh>, instances: &[Instance]) {
+        self.enter(Layer::Instances);
+        self.instances.draw_instanced(mesh.clone(), instances);
+    }
+
+    // Uploads a mesh for scenes to refer to by id. Meshes stay for the
+    // lifetime of the context.
+    pub fn add_mesh(&mut self, mesh: &Mesh) -> MeshId {
+        self.meshes.push(self.upload_mesh(mesh));
+        self.mesh_sources.push(mesh.clone());
+        MeshId(self.meshes.len() - 1)
+    }
+
+    // Queues a scene's commands for the next frame, in order. A scene's clear
+    // color replaces the context's.
+    pub fn queue_scene(&mut self, scene: &Scene) {
+        if let Some(color) = scene.clear_color() {
+            self.clear_color = color;
+        }
+        for command in scene.commands() {
+            match command {
+                DrawCommand::FillRect { rect, color } => self.fill_rect(*rect, *color),
+                DrawCommand::StrokeRect { rect, width, color } => {
+                    self.stroke_rect(*rect, *width, *color)
+                }
+                DrawCommand::FillRoundedRect {
+                    rect,
+                    radius,
+                    color,
+                    border_width,
+                    border_color,
+                } => self.fill_rounded_rect_with_border(
+                    *rect,
+                    *radius,
+                    *color,
+                    *border_width,
+                    *border_color,
+                ),
+                DrawCommand::Shadow {
+                    rect,
+                    radius,
+                    shadow,
+                } => self.draw_shadow(*rect, *radius, shadow),
+                DrawCommand::FillCircle {
+                    center,
+                    radius,
+                    color,
+                } => self.fill_circle(*center, *radius, *color),
+                DrawCommand::Line {
+                    from,
+                    to,
+                    width,
+                    color,
+                } => self.line(*from, *to, *width, *color),
+                DrawCommand::Gradient { rect, gradient } => {
+                    self.fill_rect_gradient(*rect, gradient)
+                }
+                DrawCommand::Text {
+                    text,
+                    position,
+                    size,
+                    color,
+                } => self.queue_text(text, *position, *size, *color),
+                DrawCommand::Sprite { sprite, rect, tint } => {
+                    self.draw_sprite(*sprite, *rect, *tint)
+                }
+                DrawCommand::Texture {
+                    name,
+                    rect,
+                    tint,
+                    placeholder,
+                } => match self.sprite(name) {
+                    Some(sprite) => self.draw_sprite(sprite, *rect, *tint),
+                    None => self.fill_rect(*rect, *placeholder),
+                },
+                DrawCommand::NinePatch { patch, rect } => self.draw_nine_patch(patch, *rect),
+                DrawCommand::Mesh(id) => match self.meshes.get(id.0).cloned() {
+                    Some(mesh) => self.draw_mesh(&mesh),
+                    None => log::warn!("scene refers to unknown mesh {:?}", id),
+                },
+                DrawCommand::Instanced { mesh, instances } => {
+                    match self.meshes.get(mesh.0).cloned() {
+                        Some(gpu_mesh) => self.draw_instanced(&gpu_mesh, instances),
+                        None => log::warn!("scene refers to unknown mesh {:?}", mesh),
+                    }
+                }
+            }
+        }
+    }
+
+    fn enter(&mut self, layer: Layer) {
+        if self.queued_layers.last().map(|&(last, _)| last) == Some(layer) {
+            return;
+        }
+        let mark = match layer {
+            Layer::Shapes => self.shapes.mark(),
+            Layer::Gradients => self.gradients.mark(),
+            Layer::RoundedRects => self.rounded_rects.mark(),
+            Layer::Instances => self.instances.mark(),
+            Layer::Text => self.text.mark(),
+            Layer::Sprites => self.sprites.mark(),
+        };
+        self.queued_layers.push((layer, mark));
+    }
+
+    // Turns the queued runs into ranges; each one ends where the next run of
+    // the same layer starts.
+    fn take_layers(&mut self) -> Vec<(Layer, Range<u32>)> {
+        let mut ends = [
+            self.shapes.mark(),
+            self.gradients.mark(),
+            self.rounded_rects.mark(),
+            self.instances.mark(),
+            self.text.mark(),
+            self.sprites.mark(),
+        ];
+        let mut layers: Vec<_> = self
+            .queued_layers
+            .drain(..)
+            .rev()
+            .map(|(layer, start)| {
+                let end = std::mem::replace(&mut ends[layer as usize], start);
+                (layer, start..end)
+            })
+            .collect();
+        layers.reverse();
+        layers
+    }
+
+    pub fn draw_params(&self) -> DrawParams {
+        self.draw_params
+    }
+
+    pub fn set_draw_params(&mut self, params: DrawParams) {
+        self.draw_params = params;
+    }
+
+    // Resizes come in storms while a window is dragged, so only the last one
+    // before a frame reconfigures the surface. Zero sizes pause drawing
+    // instead, and sizes past the device's limit are clamped.
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            self.zero_sized = true;
+            self.pending_size = None;
+            return;
+        }
+        self.zero_sized = false;
+        let max = self.gpu.device.limits().max_texture_dimension_2d;
+        let size = PhysicalSize::new(size.width.min(max), size.height.min(max));
+        if size != self.size() || self.pending_size.is_some() {
+            self.pending_size = Some(size);
+        }
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.surface_config.width, self.surface_config.height)
+    }
+
+    // Physical pixels per logical pixel of the window, 1 when headless.
+    // Whoever handles ScaleFactorChanged keeps it current.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        self.stats.get_mut().set_scale_factor(scale_factor);
+    }
+
+    // Shown with the achieved rate in the stats; the limit itself is up to
+    // the event loop.
+    pub fn set_frame_rate_limit(&mut self, limit: Option<u32>) {
+        self.stats.get_mut().set_frame_rate_limit(limit);
+    }
+
+    // Whether the last resize was to a zero size; `draw` skips those frames.
+    pub fn is_zero_sized(&self) -> bool {
+        self.zero_sized
+    }
+
+    // While the window can't be seen `draw` doesn't even acquire a
+    // texture, and the clock stands still: resuming picks up where it
+    // stopped instead of jumping ahead by the time spent suspended.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        if suspended == self.suspended {
+            return;
+        }
+        self.suspended = suspended;
+        self.stats
+            .get_mut()
+            .set_suspended(suspended, Instant::now());
+        if !suspended {
+            self.advance(Duration::ZERO);
+        }
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    // A redraw that wasn't drawn as the window was suspended.
+    pub(crate) fn skip_frame(&self) {
+        self.stats.borrow_mut().add_skipped();
+    }
+
+    // A redraw that wasn't asked for as the scene already showed.
+    pub(crate) fn skip_redundant(&self) {
+        self.stats.borrow_mut().add_redundant();
+    }
+
+    fn apply_resize(&mut self) {
+        let size = match self.pending_size.take() {
+            Some(size) => size,
+            None => return,
+        };
+        log::debug!(
+            "resizing from {}x{} to {}x{}",
+            self.surface_config.width,
+            self.surface_config.height,
+            size.width,
+            size.height
+        );
+        self.surface_config.width = size.width;
+        self.surface_config.height = size.height;
+        self.recreate_surface();
+        self.msaa = Attachment::msaa(&self.gpu.device, &self.surface_config, self.sample_count);
+        self.depth = Attachment::depth(
+            &self.gpu.device,
+            &self.surface_config,
+            self.sample_count,
+            self.depth.is_some(),
+        );
+        if let Some(post) = &mut self.post {
+            post.resize(&self.gpu.device, &self.surface_config);
+        }
+        self.refit_image();
+        self.write_globals();
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // Falls back to the nearest count the adapter supports for the format.
+    pub fn set_sample_count(&mut self, count: u32) {
+        let count = supported_sample_count(&self.gpu.adapter, self.surface_config.format, count);
+        if count != self.sample_count {
+            self.set_target(TargetConfig {
+                sample_count: count,
+                ..self.target
+            });
+        }
+    }
+
+    // Switches every pipeline to the new target, building only the ones
+    // the cache has not seen yet, and recreates the attachments to match.
+    fn set_target(&mut self, target: TargetConfig) {
+        let device = &self.gpu.device;
+        let user_target = TargetConfig {
+            blend: self.user_blend,
+            ..target
+        };
+        self.pipelines.retain_target(&target);
+        self.render_pipeline = self.pipelines.get_or_create(
+            device,
+            self.main_shader,
+            &[Vertex::layout()],
+            &user_target,
+        );
+        self.texture_pipeline = self.pipelines.get_or_create(
+            device,
+            self.texture_shader,
+            &[TexturedVertex::layout()],
+            &user_target,
+        );
+        self.text.retarget(device, &mut self.pipelines, &target);
+        self.shapes.retarget(device, &mut self.pipelines, &target);
+        self.gradients
+            .retarget(device, &mut self.pipelines, &target);
+        self.rounded_rects
+            .retarget(device, &mut self.pipelines, &target);
+        self.instances
+            .retarget(device, &mut self.pipelines, &target);
+        self.sprites
+            .retarget(device, &self.gpu.queue, &mut self.pipelines, &target);
+        if let Some(compute) = &mut self.compute {
+            compute.retarget(device, &mut self.pipelines, &user_target);
+        }
+        self.sample_count = target.sample_count;
+        self.msaa = Attachment::msaa(device, &self.surface_config, target.sample_count);
+        self.depth = Attachment::depth(
+            device,
+            &self.surface_config,
+            target.sample_count,
+            target.depth_format.is_some(),
+        );
+        if target.format != self.target.format {
+            if let Some(post) = &mut self.post {
+                post.retarget(device, &self.surface_config);
+            }
+        }
+        self.target = target;
+    }
+
+    fn user_target(&self) -> TargetConfig {
+        TargetConfig {
+            blend: self.user_blend,
+            ..self.target
+        }
+    }
+
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.target.alpha_mode
+    }
+
+    // Switches the built-in renderers, text and emoji included, between
+    // premultiplied and straight colors. Sprites are uploaded again and
+    // emoji drawn again to match; the main shader keeps its blend state.
+    pub fn set_alpha_mode(&mut self, mode: AlphaMode) {
+        if mode != self.target.alpha_mode {
+            log::info!("switching to {:?} alpha", mode);
+            self.set_target(TargetConfig {
+                blend: self.builtin_blend.unwrap_or_else(|| mode.blend()),
+                alpha_mode: mode,
+                ..self.target
+            });
+            self.write_globals();
+        }
+    }
+
+    pub fn post_effects(&self) -> &[PostEffect] {
+        self.post.as_ref().map_or(&[], PostChain::effects)
+    }
+
+    // Renders the scene into a texture and runs the effects over it on the
+    // way to the output. With no effects the scene goes to the output
+    // directly and the textures are freed.
+    pub fn set_post_effects(&mut self, effects: &[PostEffect]) {
+        self.post = PostChain::new(&self.gpu.device, &self.surface_config, effects);
+    }
+
+    pub fn pipeline_stats(&self) -> PipelineCacheStats {
+        self.pipelines.stats()
+    }
+
+    pub fn depth_view(&self) -> Option<&wgpu::TextureView> {
+        self.depth.as_ref().map(|depth| &depth.view)
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.surface_config.present_mode
+    }
+
+    // Reconfigures the surface right away, e.g. to toggle vsync at runtime.
+    // Later resizes keep the mode.
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        let mode = choose_present_mode(&self.gpu.adapter, &[mode]);
+        if mode != self.surface_config.present_mode {
+            log::info!("switching to present mode {:?}", mode);
+            self.surface_config.present_mode = mode;
+            self.recreate_surface();
+        }
+    }
+
+    pub fn recreate_surface(&mut self) {
+        match &mut self.output {
+            Output::Surface(surface) => {
+                // The preferred format can change with the monitor, e.g. when
+                // HDR is turned on; the cache has pipelines for each.
+                if let Some(format) = surface.get_preferred_format(&self.gpu.adapter) {
+                    if format != self.surface_config.format {
+                        log::info!(
+                            "surface format changed from {:?} to {:?}",
+                            self.surface_config.format,
+                            format
+                        );
+                        self.surface_config.format = format;
+                        surface.configure(&self.gpu.device, &self.surface_config);
+                        self.set_target(TargetConfig {
+                            format,
+                            ..self.target
+                        });
+                        return;
+                    }
+                }
+                surface.configure(&self.gpu.device, &self.surface_config)
+            }
+            Output::Offscreen(texture) => {
+                *texture = create_offscreen(&self.gpu.device, &self.surface_config)
+            }
+        }
+    }
+
+    pub fn is_device_lost(&self) -> bool {
+        self.gpu.is_lost()
+    }
+
+    // Runs `hook` on the context that replaces this one after the device
+    // was lost, to create again what user code made on the old device,
+    // such as `upload_mesh` results and `set_texture` textures. Send, as
+    // the context moves to its window's render thread.
+    pub fn on_recreate(&mut self, hook: impl FnMut(&mut RenderContext) + Send + 'static) {
+        self.recreate_hooks.push(Box::new(hook));
+    }
+
+    // Takes over the state of a context whose device was lost; `self` is
+    // a new context for the same window. Geometry, the custom shader,
+    // fonts, an image from `set_image`, scene meshes, settings and the
+    // clock carry over, a recording in progress stops.
+    pub fn restore(&mut self, mut lost: RenderContext) {
+        // Before the sprites, which are uploaded for the mode.
+        self.set_alpha_mode(lost.alpha_mode());
+        if let Some(source) = lost.shader_source.take() {
+            if let Err(e) = self.reload_shader(&source) {
+                log::error!("failed to restore the shader: {}", e);
+            }
+        }
+        let (vertices, indices) = std::mem::take(&mut lost.geometry);
+        if indices.is_empty() {
+            self.set_geometry(&vertices);
+        } else {
+            self.set_indexed_geometry(&vertices, &indices);
+        }
+        for font in lost.text.fonts().iter().skip(self.text.fonts().len()) {
+            self.text.add_font(font.clone());
+        }
+        self.text.set_sdf(lost.text.sdf());
+        self.text.set_emoji_font(lost.text.emoji_font().cloned());
+        for sprite in lost
+            .sprites
+            .atlas()
+            .sources()
+            .iter()
+            .skip(self.sprites.atlas().len())
+        {
+            if let Err(e) = self.add_sprite(&sprite.name, sprite.image.clone()) {
+                log::error!("failed to restore a sprite: {:#}", e);
+            }
+        }
+        // The rasterizations came back with the sprites, under the same ids.
+        #[cfg(feature = "svg")]
+        {
+            self.svgs = std::mem::take(&mut lost.svgs);
+        }
+        for mesh in &lost.mesh_sources {
+            self.add_mesh(mesh);
+        }
+        if let Some(compute) = &lost.compute {
+            // From the initial contents, the state the GPU had is gone.
+            if let Err(e) = self.set_compute(compute.options().clone()) {
+                log::error!("failed to restore the compute pass: {:#}", e);
+            }
+        }
+        match lost.textured_quad.take() {
+            Some(TexturedQuad {
+                image: Some((fit, image)),
+                ..
+            }) => self.set_rgba_image(image, fit),
+            Some(_) => log::warn!("the texture was lost with the device"),
+            None => {}
+        }
+        if lost.is_recording() {
+            log::warn!("the recording stopped with the device");
+        }
+        self.draw_params = lost.draw_params;
+        self.clear_color = lost.clear_color;
+        self.opaque = lost.opaque;
+        self.keep_contents = lost.keep_contents;
+        self.opacity = lost.opacity;
+        self.suspended = lost.suspended;
+        self.set_post_effects(lost.post_effects());
+        self.set_present_mode(lost.present_mode());
+        self.set_scale_factor(lost.scale_factor);
+        self.elapsed.set(lost.elapsed.get());
+        self.last_frame.set(lost.last_frame.get());
+        self.frame_index.set(lost.frame_index.get());
+        self.stats.swap(&lost.stats);
+
+        let mut hooks = std::mem::take(&mut lost.recreate_hooks);
+        for hook in &mut hooks {
+            hook(self);
+        }
+        hooks.append(&mut self.recreate_hooks);
+        self.recreate_hooks = hooks;
+    }
+
+    // Saves the next frame `draw` renders as PNG. The frame is additionally
+    // rendered into a copyable texture, since surface textures can't be
+    // read on every platform, and the file is written off the render thread.
+    pub fn request_capture(&self, path: PathBuf) {
+        self.capture.set(Some(path));
+    }
+
+    // Records every frame `draw` presents from now on, at the current size,
+    // until `stop_recording` or the frame limit.
+    pub fn start_recording(&self, options: &RecordOptions) -> anyhow::Result<()> {
+        let recorder = Recorder::new(self.gpu.clone(), &self.surface_config, options)?;
+        *self.recorder.borrow_mut() = Some(recorder);
+        Ok(())
+    }
+
+    // Blocks until all recorded frames are written.
+    pub fn stop_recording(&self) {
+        self.recorder.borrow_mut().take();
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.borrow().is_some()
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
+    // Linear, like vertex colors; see `HexColor::to_wgpu` for hex colors.
+    pub fn set_clear_color(&mut self, color: wgpu::Color) {
+        self.clear_color = color;
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.opaque
+    }
+
+    // Ignores the clear color's alpha, as for fullscreen windows, which most
+    // compositors don't blend with what is below.
+    pub fn set_opaque(&mut self, opaque: bool) {
+        self.opaque = opaque;
+    }
+
+    pub fn keeps_contents(&self) -> bool {
+        self.keep_contents
+    }
+
+    // Loads the previous frame instead of clearing, for drawing
+    // incrementally. Surface textures are recycled, so without MSAA what
+    // gets loaded may be a frame or two older than the last one.
+    pub fn set_keep_contents(&mut self, keep: bool) {
+        self.keep_contents = keep;
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    // Multiplies into the alpha of everything drawn, the clear color too,
+    // e.g. to fade the window in and out. Applies from the next frame.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    // sRGB formats encode on write; other formats store the value as is,
+    // so the clear color is encoded here to look the same on both.
+    //
+    // Compositors, DWM's blur in particular, take the window's pixels as
+    // premultiplied. Blending premultiplied colors with
+    // `PREMULTIPLIED_BLEND`, or straight ones with `COMPOSITE_BLEND`, over a
+    // premultiplied background keeps it premultiplied, so only the clear
+    // color has to be premultiplied to begin with. The post effects take
+    // the frame as premultiplied too.
+    fn load_op(&self) -> wgpu::LoadOp<wgpu::Color> {
+        if self.keep_contents {
+            return wgpu::LoadOp::Load;
+        }
+        let srgb = self.surface_config.format.describe().srgb;
+        let color = self.clear_color;
+        let alpha = if self.opaque { 1.0 } else { color.a } * self.opacity as f64;
+        let encode = |c: f64| {
+            let c = if srgb {
+                c
+            } else {
+                linear_to_srgb(c as f32) as f64
+            };
+            c * alpha
+        };
+        wgpu::LoadOp::Clear(wgpu::Color {
+            r: encode(color.r),
+            g: encode(color.g),
+            b: encode(color.b),
+            a: alpha,
+        })
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self.output, Output::Offscreen(_))
+    }
+
+    // Copies the last headless frame back to the CPU. Rows are fetched
+    // with the padded stride wgpu requires and trimmed again here.
+    pub fn read_pixels(&self) -> anyhow::Result<image::RgbaImage> {
+        let texture = match &self.output {
+            Output::Offscreen(texture) => texture,
+            Output::Surface(_) => anyhow::bail!("only headless frames can be read back"),
+        };
+        read_texture(
+            &self.gpu.device,
+            &self.gpu.queue,
+            texture,
+            self.surface_config.format,
+            self.surface_config.width,
+            self.surface_config.height,
+        )
+    }
+
+    pub fn globals(&self) -> Globals {
+        Globals {
+            time: self.elapsed.get().as_secs_f32(),
+            frame: self.frame_index.get(),
+            resolution: [
+                self.surface_config.width as f32,
+                self.surface_config.height as f32,
+            ],
+            delta_time: self.delta.get().as_secs_f32(),
+            scale_factor: self.scale_factor as f32,
+            encode_srgb: u32::from(!self.target.format.describe().srgb),
+            premultiply: u32::from(self.target.alpha_mode.is_premultiplied()),
+            opacity: self.opacity,
+            padding: 0,
+        }
+    }
+
+    fn write_globals(&self) {
+        self.globals.write(&self.gpu.queue, &self.globals());
+    }
+
+    // Wall-clock time since the clock was last advanced, zero before the
+    // first frame.
+    pub fn since_last_frame(&self) -> Duration {
+        self.last_frame
+            .get()
+            .map_or(Duration::ZERO, |last| last.elapsed())
+    }
+
+    // Moves the `time` uniform forward. `draw` does this on its own; callers
+    // driving `begin_frame` directly have to do it themselves.
+    pub fn advance(&self, delta: Duration) {
+        self.elapsed.set(self.elapsed.get() + delta);
+        self.delta.set(delta);
+        self.last_frame.set(Some(Instant::now()));
+    }
+
+    pub fn stats(&self) -> Ref<FrameStats> {
+        self.stats.borrow()
+    }
+
+    // Applies a pending resize first, like `draw`. `None` while the window
+    // is zero-sized, as there is nothing to draw into.
+    pub fn begin_frame(&mut self) -> Result<Option<Frame>, wgpu::SurfaceError> {
+        self.apply_resize();
+        if self.zero_sized {
+            return Ok(None);
+        }
+        self.acquire_frame().map(Some)
+    }
+
+    fn acquire_frame(&self) -> Result<Frame, wgpu::SurfaceError> {
+        self.write_globals();
+        let started = Instant::now();
+        let (surface_texture, view) = match &self.output {
+            Output::Surface(surface) => {
+                let surface_texture = surface.get_current_texture()?;
+                let view = surface_texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(surface_texture), view)
+            }
+            Output::Offscreen(texture) => (
+                None,
+                texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            ),
+        };
+        let acquired = Instant::now();
+        let encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        Ok(Frame {
+            ctx: self,
+            surface_texture,
+            view,
+            encoder,
+            started,
+            acquired,
+        })
+    }
+}
+
+pub struct Frame<'a> {
+    ctx: &'a RenderContext,
+    // None for offscreen output, which has nothing to present.
+    surface_texture: Option<wgpu::SurfaceTexture>,
+    view: wgpu::TextureView,
+    encoder: wgpu::CommandEncoder,
+    started: Instant,
+    acquired: Instant,
+}
+
+impl<'a> Frame<'a> {
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder {
+        &mut self.encoder
+    }
+
+    pub fn begin_render_pass(&mut self, load: wgpu::LoadOp<wgpu::Color>) -> wgpu::RenderPass {
+        render_pass(&mut self.encoder, self.ctx, &self.view, load)
+    }
+
+    // A pass with the frame's attachments that ends up in `target` instead
+    // of the surface; `target` must match the surface's size and format.
+    pub fn begin_render_pass_to<'p>(
+        &'p mut self,
+        target: &'p wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPass<'p> {
+        render_pass(&mut self.encoder, self.ctx, target, load)
+    }
+
+    pub fn end(self) {
+        let encoded = Instant::now();
+        self.ctx.gpu.queue.submit(Some(self.encoder.finish()));
+        let submitted = Instant::now();
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+        let presented = Instant::now();
+
+        self.ctx.stats.borrow_mut().record(
+            self.started,
+            FrameTimings {
+                acquire: self.acquired - self.started,
+                encode: encoded - self.acquired,
+                submit: submitted - encoded,
+                present: presented - submitted,
+            },
+        );
+        self.ctx
+            .frame_index
+            .set(self.ctx.frame_index.get().wrapping_add(1));
+    }
+}
+
+fn render_pass<'p>(
+    encoder: &'p mut wgpu::CommandEncoder,
+    ctx: &'p RenderContext,
+    target: &'p wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPass<'p> {
+    let (view, resolve_target) = match &ctx.msaa {
+        Some(msaa) => {
+            assert_eq!(
+                (msaa.width, msaa.height),
+                (ctx.surface_config.width, ctx.surface_config.height),
+                "MSAA target is out of sync with the surface"
+            );
+            (&msaa.view, Some(target))
+        }
+        None => (target, None),
+    };
+    let depth_stencil_attachment =
+        ctx.depth
+            .as_ref()
+            .map(|depth| wgpu::RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            });
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations { load, store: true },
+        }],
+        depth_stencil_attachment,
+    })
+}
+
+// Queues `scene` and draws it; see `RenderContext::queue_scene`.
+pub fn draw_scene(ctx: &mut RenderContext, scene: &Scene) -> Result<(), wgpu::SurfaceError> {
+    ctx.queue_scene(scene);
+    draw(ctx)
+}
+
+pub fn draw(ctx: &mut RenderContext) -> Result<(), wgpu::SurfaceError> {
+    let delta = ctx.since_last_frame();
+    draw_with_delta(ctx, delta)
+}
+
+// Records a frame of the caller's own passes between `begin_frame` and
+// `Frame::end`. `None` while the window is zero-sized.
+pub fn render_with<R>(
+    ctx: &mut RenderContext,
+    record: impl FnOnce(&mut Frame) -> R,
+) -> Result<Option<R>, wgpu::SurfaceError> {
+    let delta = ctx.since_last_frame();
+    ctx.advance(delta);
+    let mut frame = match ctx.begin_frame()? {
+        Some(frame) => frame,
+        None => return Ok(None),
+    };
+    let result = record(&mut frame);
+    frame.end();
+    Ok(Some(result))
+}
+
+// Like `draw`, but with an explicit clock step, e.g. a clamped or fixed one
+// for smooth animation.
+pub fn draw_with_delta(ctx: &mut RenderContext, delta: Duration) -> Result<(), wgpu::SurfaceError> {
+    draw_with_overlay(ctx, delta, |_, _| {})
+}
+
+// Like `draw_with_delta`; `overlay` can record its own passes on top of the
+// presented scene, e.g. a debug UI. Screenshots and recordings don't
+// include it.
+pub fn draw_with_overlay(
+    ctx: &mut RenderContext,
+    delta: Duration,
+    overlay: impl FnOnce(&mut wgpu::CommandEncoder, &wgpu::TextureView),
+) -> Result<(), wgpu::SurfaceError> {
+    // A lost device fails every call; wait for the context to be rebuilt.
+    if ctx.zero_sized || ctx.suspended || ctx.is_device_lost() {
+        return Ok(());
+    }
+    let gpu = ctx.gpu.clone();
+    gpu.push_error_scopes();
+    let result = draw_frame(ctx, delta, overlay);
+    let errors = gpu.pop_error_scopes("drawing a frame");
+    ctx.stats.get_mut().add_gpu_errors(errors);
+    if log::log_enabled!(log::Level::Trace) {
+        if let Some(skipped) = ctx.frame_log.check() {
+            log::trace!(
+                "frame {} ({} since the last message): {:?}, {:?}",
+                ctx.frame_index.get(),
+                skipped,
+                ctx.stats.borrow().last(),
+                result
+            );
+        }
+    }
+    result
+}
+
+fn draw_frame(
+    ctx: &mut RenderContext,
+    delta: Duration,
+    overlay: impl FnOnce(&mut wgpu::CommandEncoder, &wgpu::TextureView),
+) -> Result<(), wgpu::SurfaceError> {
+    ctx.apply_resize();
+    ctx.advance(delta);
+    ctx.draw_params_binding
+        .upload(&ctx.gpu.device, &ctx.gpu.queue, &[ctx.draw_params]);
+    ctx.layers = ctx.take_layers();
+    ctx.shapes.prepare(&ctx.gpu.device, &ctx.gpu.queue);
+    ctx.gradients.prepare(&ctx.gpu.device, &ctx.gpu.queue);
+    ctx.rounded_rects.prepare(&ctx.gpu.device, &ctx.gpu.queue);
+    ctx.instances.prepare(&ctx.gpu.device, &ctx.gpu.queue);
+    ctx.text.prepare(&ctx.gpu.device, &ctx.gpu.queue);
+    ctx.sprites.prepare(&ctx.gpu.device, &ctx.gpu.queue);
+    let ctx = &*ctx;
+    let mut frame = ctx.acquire_frame()?;
+    if let Some(compute) = &ctx.compute {
+        compute.dispatch(&mut frame.encoder, &ctx.globals.bind_group);
+    }
+    encode_frame(ctx, &mut frame.encoder, &frame.view);
+    overlay(&mut frame.encoder, &frame.view);
+
+    let capture = ctx.capture.take().map(|path| {
+        let texture = create_capture(&ctx.gpu.device, &ctx.surface_config);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        encode_frame(ctx, frame.encoder(), &view);
+        let readback = Readback::new(
+            &ctx.gpu.device,
+            ctx.surface_config.width,
+            ctx.surface_config.height,
+        );
+        readback.copy(frame.encoder(), &texture);
+        (path, readback)
+    });
+
+    let mut recorder = ctx.recorder.borrow_mut();
+    if let Some(recorder) = recorder.as_mut() {
+        if let Some(view) = recorder.begin(&ctx.surface_config) {
+            encode_frame(ctx, frame.encoder(), view);
+        }
+        recorder.copy(frame.encoder());
+    }
+
+    frame.end();
+
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.submit();
+    }
+    if recorder.as_ref().map_or(false, Recorder::is_done) {
+        *recorder = None;
+    }
+
+    if let Some((path, readback)) = capture {
+        save_capture(ctx.gpu.clone(), ctx.surface_config.format, readback, path);
+    }
+    Ok(())
+}
+
+// The scene and the post effects, if any, ending up in `target`.
+fn encode_frame(
+    ctx: &RenderContext,
+    encoder: &mut wgpu::CommandEncoder,
+    target: &wgpu::TextureView,
+) {
+    match &ctx.post {
+        Some(post) => {
+            {
+                let mut rpass = render_pass(encoder, ctx, post.input_view(), ctx.load_op());
+                encode_scene(ctx, &mut rpass);
+            }
+            post.encode(encoder, target);
+        }
+        None => {
+            let mut rpass = render_pass(encoder, ctx, target, ctx.load_op());
+            encode_scene(ctx, &mut rpass);
+        }
+    }
+}
+
+fn encode_scene<'p>(ctx: &'p RenderContext, rpass: &mut wgpu::RenderPass<'p>) {
+    rpass.set_pipeline(&ctx.render_pipeline);
+    rpass.set_bind_group(0, &ctx.globals.bind_group, &[]);
+    ctx.draw_params_binding.apply(rpass, 0);
+    rpass.set_vertex_buffer(0, ctx.vertex_buffer.slice());
+    if ctx.index_count > 0 {
+        rpass.set_index_buffer(ctx.index_buffer.slice(), wgpu::IndexFormat::Uint16);
+        rpass.draw_indexed(0..ctx.index_count, 0, 0..1);
+    } else {
+        rpass.draw(0..ctx.vertex_count, 0..1);
+    }
+
+    if let Some(quad) = &ctx.textured_quad {
+        rpass.set_pipeline(&ctx.texture_pipeline);
+        rpass.set_bind_group(0, &quad.bind_group, &[]);
+        rpass.set_bind_group(1, &ctx.globals.bind_group, &[]);
+        rpass.set_vertex_buffer(0, quad.vertex_buffer.slice(..));
+        rpass.set_index_buffer(ctx.quad_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..1);
+    }
+
+    let globals = &ctx.globals.bind_group;
+    if let Some(compute) = &ctx.compute {
+        compute.render(rpass, globals);
+    }
+    for (layer, range) in &ctx.layers {
+        let range = range.clone();
+        match layer {
+            Layer::Shapes => ctx.shapes.render_range(rpass, globals, range),
+            Layer::Gradients => ctx.gradients.render_range(rpass, globals, range),
+            Layer::RoundedRects => ctx.rounded_rects.render_range(rpass, globals, range),
+            Layer::Instances => ctx.instances.render_range(rpass, globals, range),
+            Layer::Text => ctx.text.render_range(rpass, globals, range),
+            Layer::Sprites => ctx.sprites.render_range(rpass, globals, range),
+        }
+    }
+}
+
+fn create_capture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("capture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    })
+}
+
+// Waits for the copy and writes the PNG on its own thread so the event loop
+// keeps going; failures are only logged.
+fn save_capture(gpu: Arc<Gpu>, format: wgpu::TextureFormat, readback: Readback, path: PathBuf) {
+    std::thread::spawn(move || {
+        let result = readback
+            .read(&gpu.device, format)
+            .and_then(|image| Ok(image.save(&path)?));
+        match result {
+            Ok(()) => log::info!("saved screenshot {:?}", path),
+            Err(e) => log::error!("failed to save screenshot {:?}: {}", path, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run with `cargo test -- --ignored` where there is an adapter.
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn stacked_translucent_rects_accumulate_alpha() {
+        let options = RenderOptions {
+            alpha_mode: AlphaMode::Straight,
+            ..Default::default()
+        };
+        let mut ctx =
+            pollster::block_on(RenderContext::headless(PhysicalSize::new(4, 4), &options)).unwrap();
+        assert_eq!(ctx.target.blend, COMPOSITE_BLEND);
+        ctx.set_clear_color(wgpu::Color::TRANSPARENT);
+        ctx.set_geometry(&[]);
+        let rect = Rect::new(0.0, 0.0, 4.0, 4.0);
+        ctx.fill_rect(rect, [1.0, 0.0, 0.0, 0.5]);
+        ctx.fill_rect(rect, [0.0, 0.0, 1.0, 0.5]);
+        draw(&mut ctx).unwrap();
+
+        let pixels = ctx.read_pixels().unwrap();
+        // 0.5 + 0.5 * (1 - 0.5). Blending alpha like color would give 0.5.
+        let alpha = pixels.get_pixel(2, 2)[3];
+        assert!((190..=192).contains(&alpha), "alpha {}", alpha);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread::JoinHandle,
+    time::Instant,
+};
+
+use anyhow::Context;
+use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy, window::WindowId};
+
+use crate::{
+    animation::MAX_FRAME_DELTA,
+    event::UserEvent,
+    file_drop::FileDrop,
+    render::{draw_with_delta, RenderContext},
+    scene::Scene,
+};
+
+// What a window shows in one frame, taken after its tweens stepped.
+pub(crate) struct Frame {
+    pub scene: Scene,
+    pub file_drop: FileDrop,
+    pub show_stats: bool,
+    // Whether the clock steps by at most `MAX_FRAME_DELTA`, as when
+    // animating.
+    pub clamp_delta: bool,
+}
+
+impl Frame {
+    fn draw(&self, ctx: &mut RenderContext) -> Result<Instant, wgpu::SurfaceError> {
+        ctx.queue_scene(&self.scene);
+        self.file_drop.queue(ctx);
+        if self.show_stats {
+            let readout = ctx.stats().to_string();
+            ctx.queue_text(&readout, [8.0, 8.0], 14.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+        let delta = if self.clamp_delta {
+            ctx.since_last_frame().min(MAX_FRAME_DELTA)
+        } else {
+            ctx.since_last_frame()
+        };
+        let result = draw_with_delta(ctx, delta);
+        settle_frame(ctx, result)
+    }
+}
+
+// Recreates a lost or outdated surface for the next frame, which is still
+// the caller's to ask for, and stops at running out of GPU memory: resources
+// may be half created after that, so nothing should use them. Frames drawn
+// on the event loop and on render threads end here alike.
+pub(crate) fn settle_frame(
+    ctx: &mut RenderContext,
+    result: Result<(), wgpu::SurfaceError>,
+) -> Result<Instant, wgpu::SurfaceError> {
+    if ctx.gpu().is_out_of_memory() {
+        return Err(wgpu::SurfaceError::OutOfMemory);
+    }
+    match result {
+        Ok(()) => Ok(Instant::now()),
+        Err(e @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+            ctx.recreate_surface();
+            Err(e)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+enum Message {
+    Frame(Frame),
+    Resize(PhysicalSize<u32>),
+}
+
+// Draws a window's frames off the event loop, so a slow frame doesn't hold
+// up input. The context is shared with the event loop, which still reaches
+// it for everything but drawing; the lock is only held for a frame, and the
+// event loop sends the next frame only once the last one is done, see
+// `UserEvent::FrameDone`. Dropping it waits for the frame being drawn and
+// ends the thread, ahead of the surface and the window.
+pub(crate) struct RenderThread {
+    sender: Option<mpsc::Sender<Message>>,
+    thread: Option<JoinHandle<()>>,
+    pub in_flight: bool,
+    // A redraw was asked for while a frame was in flight.
+    pub pending: bool,
+}
+
+impl RenderThread {
+    pub fn spawn(
+        id: WindowId,
+        ctx: Arc<Mutex<RenderContext>>,
+        proxy: EventLoopProxy<UserEvent>,
+    ) -> anyhow::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name(format!("render {:?}", id))
+            .spawn(move || run(id, &ctx, &receiver, &proxy))
+            .context("failed to start a render thread")?;
+        Ok(Self {
+            sender: Some(sender),
+            thread: Some(thread),
+            in_flight: false,
+            pending: false,
+        })
+    }
+
+    pub fn draw(&mut self, frame: Frame) {
+        self.send(Message::Frame(frame));
+        self.in_flight = true;
+        self.pending = false;
+    }
+
+    pub fn resize(&self, size: PhysicalSize<u32>) {
+        self.send(Message::Resize(size));
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(sender) = &self.sender {
+            if sender.send(message).is_err() {
+                log::error!("the render thread is gone");
+            }
+        }
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("the render thread panicked");
+            }
+        }
+    }
+}
+
+fn run(
+    id: WindowId,
+    ctx: &Mutex<RenderContext>,
+    receiver: &mpsc::Receiver<Message>,
+    proxy: &EventLoopProxy<UserEvent>,
+) {
+    while let Ok(first) = receiver.recv() {
+        // Only the latest of what queued up counts, so a drag's worth of
+        // resizes reconfigures the surface once.
+        let mut frame = None;
+        let mut size = None;
+        for message in std::iter::once(first).chain(receiver.try_iter()) {
+            match message {
+                Message::Frame(next) => frame = Some(next),
+                Message::Resize(next) => size = Some(next),
+            }
+        }
+        let mut ctx = ctx.lock().unwrap();
+        if let Some(size) = size {
+            ctx.resize(size);
+        }
+        let frame = match frame {
+            Some(frame) => frame,
+            None => continue,
+        };
+        // Caught while the lock is still held, so the context isn't left
+        // poisoned for the event loop, which closes the window instead.
+        let drawn = panic::catch_unwind(AssertUnwindSafe(|| frame.draw(&mut ctx)));
+        drop(ctx);
+        let event = match drawn {
+            Ok(result) => UserEvent::FrameDone(id, result),
+            Err(_) => UserEvent::RenderThreadPanicked(id),
+        };
+        let panicked = matches!(event, UserEvent::RenderThreadPanicked(_));
+        if proxy.send_event(event).is_err() || panicked {
+            break;
+        }
+    }
+}
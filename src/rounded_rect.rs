@@ -0,0 +1,247 @@
+use std::{borrow::Cow, ops::Range, sync::Arc};
+
+use crate::{
+    buffer::DynamicBuffer,
+    pipeline::{PipelineCache, ShaderId, TargetConfig},
+    shapes::Rect,
+};
+
+const VERTICES_PER_RECT: u32 = 6;
+// The quad reaches this far past the rect so the antialiased edge, which
+// straddles it, isn't cut off.
+const EDGE_MARGIN: f32 = 1.0;
+
+// In pixels, clockwise from the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CornerRadii {
+    pub top_left: f32,
+    pub top_right: f32,
+    pub bottom_right: f32,
+    pub bottom_left: f32,
+}
+
+impl CornerRadii {
+    pub const fn new(top_left: f32, top_right: f32, bottom_right: f32, bottom_left: f32) -> Self {
+        Self {
+            top_left,
+            top_right,
+            bottom_right,
+            bottom_left,
+        }
+    }
+
+    pub const fn all(radius: f32) -> Self {
+        Self::new(radius, radius, radius, radius)
+    }
+
+    // Each corner between zero and half the shorter side, where the corners
+    // of that side meet.
+    fn clamped(self, rect: Rect) -> [f32; 4] {
+        let max = (rect.width.min(rect.height) / 2.0).max(0.0);
+        [
+            self.top_left,
+            self.top_right,
+            self.bottom_right,
+            self.bottom_left,
+        ]
+        .map(|radius| radius.clamp(0.0, max))
+    }
+}
+
+impl From<f32> for CornerRadii {
+    fn from(radius: f32) -> Self {
+        Self::all(radius)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct RoundedRectVertex {
+    position: [f32; 2],
+    center: [f32; 2],
+    half_size: [f32; 2],
+    radii: [f32; 4],
+    color: [f32; 4],
+    border_color: [f32; 4],
+    border_width: f32,
+    // The shadow's standard deviation, zero for a sharp shape.
+    sigma: f32,
+}
+
+impl RoundedRectVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32,
+        7 => Float32,
+    ];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Rounded rects and their shadows for the next frame, one quad each. The
+// fragment shader measures the distance to the rounded outline, so edges
+// and corners come out smooth at any size without tessellating the arcs,
+// and integrates a gaussian over the shape for blurred shadows.
+pub(crate) struct RoundedRectRenderer {
+    vertices: Vec<RoundedRectVertex>,
+    shader: ShaderId,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+}
+
+impl RoundedRectRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+        globals_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("rounded rect"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("rounded_rect.wgsl"))),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("rounded rect"),
+            bind_group_layouts: &[globals_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = cache.add_shader("rounded rect", shader, pipeline_layout);
+        let pipeline = cache.get_or_create(device, shader, &Self::buffers(), target);
+
+        let vertex_buffer = DynamicBuffer::new(
+            device,
+            "rounded rect vertices",
+            wgpu::BufferUsages::VERTEX,
+            0,
+        );
+
+        Self {
+            vertices: Vec::new(),
+            shader,
+            pipeline,
+            vertex_buffer,
+        }
+    }
+
+    fn buffers() -> [wgpu::VertexBufferLayout<'static>; 1] {
+        [RoundedRectVertex::layout()]
+    }
+
+    // Switches to the pipeline for a new target format or sample count.
+    pub(crate) fn retarget(
+        &mut self,
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+    ) {
+        self.pipeline = cache.get_or_create(device, self.shader, &Self::buffers(), target);
+    }
+
+    // The border lies inside `rect`, over the fill's edge, like
+    // `stroke_rect`'s stroke.
+    pub(crate) fn draw(
+        &mut self,
+        rect: Rect,
+        radii: CornerRadii,
+        color: [f32; 4],
+        border_width: f32,
+        border_color: [f32; 4],
+    ) {
+        let border_width = border_width.clamp(0.0, (rect.width.min(rect.height) / 2.0).max(0.0));
+        self.push(rect, radii, color, border_width, border_color, 0.0);
+    }
+
+    // `blur_radius` is twice the gaussian's standard deviation, as in CSS;
+    // zero gives the shape's sharp silhouette.
+    pub(crate) fn draw_shadow(
+        &mut self,
+        rect: Rect,
+        radii: CornerRadii,
+        blur_radius: f32,
+        color: [f32; 4],
+    ) {
+        self.push(
+            rect,
+            radii,
+            color,
+            0.0,
+            [0.0; 4],
+            blur_radius.max(0.0) / 2.0,
+        );
+    }
+
+    fn push(
+        &mut self,
+        rect: Rect,
+        radii: CornerRadii,
+        color: [f32; 4],
+        border_width: f32,
+        border_color: [f32; 4],
+        sigma: f32,
+    ) {
+        if rect.width <= 0.0 || rect.height <= 0.0 {
+            return;
+        }
+        let half_size = [rect.width / 2.0, rect.height / 2.0];
+        let center = [rect.x + half_size[0], rect.y + half_size[1]];
+        let radii = radii.clamped(rect);
+        let vertex = |position| RoundedRectVertex {
+            position,
+            center,
+            half_size,
+            radii,
+            color,
+            border_color,
+            border_width,
+            sigma,
+        };
+        // A blurred shadow fades out within three standard deviations.
+        let margin = EDGE_MARGIN + sigma * 3.0;
+        let (x0, y0) = (rect.x - margin, rect.y - margin);
+        let (x1, y1) = (rect.x + rect.width + margin, rect.y + rect.height + margin);
+        self.vertices
+            .extend([[x0, y0], [x1, y0], [x1, y1], [x0, y0], [x1, y1], [x0, y1]].map(vertex));
+    }
+
+    // The number of rounded rects queued so far.
+    pub(crate) fn mark(&self) -> u32 {
+        self.vertices.len() as u32 / VERTICES_PER_RECT
+    }
+
+    pub(crate) fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.vertex_buffer
+            .write(device, queue, bytemuck::cast_slice(&self.vertices));
+        self.vertices.clear();
+    }
+
+    pub(crate) fn render_range<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        globals: &'a wgpu::BindGroup,
+        rects: Range<u32>,
+    ) {
+        if rects.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice());
+        rpass.draw(
+            rects.start * VERTICES_PER_RECT..rects.end * VERTICES_PER_RECT,
+            0..1,
+        );
+    }
+}
@@ -0,0 +1,159 @@
+struct Globals {
+    time: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    delta_time: f32;
+    scale_factor: f32;
+    encode_srgb: u32;
+    premultiply: u32;
+    opacity: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> globals: Globals;
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] center: vec2<f32>;
+    [[location(2)]] half_size: vec2<f32>;
+    // Top-left, top-right, bottom-right and bottom-left.
+    [[location(3)]] radii: vec4<f32>;
+    [[location(4)]] color: vec4<f32>;
+    [[location(5)]] border_color: vec4<f32>;
+    [[location(6)]] border_width: f32;
+    [[location(7)]] sigma: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] pixel: vec2<f32>;
+    [[location(1), interpolate(flat)]] center: vec2<f32>;
+    [[location(2), interpolate(flat)]] half_size: vec2<f32>;
+    [[location(3), interpolate(flat)]] radii: vec4<f32>;
+    [[location(4), interpolate(flat)]] color: vec4<f32>;
+    [[location(5), interpolate(flat)]] border_color: vec4<f32>;
+    [[location(6), interpolate(flat)]] border_width: f32;
+    [[location(7), interpolate(flat)]] sigma: f32;
+};
+
+// Pixel coordinates with the origin at the top-left corner.
+[[stage(vertex)]]
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = input.position / globals.resolution * 2.0 - 1.0;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.pixel = input.position;
+    out.center = input.center;
+    out.half_size = input.half_size;
+    out.radii = input.radii;
+    out.color = input.color;
+    out.border_color = input.border_color;
+    out.border_width = input.border_width;
+    out.sigma = input.sigma;
+    return out;
+}
+
+// Signed distance from `p`, relative to the center, to the outline; negative
+// inside. The radius is the one of the corner in p's quadrant, y grows down.
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radii: vec4<f32>) -> f32 {
+    let radius = corner_radius(p, radii);
+    let q = abs(p) - half_size + radius;
+    return min(max(q.x, q.y), 0.0) + length(max(q, vec2<f32>(0.0))) - radius;
+}
+
+fn corner_radius(p: vec2<f32>, radii: vec4<f32>) -> f32 {
+    let side = select(radii.xw, radii.yz, p.x > 0.0);
+    return select(side.x, side.y, p.y > 0.0);
+}
+
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    return exp(-(x * x) / (2.0 * sigma * sigma)) / (2.506628 * sigma);
+}
+
+// Abramowitz and Stegun's approximation, good to 5e-4.
+fn erf(x: vec2<f32>) -> vec2<f32> {
+    let s = sign(x);
+    let a = abs(x);
+    var r = 1.0 + (0.278393 + (0.230389 + 0.078108 * (a * a)) * a) * a;
+    r = r * r;
+    return s - s / (r * r);
+}
+
+// The blurred coverage of one row of the rounded box at `y`, solved exactly
+// along x.
+fn shadow_row(x: f32, y: f32, sigma: f32, radius: f32, half_size: vec2<f32>) -> f32 {
+    let delta = min(half_size.y - radius - abs(y), 0.0);
+    let curved = half_size.x - radius + sqrt(max(0.0, radius * radius - delta * delta));
+    let integral = 0.5 + 0.5 * erf((x + vec2<f32>(-curved, curved)) * (0.707107 / sigma));
+    return integral.y - integral.x;
+}
+
+// Evan Wallace's rounded box shadow: the rows within three standard
+// deviations, weighted by the gaussian along y.
+fn rounded_box_shadow(p: vec2<f32>, half_size: vec2<f32>, radius: f32, sigma: f32) -> f32 {
+    let low = p.y - half_size.y;
+    let high = p.y + half_size.y;
+    let start = clamp(-3.0 * sigma, low, high);
+    let end = clamp(3.0 * sigma, low, high);
+    let step = (end - start) / 4.0;
+    var y = start + step * 0.5;
+    var value = 0.0;
+    for (var i = 0; i < 4; i = i + 1) {
+        value = value + shadow_row(p.x, p.y - y, sigma, radius, half_size) * gaussian(y, sigma) * step;
+        y = y + step;
+    }
+    return value;
+}
+
+// Targets that aren't sRGB store colors as written, so the linear output
+// is encoded here to look the same as on an sRGB target.
+fn encode_output(color: vec4<f32>) -> vec4<f32> {
+    if (globals.encode_srgb == 0u) {
+        return color;
+    }
+    let c = max(color.rgb, vec3<f32>(0.0));
+    let curve = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return vec4<f32>(select(curve, c * 12.92, c <= vec3<f32>(0.0031308)), color.a);
+}
+
+// The window's fade scales alpha, and the blend state of
+// `AlphaMode::Premultiplied` takes the color already multiplied by it.
+fn output_color(color: vec4<f32>) -> vec4<f32> {
+    let c = encode_output(vec4<f32>(color.rgb, color.a * globals.opacity));
+    if (globals.premultiply == 0u) {
+        return c;
+    }
+    return vec4<f32>(c.rgb * c.a, c.a);
+}
+
+fn premultiply(color: vec4<f32>, coverage: f32) -> vec4<f32> {
+    let alpha = color.a * coverage;
+    return vec4<f32>(color.rgb * alpha, alpha);
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let p = input.pixel - input.center;
+    if (input.sigma > 0.0) {
+        let radius = corner_radius(p, input.radii);
+        let coverage = clamp(rounded_box_shadow(p, input.half_size, radius, input.sigma), 0.0, 1.0);
+        if (coverage * input.color.a <= 0.0) {
+            discard;
+        }
+        return output_color(vec4<f32>(input.color.rgb, input.color.a * coverage));
+    }
+
+    let distance = rounded_box(p, input.half_size, input.radii);
+    // One screen pixel in distance units, the width of the edge ramp.
+    let edge = max(fwidth(distance), 0.0001);
+    let outer = clamp(0.5 - distance / edge, 0.0, 1.0);
+    let inner = clamp(0.5 - (distance + input.border_width) / edge, 0.0, 1.0);
+
+    // The fill and the border ring cover separate parts of an edge pixel,
+    // so their premultiplied colors add up.
+    let color = premultiply(input.color, inner) + premultiply(input.border_color, outer - inner);
+    if (color.a <= 0.0) {
+        discard;
+    }
+    return output_color(vec4<f32>(color.rgb / color.a, color.a));
+}
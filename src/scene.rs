@@ -0,0 +1,417 @@
+use std::{
+    collections::BTreeSet,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use winit::event::{MouseButton, MouseScrollDelta, TouchPhase};
+
+use crate::{
+    gradient::Gradient, instance::Instance, nine_patch::NinePatch, rounded_rect::CornerRadii,
+    shadow::Shadow, shapes::Rect, sprite::SpriteId,
+};
+
+// A mesh uploaded with `RenderContext::add_mesh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshId(pub(crate) usize);
+
+// An interactive part of a scene, picked by whoever adds it, see
+// `Scene::add_region`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegionId(pub u32);
+
+// The cursor moved onto or off a region, see `Handle::on_hover`. Moving
+// from one region straight onto another exits the first one first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoverEvent {
+    Enter(RegionId),
+    Exit(RegionId),
+}
+
+// A mouse button on a region, see `Handle::on_click`. A press is followed
+// by exactly one of the other two: a click when the same button is released
+// over the same region, a cancel when released anywhere else, or when the
+// window loses focus first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickEvent {
+    Press(RegionId, MouseButton),
+    Click(RegionId, MouseButton),
+    Cancel(RegionId, MouseButton),
+}
+
+// A finger on the scene, see `Handle::on_touch`. Each finger keeps its
+// `id` from `Started` to `Ended` or `Cancelled`. `position` is in physical
+// pixels like the cursor's, and `region` the one under it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TouchEvent {
+    pub id: u64,
+    pub phase: TouchPhase,
+    pub position: [f32; 2],
+    pub region: Option<RegionId>,
+}
+
+// How far a line of a wheel that scrolls line by line goes, in logical
+// pixels.
+const LINE_HEIGHT: f32 = 20.0;
+
+// Wheels that click from line to line report lines, touchpads and smooth
+// wheels pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollSource {
+    Line,
+    Pixel,
+}
+
+// A scroll over the scene, see `Handle::on_scroll`. The deltas are in
+// physical pixels from either source and positive when the content should
+// move right and down, as winit has them. `region` is the one under the
+// cursor at `position`, if any.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollEvent {
+    pub dx: f32,
+    pub dy: f32,
+    pub source: ScrollSource,
+    pub position: [f32; 2],
+    pub region: Option<RegionId>,
+}
+
+impl ScrollEvent {
+    pub(crate) fn new(
+        delta: MouseScrollDelta,
+        scale_factor: f64,
+        position: [f32; 2],
+        region: Option<RegionId>,
+    ) -> Self {
+        let (dx, dy, source) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => {
+                let line = LINE_HEIGHT * scale_factor as f32;
+                (x * line, y * line, ScrollSource::Line)
+            }
+            MouseScrollDelta::PixelDelta(delta) => {
+                (delta.x as f32, delta.y as f32, ScrollSource::Pixel)
+            }
+        };
+        Self {
+            dx,
+            dy,
+            source,
+            position,
+            region,
+        }
+    }
+}
+
+// Everything is in pixels from the top-left corner, like the immediate-mode
+// calls on `RenderContext` each command maps to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    FillRect {
+        rect: Rect,
+        color: [f32; 4],
+    },
+    StrokeRect {
+        rect: Rect,
+        width: f32,
+        color: [f32; 4],
+    },
+    // A transparent `color` with a border strokes the rect.
+    FillRoundedRect {
+        rect: Rect,
+        radius: CornerRadii,
+        color: [f32; 4],
+        border_width: f32,
+        border_color: [f32; 4],
+    },
+    // Goes before the command for the shape casting it; `rect` is the
+    // shape's, the shadow's offset moves it.
+    Shadow {
+        rect: Rect,
+        radius: CornerRadii,
+        shadow: Shadow,
+    },
+    FillCircle {
+        center: [f32; 2],
+        radius: f32,
+        color: [f32; 4],
+    },
+    Line {
+        from: [f32; 2],
+        to: [f32; 2],
+        width: f32,
+        color: [f32; 4],
+    },
+    Gradient {
+        rect: Rect,
+        gradient: Gradient,
+    },
+    Text {
+        text: String,
+        position: [f32; 2],
+        size: f32,
+        color: [f32; 4],
+    },
+    Sprite {
+        sprite: SpriteId,
+        rect: Rect,
+        tint: [f32; 4],
+    },
+    // The sprite of that name, once there is one, e.g. from
+    // `Handle::load_texture`; `placeholder` fills the rect until then.
+    Texture {
+        name: String,
+        rect: Rect,
+        tint: [f32; 4],
+        placeholder: [f32; 4],
+    },
+    NinePatch {
+        patch: NinePatch,
+        rect: Rect,
+    },
+    Mesh(MeshId),
+    Instanced {
+        mesh: MeshId,
+        instances: Vec<Instance>,
+    },
+}
+
+// What changed in a scene since its damage was last taken: some commands,
+// by index, or all of it. Frames are still drawn whole; this is what drawing
+// only the changed part would go by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Damage {
+    None,
+    Commands(BTreeSet<usize>),
+    Full,
+}
+
+impl Default for Damage {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Damage {
+    pub fn is_none(&self) -> bool {
+        matches!(self, Self::None)
+    }
+
+    // `None` for a change to more than one command.
+    fn add(&mut self, index: Option<usize>) {
+        match (index, &mut *self) {
+            (_, Self::Full) => {}
+            (None, _) => *self = Self::Full,
+            (Some(index), Self::Commands(indices)) => {
+                indices.insert(index);
+            }
+            (Some(index), Self::None) => *self = Self::Commands(BTreeSet::from([index])),
+        }
+    }
+}
+
+// Unique across scenes, so a window that presented a generation knows it shows
+// exactly that content, whichever scene it came from.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+fn next_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+// Retained content for a window, drawn in order with later commands on top.
+// Only plain data, no GPU handles, so it can be built anywhere and kept
+// between frames. Any change moves the scene to a new generation and marks
+// the scene dirty; the event loop redraws a window only when the generation
+// differs from the one it last presented.
+#[derive(Debug, Clone)]
+pub struct Scene {
+    clear_color: Option<wgpu::Color>,
+    commands: Vec<DrawCommand>,
+    // In the order added, the last one on top.
+    regions: Vec<(RegionId, Rect)>,
+    hovered: Option<RegionId>,
+    pressed: Option<RegionId>,
+    generation: u64,
+    damage: Damage,
+}
+
+// Compares what is drawn and hit tested, not the bookkeeping.
+impl PartialEq for Scene {
+    fn eq(&self, other: &Self) -> bool {
+        self.clear_color == other.clear_color
+            && self.commands == other.commands
+            && self.regions == other.regions
+            && self.hovered == other.hovered
+            && self.pressed == other.pressed
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene {
+    // Starts out dirty so the first frame gets drawn.
+    pub fn new() -> Self {
+        Self {
+            clear_color: None,
+            commands: Vec::new(),
+            regions: Vec::new(),
+            hovered: None,
+            pressed: None,
+            generation: next_generation(),
+            damage: Damage::Full,
+        }
+    }
+
+    // `None` keeps the context's clear color.
+    pub fn clear_color(&self) -> Option<wgpu::Color> {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, color: Option<wgpu::Color>) {
+        if self.clear_color != color {
+            self.clear_color = color;
+            self.touch(None);
+        }
+    }
+
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    // Marks the scene dirty, whether or not the command is changed.
+    pub fn command_mut(&mut self, index: usize) -> Option<&mut DrawCommand> {
+        let command = self.commands.get_mut(index)?;
+        self.touch(Some(index));
+        Some(command)
+    }
+
+    pub fn push(&mut self, command: DrawCommand) {
+        self.commands.push(command);
+        self.touch(Some(self.commands.len() - 1));
+    }
+
+    pub fn clear(&mut self) {
+        if !self.commands.is_empty() {
+            self.commands.clear();
+            self.touch(None);
+        }
+    }
+
+    // Replaces the commands, staying clean if nothing changed.
+    pub fn set_commands(&mut self, commands: Vec<DrawCommand>) {
+        if self.commands != commands {
+            self.commands = commands;
+            self.touch(None);
+        }
+    }
+
+    // Registers `rect` for hit testing. Regions added later are on top and
+    // win where they overlap, like commands. They are in the commands'
+    // pixels, which are the physical pixels winit reports the cursor in,
+    // so nothing has to be scaled for the scale factor. Regions aren't
+    // drawn and don't mark the scene dirty.
+    pub fn add_region(&mut self, id: RegionId, rect: Rect) {
+        self.regions.push((id, rect));
+    }
+
+    pub fn regions(&self) -> &[(RegionId, Rect)] {
+        &self.regions
+    }
+
+    pub fn clear_regions(&mut self) {
+        self.regions.clear();
+    }
+
+    // The topmost region containing `position`.
+    pub fn hit_test(&self, position: [f32; 2]) -> Option<RegionId> {
+        self.regions
+            .iter()
+            .rev()
+            .find(|(_, rect)| rect.contains(position))
+            .map(|&(id, _)| id)
+    }
+
+    // The region under the cursor, kept up to date by the event loop. A
+    // change marks the scene dirty, so commands can be drawn differently
+    // for it.
+    pub fn hovered(&self) -> Option<RegionId> {
+        self.hovered
+    }
+
+    pub(crate) fn set_hovered(&mut self, hovered: Option<RegionId>) {
+        if self.hovered != hovered {
+            self.hovered = hovered;
+            self.touch(None);
+        }
+    }
+
+    // The region held down between a press and its click or cancel, which
+    // marks the scene dirty like hovering.
+    pub fn pressed(&self) -> Option<RegionId> {
+        self.pressed
+    }
+
+    pub(crate) fn set_pressed(&mut self, pressed: Option<RegionId>) {
+        if self.pressed != pressed {
+            self.pressed = pressed;
+            self.touch(None);
+        }
+    }
+
+    // Takes over `scene`'s content and regions, keeping this one's
+    // generation if it draws the same. Returns whether anything changed.
+    pub fn replace(&mut self, scene: Scene) -> bool {
+        let generation = self.generation;
+        self.regions = scene.regions;
+        self.set_clear_color(scene.clear_color);
+        self.set_commands(scene.commands);
+        self.generation != generation
+    }
+
+    fn touch(&mut self, index: Option<usize>) {
+        self.generation = next_generation();
+        self.damage.add(index);
+    }
+
+    // Changes with every change to what is drawn.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn damage(&self) -> &Damage {
+        &self.damage
+    }
+
+    // Returns what changed since the last call.
+    pub fn take_damage(&mut self) -> Damage {
+        std::mem::take(&mut self.damage)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.damage.is_none()
+    }
+
+    // Forces a new generation, whether or not anything changed.
+    pub fn mark_dirty(&mut self) {
+        self.touch(None);
+    }
+
+    // Returns whether the scene changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        !self.take_damage().is_none()
+    }
+}
+
+impl Extend<DrawCommand> for Scene {
+    fn extend<T: IntoIterator<Item = DrawCommand>>(&mut self, commands: T) {
+        let len = self.commands.len();
+        self.commands.extend(commands);
+        if self.commands.len() != len {
+            self.generation = next_generation();
+            for index in len..self.commands.len() {
+                self.damage.add(Some(index));
+            }
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use std::{collections::HashMap, num::NonZeroU32};
+
+use ab_glyph::{Font, FontArc, GlyphId, PxScale};
+
+use crate::{packer::ShelfPacker, text::FontId};
+
+// Glyphs are rendered once at this size and scaled to any other.
+pub(crate) const SDF_SIZE: f32 = 48.0;
+// How far, in pixels at `SDF_SIZE`, distances reach out of and into the
+// glyph. Also how far outlines and shadows can reach before being cut off.
+pub(crate) const SDF_SPREAD: u32 = 6;
+pub(crate) const SDF_PAGE_SIZE: u32 = 512;
+// More pages are added as needed up to this many, after that the least
+// recently used one is emptied for new glyphs.
+const MAX_PAGES: u32 = 4;
+const GLYPH_PADDING: u32 = 1;
+const INF: f64 = 1e20;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SdfGlyph {
+    pub(crate) page: u32,
+    // Offset of the field from the pen position and its size, in pixels at
+    // `SDF_SIZE`. Includes the spread around the outline.
+    pub(crate) offset: [f32; 2],
+    pub(crate) size: [f32; 2],
+    pub(crate) uv_min: [f32; 2],
+    pub(crate) uv_max: [f32; 2],
+}
+
+// Squared distances to the nearest zero of `f` along one row, by
+// Felzenszwalb and Huttenlocher's lower envelope of parabolas.
+fn edt_1d(f: &[f64], d: &mut [f64], v: &mut [usize], z: &mut [f64]) {
+    let n = f.len();
+    let parabola = |q: usize| f[q] + (q * q) as f64;
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = -INF;
+    z[1] = INF;
+    for q in 1..n {
+        let mut s = (parabola(q) - parabola(v[k])) / (2 * (q - v[k])) as f64;
+        while s <= z[k] {
+            k -= 1;
+            s = (parabola(q) - parabola(v[k])) / (2 * (q - v[k])) as f64;
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = INF;
+    }
+    k = 0;
+    for (q, d) in d.iter_mut().enumerate() {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+        let dq = q as f64 - v[k] as f64;
+        *d = dq * dq + f[v[k]];
+    }
+}
+
+// In place, columns then rows.
+fn edt(grid: &mut [f64], width: usize, height: usize) {
+    let n = width.max(height);
+    let (mut f, mut d) = (vec![0.0; n], vec![0.0; n]);
+    let (mut v, mut z) = (vec![0; n], vec![0.0; n + 1]);
+    for x in 0..width {
+        for y in 0..height {
+            f[y] = grid[y * width + x];
+        }
+        edt_1d(&f[..height], &mut d[..height], &mut v, &mut z);
+        for y in 0..height {
+            grid[y * width + x] = d[y];
+        }
+    }
+    for y in 0..height {
+        let row = &mut grid[y * width..(y + 1) * width];
+        f[..width].copy_from_slice(row);
+        edt_1d(&f[..width], &mut d[..width], &mut v, &mut z);
+        row.copy_from_slice(&d[..width]);
+    }
+}
+
+// Turns coverage into a distance field with `spread` pixels of border. 0.5
+// is on the outline, larger values inside. Partly covered pixels place the
+// outline between pixel centers, which keeps the field smooth.
+fn distance_field(coverage: &[f32], width: u32, height: u32, spread: u32) -> (Vec<u8>, u32, u32) {
+    let (w, h) = (
+        (width + spread * 2) as usize,
+        (height + spread * 2) as usize,
+    );
+    let mut outer = vec![INF; w * h];
+    let mut inner = vec![0.0; w * h];
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let c = coverage[y * width as usize + x] as f64;
+            let i = (y + spread as usize) * w + x + spread as usize;
+            if c >= 1.0 {
+                outer[i] = 0.0;
+                inner[i] = INF;
+            } else if c > 0.0 {
+                outer[i] = (0.5 - c).max(0.0).powi(2);
+                inner[i] = (c - 0.5).max(0.0).powi(2);
+            }
+        }
+    }
+    edt(&mut outer, w, h);
+    edt(&mut inner, w, h);
+    let field = outer
+        .iter()
+        .zip(&inner)
+        .map(|(outer, inner)| {
+            let distance = outer.sqrt() - inner.sqrt();
+            ((0.5 - distance / (2.0 * spread as f64)).clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect();
+    (field, w as u32, h as u32)
+}
+
+struct Page {
+    packer: ShelfPacker,
+    // A CPU copy, uploaded again when pages are added.
+    data: Vec<u8>,
+    last_used: u64,
+}
+
+impl Page {
+    fn new() -> Self {
+        Self {
+            packer: ShelfPacker::new(SDF_PAGE_SIZE, SDF_PAGE_SIZE),
+            data: vec![0; (SDF_PAGE_SIZE * SDF_PAGE_SIZE) as usize],
+            last_used: 0,
+        }
+    }
+}
+
+// Distance fields of glyphs at a single size, in the layers of one array
+// texture so text on every page draws together.
+pub(crate) struct SdfAtlas {
+    pages: Vec<Page>,
+    glyphs: HashMap<(FontId, GlyphId), Option<SdfGlyph>>,
+    frame: u64,
+    texture: wgpu::Texture,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl SdfAtlas {
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sdf atlas"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sdf atlas"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let pages = vec![Page::new()];
+        let (texture, bind_group) = create_texture(device, &layout, &sampler, 1);
+        Self {
+            pages,
+            glyphs: HashMap::new(),
+            frame: 0,
+            texture,
+            layout,
+            sampler,
+            bind_group,
+        }
+    }
+
+    pub(crate) fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    // Pages used since then aren't emptied for new glyphs.
+    pub(crate) fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.glyphs.clear();
+        for page in &mut self.pages {
+            page.packer.clear();
+            page.data.fill(0);
+        }
+    }
+
+    // Returns None when every page is full of glyphs this frame uses;
+    // glyphs without an outline are cached as Some(None).
+    pub(crate) fn get_or_insert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        font: &FontArc,
+        font_id: FontId,
+        glyph_id: GlyphId,
+    ) -> Option<Option<SdfGlyph>> {
+        if let Some(cached) = self.glyphs.get(&(font_id, glyph_id)) {
+            if let Some(glyph) = cached {
+                self.pages[glyph.page as usize].last_used = self.frame;
+            }
+            return Some(*cached);
+        }
+
+        let glyph =
+            glyph_id.with_scale_and_position(PxScale::from(SDF_SIZE), ab_glyph::point(0.0, 0.0));
+        let outlined = match font.outline_glyph(glyph) {
+            Some(outlined) => outlined,
+            None => {
+                self.glyphs.insert((font_id, glyph_id), None);
+                return Some(None);
+            }
+        };
+        let bounds = outlined.px_bounds();
+        let (width, height) = (bounds.width().ceil() as u32, bounds.height().ceil() as u32);
+        if width == 0 || height == 0 {
+            self.glyphs.insert((font_id, glyph_id), None);
+            return Some(None);
+        }
+        let mut coverage = vec![0.0; (width * height) as usize];
+        outlined.draw(|x, y, c| {
+            if x < width && y < height {
+                coverage[(y * width + x) as usize] = c.clamp(0.0, 1.0);
+            }
+        });
+        let (field, field_width, field_height) =
+            distance_field(&coverage, width, height, SDF_SPREAD);
+
+        let (width, height) = (
+            field_width + GLYPH_PADDING * 2,
+            field_height + GLYPH_PADDING * 2,
+        );
+        let (page, x, y) = self.allocate(device, queue, width, height)?;
+        // The padding is cleared too, it may hold an evicted glyph.
+        let data = &mut self.pages[page as usize].data;
+        for row in 0..height {
+            let start = ((y + row) * SDF_PAGE_SIZE + x) as usize;
+            data[start..start + width as usize].fill(0);
+        }
+        for row in 0..field_height {
+            let start = ((y + GLYPH_PADDING + row) * SDF_PAGE_SIZE + x + GLYPH_PADDING) as usize;
+            let source = (row * field_width) as usize;
+            data[start..start + field_width as usize]
+                .copy_from_slice(&field[source..source + field_width as usize]);
+        }
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: page },
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: (y * SDF_PAGE_SIZE + x) as wgpu::BufferAddress,
+                bytes_per_row: NonZeroU32::new(SDF_PAGE_SIZE),
+                rows_per_image: NonZeroU32::new(SDF_PAGE_SIZE),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        let (x, y) = (x + GLYPH_PADDING, y + GLYPH_PADDING);
+
+        let spread = SDF_SPREAD as f32;
+        let cached = SdfGlyph {
+            page,
+            offset: [bounds.min.x - spread, bounds.min.y - spread],
+            size: [field_width as f32, field_height as f32],
+            uv_min: [
+                x as f32 / SDF_PAGE_SIZE as f32,
+                y as f32 / SDF_PAGE_SIZE as f32,
+            ],
+            uv_max: [
+                (x + field_width) as f32 / SDF_PAGE_SIZE as f32,
+                (y + field_height) as f32 / SDF_PAGE_SIZE as f32,
+            ],
+        };
+        self.glyphs.insert((font_id, glyph_id), Some(cached));
+        Some(Some(cached))
+    }
+
+    // Tries the pages in order, then a new page, then the least recently
+    // used page that this frame hasn't drawn from.
+    fn allocate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+    ) -> Option<(u32, u32, u32)> {
+        let frame = self.frame;
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if let Some((x, y)) = page.packer.allocate(width, height) {
+                page.last_used = frame;
+                return Some((index as u32, x, y));
+            }
+        }
+
+        let max_pages = MAX_PAGES.min(device.limits().max_texture_array_layers);
+        let index = if (self.pages.len() as u32) < max_pages {
+            self.pages.push(Page::new());
+            self.recreate(device, queue);
+            log::debug!("sdf atlas grew to {} pages", self.pages.len());
+            self.pages.len() - 1
+        } else {
+            let (index, _) = self
+                .pages
+                .iter()
+                .enumerate()
+                .filter(|(_, page)| page.last_used < frame)
+                .min_by_key(|(_, page)| page.last_used)?;
+            log::debug!("evicting sdf atlas page {}", index);
+            self.glyphs
+                .retain(|_, glyph| glyph.map_or(true, |glyph| glyph.page as usize != index));
+            let page = &mut self.pages[index];
+            page.packer.clear();
+            page.data.fill(0);
+            index
+        };
+        let page = &mut self.pages[index];
+        let (x, y) = page.packer.allocate(width, height)?;
+        page.last_used = frame;
+        Some((index as u32, x, y))
+    }
+
+    fn recreate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let (texture, bind_group) =
+            create_texture(device, &self.layout, &self.sampler, self.pages.len() as u32);
+        for (index, page) in self.pages.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: index as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &page.data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(SDF_PAGE_SIZE),
+                    rows_per_image: NonZeroU32::new(SDF_PAGE_SIZE),
+                },
+                wgpu::Extent3d {
+                    width: SDF_PAGE_SIZE,
+                    height: SDF_PAGE_SIZE,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        self.texture = texture;
+        self.bind_group = bind_group;
+    }
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    pages: u32,
+) -> (wgpu::Texture, wgpu::BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("sdf atlas"),
+        size: wgpu::Extent3d {
+            width: SDF_PAGE_SIZE,
+            height: SDF_PAGE_SIZE,
+            depth_or_array_layers: pages,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+    // A single layer would otherwise get a plain 2D view.
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("sdf atlas"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+    (texture, bind_group)
+}
@@ -0,0 +1,115 @@
+struct Globals {
+    time: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    delta_time: f32;
+    scale_factor: f32;
+    encode_srgb: u32;
+    premultiply: u32;
+    opacity: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> globals: Globals;
+
+[[group(1), binding(0)]]
+var t_sdf: texture_2d_array<f32>;
+[[group(1), binding(1)]]
+var s_sdf: sampler;
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] tex_coords: vec2<f32>;
+    [[location(2)]] color: vec4<f32>;
+    [[location(3)]] outline_color: vec4<f32>;
+    [[location(4)]] shadow_color: vec4<f32>;
+    // In texture coordinates.
+    [[location(5)]] shadow_offset: vec2<f32>;
+    // Outline width, shadow softness and how many pixels on screen the
+    // field's range from 0 to 1 covers.
+    [[location(6)]] params: vec3<f32>;
+    [[location(7)]] page: i32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+    [[location(2)]] outline_color: vec4<f32>;
+    [[location(3)]] shadow_color: vec4<f32>;
+    [[location(4)]] shadow_offset: vec2<f32>;
+    [[location(5)]] params: vec3<f32>;
+    [[location(6), interpolate(flat)]] page: i32;
+};
+
+// Pixel coordinates with the origin at the top-left corner.
+[[stage(vertex)]]
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = input.position / globals.resolution * 2.0 - 1.0;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.tex_coords = input.tex_coords;
+    out.color = input.color;
+    out.outline_color = input.outline_color;
+    out.shadow_color = input.shadow_color;
+    out.shadow_offset = input.shadow_offset;
+    out.params = input.params;
+    out.page = input.page;
+    return out;
+}
+
+// Targets that aren't sRGB store colors as written, so the linear output
+// is encoded here to look the same as on an sRGB target.
+fn encode_output(color: vec4<f32>) -> vec4<f32> {
+    if (globals.encode_srgb == 0u) {
+        return color;
+    }
+    let c = max(color.rgb, vec3<f32>(0.0));
+    let curve = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return vec4<f32>(select(curve, c * 12.92, c <= vec3<f32>(0.0031308)), color.a);
+}
+
+// The window's fade scales alpha, and the blend state of
+// `AlphaMode::Premultiplied` takes the color already multiplied by it.
+fn output_color(color: vec4<f32>) -> vec4<f32> {
+    let c = encode_output(vec4<f32>(color.rgb, color.a * globals.opacity));
+    if (globals.premultiply == 0u) {
+        return c;
+    }
+    return vec4<f32>(c.rgb * c.a, c.a);
+}
+
+fn premultiply(color: vec4<f32>, coverage: f32) -> vec4<f32> {
+    let alpha = color.a * coverage;
+    return vec4<f32>(color.rgb * alpha, alpha);
+}
+
+fn over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    return top + bottom * (1.0 - top.a);
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let distance = textureSample(t_sdf, s_sdf, input.tex_coords, input.page).r;
+    let shadow_distance =
+        textureSample(t_sdf, s_sdf, input.tex_coords - input.shadow_offset, input.page).r;
+    // Half a screen pixel in field units, so edges stay one pixel soft at
+    // any scale.
+    let smoothing = max(fwidth(distance) * 0.5, 0.0001);
+    let range = max(input.params.z, 0.0001);
+
+    let fill = smoothstep(0.5 - smoothing, 0.5 + smoothing, distance);
+    let outline_edge = 0.5 - input.params.x / range;
+    let outline = smoothstep(outline_edge - smoothing, outline_edge + smoothing, distance);
+    let softness = max(input.params.y / range, smoothing);
+    let shadow = smoothstep(0.5 - softness, 0.5 + softness, shadow_distance);
+
+    let color = over(
+        premultiply(input.color, fill),
+        over(premultiply(input.outline_color, outline), premultiply(input.shadow_color, shadow))
+    );
+    if (color.a <= 0.0) {
+        discard;
+    }
+    return output_color(vec4<f32>(color.rgb / color.a, color.a));
+}
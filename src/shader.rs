@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+// The shader built into the binary, used when no other is given or the
+// given one doesn't validate.
+pub const DEFAULT_SHADER: &str = include_str!("shader.wgsl");
+
+fn parse(source: &str) -> anyhow::Result<naga::Module> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| anyhow::anyhow!("{}", e.emit_to_string(source)))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| anyhow::anyhow!("{}", e.into_inner()))?;
+    Ok(module)
+}
+
+fn entry_point<'m>(
+    module: &'m naga::Module,
+    name: &str,
+    stage: naga::ShaderStage,
+) -> anyhow::Result<&'m naga::EntryPoint> {
+    module
+        .entry_points
+        .iter()
+        .find(|entry| entry.name == name && entry.stage == stage)
+        .ok_or_else(|| anyhow::anyhow!("the shader has no {:?} entry point `{}`", stage, name))
+}
+
+// Parses and validates WGSL with naga before wgpu sees it, so errors come
+// with line numbers instead of a validation panic, and checks for the entry
+// points the main pipeline uses.
+pub fn validate_shader(source: &str) -> anyhow::Result<()> {
+    let module = parse(source)?;
+    entry_point(&module, "vs_main", naga::ShaderStage::Vertex)?;
+    entry_point(&module, "fs_main", naga::ShaderStage::Fragment)?;
+    Ok(())
+}
+
+// Like `validate_shader`, also requiring a compute entry point, whose
+// workgroup width is returned. Dispatches are sized with it.
+pub(crate) fn validate_compute_shader(source: &str, entry: &str) -> anyhow::Result<u32> {
+    let module = parse(source)?;
+    entry_point(&module, "vs_main", naga::ShaderStage::Vertex)?;
+    entry_point(&module, "fs_main", naga::ShaderStage::Fragment)?;
+    let compute = entry_point(&module, entry, naga::ShaderStage::Compute)?;
+    let [width, height, depth] = compute.workgroup_size;
+    if height > 1 || depth > 1 {
+        anyhow::bail!(
+            "`{}` has a {}x{}x{} workgroup, only one dimension is dispatched",
+            entry,
+            width,
+            height,
+            depth
+        );
+    }
+    Ok(width.max(1))
+}
+
+// Reads and validates a WGSL file.
+pub fn load_shader(path: &Path) -> anyhow::Result<String> {
+    let source =
+        std::fs::read_to_string(path).with_context(|| format!("failed to read {:?}", path))?;
+    validate_shader(&source).with_context(|| format!("invalid shader {:?}", path))?;
+    Ok(source)
+}
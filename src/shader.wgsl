@@ -1,11 +1,55 @@
+struct Globals {
+    time: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    delta_time: f32;
+    scale_factor: f32;
+    encode_srgb: u32;
+    premultiply: u32;
+    opacity: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> globals: Globals;
+
+struct DrawParams {
+    color: vec4<f32>;
+    offset: vec2<f32>;
+};
+
+var<push_constant> draw_params: DrawParams;
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
 [[stage(vertex)]]
-fn vs_main([[builtin(vertex_index)]] in_vertex_index: u32) -> [[builtin(position)]] vec4<f32> {
-    let x = f32(i32(in_vertex_index) - 1);
-    let y = f32(i32(in_vertex_index & 1u) * 2 - 1);
-    return vec4<f32>(x, y, 0.0, 1.0);
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(input.position + draw_params.offset, 0.0, 1.0);
+    out.color = input.color * draw_params.color;
+    return out;
+}
+
+// Targets that aren't sRGB store colors as written, so the linear output
+// is encoded here to look the same as on an sRGB target.
+fn encode_output(color: vec4<f32>) -> vec4<f32> {
+    if (globals.encode_srgb == 0u) {
+        return color;
+    }
+    let c = max(color.rgb, vec3<f32>(0.0));
+    let curve = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return vec4<f32>(select(curve, c * 12.92, c <= vec3<f32>(0.0031308)), color.a);
 }
 
 [[stage(fragment)]]
-fn fs_main() -> [[location(0)]] vec4<f32> {
-    return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let pulse = 0.75 + 0.25 * sin(globals.time * 2.0);
+    return encode_output(vec4<f32>(input.color.rgb * pulse, input.color.a * globals.opacity));
 }
@@ -0,0 +1,85 @@
+use crate::{render::RenderContext, rounded_rect::CornerRadii, shapes::Rect, text::TextStyle};
+
+// A drop shadow, in pixels. The blur radius is twice the standard deviation
+// of the gaussian, as in CSS; zero gives a sharp silhouette. The color's
+// alpha is what the shadow adds to the window's, so it darkens the desktop
+// behind a transparent window too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    pub offset: [f32; 2],
+    pub blur_radius: f32,
+    pub color: [f32; 4],
+}
+
+impl Shadow {
+    pub const fn new(offset: [f32; 2], blur_radius: f32, color: [f32; 4]) -> Self {
+        Self {
+            offset,
+            blur_radius,
+            color,
+        }
+    }
+
+    pub(crate) fn offset_rect(&self, rect: Rect) -> Rect {
+        Rect::new(
+            rect.x + self.offset[0],
+            rect.y + self.offset[1],
+            rect.width,
+            rect.height,
+        )
+    }
+}
+
+// Draw calls that queue a shadow right before their shape, see
+// `RenderContext::with_shadow`.
+pub struct Shadowed<'a> {
+    pub(crate) ctx: &'a mut RenderContext,
+    pub(crate) shadow: Shadow,
+}
+
+impl<'a> Shadowed<'a> {
+    pub fn fill_rect(self, rect: Rect, color: [f32; 4]) {
+        self.ctx.draw_shadow(rect, 0.0, &self.shadow);
+        self.ctx.fill_rect(rect, color);
+    }
+
+    pub fn fill_rounded_rect(self, rect: Rect, radius: impl Into<CornerRadii>, color: [f32; 4]) {
+        let radius = radius.into();
+        self.ctx.draw_shadow(rect, radius, &self.shadow);
+        self.ctx.fill_rounded_rect(rect, radius, color);
+    }
+
+    pub fn fill_rounded_rect_with_border(
+        self,
+        rect: Rect,
+        radius: impl Into<CornerRadii>,
+        color: [f32; 4],
+        border_width: f32,
+        border_color: [f32; 4],
+    ) {
+        let radius = radius.into();
+        self.ctx.draw_shadow(rect, radius, &self.shadow);
+        self.ctx
+            .fill_rounded_rect_with_border(rect, radius, color, border_width, border_color);
+    }
+
+    // Without blur the glyphs are drawn again offset in the shadow's color.
+    // A blurred shadow comes from distance field text, which limits how far
+    // it reaches, see `TextStyle`.
+    pub fn queue_text(self, text: &str, position: [f32; 2], size: f32, color: [f32; 4]) {
+        let Shadow {
+            offset,
+            blur_radius,
+            color: shadow_color,
+        } = self.shadow;
+        if blur_radius <= 0.0 {
+            let shadow_position = [position[0] + offset[0], position[1] + offset[1]];
+            self.ctx
+                .queue_text(text, shadow_position, size, shadow_color);
+            self.ctx.queue_text(text, position, size, color);
+        } else {
+            let style = TextStyle::new(color).shadow(offset, blur_radius, shadow_color);
+            self.ctx.queue_styled_text(text, position, size, &style);
+        }
+    }
+}
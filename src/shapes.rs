@@ -0,0 +1,236 @@
+use std::{borrow::Cow, f32::consts::PI, ops::Range, sync::Arc};
+
+use crate::{
+    buffer::DynamicBuffer,
+    mesh::GpuMesh,
+    pipeline::{PipelineCache, ShaderId, TargetConfig},
+    vertex::Vertex,
+};
+
+// Largest distance, in pixels, a circle's polygon may stray from the circle.
+const CIRCLE_TOLERANCE: f32 = 0.25;
+
+// In pixels, with the origin at the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // Half-open, so rects sharing an edge don't both contain it.
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        point[0] >= self.x
+            && point[0] < self.x + self.width
+            && point[1] >= self.y
+            && point[1] < self.y + self.height
+    }
+}
+
+fn circle_segments(radius: f32) -> u32 {
+    if radius <= CIRCLE_TOLERANCE {
+        return 8;
+    }
+    let step = (1.0 - CIRCLE_TOLERANCE / radius).acos() * 2.0;
+    ((2.0 * PI / step).ceil() as u32).clamp(8, 256)
+}
+
+#[derive(Clone)]
+enum Batch {
+    Vertices(Range<u32>),
+    Mesh(Arc<GpuMesh>),
+}
+
+// Immediate-mode shapes, collected into one vertex buffer. Like queued text,
+// shapes only last for the next frame. Uploaded meshes share the pipeline
+// and are kept in queue order with the shapes around them.
+pub(crate) struct ShapeRenderer {
+    vertices: Vec<Vertex>,
+    queued: Vec<Batch>,
+    batches: Vec<Batch>,
+    // Set by `mark` so the next shape starts a batch of its own.
+    sealed: bool,
+    shader: ShaderId,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+}
+
+impl ShapeRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+        globals_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("shapes"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shapes.wgsl"))),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shapes"),
+            bind_group_layouts: &[globals_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = cache.add_shader("shapes", shader, pipeline_layout);
+        let pipeline = cache.get_or_create(device, shader, &Self::buffers(), target);
+
+        let vertex_buffer =
+            DynamicBuffer::new(device, "shape vertices", wgpu::BufferUsages::VERTEX, 0);
+
+        Self {
+            vertices: Vec::new(),
+            queued: Vec::new(),
+            batches: Vec::new(),
+            sealed: false,
+            shader,
+            pipeline,
+            vertex_buffer,
+        }
+    }
+
+    fn buffers() -> [wgpu::VertexBufferLayout<'static>; 1] {
+        [Vertex::layout()]
+    }
+
+    // Switches to the pipeline for a new target format or sample count.
+    pub(crate) fn retarget(
+        &mut self,
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+    ) {
+        self.pipeline = cache.get_or_create(device, self.shader, &Self::buffers(), target);
+    }
+
+    fn triangle(&mut self, a: [f32; 2], b: [f32; 2], c: [f32; 2], color: [f32; 4]) {
+        let start = self.vertices.len() as u32;
+        self.vertices.extend([
+            Vertex::new(a, color),
+            Vertex::new(b, color),
+            Vertex::new(c, color),
+        ]);
+        let end = self.vertices.len() as u32;
+        match self.queued.last_mut() {
+            Some(Batch::Vertices(range)) if !self.sealed => range.end = end,
+            _ => self.queued.push(Batch::Vertices(start..end)),
+        }
+        self.sealed = false;
+    }
+
+    fn quad(&mut self, corners: [[f32; 2]; 4], color: [f32; 4]) {
+        self.triangle(corners[0], corners[1], corners[2], color);
+        self.triangle(corners[0], corners[2], corners[3], color);
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, color: [f32; 4]) {
+        let (x0, y0) = (rect.x, rect.y);
+        let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+        self.quad([[x0, y0], [x1, y0], [x1, y1], [x0, y1]], color);
+    }
+
+    // The stroke lies inside `rect`; the edges don't overlap, so
+    // translucent colors stay even.
+    pub fn stroke_rect(&mut self, rect: Rect, width: f32, color: [f32; 4]) {
+        let width = width.min(rect.width / 2.0).min(rect.height / 2.0);
+        let inner = rect.height - 2.0 * width;
+        self.fill_rect(Rect::new(rect.x, rect.y, rect.width, width), color);
+        self.fill_rect(
+            Rect::new(rect.x, rect.y + rect.height - width, rect.width, width),
+            color,
+        );
+        self.fill_rect(Rect::new(rect.x, rect.y + width, width, inner), color);
+        self.fill_rect(
+            Rect::new(rect.x + rect.width - width, rect.y + width, width, inner),
+            color,
+        );
+    }
+
+    pub fn fill_circle(&mut self, center: [f32; 2], radius: f32, color: [f32; 4]) {
+        let segments = circle_segments(radius);
+        let point = |i: u32| {
+            let angle = i as f32 / segments as f32 * 2.0 * PI;
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        };
+        for i in 0..segments {
+            self.triangle(center, point(i), point(i + 1), color);
+        }
+    }
+
+    // A `width` pixel wide quad centered on the segment, with butt caps.
+    pub fn line(&mut self, p0: [f32; 2], p1: [f32; 2], width: f32, color: [f32; 4]) {
+        let d = [p1[0] - p0[0], p1[1] - p0[1]];
+        let length = (d[0] * d[0] + d[1] * d[1]).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let n = [-d[1] / length * width / 2.0, d[0] / length * width / 2.0];
+        self.quad(
+            [
+                [p0[0] + n[0], p0[1] + n[1]],
+                [p1[0] + n[0], p1[1] + n[1]],
+                [p1[0] - n[0], p1[1] - n[1]],
+                [p0[0] - n[0], p0[1] - n[1]],
+            ],
+            color,
+        );
+    }
+
+    pub fn draw_mesh(&mut self, mesh: Arc<GpuMesh>) {
+        self.queued.push(Batch::Mesh(mesh));
+    }
+
+    // The number of batches queued so far, for drawing a part of the next
+    // frame's shapes with `render_range`.
+    pub(crate) fn mark(&mut self) -> u32 {
+        self.sealed = true;
+        self.queued.len() as u32
+    }
+
+    pub(crate) fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.vertex_buffer
+            .write(device, queue, bytemuck::cast_slice(&self.vertices));
+        self.vertices.clear();
+        self.batches.clear();
+        std::mem::swap(&mut self.batches, &mut self.queued);
+        self.sealed = false;
+    }
+
+    pub(crate) fn render_range<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        globals: &'a wgpu::BindGroup,
+        batches: Range<u32>,
+    ) {
+        let batches = &self.batches[batches.start as usize..batches.end as usize];
+        if batches.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals, &[]);
+        for batch in batches {
+            match batch {
+                Batch::Vertices(range) => {
+                    rpass.set_vertex_buffer(0, self.vertex_buffer.slice());
+                    rpass.draw(range.clone(), 0..1);
+                }
+                Batch::Mesh(mesh) => mesh.render(rpass),
+            }
+        }
+    }
+}
@@ -0,0 +1,633 @@
+use std::{borrow::Cow, collections::HashMap, num::NonZeroU32, ops::Range, path::Path, sync::Arc};
+
+use anyhow::Context;
+
+use crate::{
+    buffer::DynamicBuffer,
+    color::premultiply_rgba8,
+    nine_patch::NinePatch,
+    packer::ShelfPacker,
+    pipeline::{PipelineCache, ShaderId, TargetConfig},
+    shapes::Rect,
+    texture::Texture,
+};
+
+// The atlas starts at this size and doubles whenever a sprite doesn't fit.
+const INITIAL_ATLAS_SIZE: u32 = 256;
+// Border around every sprite, filled with its edge pixels so linear
+// filtering at the edge doesn't pick up the neighbours.
+const SPRITE_PADDING: u32 = 1;
+const VERTICES_PER_SPRITE: u32 = 6;
+
+// A sprite added to the context's atlas, see `RenderContext::add_sprite`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpriteId(pub(crate) usize);
+
+// CPU copies are kept to pack the sprites again when the atlas grows and to
+// restore them on a new device.
+pub(crate) struct SpriteSource {
+    pub(crate) name: String,
+    pub(crate) image: image::RgbaImage,
+    // Top-left corner inside the padding.
+    origin: (u32, u32),
+    // Room allocated for it without the padding, more than the image when
+    // a smaller one replaced it.
+    slot: (u32, u32),
+}
+
+// The sprite with its edge pixels repeated into the padding.
+fn padded(image: &image::RgbaImage) -> (Vec<u8>, u32, u32) {
+    let (width, height) = image.dimensions();
+    let (padded_width, padded_height) = (width + SPRITE_PADDING * 2, height + SPRITE_PADDING * 2);
+    let mut data = Vec::with_capacity((padded_width * padded_height * 4) as usize);
+    for y in 0..padded_height {
+        let sy = y.saturating_sub(SPRITE_PADDING).min(height - 1);
+        for x in 0..padded_width {
+            let sx = x.saturating_sub(SPRITE_PADDING).min(width - 1);
+            data.extend_from_slice(&image.get_pixel(sx, sy).0);
+        }
+    }
+    (data, padded_width, padded_height)
+}
+
+// sRGB targets get an sRGB view so blending happens on linear values;
+// other targets take the encoded values as they are.
+pub(crate) fn atlas_format(target: &TargetConfig) -> wgpu::TextureFormat {
+    if target.format.describe().srgb {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    }
+}
+
+// Images packed onto shelves in one texture, so any number of sprites draw
+// with a single bind group.
+pub struct TextureAtlas {
+    sprites: Vec<SpriteSource>,
+    names: HashMap<String, SpriteId>,
+    packer: ShelfPacker,
+    format: wgpu::TextureFormat,
+    // Texels are multiplied by alpha on upload, for premultiplied
+    // pipelines.
+    premultiplied: bool,
+    texture: wgpu::Texture,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TextureAtlas {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, premultiplied: bool) -> Self {
+        let layout = Texture::bind_group_layout(device);
+        let texture = create_texture(device, format, INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE);
+        let bind_group = create_bind_group(device, &layout, &texture);
+        Self {
+            sprites: Vec::new(),
+            names: HashMap::new(),
+            packer: ShelfPacker::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE),
+            format,
+            premultiplied,
+            texture,
+            layout,
+            bind_group,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    // The atlas size in texels.
+    pub fn size(&self) -> (u32, u32) {
+        self.packer.size()
+    }
+
+    pub fn get(&self, name: &str) -> Option<SpriteId> {
+        self.names.get(name).copied()
+    }
+
+    pub(crate) fn sources(&self) -> &[SpriteSource] {
+        &self.sprites
+    }
+
+    // In pixels.
+    pub fn sprite_size(&self, id: SpriteId) -> Option<(u32, u32)> {
+        Some(self.sprites.get(id.0)?.image.dimensions())
+    }
+
+    // Where the sprite is in the atlas, in texture coordinates from 0 to 1.
+    // Changes when the atlas grows.
+    pub fn uv_rect(&self, id: SpriteId) -> Option<Rect> {
+        let (width, height) = self.sprite_size(id)?;
+        self.region_uv_rect(id, Rect::new(0.0, 0.0, width as f32, height as f32))
+    }
+
+    // Like `uv_rect` for part of the sprite, given in its pixels.
+    pub fn region_uv_rect(&self, id: SpriteId, region: Rect) -> Option<Rect> {
+        let sprite = self.sprites.get(id.0)?;
+        let (atlas_width, atlas_height) = self.packer.size();
+        Some(Rect::new(
+            (sprite.origin.0 as f32 + region.x) / atlas_width as f32,
+            (sprite.origin.1 as f32 + region.y) / atlas_height as f32,
+            region.width / atlas_width as f32,
+            region.height / atlas_height as f32,
+        ))
+    }
+
+    // A name added before is pointed at the new sprite.
+    fn add(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+        image: image::RgbaImage,
+    ) -> anyhow::Result<SpriteId> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            anyhow::bail!("sprite {:?} is empty", name);
+        }
+        let padded = (width + SPRITE_PADDING * 2, height + SPRITE_PADDING * 2);
+        let mut sprite = SpriteSource {
+            name: name.to_string(),
+            image,
+            origin: (0, 0),
+            slot: (width, height),
+        };
+        match self.packer.allocate(padded.0, padded.1) {
+            Some((x, y)) => {
+                sprite.origin = (x + SPRITE_PADDING, y + SPRITE_PADDING);
+                upload(
+                    queue,
+                    &self.texture,
+                    self.format,
+                    self.premultiplied,
+                    &sprite,
+                );
+                self.sprites.push(sprite);
+            }
+            None => {
+                self.sprites.push(sprite);
+                if let Err(e) = self.grow(device, queue) {
+                    self.sprites.pop();
+                    return Err(e.context(format!("no room for sprite {:?}", name)));
+                }
+            }
+        }
+        let id = SpriteId(self.sprites.len() - 1);
+        self.names.insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    // Gives a sprite a new image, in place when it fits the room the old
+    // one had.
+    fn replace(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        id: SpriteId,
+        image: image::RgbaImage,
+    ) -> anyhow::Result<()> {
+        let (width, height) = image.dimensions();
+        let sprite = match self.sprites.get_mut(id.0) {
+            Some(sprite) => sprite,
+            None => anyhow::bail!("unknown sprite {:?}", id),
+        };
+        if width == 0 || height == 0 {
+            anyhow::bail!("sprite {:?} is empty", sprite.name);
+        }
+        let old = std::mem::replace(&mut sprite.image, image);
+        if width <= sprite.slot.0 && height <= sprite.slot.1 {
+            upload(
+                queue,
+                &self.texture,
+                self.format,
+                self.premultiplied,
+                sprite,
+            );
+            return Ok(());
+        }
+        let padded = (width + SPRITE_PADDING * 2, height + SPRITE_PADDING * 2);
+        if let Some((x, y)) = self.packer.allocate(padded.0, padded.1) {
+            sprite.origin = (x + SPRITE_PADDING, y + SPRITE_PADDING);
+            sprite.slot = (width, height);
+            upload(
+                queue,
+                &self.texture,
+                self.format,
+                self.premultiplied,
+                sprite,
+            );
+            return Ok(());
+        }
+        // Packing everything again also gives back the room the old image
+        // had.
+        if let Err(e) = self.grow(device, queue) {
+            let sprite = &mut self.sprites[id.0];
+            sprite.image = old;
+            return Err(e.context(format!("no room for sprite {:?}", sprite.name)));
+        }
+        Ok(())
+    }
+
+    // Packs every sprite again into the smallest doubling of the current
+    // size that holds them all, then uploads them to a new texture.
+    fn grow(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<()> {
+        let max = device.limits().max_texture_dimension_2d;
+        let (mut width, mut height) = self.packer.size();
+        loop {
+            if width <= height {
+                width *= 2;
+            } else {
+                height *= 2;
+            }
+            if width > max || height > max {
+                anyhow::bail!(
+                    "the atlas would be larger than the device's limit of {}",
+                    max
+                );
+            }
+            if let Some((packer, origins)) = self.pack(width, height) {
+                log::debug!(
+                    "growing the sprite atlas to {}x{} for {} sprites",
+                    width,
+                    height,
+                    self.sprites.len()
+                );
+                for (sprite, origin) in self.sprites.iter_mut().zip(origins) {
+                    sprite.origin = origin;
+                    sprite.slot = sprite.image.dimensions();
+                }
+                self.packer = packer;
+                self.recreate(device, queue, width, height);
+                return Ok(());
+            }
+        }
+    }
+
+    // Tallest first, which leaves less room unused on the shelves.
+    fn pack(&self, width: u32, height: u32) -> Option<(ShelfPacker, Vec<(u32, u32)>)> {
+        let mut packer = ShelfPacker::new(width, height);
+        let mut order: Vec<_> = (0..self.sprites.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(self.sprites[index].image.height()));
+        let mut origins = vec![(0, 0); self.sprites.len()];
+        for index in order {
+            let (w, h) = self.sprites[index].image.dimensions();
+            let (x, y) = packer.allocate(w + SPRITE_PADDING * 2, h + SPRITE_PADDING * 2)?;
+            origins[index] = (x + SPRITE_PADDING, y + SPRITE_PADDING);
+        }
+        Some((packer, origins))
+    }
+
+    fn recreate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.texture = create_texture(device, self.format, width, height);
+        self.bind_group = create_bind_group(device, &self.layout, &self.texture);
+        for sprite in &self.sprites {
+            upload(
+                queue,
+                &self.texture,
+                self.format,
+                self.premultiplied,
+                sprite,
+            );
+        }
+    }
+}
+
+fn upload(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    premultiplied: bool,
+    sprite: &SpriteSource,
+) {
+    let (mut data, width, height) = padded(&sprite.image);
+    if premultiplied {
+        premultiply_rgba8(&mut data, format.describe().srgb);
+    }
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: sprite.origin.0 - SPRITE_PADDING,
+                y: sprite.origin.1 - SPRITE_PADDING,
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        &data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(width * 4),
+            rows_per_image: NonZeroU32::new(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("sprite atlas"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &wgpu::Texture,
+) -> wgpu::BindGroup {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("sprite atlas"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("sprite atlas"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    })
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    tint: [f32; 4],
+}
+
+impl SpriteVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+struct QueuedSprite {
+    id: SpriteId,
+    // In the sprite's pixels, all of it if `None`.
+    region: Option<Rect>,
+    dest: Rect,
+    tint: [f32; 4],
+}
+
+// Sprites queued for the next frame. Their texture coordinates are looked
+// up when the frame is prepared, after any growth of the atlas.
+pub(crate) struct SpriteRenderer {
+    atlas: TextureAtlas,
+    queued: Vec<QueuedSprite>,
+    shader: ShaderId,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+}
+
+impl SpriteRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+        globals_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let atlas = TextureAtlas::new(
+            device,
+            atlas_format(target),
+            target.alpha_mode.is_premultiplied(),
+        );
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("sprite"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("sprite.wgsl"))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sprite"),
+            bind_group_layouts: &[globals_layout, &atlas.layout],
+            push_constant_ranges: &[],
+        });
+        let shader = cache.add_shader("sprite", shader, pipeline_layout);
+        let pipeline = cache.get_or_create(device, shader, &Self::buffers(), target);
+
+        let vertex_buffer =
+            DynamicBuffer::new(device, "sprite vertices", wgpu::BufferUsages::VERTEX, 0);
+
+        Self {
+            atlas,
+            queued: Vec::new(),
+            shader,
+            pipeline,
+            vertex_buffer,
+        }
+    }
+
+    fn buffers() -> [wgpu::VertexBufferLayout<'static>; 1] {
+        [SpriteVertex::layout()]
+    }
+
+    // Switches to the pipeline for a new target format or sample count.
+    pub(crate) fn retarget(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+    ) {
+        self.pipeline = cache.get_or_create(device, self.shader, &Self::buffers(), target);
+        let format = atlas_format(target);
+        let premultiplied = target.alpha_mode.is_premultiplied();
+        if format != self.atlas.format || premultiplied != self.atlas.premultiplied {
+            self.atlas.format = format;
+            self.atlas.premultiplied = premultiplied;
+            let (width, height) = self.atlas.size();
+            self.atlas.recreate(device, queue, width, height);
+        }
+    }
+
+    pub(crate) fn atlas(&self) -> &TextureAtlas {
+        &self.atlas
+    }
+
+    pub(crate) fn add_sprite(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+        image: image::RgbaImage,
+    ) -> anyhow::Result<SpriteId> {
+        self.atlas.add(device, queue, name, image)
+    }
+
+    pub(crate) fn replace_sprite(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        id: SpriteId,
+        image: image::RgbaImage,
+    ) -> anyhow::Result<()> {
+        self.atlas.replace(device, queue, id, image)
+    }
+
+    // Every PNG in `dir`, named by its file name without the extension.
+    pub(crate) fn load_dir(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dir: &Path,
+    ) -> anyhow::Result<HashMap<String, SpriteId>> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read {:?}", dir))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("png"))
+            })
+            .collect();
+        // Sorted so the ids don't depend on the directory order.
+        paths.sort();
+        let mut sprites = HashMap::new();
+        for path in paths {
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let image = image::open(&path)
+                .with_context(|| format!("failed to load {:?}", path))?
+                .to_rgba8();
+            let id = self.add_sprite(device, queue, &name, image)?;
+            sprites.insert(name, id);
+        }
+        log::info!("loaded {} sprites from {:?}", sprites.len(), dir);
+        Ok(sprites)
+    }
+
+    pub(crate) fn draw(&mut self, id: SpriteId, dest: Rect, tint: [f32; 4]) {
+        self.draw_region(id, None, dest, tint);
+    }
+
+    pub(crate) fn draw_region(
+        &mut self,
+        id: SpriteId,
+        region: Option<Rect>,
+        dest: Rect,
+        tint: [f32; 4],
+    ) {
+        if id.0 >= self.atlas.len() {
+            log::warn!("unknown sprite {:?}", id);
+            return;
+        }
+        self.queued.push(QueuedSprite {
+            id,
+            region,
+            dest,
+            tint,
+        });
+    }
+
+    // Queued like separate sprites, so they batch with the ones around them.
+    pub(crate) fn draw_nine_patch(&mut self, patch: &NinePatch, dest: Rect) {
+        let size = match self.atlas.sprite_size(patch.sprite) {
+            Some(size) => size,
+            None => {
+                log::warn!("unknown sprite {:?}", patch.sprite);
+                return;
+            }
+        };
+        for (region, dest) in patch.slices(size, dest) {
+            self.draw_region(patch.sprite, Some(region), dest, patch.tint);
+        }
+    }
+
+    // The number of sprites queued so far.
+    pub(crate) fn mark(&self) -> u32 {
+        self.queued.len() as u32
+    }
+
+    pub(crate) fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let mut vertices = Vec::with_capacity(self.queued.len() * VERTICES_PER_SPRITE as usize);
+        for sprite in &self.queued {
+            let (dest, tint) = (sprite.dest, sprite.tint);
+            // Ids are checked when queued and sprites are never removed.
+            let uv = match sprite.region {
+                Some(region) => self.atlas.region_uv_rect(sprite.id, region),
+                None => self.atlas.uv_rect(sprite.id),
+            }
+            .unwrap_or(Rect::new(0.0, 0.0, 0.0, 0.0));
+            let vertex = |x: f32, y: f32| SpriteVertex {
+                position: [dest.x + dest.width * x, dest.y + dest.height * y],
+                tex_coords: [uv.x + uv.width * x, uv.y + uv.height * y],
+                tint,
+            };
+            vertices.extend([
+                vertex(0.0, 0.0),
+                vertex(1.0, 0.0),
+                vertex(1.0, 1.0),
+                vertex(0.0, 0.0),
+                vertex(1.0, 1.0),
+                vertex(0.0, 1.0),
+            ]);
+        }
+        self.vertex_buffer
+            .write(device, queue, bytemuck::cast_slice(&vertices));
+        self.queued.clear();
+    }
+
+    pub(crate) fn render_range<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        globals: &'a wgpu::BindGroup,
+        sprites: Range<u32>,
+    ) {
+        if sprites.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals, &[]);
+        rpass.set_bind_group(1, &self.atlas.bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice());
+        rpass.draw(
+            sprites.start * VERTICES_PER_SPRITE..sprites.end * VERTICES_PER_SPRITE,
+            0..1,
+        );
+    }
+}
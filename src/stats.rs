@@ -0,0 +1,245 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+// Number of frames kept for the rolling statistics.
+const HISTORY: usize = 120;
+
+// CPU time spent in each phase of one frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTimings {
+    // Waiting in `get_current_texture`.
+    pub acquire: Duration,
+    // Recording commands between acquire and submit.
+    pub encode: Duration,
+    pub submit: Duration,
+    pub present: Duration,
+}
+
+impl FrameTimings {
+    pub fn total(&self) -> Duration {
+        self.acquire + self.encode + self.submit + self.present
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    history: VecDeque<FrameTimings>,
+    frames: u64,
+    last_frame: Option<Instant>,
+    // Wall-clock time between the starts of consecutive frames.
+    intervals: VecDeque<Duration>,
+    scale_factor: f64,
+    frame_rate_limit: Option<u32>,
+    // wgpu errors caught while creating the renderers and drawing.
+    gpu_errors: u64,
+    // While the window is occluded or minimized, and for how long it was
+    // before.
+    suspended_since: Option<Instant>,
+    suspended_for: Duration,
+    // Redraws dropped meanwhile.
+    skipped: u64,
+    // Scene changes that needed no redraw, being on screen already or
+    // changing nothing.
+    redundant: u64,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            history: VecDeque::with_capacity(HISTORY),
+            frames: 0,
+            last_frame: None,
+            intervals: VecDeque::with_capacity(HISTORY),
+            scale_factor: 1.0,
+            frame_rate_limit: None,
+            gpu_errors: 0,
+            suspended_since: None,
+            suspended_for: Duration::ZERO,
+            skipped: 0,
+            redundant: 0,
+        }
+    }
+}
+
+fn push<T>(queue: &mut VecDeque<T>, value: T) {
+    if queue.len() == HISTORY {
+        queue.pop_front();
+    }
+    queue.push_back(value);
+}
+
+impl FrameStats {
+    pub(crate) fn record(&mut self, started: Instant, timings: FrameTimings) {
+        if let Some(last) = self.last_frame {
+            push(&mut self.intervals, started - last);
+        }
+        self.last_frame = Some(started);
+        push(&mut self.history, timings);
+        self.frames += 1;
+    }
+
+    pub(crate) fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    // Of the window the frames were drawn for.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    pub(crate) fn set_frame_rate_limit(&mut self, limit: Option<u32>) {
+        self.frame_rate_limit = limit;
+    }
+
+    // The cap `fps` is held to, if any.
+    pub fn frame_rate_limit(&self) -> Option<u32> {
+        self.frame_rate_limit
+    }
+
+    pub(crate) fn add_gpu_errors(&mut self, count: u64) {
+        self.gpu_errors += count;
+    }
+
+    pub fn gpu_errors(&self) -> u64 {
+        self.gpu_errors
+    }
+
+    pub(crate) fn set_suspended(&mut self, suspended: bool, at: Instant) {
+        match (suspended, self.suspended_since) {
+            (true, None) => self.suspended_since = Some(at),
+            (false, Some(since)) => {
+                self.suspended_for += at.saturating_duration_since(since);
+                self.suspended_since = None;
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn add_skipped(&mut self) {
+        self.skipped += 1;
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_since.is_some()
+    }
+
+    // Total time spent occluded or minimized, so far.
+    pub fn suspended_for(&self) -> Duration {
+        let current = self
+            .suspended_since
+            .map_or(Duration::ZERO, |since| since.elapsed());
+        self.suspended_for + current
+    }
+
+    // Redraws skipped while suspended.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    pub(crate) fn add_redundant(&mut self) {
+        self.redundant += 1;
+    }
+
+    // Redraws left out as the scene's generation was presented already.
+    pub fn redundant(&self) -> u64 {
+        self.redundant
+    }
+
+    // Total number of frames presented.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn last(&self) -> Option<FrameTimings> {
+        self.history.back().copied()
+    }
+
+    pub fn average(&self) -> FrameTimings {
+        let n = self.history.len().max(1) as u32;
+        let sum = self
+            .history
+            .iter()
+            .fold(FrameTimings::default(), |sum, t| FrameTimings {
+                acquire: sum.acquire + t.acquire,
+                encode: sum.encode + t.encode,
+                submit: sum.submit + t.submit,
+                present: sum.present + t.present,
+            });
+        FrameTimings {
+            acquire: sum.acquire / n,
+            encode: sum.encode / n,
+            submit: sum.submit / n,
+            present: sum.present / n,
+        }
+    }
+
+    // Percentile of the total CPU frame time, `p` in 0..=100.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let mut totals: Vec<_> = self.history.iter().map(FrameTimings::total).collect();
+        if totals.is_empty() {
+            return Duration::ZERO;
+        }
+        totals.sort_unstable();
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * (totals.len() - 1) as f64).round() as usize;
+        totals[rank]
+    }
+
+    // Frames per second over the recent history, based on wall-clock time
+    // between frames rather than on CPU time.
+    pub fn fps(&self) -> f64 {
+        let elapsed: Duration = self.intervals.iter().sum();
+        if elapsed.is_zero() {
+            0.0
+        } else {
+            self.intervals.len() as f64 / elapsed.as_secs_f64()
+        }
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let avg = self.average();
+        write!(f, "{:.1} fps", self.fps())?;
+        if let Some(limit) = self.frame_rate_limit {
+            write!(f, " (limit {})", limit)?;
+        }
+        write!(
+            f,
+            ", frame {:.2}ms (p50 {:.2}ms, p99 {:.2}ms): acquire {:.2}ms, encode {:.2}ms, submit {:.2}ms, present {:.2}ms, scale {}",
+            ms(avg.total()),
+            ms(self.percentile(50.0)),
+            ms(self.percentile(99.0)),
+            ms(avg.acquire),
+            ms(avg.encode),
+            ms(avg.submit),
+            ms(avg.present),
+            self.scale_factor,
+        )?;
+        if self.gpu_errors > 0 {
+            write!(f, ", {} GPU errors", self.gpu_errors)?;
+        }
+        if self.is_suspended() {
+            write!(f, ", suspended")?;
+        }
+        let suspended_for = self.suspended_for();
+        if !suspended_for.is_zero() {
+            write!(
+                f,
+                ", {} frames skipped while occluded ({:.1}s)",
+                self.skipped,
+                suspended_for.as_secs_f64()
+            )?;
+        }
+        if self.redundant > 0 {
+            write!(f, ", {} redundant redraws skipped", self.redundant)?;
+        }
+        Ok(())
+    }
+}
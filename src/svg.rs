@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Context;
+
+use crate::sprite::{SpriteId, SpriteRenderer};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// A parsed SVG document, rasterized into the sprite atlas at the pixel size
+// it's drawn at, see `RenderContext::draw_svg`. Clones share the document
+// and its rasterizations.
+#[derive(Clone)]
+pub struct Svg {
+    id: usize,
+    tree: Arc<usvg::Tree>,
+}
+
+impl std::fmt::Debug for Svg {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Svg")
+            .field("id", &self.id)
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
+impl Svg {
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+        Self::from_data(&data).with_context(|| format!("failed to load {:?}", path))
+    }
+
+    // SVG source or gzip-compressed SVGZ.
+    pub fn from_data(data: &[u8]) -> anyhow::Result<Self> {
+        let tree = usvg::Tree::from_data(data, &usvg::Options::default().to_ref())
+            .context("not a valid SVG document")?;
+        Ok(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            tree: Arc::new(tree),
+        })
+    }
+
+    // The size the document asks for, in its own units.
+    pub fn size(&self) -> (f32, f32) {
+        let size = self.tree.svg_node().size;
+        (size.width() as f32, size.height() as f32)
+    }
+
+    // The pixel size that fits `width` by `height` keeping the aspect ratio.
+    pub(crate) fn fit(&self, width: f32, height: f32) -> (u32, u32) {
+        let (svg_width, svg_height) = self.size();
+        let scale = (width / svg_width).min(height / svg_height).max(0.0);
+        (
+            (svg_width * scale).round() as u32,
+            (svg_height * scale).round() as u32,
+        )
+    }
+
+    fn rasterize(&self, (width, height): (u32, u32)) -> anyhow::Result<image::RgbaImage> {
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)
+            .with_context(|| format!("cannot rasterize an SVG at {}x{}", width, height))?;
+        resvg::render(
+            &self.tree,
+            usvg::FitTo::Size(width, height),
+            pixmap.as_mut(),
+        )
+        .context("failed to render the SVG")?;
+        // tiny-skia keeps premultiplied colors, the sprite shader blends
+        // straight ones.
+        let mut data = pixmap.take();
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u32;
+            if alpha > 0 && alpha < 255 {
+                for channel in &mut pixel[..3] {
+                    *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+                }
+            }
+        }
+        Ok(image::RgbaImage::from_raw(width, height, data).expect("the pixmap is width * height"))
+    }
+}
+
+struct Raster {
+    size: (u32, u32),
+    sprite: SpriteId,
+    last_used: u32,
+}
+
+// The atlas sprites each SVG was rasterized into. A new size reuses a
+// sprite no draw needed this frame, so a scale factor change re-rasterizes
+// in place rather than piling up sizes that are never drawn again.
+#[derive(Default)]
+pub(crate) struct SvgCache {
+    rasters: HashMap<usize, Vec<Raster>>,
+}
+
+impl SvgCache {
+    pub(crate) fn sprite(
+        &mut self,
+        sprites: &mut SpriteRenderer,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        svg: &Svg,
+        size: (u32, u32),
+        frame: u32,
+    ) -> anyhow::Result<SpriteId> {
+        let rasters = self.rasters.entry(svg.id).or_default();
+        if let Some(raster) = rasters.iter_mut().find(|raster| raster.size == size) {
+            raster.last_used = frame;
+            return Ok(raster.sprite);
+        }
+        let image = svg.rasterize(size)?;
+        if let Some(raster) = rasters.iter_mut().find(|raster| raster.last_used != frame) {
+            sprites.replace_sprite(device, queue, raster.sprite, image)?;
+            raster.size = size;
+            raster.last_used = frame;
+            return Ok(raster.sprite);
+        }
+        let name = format!("svg-{}-{}", svg.id, rasters.len());
+        let sprite = sprites.add_sprite(device, queue, &name, image)?;
+        rasters.push(Raster {
+            size,
+            sprite,
+            last_used: frame,
+        });
+        Ok(sprite)
+    }
+}
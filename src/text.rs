@@ -0,0 +1,837 @@
+use std::{borrow::Cow, collections::HashMap, num::NonZeroU32, ops::Range, path::Path, sync::Arc};
+
+use ab_glyph::{Font, FontArc, Glyph, GlyphId, PxScale, ScaleFont};
+use anyhow::Context;
+
+use crate::{
+    buffer::DynamicBuffer,
+    emoji::{self, EmojiRenderer},
+    packer::ShelfPacker,
+    pipeline::{PipelineCache, ShaderId, TargetConfig},
+    sdf::{SdfAtlas, SdfGlyph, SDF_PAGE_SIZE, SDF_SIZE, SDF_SPREAD},
+};
+
+const DEFAULT_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+
+const ATLAS_SIZE: u32 = 1024;
+
+// Ends text cut off by `wrap_text`.
+const ELLIPSIS: char = '\u{2026}';
+
+// Empty border kept around every glyph so linear filtering doesn't pick up
+// its neighbours.
+const GLYPH_PADDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+impl FontId {
+    pub const DEFAULT: FontId = FontId(0);
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+impl TextVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SdfVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+    outline_color: [f32; 4],
+    shadow_color: [f32; 4],
+    shadow_offset: [f32; 2],
+    // Outline width, shadow softness and the pixels the field's range
+    // covers on screen.
+    params: [f32; 3],
+    page: i32,
+}
+
+impl SdfVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x2,
+        6 => Float32x3,
+        7 => Sint32,
+    ];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// How distance field text looks. Outlines grow outwards from the glyphs
+// and shadows are drawn beneath them; both are in pixels and reach at most
+// `SDF_SPREAD` pixels at 48px, proportionally more at larger sizes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub color: [f32; 4],
+    pub outline_width: f32,
+    pub outline_color: [f32; 4],
+    pub shadow_offset: [f32; 2],
+    pub shadow_softness: f32,
+    pub shadow_color: [f32; 4],
+}
+
+impl TextStyle {
+    pub fn new(color: [f32; 4]) -> Self {
+        Self {
+            color,
+            outline_width: 0.0,
+            outline_color: [0.0; 4],
+            shadow_offset: [0.0; 2],
+            shadow_softness: 0.0,
+            shadow_color: [0.0; 4],
+        }
+    }
+
+    pub fn outline(self, width: f32, color: [f32; 4]) -> Self {
+        Self {
+            outline_width: width,
+            outline_color: color,
+            ..self
+        }
+    }
+
+    pub fn shadow(self, offset: [f32; 2], softness: f32, color: [f32; 4]) -> Self {
+        Self {
+            shadow_offset: offset,
+            shadow_softness: softness,
+            shadow_color: color,
+            ..self
+        }
+    }
+}
+
+struct Section {
+    font: FontId,
+    text: String,
+    position: [f32; 2],
+    size: f32,
+    color: [f32; 4],
+    // Drawn from the distance field atlas when set.
+    style: Option<TextStyle>,
+}
+
+// A section's vertices, in the buffer of its kind and the emoji buffer.
+#[derive(Debug, Clone, Default)]
+struct DrawnSection {
+    sdf: bool,
+    vertices: Range<u32>,
+    emoji: Range<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: FontId,
+    glyph: GlyphId,
+    // Quarter pixel steps, so e.g. 12.0 and 12.1 share an entry.
+    size: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedGlyph {
+    // Offset of the bitmap from the pen position, in pixels.
+    offset: [f32; 2],
+    size: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+}
+
+// Glyph bitmaps are rasterized on demand into a single R8 texture. When it
+// fills up the whole atlas is dropped and refilled with what the current
+// frame needs.
+struct GlyphAtlas {
+    texture: wgpu::Texture,
+    packer: ShelfPacker,
+    glyphs: HashMap<GlyphKey, Option<CachedGlyph>>,
+}
+
+impl GlyphAtlas {
+    fn new(device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("glyph atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        Self {
+            texture,
+            packer: ShelfPacker::new(ATLAS_SIZE, ATLAS_SIZE),
+            glyphs: HashMap::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.packer.clear();
+        self.glyphs.clear();
+    }
+
+    // Returns None when the atlas is full; glyphs without an outline (spaces)
+    // are cached as Some(None).
+    fn get_or_insert(
+        &mut self,
+        queue: &wgpu::Queue,
+        font: &FontArc,
+        key: GlyphKey,
+        glyph: Glyph,
+    ) -> Option<Option<CachedGlyph>> {
+        if let Some(cached) = self.glyphs.get(&key) {
+            return Some(*cached);
+        }
+
+        let origin = glyph.position;
+        let outlined = match font.outline_glyph(glyph) {
+            Some(outlined) => outlined,
+            None => {
+                self.glyphs.insert(key, None);
+                return Some(None);
+            }
+        };
+
+        let bounds = outlined.px_bounds();
+        let width = bounds.width().ceil() as u32;
+        let height = bounds.height().ceil() as u32;
+        if width == 0 || height == 0 {
+            self.glyphs.insert(key, None);
+            return Some(None);
+        }
+
+        let (x, y) = self
+            .packer
+            .allocate(width + GLYPH_PADDING * 2, height + GLYPH_PADDING * 2)?;
+        let (x, y) = (x + GLYPH_PADDING, y + GLYPH_PADDING);
+
+        let mut coverage = vec![0u8; (width * height) as usize];
+        outlined.draw(|gx, gy, c| {
+            if gx < width && gy < height {
+                coverage[(gy * width + gx) as usize] = (c.clamp(0.0, 1.0) * 255.0) as u8;
+            }
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &coverage,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(width),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let (atlas_width, atlas_height) = self.packer.size();
+        let cached = CachedGlyph {
+            offset: [bounds.min.x - origin.x, bounds.min.y - origin.y],
+            size: [width as f32, height as f32],
+            uv_min: [
+                x as f32 / atlas_width as f32,
+                y as f32 / atlas_height as f32,
+            ],
+            uv_max: [
+                (x + width) as f32 / atlas_width as f32,
+                (y + height) as f32 / atlas_height as f32,
+            ],
+        };
+        self.glyphs.insert(key, Some(cached));
+        Some(Some(cached))
+    }
+}
+
+// Text is drawn from glyph bitmaps rasterized at the size they're shown,
+// or from distance fields that stay sharp at any size and can be outlined
+// and shadowed. Fonts are shared by both, and emoji in either are drawn in
+// color from the emoji font when there is one.
+pub struct TextRenderer {
+    fonts: Vec<FontArc>,
+    sections: Vec<Section>,
+    // Whether `queue_text` uses distance fields.
+    sdf: bool,
+    atlas: GlyphAtlas,
+    bind_group: wgpu::BindGroup,
+    shader: ShaderId,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+    sdf_atlas: SdfAtlas,
+    sdf_shader: ShaderId,
+    sdf_pipeline: Arc<wgpu::RenderPipeline>,
+    sdf_vertex_buffer: DynamicBuffer,
+    emoji: EmojiRenderer,
+    drawn: Vec<DrawnSection>,
+}
+
+impl TextRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+        globals_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let atlas = GlyphAtlas::new(device);
+
+        let atlas_view = atlas
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("glyph atlas"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let layout = crate::texture::Texture::bind_group_layout(device);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("glyph atlas"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("text"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("text.wgsl"))),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("text"),
+            bind_group_layouts: &[globals_layout, &layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = cache.add_shader("text", shader, pipeline_layout);
+        let pipeline = cache.get_or_create(device, shader, &Self::buffers(), target);
+
+        let vertex_buffer =
+            DynamicBuffer::new(device, "text vertices", wgpu::BufferUsages::VERTEX, 0);
+
+        let sdf_atlas = SdfAtlas::new(device);
+        let sdf_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("sdf text"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("sdf.wgsl"))),
+        });
+        let sdf_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sdf text"),
+            bind_group_layouts: &[globals_layout, sdf_atlas.layout()],
+            push_constant_ranges: &[],
+        });
+        let sdf_shader = cache.add_shader("sdf text", sdf_shader, sdf_pipeline_layout);
+        let sdf_pipeline = cache.get_or_create(device, sdf_shader, &Self::sdf_buffers(), target);
+        let sdf_vertex_buffer =
+            DynamicBuffer::new(device, "sdf text vertices", wgpu::BufferUsages::VERTEX, 0);
+        let emoji = EmojiRenderer::new(device, cache, target, globals_layout);
+
+        Self {
+            fonts: vec![FontArc::try_from_slice(DEFAULT_FONT).expect("bundled font is valid")],
+            sections: Vec::new(),
+            sdf: false,
+            atlas,
+            bind_group,
+            shader,
+            pipeline,
+            vertex_buffer,
+            sdf_atlas,
+            sdf_shader,
+            sdf_pipeline,
+            sdf_vertex_buffer,
+            emoji,
+            drawn: Vec::new(),
+        }
+    }
+
+    fn buffers() -> [wgpu::VertexBufferLayout<'static>; 1] {
+        [TextVertex::layout()]
+    }
+
+    fn sdf_buffers() -> [wgpu::VertexBufferLayout<'static>; 1] {
+        [SdfVertex::layout()]
+    }
+
+    // Switches to the pipeline for a new target format or sample count.
+    pub(crate) fn retarget(
+        &mut self,
+        device: &wgpu::Device,
+        cache: &mut PipelineCache,
+        target: &TargetConfig,
+    ) {
+        self.pipeline = cache.get_or_create(device, self.shader, &Self::buffers(), target);
+        self.sdf_pipeline =
+            cache.get_or_create(device, self.sdf_shader, &Self::sdf_buffers(), target);
+        self.emoji.retarget(device, cache, target);
+    }
+
+    pub(crate) fn fonts(&self) -> &[FontArc] {
+        &self.fonts
+    }
+
+    pub fn add_font(&mut self, font: FontArc) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    pub fn load_font(&mut self, path: impl AsRef<Path>) -> anyhow::Result<FontId> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+        let font =
+            FontArc::try_from_vec(bytes).with_context(|| format!("invalid font {:?}", path))?;
+        Ok(self.add_font(font))
+    }
+
+    pub fn emoji_font(&self) -> Option<&FontArc> {
+        self.emoji.font()
+    }
+
+    // A font with color bitmaps (CBDT or sbix, as Noto Color Emoji and
+    // Apple Color Emoji have) that emoji are drawn from. Without one they
+    // come from the text font like any other character.
+    pub fn set_emoji_font(&mut self, font: Option<FontArc>) {
+        self.emoji.set_font(font);
+    }
+
+    pub fn load_emoji_font(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        self.set_emoji_font(Some(emoji::load_font(path.as_ref())?));
+        Ok(())
+    }
+
+    pub fn sdf(&self) -> bool {
+        self.sdf
+    }
+
+    // Makes `queue_text` draw distance field text, which scales and
+    // animates in size without blurring.
+    pub fn set_sdf(&mut self, sdf: bool) {
+        self.sdf = sdf;
+    }
+
+    // `position` is the top-left corner of the first line in pixels; lines
+    // are separated by '\n'. Queued text is drawn by the next frame only.
+    pub fn queue_text(&mut self, text: &str, position: [f32; 2], size: f32, color: [f32; 4]) {
+        self.queue_text_with_font(FontId::DEFAULT, text, position, size, color);
+    }
+
+    pub fn queue_text_with_font(
+        &mut self,
+        font: FontId,
+        text: &str,
+        position: [f32; 2],
+        size: f32,
+        color: [f32; 4],
+    ) {
+        let style = self.sdf.then(|| TextStyle::new(color));
+        self.sections.push(Section {
+            font,
+            text: text.to_string(),
+            position,
+            size,
+            color,
+            style,
+        });
+    }
+
+    // Like `queue_text`, always as distance field text.
+    pub fn queue_styled_text(
+        &mut self,
+        text: &str,
+        position: [f32; 2],
+        size: f32,
+        style: &TextStyle,
+    ) {
+        self.queue_styled_text_with_font(FontId::DEFAULT, text, position, size, style);
+    }
+
+    pub fn queue_styled_text_with_font(
+        &mut self,
+        font: FontId,
+        text: &str,
+        position: [f32; 2],
+        size: f32,
+        style: &TextStyle,
+    ) {
+        self.sections.push(Section {
+            font,
+            text: text.to_string(),
+            position,
+            size,
+            color: style.color,
+            style: Some(*style),
+        });
+    }
+
+    // Breaks `text` into lines that fit `bounds` in pixels at `size`,
+    // between words where it can, and cuts off what doesn't fit below with
+    // an ellipsis. Kerning isn't counted, which rarely matters at the
+    // margins text is given. The result goes to `queue_text` as is.
+    pub fn wrap_text(&self, font: FontId, text: &str, size: f32, bounds: [f32; 2]) -> String {
+        let scaled = self.fonts[font.0].as_scaled(PxScale::from(size));
+        let advance = |c: char| match self.emoji.glyph_id(c) {
+            Some(id) => self.emoji.h_advance(id, size),
+            None => scaled.h_advance(scaled.glyph_id(c)),
+        };
+        let width = |line: &str| line.chars().map(advance).sum::<f32>();
+        let line_height = scaled.height() + scaled.line_gap();
+        let max_lines = ((bounds[1] / line_height).floor() as usize).max(1);
+
+        let mut lines = Vec::new();
+        'paragraphs: for paragraph in text.lines() {
+            let mut line = String::new();
+            let mut line_width = 0.0;
+            // Where the line can break, after its last space.
+            let mut space = None;
+            for c in paragraph.chars() {
+                let char_width = advance(c);
+                if line_width + char_width > bounds[0] && !line.is_empty() {
+                    let carried = match space {
+                        Some(space) => line.split_off(space),
+                        None => String::new(),
+                    };
+                    lines.push(line.trim_end().to_string());
+                    if lines.len() > max_lines {
+                        break 'paragraphs;
+                    }
+                    line_width = width(&carried);
+                    line = carried;
+                    space = None;
+                }
+                line.push(c);
+                line_width += char_width;
+                if c.is_whitespace() {
+                    space = Some(line.len());
+                }
+            }
+            lines.push(line);
+            if lines.len() > max_lines {
+                break;
+            }
+        }
+
+        if lines.len() > max_lines {
+            lines.truncate(max_lines);
+            if let Some(last) = lines.last_mut() {
+                while !last.is_empty() && width(last) + advance(ELLIPSIS) > bounds[0] {
+                    last.pop();
+                }
+                last.push(ELLIPSIS);
+            }
+        }
+        lines.join("\n")
+    }
+
+    // The number of sections queued so far.
+    pub(crate) fn mark(&self) -> u32 {
+        self.sections.len() as u32
+    }
+
+    pub(crate) fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let mut laid_out = self.layout(device, queue);
+        if laid_out.is_none() {
+            // Out of atlas space: start over with only this frame's glyphs.
+            self.atlas.clear();
+            self.sdf_atlas.clear();
+            self.emoji.clear();
+            laid_out = self.layout(device, queue);
+        }
+        let (vertices, sdf_vertices, drawn) = laid_out.unwrap_or_else(|| {
+            log::warn!("glyph atlas is too small for the queued text");
+            self.emoji.clear();
+            (
+                Vec::new(),
+                Vec::new(),
+                vec![DrawnSection::default(); self.sections.len()],
+            )
+        });
+        self.sections.clear();
+
+        self.vertex_buffer
+            .write(device, queue, bytemuck::cast_slice(&vertices));
+        self.sdf_vertex_buffer
+            .write(device, queue, bytemuck::cast_slice(&sdf_vertices));
+        self.emoji.prepare(device, queue);
+        self.drawn = drawn;
+    }
+
+    fn layout(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Option<(Vec<TextVertex>, Vec<SdfVertex>, Vec<DrawnSection>)> {
+        self.sdf_atlas.begin_frame();
+        let mut vertices = Vec::new();
+        let mut sdf_vertices = Vec::new();
+        let mut drawn = Vec::with_capacity(self.sections.len());
+        for section in &self.sections {
+            let emoji_start = self.emoji.mark();
+            if section.style.is_some() {
+                let start = sdf_vertices.len() as u32;
+                let font = &self.fonts[section.font.0];
+                layout_sdf(
+                    &mut self.sdf_atlas,
+                    &mut self.emoji,
+                    device,
+                    queue,
+                    font,
+                    section,
+                    &mut sdf_vertices,
+                )?;
+                drawn.push(DrawnSection {
+                    sdf: true,
+                    vertices: start..sdf_vertices.len() as u32,
+                    emoji: emoji_start..self.emoji.mark(),
+                });
+                continue;
+            }
+            let start = vertices.len() as u32;
+            let font = &self.fonts[section.font.0];
+            let scale = PxScale::from(section.size);
+            let scaled = font.as_scaled(scale);
+            let line_height = scaled.height() + scaled.line_gap();
+
+            for (line_index, line) in section.text.lines().enumerate() {
+                let baseline =
+                    section.position[1] + scaled.ascent() + line_index as f32 * line_height;
+                let mut x = section.position[0];
+                let mut previous = None;
+
+                for c in emoji::drawn_chars(line) {
+                    if let Some(id) = self.emoji.glyph_id(c) {
+                        previous = None;
+                        let pen = [x.round(), baseline.round()];
+                        x += self.emoji.h_advance(id, section.size);
+                        self.emoji
+                            .queue(queue, id, pen, section.size, section.color[3])?;
+                        continue;
+                    }
+                    let id = scaled.glyph_id(c);
+                    if let Some(previous) = previous {
+                        x += scaled.kern(previous, id);
+                    }
+                    previous = Some(id);
+
+                    // Glyphs are placed on whole pixels so one cached bitmap
+                    // fits every occurrence.
+                    let pen = [x.round(), baseline.round()];
+                    let glyph = id.with_scale_and_position(scale, ab_glyph::point(pen[0], pen[1]));
+                    let key = GlyphKey {
+                        font: section.font,
+                        glyph: id,
+                        size: (section.size * 4.0).round() as u32,
+                    };
+                    x += scaled.h_advance(id);
+
+                    let cached = match self.atlas.get_or_insert(queue, font, key, glyph)? {
+                        Some(cached) => cached,
+                        None => continue,
+                    };
+                    push_quad(&mut vertices, pen, &cached, section.color);
+                }
+            }
+            drawn.push(DrawnSection {
+                sdf: false,
+                vertices: start..vertices.len() as u32,
+                emoji: emoji_start..self.emoji.mark(),
+            });
+        }
+        Some((vertices, sdf_vertices, drawn))
+    }
+
+    pub(crate) fn render_range<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        globals: &'a wgpu::BindGroup,
+        sections: Range<u32>,
+    ) {
+        let drawn = &self.drawn[sections.start as usize..sections.end as usize];
+        // One draw per run of sections of the same kind, with the run's emoji
+        // on top. Emoji don't overlap the text around them.
+        let mut start = 0;
+        while start < drawn.len() {
+            let sdf = drawn[start].sdf;
+            let end = drawn[start..]
+                .iter()
+                .position(|section| section.sdf != sdf)
+                .map_or(drawn.len(), |length| start + length);
+            let vertices = drawn[start].vertices.start..drawn[end - 1].vertices.end;
+            let emoji = drawn[start].emoji.start..drawn[end - 1].emoji.end;
+            start = end;
+            if !vertices.is_empty() {
+                if sdf {
+                    rpass.set_pipeline(&self.sdf_pipeline);
+                    rpass.set_bind_group(1, &self.sdf_atlas.bind_group, &[]);
+                    rpass.set_vertex_buffer(0, self.sdf_vertex_buffer.slice());
+                } else {
+                    rpass.set_pipeline(&self.pipeline);
+                    rpass.set_bind_group(1, &self.bind_group, &[]);
+                    rpass.set_vertex_buffer(0, self.vertex_buffer.slice());
+                }
+                rpass.set_bind_group(0, globals, &[]);
+                rpass.draw(vertices, 0..1);
+            }
+            self.emoji.render_range(rpass, globals, emoji);
+        }
+    }
+}
+
+// Glyphs aren't snapped to pixels, so text moves and scales smoothly.
+fn layout_sdf(
+    atlas: &mut SdfAtlas,
+    emoji: &mut EmojiRenderer,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    font: &FontArc,
+    section: &Section,
+    vertices: &mut Vec<SdfVertex>,
+) -> Option<()> {
+    let style = section
+        .style
+        .unwrap_or_else(|| TextStyle::new(section.color));
+    let scaled = font.as_scaled(PxScale::from(section.size));
+    let line_height = scaled.height() + scaled.line_gap();
+    let scale = section.size / SDF_SIZE;
+
+    for (line_index, line) in section.text.lines().enumerate() {
+        let baseline = section.position[1] + scaled.ascent() + line_index as f32 * line_height;
+        let mut x = section.position[0];
+        let mut previous = None;
+
+        for c in emoji::drawn_chars(line) {
+            if let Some(id) = emoji.glyph_id(c) {
+                previous = None;
+                emoji.queue(queue, id, [x, baseline], section.size, style.color[3])?;
+                x += emoji.h_advance(id, section.size);
+                continue;
+            }
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                x += scaled.kern(previous, id);
+            }
+            previous = Some(id);
+            let pen = [x, baseline];
+            x += scaled.h_advance(id);
+
+            let glyph = match atlas.get_or_insert(device, queue, font, section.font, id)? {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            push_sdf_quad(vertices, pen, scale, &glyph, &style);
+        }
+    }
+    Some(())
+}
+
+fn push_quad(vertices: &mut Vec<TextVertex>, pen: [f32; 2], glyph: &CachedGlyph, color: [f32; 4]) {
+    let min = [pen[0] + glyph.offset[0], pen[1] + glyph.offset[1]];
+    let max = [min[0] + glyph.size[0], min[1] + glyph.size[1]];
+    let vertex = |x: usize, y: usize| TextVertex {
+        position: [[min[0], max[0]][x], [min[1], max[1]][y]],
+        tex_coords: [
+            [glyph.uv_min[0], glyph.uv_max[0]][x],
+            [glyph.uv_min[1], glyph.uv_max[1]][y],
+        ],
+        color,
+    };
+    vertices.extend_from_slice(&[
+        vertex(0, 0),
+        vertex(1, 0),
+        vertex(1, 1),
+        vertex(0, 0),
+        vertex(1, 1),
+        vertex(0, 1),
+    ]);
+}
+
+fn push_sdf_quad(
+    vertices: &mut Vec<SdfVertex>,
+    pen: [f32; 2],
+    scale: f32,
+    glyph: &SdfGlyph,
+    style: &TextStyle,
+) {
+    let min = [
+        pen[0] + glyph.offset[0] * scale,
+        pen[1] + glyph.offset[1] * scale,
+    ];
+    let max = [
+        min[0] + glyph.size[0] * scale,
+        min[1] + glyph.size[1] * scale,
+    ];
+    // Screen pixels to texels at `SDF_SIZE`.
+    let texel = 1.0 / (scale * SDF_PAGE_SIZE as f32);
+    let params = [
+        style.outline_width,
+        style.shadow_softness,
+        2.0 * SDF_SPREAD as f32 * scale,
+    ];
+    let vertex = |x: usize, y: usize| SdfVertex {
+        position: [[min[0], max[0]][x], [min[1], max[1]][y]],
+        tex_coords: [
+            [glyph.uv_min[0], glyph.uv_max[0]][x],
+            [glyph.uv_min[1], glyph.uv_max[1]][y],
+        ],
+        color: style.color,
+        outline_color: style.outline_color,
+        shadow_color: style.shadow_color,
+        shadow_offset: [
+            style.shadow_offset[0] * texel,
+            style.shadow_offset[1] * texel,
+        ],
+        params,
+        page: glyph.page as i32,
+    };
+    vertices.extend_from_slice(&[
+        vertex(0, 0),
+        vertex(1, 0),
+        vertex(1, 1),
+        vertex(0, 0),
+        vertex(1, 1),
+        vertex(0, 1),
+    ]);
+}
@@ -0,0 +1,250 @@
+use std::{num::NonZeroU32, path::Path};
+
+use anyhow::Context;
+
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub size: wgpu::Extent3d,
+}
+
+impl Texture {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub fn from_path(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+        Self::from_bytes(device, queue, &bytes)
+            .with_context(|| format!("failed to load {:?}", path))
+    }
+
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+    ) -> anyhow::Result<Self> {
+        let image = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(device, queue, &image))
+    }
+
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &image::DynamicImage,
+    ) -> Self {
+        let rgba = image.to_rgba8();
+        let (width, height) = rgba.dimensions();
+        Self::from_rgba(device, queue, width, height, &rgba)
+    }
+
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Self {
+        Self::from_rgba_with_format(device, queue, width, height, rgba, Self::FORMAT)
+    }
+
+    // `Rgba8Unorm` for drawing to a target that doesn't encode sRGB, so the
+    // stored values reach it unchanged.
+    pub fn from_rgba_with_format(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        // Scaled down to fit rather than creating a texture the device can't
+        // have, which downlevel limits make likely for photos.
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            log::warn!(
+                "{}x{} is larger than the device allows, scaling to fit {}x{}",
+                width,
+                height,
+                max,
+                max
+            );
+            let image = image::RgbaImage::from_raw(width, height, rgba.to_vec())
+                .expect("rgba holds width * height pixels");
+            let image = image::DynamicImage::ImageRgba8(image).resize(
+                max,
+                max,
+                image::imageops::FilterType::Triangle,
+            );
+            let rgba = image.to_rgba8();
+            let (width, height) = rgba.dimensions();
+            return Self::from_rgba_with_format(device, queue, width, height, &rgba, format);
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+
+        let (data, bytes_per_row) = pad_rows(rgba, width, height);
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(bytes_per_row),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Non power of two sizes are fine as long as we don't ask for
+        // mipmaps or repeat addressing.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("texture"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            size,
+        }
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("texture"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+}
+
+// Pads each row up to COPY_BYTES_PER_ROW_ALIGNMENT so the same data layout
+// also works for buffer to texture copies.
+fn pad_rows(rgba: &[u8], width: u32, height: u32) -> (Vec<u8>, u32) {
+    let unpadded = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded = (unpadded + align - 1) / align * align;
+    if padded == unpadded {
+        return (rgba.to_vec(), unpadded);
+    }
+    let mut data = vec![0; (padded * height) as usize];
+    for (src, dst) in rgba
+        .chunks_exact(unpadded as usize)
+        .zip(data.chunks_exact_mut(padded as usize))
+    {
+        dst[..unpadded as usize].copy_from_slice(src);
+    }
+    (data, padded)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TexturedVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+}
+
+impl TexturedVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+
+    // Quad from `min` to `max` in clip space, with v pointing down.
+    pub(crate) fn quad(min: [f32; 2], max: [f32; 2]) -> [Self; 4] {
+        [
+            Self {
+                position: [min[0], min[1]],
+                tex_coords: [0.0, 1.0],
+            },
+            Self {
+                position: [max[0], min[1]],
+                tex_coords: [1.0, 1.0],
+            },
+            Self {
+                position: [max[0], max[1]],
+                tex_coords: [1.0, 0.0],
+            },
+            Self {
+                position: [min[0], max[1]],
+                tex_coords: [0.0, 0.0],
+            },
+        ]
+    }
+}
+
+pub(crate) const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
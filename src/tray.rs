@@ -0,0 +1,118 @@
+use anyhow::Context;
+use tray_icon::{
+    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    Icon, TrayIcon, TrayIconBuilder,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::event::UserEvent;
+
+const ICON_SIZE: u32 = 32;
+
+// A plain rounded square, drawn here since there are no image assets.
+fn icon() -> anyhow::Result<Icon> {
+    let radius = ICON_SIZE as f32 / 4.0;
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            // Distance outside the rounded corners, one pixel of antialiasing.
+            let corner = |p: u32| {
+                let p = p as f32 + 0.5;
+                (radius - p).max(p - (ICON_SIZE as f32 - radius)).max(0.0)
+            };
+            let (dx, dy) = (corner(x), corner(y));
+            let outside = ((dx * dx + dy * dy).sqrt() - radius).max(0.0);
+            let alpha = (1.0 - outside).clamp(0.0, 1.0);
+            rgba.extend_from_slice(&[26, 102, 230, (alpha * 255.0) as u8]);
+        }
+    }
+    Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE).context("failed to create the tray icon")
+}
+
+struct Items {
+    toggle: MenuItem,
+    reload: MenuItem,
+    quit: MenuItem,
+}
+
+fn build() -> anyhow::Result<(TrayIcon, Items)> {
+    let items = Items {
+        toggle: MenuItem::new("Show/Hide", true, None),
+        reload: MenuItem::new("Reload config", true, None),
+        quit: MenuItem::new("Quit", true, None),
+    };
+    let menu = Menu::new();
+    menu.append_items(&[
+        &items.toggle,
+        &items.reload,
+        &PredefinedMenuItem::separator(),
+        &items.quit,
+    ]);
+    let tray = TrayIconBuilder::new()
+        .with_tooltip("mgraphics")
+        .with_icon(icon()?)
+        .with_menu(Box::new(menu))
+        .build()
+        .context("failed to create the tray icon")?;
+    Ok((tray, items))
+}
+
+// Menu events arrive on tray-icon's own channel, so a thread forwards them
+// to the event loop.
+fn forward(items: &Items, proxy: EventLoopProxy<UserEvent>) {
+    let (toggle, reload, quit) = (items.toggle.id(), items.reload.id(), items.quit.id());
+    std::thread::spawn(move || {
+        for event in MenuEvent::receiver() {
+            let event = match event.id {
+                id if id == toggle => UserEvent::ToggleVisibility,
+                id if id == reload => UserEvent::ReloadConfig,
+                id if id == quit => UserEvent::Quit,
+                _ => continue,
+            };
+            if proxy.send_event(event).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// Keeps the icon in the tray while alive.
+pub(crate) struct Tray {
+    #[cfg(not(target_os = "linux"))]
+    _icon: TrayIcon,
+}
+
+impl Tray {
+    // The icon lives on the event loop's thread, whose message loop drives it.
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(proxy: EventLoopProxy<UserEvent>) -> anyhow::Result<Self> {
+        let (icon, items) = build()?;
+        forward(&items, proxy);
+        Ok(Self { _icon: icon })
+    }
+
+    // On Linux the icon is a GTK widget and needs a GTK main loop, which
+    // winit doesn't run, so it gets a thread of its own.
+    #[cfg(target_os = "linux")]
+    pub fn new(proxy: EventLoopProxy<UserEvent>) -> anyhow::Result<Self> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let built = gtk::init()
+                .context("failed to initialize GTK")
+                .and_then(|()| build());
+            match built {
+                Ok((_icon, items)) => {
+                    forward(&items, proxy);
+                    let _ = tx.send(Ok(()));
+                    gtk::main();
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                }
+            }
+        });
+        rx.recv()
+            .map_err(|_| anyhow::anyhow!("the tray thread exited"))??;
+        Ok(Self {})
+    }
+}
@@ -0,0 +1,499 @@
+use std::{
+    f32::consts::PI,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::scene::{DrawCommand, Scene};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineInOut,
+    ExpoOut,
+    // Overshoots a little before settling.
+    BackOut,
+}
+
+impl Easing {
+    // Maps progress from 0 to 1 onto the curve, which starts at 0 and ends
+    // at 1 but may leave that range in between.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(2) / 2.0
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(3) / 2.0
+                }
+            }
+            Easing::SineInOut => (1.0 - (PI * t).cos()) / 2.0,
+            Easing::ExpoOut => {
+                if t >= 1.0 {
+                    1.0
+                } else {
+                    1.0 - 2f32.powf(-10.0 * t)
+                }
+            }
+            Easing::BackOut => {
+                const C1: f32 = 1.70158;
+                const C3: f32 = C1 + 1.0;
+                1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2)
+            }
+        }
+    }
+}
+
+// What happens after the first run. Repeating tweens never finish on their
+// own; cancel them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    Once,
+    // Starts over from `from` every time.
+    Loop,
+    // Runs back to `from`, then forth again.
+    PingPong,
+}
+
+// A property of one of a scene's draw commands, by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Property {
+    // Pixels from the top-left corner: a rect's corner, a circle's center,
+    // where text starts or a line's first end, the other moving along.
+    Position(usize),
+    // Linear RGBA, the tint of sprites and nine-patches.
+    Color(usize),
+    // Just the alpha of the color.
+    Opacity(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Scalar(f32),
+    Vec2([f32; 2]),
+    Vec4([f32; 4]),
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Scalar(value)
+    }
+}
+
+impl From<[f32; 2]> for Value {
+    fn from(value: [f32; 2]) -> Self {
+        Value::Vec2(value)
+    }
+}
+
+impl From<[f32; 4]> for Value {
+    fn from(value: [f32; 4]) -> Self {
+        Value::Vec4(value)
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+impl Value {
+    // `None` when the two are different kinds of value.
+    fn lerp(self, to: Value, t: f32) -> Option<Value> {
+        Some(match (self, to) {
+            (Value::Scalar(a), Value::Scalar(b)) => Value::Scalar(lerp(a, b, t)),
+            (Value::Vec2(a), Value::Vec2(b)) => Value::Vec2([0, 1].map(|i| lerp(a[i], b[i], t))),
+            (Value::Vec4(a), Value::Vec4(b)) => {
+                Value::Vec4([0, 1, 2, 3].map(|i| lerp(a[i], b[i], t)))
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl Property {
+    fn index(self) -> usize {
+        match self {
+            Property::Position(index) | Property::Color(index) | Property::Opacity(index) => index,
+        }
+    }
+
+    fn accepts(self, value: Value) -> bool {
+        matches!(
+            (self, value),
+            (Property::Position(_), Value::Vec2(_))
+                | (Property::Color(_), Value::Vec4(_))
+                | (Property::Opacity(_), Value::Scalar(_))
+        )
+    }
+
+    // Commands without such a property are left alone.
+    fn write(self, command: &mut DrawCommand, value: Value) {
+        let color = match command {
+            DrawCommand::FillRect { color, .. }
+            | DrawCommand::StrokeRect { color, .. }
+            | DrawCommand::FillRoundedRect { color, .. }
+            | DrawCommand::FillCircle { color, .. }
+            | DrawCommand::Line { color, .. }
+            | DrawCommand::Text { color, .. }
+            | DrawCommand::Sprite { tint: color, .. }
+            | DrawCommand::Texture { tint: color, .. } => Some(color),
+            DrawCommand::NinePatch { patch, .. } => Some(&mut patch.tint),
+            DrawCommand::Shadow { shadow, .. } => Some(&mut shadow.color),
+            _ => None,
+        };
+        match (self, value) {
+            (Property::Color(_), Value::Vec4(value)) => {
+                if let Some(color) = color {
+                    *color = value;
+                }
+            }
+            (Property::Opacity(_), Value::Scalar(value)) => {
+                if let Some(color) = color {
+                    color[3] = value;
+                }
+            }
+            (Property::Position(_), Value::Vec2([x, y])) => match command {
+                DrawCommand::FillRect { rect, .. }
+                | DrawCommand::StrokeRect { rect, .. }
+                | DrawCommand::FillRoundedRect { rect, .. }
+                | DrawCommand::Shadow { rect, .. }
+                | DrawCommand::Gradient { rect, .. }
+                | DrawCommand::Sprite { rect, .. }
+                | DrawCommand::Texture { rect, .. }
+                | DrawCommand::NinePatch { rect, .. } => {
+                    rect.x = x;
+                    rect.y = y;
+                }
+                DrawCommand::FillCircle { center, .. } => *center = [x, y],
+                DrawCommand::Text { position, .. } => *position = [x, y],
+                DrawCommand::Line { from, to, .. } => {
+                    *to = [to[0] + x - from[0], to[1] + y - from[1]];
+                    *from = [x, y];
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TweenId(u64);
+
+static NEXT_TWEEN_ID: AtomicU64 = AtomicU64::new(0);
+
+// Animates a property from one value to another. The id is assigned on
+// creation, so a tween handed to another thread can still be cancelled.
+#[derive(Debug, Clone)]
+pub struct Tween {
+    id: TweenId,
+    property: Property,
+    from: Value,
+    to: Value,
+    duration: Duration,
+    easing: Easing,
+    delay: Duration,
+    repeat: Repeat,
+    // `None` until the first tick, which counts in full: the event loop
+    // clamps deltas to a frame's worth, so however long it slept before
+    // doesn't count.
+    elapsed: Option<Duration>,
+}
+
+impl Tween {
+    // `from` and `to` have to fit the property: a `[f32; 2]` position, a
+    // `[f32; 4]` color or an `f32` opacity.
+    pub fn new(
+        property: Property,
+        from: impl Into<Value>,
+        to: impl Into<Value>,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            id: TweenId(NEXT_TWEEN_ID.fetch_add(1, Ordering::Relaxed)),
+            property,
+            from: from.into(),
+            to: to.into(),
+            duration,
+            easing: Easing::Linear,
+            delay: Duration::ZERO,
+            repeat: Repeat::Once,
+            elapsed: None,
+        }
+    }
+
+    pub fn easing(self, easing: Easing) -> Self {
+        Self { easing, ..self }
+    }
+
+    // The property keeps its value until the delay is over. Only delays
+    // the first run when repeating.
+    pub fn delay(self, delay: Duration) -> Self {
+        Self { delay, ..self }
+    }
+
+    pub fn repeat(self, repeat: Repeat) -> Self {
+        Self { repeat, ..self }
+    }
+
+    pub fn id(&self) -> TweenId {
+        self.id
+    }
+
+    fn is_finished(&self) -> bool {
+        let elapsed = match self.elapsed {
+            Some(elapsed) => elapsed,
+            None => return false,
+        };
+        // A zero duration can't repeat.
+        elapsed >= self.delay + self.duration
+            && (self.repeat == Repeat::Once || self.duration.is_zero())
+    }
+
+    // `None` during the delay.
+    fn value(&self) -> Option<Value> {
+        let elapsed = self.elapsed?.checked_sub(self.delay)?;
+        let cycles = if self.duration.is_zero() {
+            1.0
+        } else {
+            elapsed.as_secs_f32() / self.duration.as_secs_f32()
+        };
+        let progress = match self.repeat {
+            _ if self.duration.is_zero() => 1.0,
+            Repeat::Once => cycles.min(1.0),
+            Repeat::Loop => cycles.fract(),
+            Repeat::PingPong => 1.0 - ((cycles % 2.0) - 1.0).abs(),
+        };
+        self.from.lerp(self.to, self.easing.apply(progress))
+    }
+}
+
+// Owns the running tweens and writes their values into a scene. Tick it
+// with the frame's delta, then apply it to the scene before drawing; once
+// it is idle there is nothing more to redraw for.
+#[derive(Debug, Default)]
+pub struct Animator {
+    tweens: Vec<Tween>,
+    // Final values of tweens that finished since the last `apply`.
+    finished: Vec<(Property, Value)>,
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A tween whose values don't fit its property is dropped with a
+    // warning. Later tweens win over earlier ones on the same property.
+    pub fn add(&mut self, tween: Tween) -> TweenId {
+        let id = tween.id;
+        if tween.property.accepts(tween.from) && tween.property.accepts(tween.to) {
+            self.tweens.push(tween);
+        } else {
+            log::warn!(
+                "tween values {:?} and {:?} don't fit {:?}",
+                tween.from,
+                tween.to,
+                tween.property
+            );
+        }
+        id
+    }
+
+    pub fn animate(
+        &mut self,
+        property: Property,
+        from: impl Into<Value>,
+        to: impl Into<Value>,
+        duration: Duration,
+        easing: Easing,
+    ) -> TweenId {
+        self.add(Tween::new(property, from, to, duration).easing(easing))
+    }
+
+    // Stops the tween where it is. Returns whether it was still running.
+    pub fn cancel(&mut self, id: TweenId) -> bool {
+        let len = self.tweens.len();
+        self.tweens.retain(|tween| tween.id != id);
+        self.tweens.len() != len
+    }
+
+    pub fn is_running(&self, id: TweenId) -> bool {
+        self.tweens.iter().any(|tween| tween.id == id)
+    }
+
+    // Nothing running and nothing left to apply.
+    pub fn is_idle(&self) -> bool {
+        self.tweens.is_empty() && self.finished.is_empty()
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        for tween in &mut self.tweens {
+            tween.elapsed = Some(tween.elapsed.unwrap_or_default() + delta);
+        }
+        let finished = &mut self.finished;
+        self.tweens.retain(|tween| {
+            if !tween.is_finished() {
+                return true;
+            }
+            finished.extend(tween.value().map(|value| (tween.property, value)));
+            false
+        });
+    }
+
+    // The current value of the last tween on the property, for animating
+    // things other than scenes.
+    pub fn value(&self, property: Property) -> Option<Value> {
+        self.tweens
+            .iter()
+            .rev()
+            .filter(|tween| tween.property == property)
+            .find_map(Tween::value)
+    }
+
+    // Commands the properties' indices don't reach are skipped.
+    pub fn apply(&mut self, scene: &mut Scene) {
+        let current = self
+            .tweens
+            .iter()
+            .filter_map(|tween| Some((tween.property, tween.value()?)));
+        for (property, value) in self.finished.drain(..).chain(current) {
+            if let Some(command) = scene.command_mut(property.index()) {
+                property.write(command, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::Rect;
+
+    const EASINGS: [Easing; 10] = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineInOut,
+        Easing::ExpoOut,
+        Easing::BackOut,
+    ];
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    fn scalar(value: Option<Value>) -> f32 {
+        match value {
+            Some(Value::Scalar(value)) => value,
+            other => panic!("expected a scalar, got {:?}", other),
+        }
+    }
+
+    fn secs(secs: f32) -> Duration {
+        Duration::from_secs_f32(secs)
+    }
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for easing in EASINGS {
+            assert_close(easing.apply(0.0), 0.0);
+            assert_close(easing.apply(1.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn easings_at_the_midpoint() {
+        let expected = [
+            0.5, 0.25, 0.75, 0.5, 0.125, 0.875, 0.5, 0.5, 0.96875, 1.0876975,
+        ];
+        for (easing, expected) in EASINGS.into_iter().zip(expected) {
+            assert_close(easing.apply(0.5), expected);
+        }
+    }
+
+    #[test]
+    fn first_tick_counts() {
+        let mut animator = Animator::new();
+        animator.add(Tween::new(Property::Opacity(0), 0.0, 1.0, secs(1.0)));
+        animator.tick(secs(0.5));
+        assert_close(scalar(animator.value(Property::Opacity(0))), 0.5);
+    }
+
+    #[test]
+    fn loop_and_ping_pong_across_cycles() {
+        let mut animator = Animator::new();
+        let tween = |repeat| Tween::new(Property::Opacity(0), 0.0, 1.0, secs(1.0)).repeat(repeat);
+        animator.add(tween(Repeat::Loop));
+        let mut ping_pong = Animator::new();
+        ping_pong.add(tween(Repeat::PingPong));
+        // Elapsed 0.25, 1.25 and 2.25 seconds.
+        for (delta, looped, ponged) in [(0.25, 0.25, 0.25), (1.0, 0.25, 0.75), (1.0, 0.25, 0.25)] {
+            animator.tick(secs(delta));
+            ping_pong.tick(secs(delta));
+            assert_close(scalar(animator.value(Property::Opacity(0))), looped);
+            assert_close(scalar(ping_pong.value(Property::Opacity(0))), ponged);
+        }
+        assert!(!animator.is_idle());
+        assert!(!ping_pong.is_idle());
+    }
+
+    #[test]
+    fn no_value_during_the_delay() {
+        let mut animator = Animator::new();
+        animator.add(Tween::new(Property::Opacity(0), 0.0, 1.0, secs(1.0)).delay(secs(1.0)));
+        animator.tick(secs(0.5));
+        assert_eq!(animator.value(Property::Opacity(0)), None);
+        animator.tick(secs(1.0));
+        assert_close(scalar(animator.value(Property::Opacity(0))), 0.5);
+    }
+
+    #[test]
+    fn idle_once_the_final_value_is_applied() {
+        let mut scene = Scene::new();
+        scene.push(DrawCommand::FillRect {
+            rect: Rect::new(0.0, 0.0, 10.0, 10.0),
+            color: [1.0, 1.0, 1.0, 0.0],
+        });
+        let mut animator = Animator::new();
+        animator.add(Tween::new(Property::Opacity(0), 0.0, 1.0, secs(1.0)));
+        animator.tick(secs(2.0));
+        // Done running, but the final value is still to be written.
+        assert_eq!(animator.value(Property::Opacity(0)), None);
+        assert!(!animator.is_idle());
+        animator.apply(&mut scene);
+        assert!(animator.is_idle());
+        match &scene.commands()[0] {
+            DrawCommand::FillRect { color, .. } => assert_close(color[3], 1.0),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
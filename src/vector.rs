@@ -0,0 +1,315 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use lyon::{
+    math::point,
+    path::traits::PathBuilder as _,
+    tessellation::{
+        BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
+        StrokeVertex, VertexBuffers,
+    },
+};
+
+pub use lyon::tessellation::{FillRule, LineCap, LineJoin};
+
+use crate::{mesh::Mesh, shapes::Rect, vertex::Vertex};
+
+// Maximum distance, in pixels, between curves and their flattened polygons.
+const TOLERANCE: f32 = 0.1;
+
+// Builds vector paths in pixel coordinates. A new subpath starts with
+// `move_to`, or implicitly at the start of an arc.
+pub struct PathBuilder {
+    builder: lyon::path::path::Builder,
+    current: Option<[f32; 2]>,
+}
+
+impl Default for PathBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PathBuilder {
+    pub fn new() -> Self {
+        Self {
+            builder: lyon::path::Path::builder(),
+            current: None,
+        }
+    }
+
+    pub fn move_to(&mut self, to: [f32; 2]) -> &mut Self {
+        if self.current.is_some() {
+            self.builder.end(false);
+        }
+        self.builder.begin(point(to[0], to[1]));
+        self.current = Some(to);
+        self
+    }
+
+    pub fn line_to(&mut self, to: [f32; 2]) -> &mut Self {
+        if self.current.is_none() {
+            return self.move_to(to);
+        }
+        self.builder.line_to(point(to[0], to[1]));
+        self.current = Some(to);
+        self
+    }
+
+    pub fn quad_to(&mut self, ctrl: [f32; 2], to: [f32; 2]) -> &mut Self {
+        if self.current.is_none() {
+            self.move_to(ctrl);
+        }
+        self.builder
+            .quadratic_bezier_to(point(ctrl[0], ctrl[1]), point(to[0], to[1]));
+        self.current = Some(to);
+        self
+    }
+
+    pub fn cubic_to(&mut self, ctrl1: [f32; 2], ctrl2: [f32; 2], to: [f32; 2]) -> &mut Self {
+        if self.current.is_none() {
+            self.move_to(ctrl1);
+        }
+        self.builder.cubic_bezier_to(
+            point(ctrl1[0], ctrl1[1]),
+            point(ctrl2[0], ctrl2[1]),
+            point(to[0], to[1]),
+        );
+        self.current = Some(to);
+        self
+    }
+
+    // Closes the current subpath back to its start.
+    pub fn close(&mut self) -> &mut Self {
+        if self.current.take().is_some() {
+            self.builder.end(true);
+        }
+        self
+    }
+
+    // Angles in radians, clockwise on screen since y points down. Connects
+    // to the current point with a line, if there is one.
+    pub fn arc(&mut self, center: [f32; 2], radius: f32, start: f32, sweep: f32) -> &mut Self {
+        let at = |angle: f32| {
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        };
+        self.line_to(at(start));
+        // One cubic per quarter turn at most keeps the error tiny.
+        let segments = (sweep.abs() / FRAC_PI_2).ceil().max(1.0) as u32;
+        let step = sweep / segments as f32;
+        let k = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+        for i in 0..segments {
+            let a0 = start + step * i as f32;
+            let a1 = a0 + step;
+            let (p0, p3) = (at(a0), at(a1));
+            let c1 = [p0[0] - k * a0.sin(), p0[1] + k * a0.cos()];
+            let c2 = [p3[0] + k * a1.sin(), p3[1] - k * a1.cos()];
+            self.cubic_to(c1, c2, p3);
+        }
+        self
+    }
+
+    pub fn rect(&mut self, rect: Rect) -> &mut Self {
+        let (x0, y0) = (rect.x, rect.y);
+        let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+        self.move_to([x0, y0])
+            .line_to([x1, y0])
+            .line_to([x1, y1])
+            .line_to([x0, y1])
+            .close()
+    }
+
+    pub fn rounded_rect(&mut self, rect: Rect, radius: f32) -> &mut Self {
+        let r = radius.min(rect.width / 2.0).min(rect.height / 2.0).max(0.0);
+        if r == 0.0 {
+            return self.rect(rect);
+        }
+        let (x0, y0) = (rect.x + r, rect.y + r);
+        let (x1, y1) = (rect.x + rect.width - r, rect.y + rect.height - r);
+        self.move_to([x0, rect.y]);
+        self.arc([x1, y0], r, -FRAC_PI_2, FRAC_PI_2)
+            .arc([x1, y1], r, 0.0, FRAC_PI_2)
+            .arc([x0, y1], r, FRAC_PI_2, FRAC_PI_2)
+            .arc([x0, y0], r, PI, FRAC_PI_2)
+            .close()
+    }
+
+    pub fn circle(&mut self, center: [f32; 2], radius: f32) -> &mut Self {
+        self.move_to([center[0] + radius, center[1]]);
+        self.arc(center, radius, 0.0, 2.0 * PI).close()
+    }
+
+    pub fn build(mut self) -> Path {
+        if self.current.is_some() {
+            self.builder.end(false);
+        }
+        Path(self.builder.build())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+impl Default for Stroke {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+        }
+    }
+}
+
+// An immutable path. Tessellating doesn't need the GPU, so it can run on any
+// thread, and the resulting meshes can be kept and drawn every frame.
+#[derive(Debug, Clone)]
+pub struct Path(lyon::path::Path);
+
+impl Path {
+    pub fn fill(&self, rule: FillRule, color: [f32; 4]) -> anyhow::Result<Mesh> {
+        let mut geometry: VertexBuffers<Vertex, u16> = VertexBuffers::new();
+        FillTessellator::new()
+            .tessellate_path(
+                &self.0,
+                &FillOptions::tolerance(TOLERANCE).with_fill_rule(rule),
+                &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| {
+                    Vertex::new(vertex.position().to_array(), color)
+                }),
+            )
+            .map_err(|e| anyhow::anyhow!("failed to fill path: {:?}", e))?;
+        Ok(Mesh {
+            vertices: geometry.vertices,
+            indices: geometry.indices,
+        })
+    }
+
+    pub fn stroke(&self, stroke: &Stroke, color: [f32; 4]) -> anyhow::Result<Mesh> {
+        let mut geometry: VertexBuffers<Vertex, u16> = VertexBuffers::new();
+        StrokeTessellator::new()
+            .tessellate_path(
+                &self.0,
+                &StrokeOptions::tolerance(TOLERANCE)
+                    .with_line_width(stroke.width)
+                    .with_line_cap(stroke.cap)
+                    .with_line_join(stroke.join),
+                &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| {
+                    Vertex::new(vertex.position().to_array(), color)
+                }),
+            )
+            .map_err(|e| anyhow::anyhow!("failed to stroke path: {:?}", e))?;
+        Ok(Mesh {
+            vertices: geometry.vertices,
+            indices: geometry.indices,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    fn area(mesh: &Mesh) -> f32 {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position);
+                ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0
+            })
+            .sum()
+    }
+
+    fn check(path: &Path) {
+        path.fill(FillRule::NonZero, WHITE).unwrap();
+        path.fill(FillRule::EvenOdd, WHITE).unwrap();
+        path.stroke(&Stroke::default(), WHITE).unwrap();
+    }
+
+    // A square with a smaller one inside, in one subpath going the same way
+    // round both, so the inner square is wound twice.
+    fn doubled_square() -> Path {
+        let mut builder = PathBuilder::new();
+        builder
+            .move_to([0.0, 0.0])
+            .line_to([10.0, 0.0])
+            .line_to([10.0, 10.0])
+            .line_to([0.0, 10.0])
+            .line_to([0.0, 0.0])
+            .line_to([2.0, 2.0])
+            .line_to([8.0, 2.0])
+            .line_to([8.0, 8.0])
+            .line_to([2.0, 8.0])
+            .line_to([2.0, 2.0])
+            .close();
+        builder.build()
+    }
+
+    #[test]
+    fn fill_rules_differ_on_self_overlap() {
+        let path = doubled_square();
+        let non_zero = path.fill(FillRule::NonZero, WHITE).unwrap();
+        let even_odd = path.fill(FillRule::EvenOdd, WHITE).unwrap();
+        assert!((area(&non_zero) - 100.0).abs() < 0.01);
+        // The hole in the middle.
+        assert!((area(&even_odd) - 64.0).abs() < 0.01);
+        assert_ne!(non_zero.indices.len(), even_odd.indices.len());
+    }
+
+    #[test]
+    fn empty_path() {
+        let path = PathBuilder::new().build();
+        check(&path);
+        assert!(path
+            .fill(FillRule::NonZero, WHITE)
+            .unwrap()
+            .indices
+            .is_empty());
+    }
+
+    #[test]
+    fn lone_move_to() {
+        let mut builder = PathBuilder::new();
+        builder.move_to([5.0, 5.0]);
+        check(&builder.build());
+    }
+
+    #[test]
+    fn zero_length_line() {
+        let mut builder = PathBuilder::new();
+        builder.move_to([5.0, 5.0]).line_to([5.0, 5.0]);
+        check(&builder.build());
+    }
+
+    #[test]
+    fn close_without_a_current_point() {
+        let mut builder = PathBuilder::new();
+        builder.close().close();
+        let path = builder.build();
+        check(&path);
+        assert!(path
+            .fill(FillRule::NonZero, WHITE)
+            .unwrap()
+            .indices
+            .is_empty());
+    }
+
+    #[test]
+    fn oversized_radius_makes_a_stadium() {
+        let mut builder = PathBuilder::new();
+        builder.rounded_rect(Rect::new(0.0, 0.0, 10.0, 20.0), 50.0);
+        let path = builder.build();
+        check(&path);
+        let mesh = path.fill(FillRule::NonZero, WHITE).unwrap();
+        // A 10x10 square between two half circles of radius 5, give or
+        // take the flattening.
+        let expected = 100.0 + PI * 25.0;
+        assert!((area(&mesh) - expected).abs() < 1.0, "area {}", area(&mesh));
+    }
+}
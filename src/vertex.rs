@@ -0,0 +1,30 @@
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    pub const fn new(position: [f32; 2], color: [f32; 4]) -> Self {
+        Self { position, color }
+    }
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// The original hardcoded triangle, still cleared to transparent black.
+pub(crate) const DEFAULT_TRIANGLE: [Vertex; 3] = [
+    Vertex::new([-1.0, -1.0], [0.0, 0.0, 0.0, 0.0]),
+    Vertex::new([0.0, 1.0], [0.0, 0.0, 0.0, 0.0]),
+    Vertex::new([1.0, -1.0], [0.0, 0.0, 0.0, 0.0]),
+];
@@ -0,0 +1,93 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+// How an image is scaled to the window. The rest of the window shows the
+// clear color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    // As large as fits whole, keeping the aspect ratio.
+    Contain,
+    // As small as fills the window, keeping the aspect ratio and cutting
+    // off the rest.
+    Cover,
+    // Fills the window, ignoring the aspect ratio.
+    Stretch,
+    // At its size in pixels, in the middle of the window.
+    Center,
+}
+
+impl Default for Fit {
+    fn default() -> Self {
+        Fit::Contain
+    }
+}
+
+impl FromStr for Fit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "contain" => Ok(Fit::Contain),
+            "cover" => Ok(Fit::Cover),
+            "stretch" => Ok(Fit::Stretch),
+            "center" => Ok(Fit::Center),
+            _ => anyhow::bail!(
+                "unknown fit {:?}, expected contain, cover, stretch or center",
+                s
+            ),
+        }
+    }
+}
+
+impl Fit {
+    // The image's corners in clip space. Edges fall on whole pixels so an
+    // unscaled image stays sharp.
+    pub fn quad(self, image: (u32, u32), surface: (u32, u32)) -> ([f32; 2], [f32; 2]) {
+        let (image_width, image_height) = (image.0.max(1) as f32, image.1.max(1) as f32);
+        let (surface_width, surface_height) = (surface.0.max(1) as f32, surface.1.max(1) as f32);
+        let (width, height) = match self {
+            Fit::Stretch => return ([-1.0, -1.0], [1.0, 1.0]),
+            Fit::Center => (image_width, image_height),
+            Fit::Contain | Fit::Cover => {
+                let (x, y) = (surface_width / image_width, surface_height / image_height);
+                let scale = if self == Fit::Contain {
+                    x.min(y)
+                } else {
+                    x.max(y)
+                };
+                (
+                    (image_width * scale).round().max(1.0),
+                    (image_height * scale).round().max(1.0),
+                )
+            }
+        };
+        let left = ((surface_width - width) / 2.0).round();
+        let top = ((surface_height - height) / 2.0).round();
+        let to_clip = |x: f32, y: f32| {
+            [
+                x / surface_width * 2.0 - 1.0,
+                1.0 - y / surface_height * 2.0,
+            ]
+        };
+        let top_left = to_clip(left, top);
+        let bottom_right = to_clip(left + width, top + height);
+        (
+            [top_left[0], bottom_right[1]],
+            [bottom_right[0], top_left[1]],
+        )
+    }
+}
+
+// Decodes PNG, JPEG or WebP, going by the contents rather than the
+// extension.
+pub fn load_image(path: &Path) -> anyhow::Result<image::DynamicImage> {
+    image::io::Reader::open(path)
+        .with_context(|| format!("failed to open {:?}", path))?
+        .with_guessed_format()
+        .with_context(|| format!("failed to read {:?}", path))?
+        .decode()
+        .with_context(|| format!("failed to decode {:?}", path))
+}
@@ -0,0 +1,50 @@
+use std::{path::PathBuf, sync::mpsc, time::Duration};
+
+use anyhow::Context;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use winit::event_loop::EventLoopProxy;
+
+use crate::event::UserEvent;
+
+// Watches the shader's directory rather than the file itself, since editors
+// commonly save by renaming a new file over the old one. Changes are only
+// forwarded to the event loop; the pipeline is rebuilt there.
+pub(crate) fn watch_shader(path: PathBuf, proxy: EventLoopProxy<UserEvent>) -> anyhow::Result<()> {
+    let dir = path
+        .parent()
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."));
+    let file_name = path.file_name().map(|name| name.to_os_string());
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, Duration::from_millis(100))?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("failed to watch {:?}", dir))?;
+
+    std::thread::spawn(move || {
+        let _watcher = watcher;
+        for event in rx {
+            let changed = match event {
+                DebouncedEvent::Create(p) | DebouncedEvent::Write(p) => p,
+                DebouncedEvent::Rename(_, p) => p,
+                DebouncedEvent::Error(e, _) => {
+                    log::warn!("shader watcher error: {}", e);
+                    continue;
+                }
+                _ => continue,
+            };
+            if changed.file_name().map(|name| name.to_os_string()) != file_name {
+                continue;
+            }
+            if proxy
+                .send_event(UserEvent::ShaderChanged(path.clone()))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
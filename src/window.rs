@@ -0,0 +1,605 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event_loop::EventLoopWindowTarget,
+    monitor::MonitorHandle,
+    window::{Fullscreen, Window, WindowBuilder},
+};
+
+use crate::{
+    backdrop::{set_backdrop, Backdrop},
+    hints::set_skip_taskbar,
+    passthrough::set_input_passthrough,
+};
+
+#[cfg(target_os = "windows")]
+#[allow(unused_imports)]
+use winit::platform::windows::WindowBuilderExtWindows;
+
+#[cfg(target_os = "linux")]
+use winit::platform::unix::{EventLoopWindowTargetExtUnix, WindowBuilderExtUnix, XWindowType};
+
+#[cfg(target_os = "macos")]
+use winit::platform::macos::WindowBuilderExtMacOS;
+
+const DEFAULT_MARGIN: u32 = 8;
+
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_TOP_MARGIN: u32 = DEFAULT_MARGIN;
+
+// Keep below the menu bar, which is taller on notched displays.
+#[cfg(target_os = "macos")]
+const DEFAULT_TOP_MARGIN: u32 = 40;
+
+// The bar's default size along and across the anchored edge.
+const DEFAULT_LENGTH: u32 = 1024;
+const DEFAULT_THICKNESS: u32 = 128;
+
+// Stands in for an unset maximum, larger than any surface gets.
+const UNBOUNDED: f64 = 65535.0;
+
+// The edge or corner of the monitor a window sticks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Start,
+    Middle,
+    End,
+}
+
+impl Anchor {
+    const NAMES: [(&'static str, Anchor); 9] = [
+        ("top", Anchor::Top),
+        ("bottom", Anchor::Bottom),
+        ("left", Anchor::Left),
+        ("right", Anchor::Right),
+        ("top-left", Anchor::TopLeft),
+        ("top-right", Anchor::TopRight),
+        ("bottom-left", Anchor::BottomLeft),
+        ("bottom-right", Anchor::BottomRight),
+        ("center", Anchor::Center),
+    ];
+
+    // Left and right anchors make a sidebar rather than a bar.
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Anchor::Left | Anchor::Right)
+    }
+
+    fn align(self) -> (Align, Align) {
+        match self {
+            Anchor::Top => (Align::Middle, Align::Start),
+            Anchor::Bottom => (Align::Middle, Align::End),
+            Anchor::Left => (Align::Start, Align::Middle),
+            Anchor::Right => (Align::End, Align::Middle),
+            Anchor::TopLeft => (Align::Start, Align::Start),
+            Anchor::TopRight => (Align::End, Align::Start),
+            Anchor::BottomLeft => (Align::Start, Align::End),
+            Anchor::BottomRight => (Align::End, Align::End),
+            Anchor::Center => (Align::Middle, Align::Middle),
+        }
+    }
+}
+
+impl FromStr for Anchor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|&(_, anchor)| anchor)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::NAMES.iter().map(|(name, _)| *name).collect();
+                anyhow::anyhow!(
+                    "unknown anchor {:?}, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+// Space kept between the window and the monitor edges, in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Margins {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Default for Margins {
+    fn default() -> Self {
+        Self {
+            top: DEFAULT_TOP_MARGIN,
+            right: DEFAULT_MARGIN,
+            bottom: DEFAULT_MARGIN,
+            left: DEFAULT_MARGIN,
+        }
+    }
+}
+
+// Like CSS: `8` for every side, `8,16` for vertical and horizontal, or
+// `top,right,bottom,left`.
+impl FromStr for Margins {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow::anyhow!("invalid margins {:?}", s))?;
+        match values[..] {
+            [all] => Ok(Self {
+                top: all,
+                right: all,
+                bottom: all,
+                left: all,
+            }),
+            [vertical, horizontal] => Ok(Self {
+                top: vertical,
+                right: horizontal,
+                bottom: vertical,
+                left: horizontal,
+            }),
+            [top, right, bottom, left] => Ok(Self {
+                top,
+                right,
+                bottom,
+                left,
+            }),
+            _ => anyhow::bail!("expected 1, 2 or 4 margins, got {:?}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowOptions {
+    // Sizes and coordinates are logical pixels, scaled by the monitor's
+    // scale factor. The size defaults to 1024x128, or 128x1024 for vertical
+    // anchors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    // Explicit coordinates relative to the monitor; they win over the anchor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<i32>,
+    pub anchor: Anchor,
+    // Only the margins of the anchored edges move the window, but all of
+    // them limit its size.
+    pub margin: Margins,
+    // Same as `anchor = "center"`.
+    pub center: bool,
+    // The primary monitor when unset or not found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<MonitorSelector>,
+    // Bounds for resizing the window, by the platform or by its corner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_height: Option<u32>,
+    pub transparent: bool,
+    // Undecorated windows that are resizable get a corner to resize them
+    // by, see `WindowDrag`.
+    pub decorations: bool,
+    pub resizable: bool,
+    // Let mouse input fall through to the windows below.
+    pub input_passthrough: bool,
+    // Stay above normal windows.
+    pub always_on_top: bool,
+    // Stay out of the taskbar, pager and alt-tab.
+    pub skip_taskbar: bool,
+    // Blur what shows through a transparent window, Windows only.
+    pub backdrop: Backdrop,
+    // Borderless fullscreen on the window's monitor, see
+    // `WindowSet::set_fullscreen`.
+    pub fullscreen: bool,
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self {
+            width: None,
+            height: None,
+            x: None,
+            y: None,
+            anchor: Anchor::Top,
+            margin: Margins::default(),
+            center: false,
+            monitor: None,
+            min_width: None,
+            min_height: None,
+            max_width: None,
+            max_height: None,
+            transparent: true,
+            decorations: true,
+            resizable: true,
+            input_passthrough: false,
+            always_on_top: false,
+            skip_taskbar: false,
+            backdrop: Backdrop::None,
+            fullscreen: false,
+        }
+    }
+}
+
+impl WindowOptions {
+    pub fn anchor(&self) -> Anchor {
+        if self.center {
+            Anchor::Center
+        } else {
+            self.anchor
+        }
+    }
+
+    // In logical pixels, with the defaults filled in and kept within the
+    // size limits.
+    pub fn size(&self) -> (u32, u32) {
+        let (width, height) = if self.anchor().is_vertical() {
+            (DEFAULT_THICKNESS, DEFAULT_LENGTH)
+        } else {
+            (DEFAULT_LENGTH, DEFAULT_THICKNESS)
+        };
+        let size = PhysicalSize::new(
+            self.width.unwrap_or(width) as f64,
+            self.height.unwrap_or(height) as f64,
+        );
+        // At a scale factor of 1, logical and physical pixels are the same.
+        let size = self.size_limits().clamp(size, 1.0);
+        (size.width, size.height)
+    }
+
+    pub(crate) fn size_limits(&self) -> SizeLimits {
+        let bound = |value: Option<u32>, unset| value.map_or(unset, f64::from);
+        SizeLimits {
+            min: LogicalSize::new(bound(self.min_width, 1.0), bound(self.min_height, 1.0)),
+            max: LogicalSize::new(
+                bound(self.max_width, UNBOUNDED),
+                bound(self.max_height, UNBOUNDED),
+            ),
+        }
+    }
+
+    fn has_min_size(&self) -> bool {
+        self.min_width.is_some() || self.min_height.is_some()
+    }
+
+    fn has_max_size(&self) -> bool {
+        self.max_width.is_some() || self.max_height.is_some()
+    }
+}
+
+// The sizes a window may be resized to, in logical pixels. A minimum
+// larger than the maximum wins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SizeLimits {
+    pub min: LogicalSize<f64>,
+    pub max: LogicalSize<f64>,
+}
+
+impl SizeLimits {
+    pub fn clamp(&self, size: PhysicalSize<f64>, scale_factor: f64) -> PhysicalSize<u32> {
+        let min = self.min.to_physical::<f64>(scale_factor);
+        let max = self.max.to_physical::<f64>(scale_factor);
+        let clamp = |value: f64, min: f64, max: f64| value.min(max).max(min).max(1.0).round();
+        PhysicalSize::new(
+            clamp(size.width, min.width, max.width) as u32,
+            clamp(size.height, min.height, max.height) as u32,
+        )
+    }
+}
+
+// A monitor by its index in the available monitors or by a
+// case-insensitive part of its name, e.g. `1` or `"HDMI"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MonitorSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for MonitorSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            anyhow::bail!("empty monitor name");
+        }
+        Ok(s.parse()
+            .map(MonitorSelector::Index)
+            .unwrap_or_else(|_| MonitorSelector::Name(s.to_string())))
+    }
+}
+
+impl fmt::Display for MonitorSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonitorSelector::Index(index) => write!(f, "{}", index),
+            MonitorSelector::Name(name) => write!(f, "{:?}", name),
+        }
+    }
+}
+
+// Falls back to the primary monitor, then to any monitor at all, since
+// some Wayland compositors don't report a primary one.
+pub(crate) fn find_monitor<T>(
+    event_loop: &EventLoopWindowTarget<T>,
+    selector: Option<&MonitorSelector>,
+) -> anyhow::Result<MonitorHandle> {
+    if let Some(selector) = selector {
+        let found = match selector {
+            MonitorSelector::Index(index) => event_loop.available_monitors().nth(*index),
+            MonitorSelector::Name(name) => {
+                let name = name.to_lowercase();
+                event_loop.available_monitors().find(|monitor| {
+                    monitor
+                        .name()
+                        .map_or(false, |n| n.to_lowercase().contains(&name))
+                })
+            }
+        };
+        match found {
+            Some(monitor) => return Ok(monitor),
+            None => log::warn!("monitor {} is not found, using the primary one", selector),
+        }
+    }
+    event_loop
+        .primary_monitor()
+        .or_else(|| event_loop.available_monitors().next())
+        .ok_or_else(|| anyhow::anyhow!("no monitors are available"))
+}
+
+fn clamp_extent(name: &str, value: u32, max: u32) -> u32 {
+    if value > max {
+        log::warn!(
+            "window {} {} exceeds the monitor, clamping to {}",
+            name,
+            value,
+            max
+        );
+        max
+    } else {
+        value
+    }
+}
+
+fn clamp_offset(name: &str, value: i64, max: i64) -> i64 {
+    let clamped = value.clamp(0, max);
+    if clamped != value {
+        log::warn!(
+            "window {} {} is off the monitor, clamping to {}",
+            name,
+            value,
+            clamped
+        );
+    }
+    clamped
+}
+
+// Where `options` puts a window on `monitor`, in physical pixels.
+fn placement(
+    monitor: &MonitorHandle,
+    options: &WindowOptions,
+) -> anyhow::Result<(PhysicalPosition<i32>, PhysicalSize<u32>)> {
+    let monitor_size = monitor.size();
+    let scale_factor = monitor.scale_factor();
+    let to_physical = |logical: f64| (logical * scale_factor).round() as i64;
+    let (width, height) = options.size();
+    let margin = options.margin;
+    let [top, right, bottom, left] =
+        [margin.top, margin.right, margin.bottom, margin.left].map(|m| to_physical(m as f64));
+
+    // Too large windows shrink toward the anchored edge instead of spilling
+    // over the opposite one.
+    let room_width = (monitor_size.width as i64 - left - right).max(1);
+    let room_height = (monitor_size.height as i64 - top - bottom).max(1);
+    let size = PhysicalSize::<u32>::new(
+        clamp_extent("width", to_physical(width as f64) as u32, room_width as u32),
+        clamp_extent(
+            "height",
+            to_physical(height as f64) as u32,
+            room_height as u32,
+        ),
+    );
+    log::info!(
+        "placing a {}x{} window ({}x{} logical) on {} at scale factor {}",
+        size.width,
+        size.height,
+        width,
+        height,
+        monitor.name().as_deref().unwrap_or("an unnamed monitor"),
+        scale_factor
+    );
+
+    Ok((anchored_position(monitor, options, size)?, size))
+}
+
+// Where `options` puts a window of `size` on `monitor`, in physical pixels.
+fn anchored_position(
+    monitor: &MonitorHandle,
+    options: &WindowOptions,
+    size: PhysicalSize<u32>,
+) -> anyhow::Result<PhysicalPosition<i32>> {
+    let monitor_size = monitor.size();
+    let scale_factor = monitor.scale_factor();
+    let to_physical = |logical: f64| (logical * scale_factor).round() as i64;
+    let monitor_pos = monitor.position();
+    let margin = options.margin;
+    let [top, right, bottom, left] =
+        [margin.top, margin.right, margin.bottom, margin.left].map(|m| to_physical(m as f64));
+
+    let free_width = (monitor_size.width as i64 - size.width as i64).max(0);
+    let free_height = (monitor_size.height as i64 - size.height as i64).max(0);
+    let offset = |align, start: i64, end: i64, free: i64| match align {
+        Align::Start => start,
+        Align::Middle => start + (free - start - end) / 2,
+        Align::End => free - end,
+    };
+
+    let (align_x, align_y) = options.anchor().align();
+    let x = match options.x {
+        Some(x) => clamp_offset("x", to_physical(x as f64), free_width),
+        None => offset(align_x, left, right, free_width),
+    };
+    let y = match options.y {
+        Some(y) => clamp_offset("y", to_physical(y as f64), free_height),
+        None => offset(align_y, top, bottom, free_height),
+    };
+
+    Ok(PhysicalPosition::new(
+        (monitor_pos.x as i64 + x).try_into()?,
+        (monitor_pos.y as i64 + y).try_into()?,
+    ))
+}
+
+// Moves and resizes an existing window as `create_window` would have placed
+// it, e.g. after its monitor went away. Returns the monitor it ended up on.
+pub(crate) fn place_window<T>(
+    event_loop: &EventLoopWindowTarget<T>,
+    window: &Window,
+    options: &WindowOptions,
+) -> anyhow::Result<MonitorHandle> {
+    let monitor = find_monitor(event_loop, options.monitor.as_ref())?;
+    let (position, size) = placement(&monitor, options)?;
+    #[cfg(target_os = "linux")]
+    let wayland = event_loop.is_wayland();
+    #[cfg(not(target_os = "linux"))]
+    let wayland = false;
+    if !wayland {
+        window.set_outer_position(position);
+    }
+    window.set_inner_size(size);
+    Ok(monitor)
+}
+
+// Moves a window to where its anchor puts a window of its current outer
+// size, keeping the size, e.g. after its decorations changed.
+pub(crate) fn anchor_window<T>(
+    event_loop: &EventLoopWindowTarget<T>,
+    window: &Window,
+    options: &WindowOptions,
+) -> anyhow::Result<()> {
+    #[cfg(target_os = "linux")]
+    if event_loop.is_wayland() {
+        return Ok(());
+    }
+    let monitor = find_monitor(event_loop, options.monitor.as_ref())?;
+    window.set_outer_position(anchored_position(&monitor, options, window.outer_size())?);
+    Ok(())
+}
+
+pub fn create_window<T>(
+    event_loop: &EventLoopWindowTarget<T>,
+    options: &WindowOptions,
+) -> anyhow::Result<Window> {
+    #[allow(unused_mut)]
+    let mut window_builder = WindowBuilder::new();
+
+    // Wayland clients cannot place their own toplevels, the compositor decides.
+    // Anchoring to an edge with an exclusive zone needs the wlr-layer-shell
+    // role, which winit can't give its surfaces; not implemented yet.
+    #[cfg(target_os = "linux")]
+    let wayland = event_loop.is_wayland();
+    #[cfg(not(target_os = "linux"))]
+    let wayland = false;
+
+    #[cfg(target_os = "linux")]
+    if !wayland {
+        window_builder = window_builder.with_x11_window_type(vec![XWindowType::Toolbar]);
+    }
+
+    let monitor = find_monitor(event_loop, options.monitor.as_ref())?;
+    let (pos, size) = placement(&monitor, options)?;
+
+    #[cfg(target_os = "macos")]
+    {
+        window_builder = window_builder
+            .with_has_shadow(false)
+            .with_titlebar_transparent(true)
+            .with_title_hidden(true);
+    }
+
+    if wayland {
+        if options.x.is_some() || options.y.is_some() || options.anchor() != Anchor::Top {
+            log::warn!("window placement is not supported on wayland yet, ignoring it");
+        }
+    } else {
+        window_builder = window_builder.with_position(pos);
+    }
+
+    // Placed all the same, for when it leaves fullscreen.
+    if options.fullscreen {
+        window_builder =
+            window_builder.with_fullscreen(Some(Fullscreen::Borderless(Some(monitor.clone()))));
+    }
+
+    let limits = options.size_limits();
+    if options.has_min_size() {
+        window_builder = window_builder.with_min_inner_size(limits.min);
+    }
+    if options.has_max_size() {
+        window_builder = window_builder.with_max_inner_size(limits.max);
+    }
+
+    let window = window_builder
+        .with_inner_size(size)
+        .with_decorations(options.decorations)
+        .with_resizable(options.resizable)
+        .with_transparent(options.transparent)
+        .with_always_on_top(options.always_on_top)
+        .build(event_loop)?;
+    log::debug!(
+        "created window {:?}, transparent {}, decorations {}",
+        window.id(),
+        options.transparent,
+        options.decorations
+    );
+
+    if options.input_passthrough {
+        if let Err(e) = set_input_passthrough(&window, true) {
+            log::warn!("failed to enable input passthrough: {}", e);
+        }
+    }
+    if options.skip_taskbar {
+        if let Err(e) = set_skip_taskbar(&window, true) {
+            log::warn!("failed to hide the window from the taskbar: {}", e);
+        }
+    }
+    if options.backdrop != Backdrop::None {
+        if !options.transparent {
+            log::warn!("the backdrop only shows through transparent windows");
+        } else if let Err(e) = set_backdrop(&window, options.backdrop) {
+            log::warn!(
+                "failed to set the backdrop, keeping plain transparency: {}",
+                e
+            );
+        }
+    }
+
+    Ok(window)
+}
@@ -0,0 +1,1092 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, MouseButton, MouseScrollDelta, Touch, TouchPhase, WindowEvent},
+    event_loop::{EventLoopProxy, EventLoopWindowTarget},
+    monitor::MonitorHandle,
+    window::{Fullscreen, Window, WindowId},
+};
+
+#[cfg(not(feature = "egui"))]
+use crate::render::draw_with_delta;
+use crate::{
+    drag::{Region, WindowDrag},
+    event::UserEvent,
+    fade::Fade,
+    file_drop::{FileDrop, FileDropEvent},
+    gpu::Gpu,
+    hints::set_skip_taskbar,
+    passthrough::set_input_passthrough,
+    render::{RenderContext, RenderOptions},
+    render_thread::{Frame, RenderThread},
+    scene::{ClickEvent, HoverEvent, RegionId, Scene, ScrollEvent, TouchEvent},
+    tween::Animator,
+    window::{anchor_window, create_window, place_window, WindowOptions},
+};
+#[cfg(feature = "egui")]
+use crate::{overlay::DebugOverlay, render::draw_with_overlay};
+
+// What placement depends on, to notice layout and scale changes.
+type MonitorGeometry = (PhysicalPosition<i32>, PhysicalSize<u32>, f64);
+
+fn geometry(monitor: &MonitorHandle) -> MonitorGeometry {
+    (monitor.position(), monitor.size(), monitor.scale_factor())
+}
+
+// winit doesn't tell which video mode is current, so take the fastest one
+// at the monitor's resolution.
+fn refresh_rate(monitor: &MonitorHandle) -> Option<u32> {
+    let size = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == size)
+        .map(|mode| u32::from(mode.refresh_rate()))
+        .max()
+}
+
+// How long to wait before trying again when a context couldn't be rebuilt
+// after the device was lost.
+const RECOVERY_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+// Shares `gpu` when its adapter can present to the window, and makes it the
+// shared one when there is none yet.
+fn create_context(
+    gpu: &mut Option<Arc<Gpu>>,
+    window: &Window,
+    options: &RenderOptions,
+) -> anyhow::Result<RenderContext> {
+    let shared = gpu.as_ref().and_then(|gpu| {
+        RenderContext::with_gpu(gpu.clone(), window, options)
+            .map_err(|e| log::warn!("{}, creating a separate device", e))
+            .ok()
+    });
+    let ctx = match shared {
+        Some(ctx) => ctx,
+        None => pollster::block_on(RenderContext::with_options(window, options))?,
+    };
+    if gpu.is_none() {
+        *gpu = Some(ctx.gpu().clone());
+    }
+    Ok(ctx)
+}
+
+// Fields drop in order: the render thread ends before the context goes,
+// and the surface before its window.
+struct Entry {
+    #[cfg(feature = "egui")]
+    overlay: DebugOverlay,
+    render_thread: Option<RenderThread>,
+    ctx: Arc<Mutex<RenderContext>>,
+    // The context's, to check for device loss without waiting on a frame.
+    gpu: Arc<Gpu>,
+    window: Window,
+    // What the window was created with and the monitor it went on, to put it
+    // back in place when that monitor changes or disappears.
+    options: WindowOptions,
+    monitor: Option<(MonitorHandle, MonitorGeometry)>,
+    scene: Scene,
+    animator: Animator,
+    passthrough: bool,
+    // Whether the window is on screen, which it stays while fading out.
+    visible: bool,
+    // Covered by other windows or minimized, which reports a zero size;
+    // either suspends drawing, see `RenderContext::set_suspended`.
+    occluded: bool,
+    minimized: bool,
+    fade: Fade,
+    drag: WindowDrag,
+    // Where the cursor is over the window, in physical pixels, and the
+    // scene region it's on.
+    cursor: Option<[f32; 2]>,
+    hovered: Option<RegionId>,
+    // The press waiting for its release. Only one at a time, other buttons
+    // pressed meanwhile are ignored.
+    pressed: Option<(RegionId, MouseButton)>,
+    // The finger standing in for the mouse.
+    primary_touch: Option<u64>,
+    file_drop: FileDrop,
+    // The scene generation on screen, and the one being drawn, if any.
+    presented: Option<u64>,
+    drawing: Option<u64>,
+    // The next frame is drawn whatever the scene's generation, as after the
+    // surface was reconfigured or lost.
+    invalidated: bool,
+    // The generation `redraw_dirty` last saw, to tell changes it had
+    // nothing left to do for.
+    checked: u64,
+    // The inner size before going fullscreen, unknown for windows that
+    // started out fullscreen.
+    windowed_size: Option<PhysicalSize<u32>>,
+    // The decorations changed, and with them the outer size, so the window
+    // is moved back to its anchor, see `reanchor`.
+    reanchor: bool,
+}
+
+impl Entry {
+    // Waits for the frame being drawn, if any.
+    fn ctx(&self) -> MutexGuard<'_, RenderContext> {
+        self.ctx.lock().unwrap()
+    }
+
+    // Once the last frame of a fade-out was drawn.
+    fn hide_if_faded(&mut self) -> bool {
+        if self.visible && self.fade.is_hidden() {
+            self.visible = false;
+            self.window.set_visible(false);
+        }
+        !self.visible
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.occluded || self.minimized
+    }
+
+    // Mapped and not suspended, so worth drawing.
+    fn is_on_screen(&self) -> bool {
+        self.visible && !self.is_suspended()
+    }
+
+    fn set_suspended(&mut self, id: WindowId, occluded: bool, minimized: bool) {
+        let was_suspended = self.is_suspended();
+        self.occluded = occluded;
+        self.minimized = minimized;
+        let suspended = self.is_suspended();
+        if suspended == was_suspended {
+            return;
+        }
+        self.ctx().set_suspended(suspended);
+        if suspended {
+            let reason = if occluded { "occluded" } else { "minimized" };
+            log::info!("{:?}: {}, suspending rendering", id, reason);
+        } else {
+            log::info!("{:?}: visible again, resuming rendering", id);
+            // Right away, so the frame from before doesn't show.
+            self.invalidated = true;
+            self.window.request_redraw();
+        }
+    }
+
+    // Whether the scene changed since the frame on screen, or the one in
+    // flight, was queued.
+    fn is_stale(&self) -> bool {
+        let generation = Some(self.scene.generation());
+        self.invalidated || (self.presented != generation && self.drawing != generation)
+    }
+
+    // Only undecorated, resizable windows that aren't fullscreen have one.
+    fn update_resize_corner(&mut self) {
+        let options = &self.options;
+        let corner = !options.decorations && options.resizable && !options.fullscreen;
+        self.drag
+            .set_resize_corner(&self.window, corner.then(|| options.size_limits()));
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.is_stale() || !self.animator.is_idle() || self.fade.is_running()
+    }
+
+    fn start_frame(&mut self) {
+        self.drawing = Some(self.scene.generation());
+        self.invalidated = false;
+        // Frames are drawn whole, so what changed is only of use for
+        // telling that something did.
+        self.scene.take_damage();
+    }
+
+    fn update_hover(&mut self) -> Vec<HoverEvent> {
+        let hovered = self.cursor.and_then(|cursor| self.scene.hit_test(cursor));
+        self.scene.set_hovered(hovered);
+        if hovered == self.hovered {
+            return Vec::new();
+        }
+        let exit = self.hovered.map(HoverEvent::Exit);
+        self.hovered = hovered;
+        exit.into_iter()
+            .chain(hovered.map(HoverEvent::Enter))
+            .collect()
+    }
+
+    fn mouse_input(&mut self, state: ElementState, button: MouseButton) -> Option<ClickEvent> {
+        let event = match (state, self.pressed) {
+            (ElementState::Pressed, None) => {
+                let region = self.hovered?;
+                self.pressed = Some((region, button));
+                ClickEvent::Press(region, button)
+            }
+            (ElementState::Released, Some((region, pressed))) if pressed == button => {
+                self.pressed = None;
+                if self.hovered == Some(region) {
+                    ClickEvent::Click(region, button)
+                } else {
+                    ClickEvent::Cancel(region, button)
+                }
+            }
+            _ => return None,
+        };
+        self.scene
+            .set_pressed(self.pressed.map(|(region, _)| region));
+        Some(event)
+    }
+
+    fn cancel_press(&mut self) -> Option<ClickEvent> {
+        let (region, button) = self.pressed.take()?;
+        self.scene.set_pressed(None);
+        Some(ClickEvent::Cancel(region, button))
+    }
+}
+
+// All open windows keyed by id. Windows share the first window's device
+// whenever its adapter can present to them.
+pub struct WindowSet {
+    render_options: RenderOptions,
+    gpu: Option<Arc<Gpu>>,
+    windows: HashMap<WindowId, Entry>,
+    last_recovery: Option<Instant>,
+    fade_duration: Duration,
+    // Where windows report their frames when they draw on render threads.
+    render_threads: Option<EventLoopProxy<UserEvent>>,
+}
+
+impl WindowSet {
+    pub fn new(render_options: RenderOptions) -> Self {
+        Self {
+            render_options,
+            gpu: None,
+            windows: HashMap::new(),
+            last_recovery: None,
+            fade_duration: Duration::ZERO,
+            render_threads: None,
+        }
+    }
+
+    // Draws the windows spawned from now on on render threads of their own,
+    // which report through `proxy`; `None` draws them on the event loop.
+    pub(crate) fn use_render_threads(&mut self, proxy: Option<EventLoopProxy<UserEvent>>) {
+        self.render_threads = proxy;
+    }
+
+    // How long windows take to fade in when shown and out when hidden,
+    // zero for neither.
+    pub fn set_fade_duration(&mut self, duration: Duration) {
+        self.fade_duration = duration;
+        for entry in self.windows.values_mut() {
+            entry.fade.set_duration(duration);
+        }
+    }
+
+    pub fn spawn_window<T>(
+        &mut self,
+        event_loop: &EventLoopWindowTarget<T>,
+        options: &WindowOptions,
+    ) -> anyhow::Result<WindowId> {
+        // An opaque clear leaves nothing for the compositor to blend, and
+        // without a compositing manager transparent windows show garbage.
+        let window = if self.render_options.clear_color.a >= 1.0 && options.transparent {
+            log::debug!("the clear color is opaque, creating an opaque window");
+            let options = WindowOptions {
+                transparent: false,
+                ..options.clone()
+            };
+            create_window(event_loop, &options)?
+        } else {
+            create_window(event_loop, options)?
+        };
+        let mut ctx = create_context(&mut self.gpu, &window, &self.render_options)?;
+        ctx.set_opaque(options.fullscreen);
+
+        let id = window.id();
+        #[cfg(feature = "egui")]
+        let overlay = DebugOverlay::new(&window, &ctx);
+        let gpu = ctx.gpu().clone();
+        let ctx = Arc::new(Mutex::new(ctx));
+        let render_thread = match &self.render_threads {
+            Some(proxy) => Some(RenderThread::spawn(id, ctx.clone(), proxy.clone())?),
+            None => None,
+        };
+        let passthrough = options.input_passthrough;
+        let monitor = window.current_monitor().map(|monitor| {
+            let geometry = geometry(&monitor);
+            (monitor, geometry)
+        });
+        let mut entry = Entry {
+            #[cfg(feature = "egui")]
+            overlay,
+            render_thread,
+            ctx,
+            gpu,
+            window,
+            options: options.clone(),
+            monitor,
+            scene: Scene::new(),
+            animator: Animator::new(),
+            passthrough,
+            visible: true,
+            occluded: false,
+            minimized: false,
+            fade: Fade::new(self.fade_duration, true),
+            drag: WindowDrag::default(),
+            cursor: None,
+            hovered: None,
+            pressed: None,
+            primary_touch: None,
+            file_drop: FileDrop::default(),
+            presented: None,
+            drawing: None,
+            invalidated: true,
+            checked: 0,
+            windowed_size: None,
+            reanchor: false,
+        };
+        entry.update_resize_corner();
+        self.windows.insert(id, entry);
+        Ok(id)
+    }
+
+    // The context is locked for as long as the guard lives, which waits for
+    // the frame a render thread is drawing.
+    pub fn get_mut(&mut self, id: WindowId) -> Option<(&Window, MutexGuard<'_, RenderContext>)> {
+        self.windows
+            .get_mut(&id)
+            .map(|entry| (&entry.window, entry.ctx.lock().unwrap()))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Window, MutexGuard<'_, RenderContext>)> {
+        self.windows
+            .values_mut()
+            .map(|entry| (&entry.window, entry.ctx.lock().unwrap()))
+    }
+
+    // Reconfigures the surface, on the render thread if the window has one,
+    // which keeps only the latest of the sizes that queued up.
+    pub(crate) fn resize(&mut self, id: WindowId, size: PhysicalSize<u32>) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            let minimized = size.width == 0 || size.height == 0;
+            entry.set_suspended(id, entry.occluded, minimized);
+            entry.invalidated = true;
+            match &entry.render_thread {
+                Some(thread) => thread.resize(size),
+                None => entry.ctx().resize(size),
+            }
+        }
+    }
+
+    // Whether the window's render thread is still drawing the last frame,
+    // in which case the redraw is remembered for when it's done.
+    pub(crate) fn defer_frame(&mut self, id: WindowId) -> bool {
+        let thread = match self.windows.get_mut(&id) {
+            Some(Entry {
+                render_thread: Some(thread),
+                ..
+            }) => thread,
+            _ => return false,
+        };
+        if thread.in_flight {
+            thread.pending = true;
+        }
+        thread.in_flight
+    }
+
+    // Hands the window's next frame to its render thread, false when it
+    // draws on the event loop. That includes windows with the debug overlay
+    // open, as egui's state lives on the event loop.
+    pub(crate) fn send_frame(&mut self, id: WindowId, show_stats: bool, clamp_delta: bool) -> bool {
+        let entry = match self.windows.get_mut(&id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        #[cfg(feature = "egui")]
+        if entry.overlay.is_visible() {
+            return false;
+        }
+        if entry.render_thread.is_none() {
+            return false;
+        }
+        entry.start_frame();
+        let thread = entry.render_thread.as_mut().unwrap();
+        thread.draw(Frame {
+            scene: entry.scene.clone(),
+            file_drop: entry.file_drop.clone(),
+            show_stats,
+            clamp_delta,
+        });
+        true
+    }
+
+    // The render thread finished the window's frame; a redraw asked for
+    // meanwhile goes ahead now.
+    pub(crate) fn frame_done(&mut self, id: WindowId) {
+        if let Some(Entry {
+            render_thread: Some(thread),
+            window,
+            ..
+        }) = self.windows.get_mut(&id)
+        {
+            thread.in_flight = false;
+            if thread.pending {
+                window.request_redraw();
+            }
+        }
+    }
+
+    // A frame of the window was presented, or failed; a failed one is
+    // redrawn whether or not the scene changed meanwhile.
+    pub(crate) fn presented(&mut self, id: WindowId, ok: bool) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            let drawn = entry.drawing.take();
+            if !ok {
+                entry.invalidated = true;
+            } else if drawn.is_some() {
+                entry.presented = drawn;
+            }
+        }
+    }
+
+    // A scene change that changed nothing, see `Scene::replace`.
+    pub(crate) fn redundant_redraw(&self, id: WindowId) {
+        if let Some(entry) = self.windows.get(&id) {
+            entry.ctx().skip_redundant();
+        }
+    }
+
+    // The window's retained content, queued at the start of each of its
+    // frames so anything queued on the context afterwards lands on top.
+    pub fn scene_mut(&mut self, id: WindowId) -> Option<&mut Scene> {
+        self.windows.get_mut(&id).map(|entry| &mut entry.scene)
+    }
+
+    // Tweens the window's scene; see `animate`.
+    pub fn animator_mut(&mut self, id: WindowId) -> Option<&mut Animator> {
+        self.windows.get_mut(&id).map(|entry| &mut entry.animator)
+    }
+
+    // Ticks the window's animator and writes its values into the scene,
+    // ahead of queueing it, and steps its fade.
+    pub(crate) fn animate(&mut self, id: WindowId, delta: Duration) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            if !entry.animator.is_idle() {
+                entry.animator.tick(delta);
+                entry.animator.apply(&mut entry.scene);
+            }
+            if entry.fade.is_running() {
+                entry.fade.tick(delta);
+                entry.ctx().set_opacity(entry.fade.opacity());
+            }
+        }
+    }
+
+    // With the file drop highlight or error on top.
+    pub(crate) fn queue_scene(&mut self, id: WindowId) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.start_frame();
+            let mut ctx = entry.ctx();
+            ctx.queue_scene(&entry.scene);
+            entry.file_drop.queue(&mut ctx);
+        }
+    }
+
+    // Asks for a redraw of every window whose scene moved on from the
+    // generation it presented, or that is invalidated, animated or faded,
+    // even while a tween waits out its delay. A change that some other
+    // redraw already put on screen is counted as a skipped redraw. Hidden
+    // windows keep their changes until they are shown; windows that
+    // finished fading out are hidden here.
+    pub(crate) fn redraw_dirty(&mut self) {
+        for entry in self.windows.values_mut() {
+            if entry.visible && entry.is_suspended() {
+                // Nothing shows a fade meanwhile, so it ends at once.
+                entry.fade.tick(Duration::MAX);
+            }
+            if entry.hide_if_faded() || entry.is_suspended() {
+                continue;
+            }
+            let generation = entry.scene.generation();
+            let changed = std::mem::replace(&mut entry.checked, generation) != generation;
+            if entry.is_dirty() {
+                entry.window.request_redraw();
+            } else if changed {
+                entry.ctx().skip_redundant();
+            }
+        }
+    }
+
+    // Fading windows count until they are hidden, so the loop keeps
+    // redrawing at the frame rate for as long as the fade runs.
+    pub(crate) fn any_dirty(&self) -> bool {
+        self.windows
+            .values()
+            .any(|entry| entry.is_on_screen() && (entry.is_dirty() || entry.fade.is_hidden()))
+    }
+
+    // The highest refresh rate among the visible windows' monitors when all
+    // of them present with vsync, `None` when any can run faster.
+    pub(crate) fn vsync_rate(&self) -> Option<u32> {
+        let mut max = None;
+        for entry in self.windows.values().filter(|entry| entry.visible) {
+            if entry.ctx().present_mode() != wgpu::PresentMode::Fifo {
+                return None;
+            }
+            let rate = entry
+                .window
+                .current_monitor()
+                .as_ref()
+                .and_then(refresh_rate)?;
+            max = max.max(Some(rate));
+        }
+        max
+    }
+
+    // Draws one frame of the window, with the debug overlay on top.
+    #[cfg(feature = "egui")]
+    pub(crate) fn draw(
+        &mut self,
+        id: WindowId,
+        delta: Duration,
+    ) -> Option<Result<(), wgpu::SurfaceError>> {
+        let entry = self.windows.get_mut(&id)?;
+        let mut ctx = entry.ctx.lock().unwrap();
+        let overlay = &mut entry.overlay;
+        overlay.prepare(&entry.window, &mut ctx);
+        Some(draw_with_overlay(&mut ctx, delta, |encoder, view| {
+            overlay.paint(encoder, view)
+        }))
+    }
+
+    #[cfg(not(feature = "egui"))]
+    pub(crate) fn draw(
+        &mut self,
+        id: WindowId,
+        delta: Duration,
+    ) -> Option<Result<(), wgpu::SurfaceError>> {
+        let entry = self.windows.get(&id)?;
+        Some(draw_with_delta(&mut entry.ctx(), delta))
+    }
+
+    // Whether the debug overlay consumed `event`.
+    #[cfg(feature = "egui")]
+    pub(crate) fn overlay_event(&mut self, id: WindowId, event: &WindowEvent) -> bool {
+        self.windows
+            .get_mut(&id)
+            .map_or(false, |entry| entry.overlay.on_event(event))
+    }
+
+    #[cfg(not(feature = "egui"))]
+    pub(crate) fn overlay_event(&mut self, _id: WindowId, _event: &WindowEvent) -> bool {
+        false
+    }
+
+    #[cfg(feature = "egui")]
+    pub fn toggle_overlay(&mut self, id: WindowId) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.overlay.toggle();
+            entry.window.request_redraw();
+        }
+    }
+
+    #[cfg(not(feature = "egui"))]
+    pub fn toggle_overlay(&mut self, _id: WindowId) {
+        log::warn!("the debug overlay needs the `egui` feature");
+    }
+
+    // Hit tests the window's scene at the cursor, `None` once it left the
+    // window, and returns what entered and exited. Only changes count, so
+    // moving within a region returns nothing.
+    pub(crate) fn hover(
+        &mut self,
+        id: WindowId,
+        cursor: Option<PhysicalPosition<f64>>,
+    ) -> Vec<HoverEvent> {
+        match self.windows.get_mut(&id) {
+            Some(entry) => {
+                entry.cursor = cursor.map(|cursor| [cursor.x as f32, cursor.y as f32]);
+                entry.update_hover()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    // Hit tests again where the cursor last was, after the scene was
+    // replaced, and carries a press over to the new scene.
+    pub(crate) fn rehover(&mut self, id: WindowId) -> Vec<HoverEvent> {
+        match self.windows.get_mut(&id) {
+            Some(entry) => {
+                entry
+                    .scene
+                    .set_pressed(entry.pressed.map(|(region, _)| region));
+                entry.update_hover()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    // Whether the cursor is over one of the window's scene regions, which
+    // keep their presses instead of starting a drag.
+    pub(crate) fn is_over_region(&self, id: WindowId) -> bool {
+        self.windows
+            .get(&id)
+            .map_or(false, |entry| entry.hovered.is_some())
+    }
+
+    pub(crate) fn mouse_input(
+        &mut self,
+        id: WindowId,
+        state: ElementState,
+        button: MouseButton,
+    ) -> Vec<ClickEvent> {
+        self.windows
+            .get_mut(&id)
+            .and_then(|entry| entry.mouse_input(state, button))
+            .into_iter()
+            .collect()
+    }
+
+    // Nothing while the cursor's whereabouts are unknown, there's no knowing
+    // what it scrolls.
+    pub(crate) fn scroll(&self, id: WindowId, delta: MouseScrollDelta) -> Vec<ScrollEvent> {
+        self.windows
+            .get(&id)
+            .and_then(|entry| {
+                let position = entry.cursor?;
+                let scale_factor = entry.window.scale_factor();
+                Some(ScrollEvent::new(
+                    delta,
+                    scale_factor,
+                    position,
+                    entry.hovered,
+                ))
+            })
+            .into_iter()
+            .collect()
+    }
+
+    pub(crate) fn touch(&self, id: WindowId, touch: &Touch) -> Vec<TouchEvent> {
+        self.windows
+            .get(&id)
+            .map(|entry| {
+                let position = [touch.location.x as f32, touch.location.y as f32];
+                TouchEvent {
+                    id: touch.id,
+                    phase: touch.phase,
+                    position,
+                    region: entry.scene.hit_test(position),
+                }
+            })
+            .into_iter()
+            .collect()
+    }
+
+    // Whether `touch` is the first finger down while no other one is, which
+    // stays the primary touch until it lifts. The others don't disturb its
+    // press or drag.
+    pub(crate) fn is_primary_touch(&mut self, id: WindowId, touch: &Touch) -> bool {
+        let entry = match self.windows.get_mut(&id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        match touch.phase {
+            TouchPhase::Started => {
+                entry.primary_touch.get_or_insert(touch.id);
+                entry.primary_touch == Some(touch.id)
+            }
+            TouchPhase::Moved => entry.primary_touch == Some(touch.id),
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                let primary = entry.primary_touch == Some(touch.id);
+                if primary {
+                    entry.primary_touch = None;
+                }
+                primary
+            }
+        }
+    }
+
+    pub(crate) fn file_hovered(&mut self, id: WindowId) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            if entry.file_drop.hovered() {
+                entry.window.request_redraw();
+            }
+        }
+    }
+
+    pub(crate) fn file_hover_cancelled(&mut self, id: WindowId) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            if entry.file_drop.cancelled() {
+                entry.window.request_redraw();
+            }
+        }
+    }
+
+    pub(crate) fn file_dropped(&mut self, id: WindowId, path: PathBuf) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            if entry.file_drop.dropped(path, entry.cursor) {
+                entry.window.request_redraw();
+            }
+        }
+    }
+
+    // The drops collected since the last call, one per window.
+    pub(crate) fn take_file_drops(&mut self) -> Vec<(WindowId, FileDropEvent)> {
+        self.windows
+            .iter_mut()
+            .filter_map(|(&id, entry)| Some((id, entry.file_drop.take()?)))
+            .collect()
+    }
+
+    // Shown until files are dragged over the window again.
+    pub(crate) fn set_file_drop_error(&mut self, id: WindowId, message: String) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.file_drop.set_error(message);
+            entry.window.request_redraw();
+        }
+    }
+
+    pub(crate) fn cancel_press(&mut self, id: WindowId) -> Vec<ClickEvent> {
+        self.windows
+            .get_mut(&id)
+            .and_then(Entry::cancel_press)
+            .into_iter()
+            .collect()
+    }
+
+    pub(crate) fn drag_mut(&mut self, id: WindowId) -> Option<(&Window, &mut WindowDrag)> {
+        self.windows
+            .get_mut(&id)
+            .map(|entry| (&entry.window, &mut entry.drag))
+    }
+
+    // Clicks inside `region` no longer start dragging the window.
+    pub fn add_interactive_region(&mut self, id: WindowId, region: Region) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.drag.add_interactive_region(region);
+        }
+    }
+
+    pub fn clear_interactive_regions(&mut self, id: WindowId) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.drag.clear_interactive_regions();
+        }
+    }
+
+    pub fn is_passthrough(&self, id: WindowId) -> bool {
+        self.windows
+            .get(&id)
+            .map_or(false, |entry| entry.passthrough)
+    }
+
+    // Switches input passthrough without recreating the window.
+    pub fn set_passthrough(&mut self, id: WindowId, enabled: bool) -> anyhow::Result<()> {
+        let entry = self
+            .windows
+            .get_mut(&id)
+            .ok_or_else(|| anyhow::anyhow!("window {:?} is not found", id))?;
+        set_input_passthrough(&entry.window, enabled)?;
+        entry.passthrough = enabled;
+        Ok(())
+    }
+
+    // The options the window was created with, updated by the runtime
+    // toggles below.
+    pub fn options(&self, id: WindowId) -> Option<&WindowOptions> {
+        self.windows.get(&id).map(|entry| &entry.options)
+    }
+
+    pub fn set_always_on_top(&mut self, id: WindowId, enabled: bool) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.window.set_always_on_top(enabled);
+            entry.options.always_on_top = enabled;
+        }
+    }
+
+    // Borderless on the monitor the window is on, where it clears opaque as
+    // transparency rarely survives fullscreen. Leaving fullscreen places the
+    // window at its anchor again, wherever the platform put it, at the size
+    // it had before. The Resized events that follow reconfigure the surface
+    // and its attachments.
+    pub fn set_fullscreen<T>(
+        &mut self,
+        event_loop: &EventLoopWindowTarget<T>,
+        id: WindowId,
+        enabled: bool,
+    ) {
+        let entry = match self.windows.get_mut(&id) {
+            Some(entry) if entry.options.fullscreen != enabled => entry,
+            _ => return,
+        };
+        entry.options.fullscreen = enabled;
+        entry.update_resize_corner();
+        entry.ctx().set_opaque(enabled);
+        if enabled {
+            entry.windowed_size = Some(entry.window.inner_size());
+            let monitor = entry.window.current_monitor();
+            log::info!(
+                "{:?}: fullscreen on {}",
+                id,
+                monitor
+                    .as_ref()
+                    .and_then(|monitor| monitor.name())
+                    .as_deref()
+                    .unwrap_or("the current monitor")
+            );
+            entry
+                .window
+                .set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+            return;
+        }
+        log::info!("{:?}: leaving fullscreen", id);
+        entry.window.set_fullscreen(None);
+        match place_window(event_loop, &entry.window, &entry.options) {
+            Ok(monitor) => {
+                let geometry = geometry(&monitor);
+                entry.monitor = Some((monitor, geometry));
+            }
+            Err(e) => log::warn!("{:?}: failed to place the window: {}", id, e),
+        }
+        // Grown or shrunk since it was placed.
+        if let Some(size) = entry.windowed_size.take() {
+            entry.window.set_inner_size(size);
+        }
+    }
+
+    // Decorations change the outer size on some platforms, so the window
+    // is anchored again once the event loop gets to it.
+    pub fn set_decorations(&mut self, id: WindowId, enabled: bool) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.window.set_decorations(enabled);
+            entry.options.decorations = enabled;
+            entry.update_resize_corner();
+            entry.reanchor = true;
+        }
+    }
+
+    pub fn set_resizable(&mut self, id: WindowId, enabled: bool) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.window.set_resizable(enabled);
+            entry.options.resizable = enabled;
+            entry.update_resize_corner();
+        }
+    }
+
+    pub fn is_fullscreen(&self, id: WindowId) -> bool {
+        self.options(id).map_or(false, |options| options.fullscreen)
+    }
+
+    pub fn set_skip_taskbar(&mut self, id: WindowId, enabled: bool) -> anyhow::Result<()> {
+        let entry = self
+            .windows
+            .get_mut(&id)
+            .ok_or_else(|| anyhow::anyhow!("window {:?} is not found", id))?;
+        set_skip_taskbar(&entry.window, enabled)?;
+        entry.options.skip_taskbar = enabled;
+        Ok(())
+    }
+
+    pub fn toggle_visible(&mut self, id: WindowId) {
+        if let Some(visible) = self.is_visible(id) {
+            self.set_visible(id, !visible);
+        }
+    }
+
+    // Shown or fading in; a window fading out already counts as hidden.
+    pub fn is_visible(&self, id: WindowId) -> Option<bool> {
+        self.windows.get(&id).map(|entry| entry.fade.is_showing())
+    }
+
+    // Whether the window is mapped, including while it fades out, and not
+    // suspended.
+    pub(crate) fn is_on_screen(&self, id: WindowId) -> bool {
+        self.windows.get(&id).map_or(false, Entry::is_on_screen)
+    }
+
+    // Occluded windows get no redraws until they're uncovered, which some
+    // platforms can't tell and never report.
+    pub(crate) fn set_occluded(&mut self, id: WindowId, occluded: bool) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            entry.set_suspended(id, occluded, entry.minimized);
+        }
+    }
+
+    // The system asked for a redraw of a window that isn't drawn.
+    pub(crate) fn redraw_skipped(&self, id: WindowId) {
+        if let Some(entry) = self.windows.get(&id).filter(|entry| entry.is_suspended()) {
+            entry.ctx().skip_frame();
+        }
+    }
+
+    // Shown windows fade in from their current opacity, hidden ones fade
+    // out and only then go off screen, so toggling mid-fade turns it
+    // around. Hidden windows get no redraws at all until shown again.
+    pub fn set_visible(&mut self, id: WindowId, visible: bool) {
+        if let Some(entry) = self.windows.get_mut(&id) {
+            if visible {
+                entry.fade.show();
+                if !entry.visible {
+                    entry.visible = true;
+                    // The time spent hidden isn't a step of the fade.
+                    entry.ctx().advance(Duration::ZERO);
+                    entry.window.set_visible(true);
+                }
+            } else {
+                entry.fade.hide();
+            }
+            entry.ctx().set_opacity(entry.fade.opacity());
+            if !entry.hide_if_faded() {
+                entry.window.request_redraw();
+            }
+        }
+    }
+
+    pub fn any_visible(&self) -> bool {
+        self.windows.values().any(|entry| entry.fade.is_showing())
+    }
+
+    // Passthrough windows don't take input, so they aren't focused either.
+    pub fn focus(&self, id: WindowId) {
+        if let Some(entry) = self.windows.get(&id) {
+            if !entry.passthrough {
+                entry.window.focus_window();
+            }
+        }
+    }
+
+    pub(crate) fn any_on_screen(&self) -> bool {
+        self.windows.values().any(Entry::is_on_screen)
+    }
+
+    pub(crate) fn request_redraw(&self, id: WindowId) {
+        if let Some(entry) = self.windows.get(&id) {
+            entry.window.request_redraw();
+        }
+    }
+
+    pub(crate) fn request_redraws(&self) {
+        for entry in self.windows.values().filter(|entry| entry.visible) {
+            if entry.is_suspended() {
+                entry.ctx().skip_frame();
+            } else {
+                entry.window.request_redraw();
+            }
+        }
+    }
+
+    // Rebuilds the context of every window whose device was lost, on a new
+    // device, keeping what the old context held; see
+    // `RenderContext::restore`.
+    pub(crate) fn recover_lost_devices(&mut self) {
+        match self.next_recovery() {
+            Some(due) if due <= Instant::now() => {}
+            _ => return,
+        }
+        self.last_recovery = Some(Instant::now());
+        if self.gpu.as_ref().map_or(false, |gpu| gpu.is_lost()) {
+            self.gpu = None;
+        }
+        for (id, entry) in &mut self.windows {
+            if !entry.gpu.is_lost() {
+                continue;
+            }
+            match create_context(&mut self.gpu, &entry.window, &self.render_options) {
+                Ok(ctx) => {
+                    entry.gpu = ctx.gpu().clone();
+                    let mut current = entry.ctx.lock().unwrap();
+                    let lost = std::mem::replace(&mut *current, ctx);
+                    current.restore(lost);
+                    #[cfg(feature = "egui")]
+                    {
+                        entry.overlay = DebugOverlay::new(&entry.window, &current);
+                    }
+                    drop(current);
+                    entry.invalidated = true;
+                    entry.window.request_redraw();
+                    log::info!("{:?}: rendering again on a new device", id);
+                }
+                Err(e) => log::error!("{:?}: failed to recreate the renderer: {:#}", id, e),
+            }
+        }
+    }
+
+    // When `recover_lost_devices` should run again, if any device is lost.
+    pub(crate) fn next_recovery(&self) -> Option<Instant> {
+        if !self.windows.values().any(|entry| entry.gpu.is_lost()) {
+            return None;
+        }
+        Some(
+            self.last_recovery
+                .map_or_else(Instant::now, |last| last + RECOVERY_RETRY_INTERVAL),
+        )
+    }
+
+    // Marks every window's device as lost, so they are all rebuilt.
+    pub fn simulate_device_loss(&mut self) {
+        log::warn!("simulating the loss of the GPU device");
+        for entry in self.windows.values() {
+            entry.gpu.simulate_loss();
+        }
+    }
+
+    // Places windows again when their monitor was disconnected, moved,
+    // resized or rescaled, keeping their anchor, and moves windows whose
+    // decorations changed back to it. Fullscreen windows are left to the
+    // platform until they leave fullscreen.
+    pub(crate) fn reanchor<T>(&mut self, event_loop: &EventLoopWindowTarget<T>) {
+        let monitors: Vec<_> = event_loop.available_monitors().collect();
+        for (id, entry) in &mut self.windows {
+            let reanchor = std::mem::take(&mut entry.reanchor);
+            let (monitor, placed) = match &entry.monitor {
+                Some(monitor) if !entry.options.fullscreen => monitor,
+                _ => continue,
+            };
+            let reason = match monitors.iter().find(|m| *m == monitor) {
+                None => "was disconnected",
+                Some(current) if geometry(current) != *placed => "changed",
+                Some(_) => {
+                    if reanchor {
+                        log::debug!("{:?}: decorations changed, anchoring the window again", id);
+                        if let Err(e) = anchor_window(event_loop, &entry.window, &entry.options) {
+                            log::warn!("{:?}: failed to anchor the window: {}", id, e);
+                        }
+                    }
+                    continue;
+                }
+            };
+            log::info!("{:?}: monitor {}, placing the window again", id, reason);
+            match place_window(event_loop, &entry.window, &entry.options) {
+                Ok(monitor) => {
+                    let geometry = geometry(&monitor);
+                    entry.monitor = Some((monitor, geometry));
+                }
+                Err(e) => log::warn!("{:?}: failed to place the window: {}", id, e),
+            }
+        }
+    }
+
+    // Whether `reanchor` has windows to move without a monitor change.
+    pub(crate) fn needs_reanchor(&self) -> bool {
+        self.windows.values().any(|entry| entry.reanchor)
+    }
+
+    pub fn ids(&self) -> Vec<WindowId> {
+        self.windows.keys().copied().collect()
+    }
+
+    pub fn remove(&mut self, id: WindowId) -> bool {
+        self.windows.remove(&id).is_some()
+    }
+
+    // Closes every window, each after its render thread ended and its
+    // surface went.
+    pub(crate) fn close_all(&mut self) {
+        self.windows.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+}